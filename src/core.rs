@@ -1,4 +1,7 @@
+use crate::service::audio::enums::OutputMode;
+use crate::service::audio::structs::SampleTap;
 use crate::service::audio::{AudioFlags, AudioService};
+use crate::service::file::library::Library;
 use crate::service::gui::GuiService;
 use crate::service::playlist::{PlaylistFlags, PlaylistService};
 use crate::service::process::ProcessService;
@@ -12,7 +15,7 @@ use tokio_util::sync::CancellationToken;
 pub struct CoreService;
 
 impl CoreService {
-    pub fn spawn() -> () {
+    pub fn spawn(library: Library, audio_output: OutputMode) {
         // Handler creation
         let (t_bus, r_bus) = mpsc::channel(100);
         let (t_process, r_process) = mpsc::channel(100);
@@ -21,6 +24,9 @@ impl CoreService {
 
         // Service creation
 
+        // shared by the audio service, which fills it, and the gui's visualizer
+        let sample_tap = SampleTap::new();
+
         // gui service
         let gui_service = GuiService::new();
 
@@ -30,6 +36,7 @@ impl CoreService {
             process_sender: t_process.clone(),
             playlist_sender: t_playlist.clone(),
             audio_sender: t_audio.clone(),
+            library: library.clone(),
         };
         let playlist_service = PlaylistService::new(playlist_flags);
 
@@ -40,6 +47,9 @@ impl CoreService {
         let audio_flags = AudioFlags {
             audio_sender: t_audio.clone(),
             event_sender: t_bus.clone(),
+            output: audio_output,
+            sample_tap: sample_tap.clone(),
+            library: library.clone(),
         };
         let audio_service = AudioService::new(audio_flags);
 
@@ -71,7 +81,7 @@ impl CoreService {
             );
 
         // start the (blocking) gui loop
-        let _ = gui_service.start_loop(t_playlist, r_bus);
+        let _ = gui_service.start_loop(t_playlist, r_bus, sample_tap, library);
 
         // send signal to shutdown program
         cancel_token.cancel();
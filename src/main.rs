@@ -1,12 +1,18 @@
 use peanut::core::CoreService;
-use peanut::service::file::util;
-use std::fs;
+use peanut::service::audio::backend;
+use peanut::service::file::library;
+use std::env;
 
 fn main() {
-    // create basic folder structure for program if it doesn't already exist
-    fs::create_dir_all(util::track_dir_path().unwrap()).unwrap();
-    fs::create_dir_all(util::data_dir_path().unwrap()).unwrap();
-    fs::create_dir_all(util::album_dir_path().unwrap()).unwrap();
+    // work out where the library is and create its folder structure if it doesn't already exist
+    let library = library::resolve_startup_library(env::args().skip(1))
+        .expect("Critical: could not find a library location");
+    library
+        .create_dirs()
+        .expect("Critical: could not create the library folders");
 
-    let _ = CoreService::spawn();
+    // play without a sound device if asked to (or if there isn't one)
+    let audio_output = backend::startup_output_mode(env::args().skip(1));
+
+    let _ = CoreService::spawn(library, audio_output);
 }
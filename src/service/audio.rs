@@ -1,13 +1,6 @@
-use std::{
-    collections::HashMap,
-    sync::{
-        Arc,
-        atomic::{AtomicU64, Ordering},
-    },
-};
+use std::collections::{HashMap, hash_map::Entry};
 
-use atomic_float::AtomicF64;
-use kira::{AudioManager, AudioManagerSettings, Tween};
+use kira::{AudioManager, AudioManagerSettings, Decibels, Tween, track::MainTrackBuilder};
 use musicbrainz_rs::MusicBrainzClient;
 use tokio::sync::mpsc;
 
@@ -17,9 +10,13 @@ use crate::{
     APP_VERSION,
     service::{
         audio::{
-            enums::{AudioMessage, LoopPolicy},
-            structs::AudioHandleWrapper,
+            backend::OutputBackend,
+            effects::{EffectChain, sample_tap::SampleTapBuilder},
+            enums::{AudioMessage, LoopPolicy, OutputMode, ProgressCadence},
+            structs::{AudioHandleWrapper, EffectSettings, SampleTap},
+            tracker::TrackerEvent,
         },
+        file::library::Library,
         gui::enums::EventSender,
         id::structs::Id,
         playlist::enums::PlaylistMessage,
@@ -27,10 +24,18 @@ use crate::{
     util::service::ServiceLogic,
 };
 
+pub mod backend;
+pub mod decode;
+pub mod effects;
 pub mod enums;
 pub mod identification;
+pub mod loudness;
+pub mod silence;
+pub mod spectrum;
 pub mod structs;
+pub mod tracker;
 mod util;
+pub mod waveform;
 
 pub type AudioSender = mpsc::Sender<AudioMessage>;
 
@@ -38,20 +43,49 @@ pub type AudioSender = mpsc::Sender<AudioMessage>;
 pub struct AudioService {
     _event_sender: EventSender,
     audio_sender: AudioSender,
-    manager: AudioManager,
+    manager: AudioManager<OutputBackend>,
     playing_cache: HashMap<Id, AudioHandleWrapper>,
+    // one effect chain per playing playlist (None for audio played outside of one)
+    chains: HashMap<Option<Id>, EffectChain>,
+    effect_settings: EffectSettings,
     musicbrainz_client: MusicBrainzClient,
+    // how often playing audio reports its progress
+    progress_cadence: ProgressCadence,
+    // where track files are played from
+    library: Library,
 }
 
 pub struct AudioFlags {
     pub event_sender: EventSender,
     pub audio_sender: AudioSender,
+    pub output: OutputMode,
+    // everything played is copied here for the visualizer
+    pub sample_tap: SampleTap,
+    pub library: Library,
 }
 
 impl AudioService {
     pub fn new(flags: AudioFlags) -> Self {
-        let manager = AudioManager::new(AudioManagerSettings::default())
-            .expect("Failed to create audio manager");
+        if flags.output == OutputMode::Null {
+            println!("Audio output disabled; playing without sound");
+        }
+        let main_track =
+            || MainTrackBuilder::new().with_effect(SampleTapBuilder::new(flags.sample_tap.clone()));
+        let manager = AudioManager::new(AudioManagerSettings {
+            backend_settings: flags.output,
+            main_track_builder: main_track(),
+            ..Default::default()
+        })
+        .or_else(|e| {
+            // no sound device; keep playing, just without sound
+            println!("Failed to open the sound device ({e}), playing without sound");
+            AudioManager::new(AudioManagerSettings {
+                backend_settings: OutputMode::Null,
+                main_track_builder: main_track(),
+                ..Default::default()
+            })
+        })
+        .expect("Failed to create audio manager");
         let mut musicbrainz_client = MusicBrainzClient::default();
         musicbrainz_client
             .set_user_agent(&format!(
@@ -65,7 +99,11 @@ impl AudioService {
             audio_sender: flags.audio_sender,
             _event_sender: flags.event_sender,
             playing_cache: HashMap::new(),
+            chains: HashMap::new(),
+            effect_settings: EffectSettings::default(),
             musicbrainz_client,
+            progress_cadence: ProgressCadence::default(),
+            library: flags.library,
         }
     }
 }
@@ -81,37 +119,59 @@ impl ServiceLogic<AudioMessage> for AudioService {
                 id,
                 progress_sender,
                 on_end,
+                on_near_end,
                 audio_config,
                 on_loop,
                 maybe_playlist_id,
             } => {
-                if self.playing_cache.contains_key(&id) {
-                    println!("failed to play audio; id is already present in cache");
+                // the same track playing again takes over from the last time; letting go of the
+                // old sound has its task stop it
+                if let Some(old) = self.playing_cache.remove(&id) {
+                    println!("Replacing audio that was already playing");
+                    let _ = old
+                        .on_end
+                        .send(Err(anyhow!("Replaced by the same audio playing again")));
                 }
-                // create arc to share last known position
-                let last_known_pos_arc = Arc::new(AtomicF64::new(0.0));
-                let seek_count_arc = Arc::new(AtomicU64::new(0));
+                let (volume, gain_db) = (audio_config.volume(), audio_config.gain_db());
+                let speed = audio_config.playback_speed();
+                let chain = match self.chains.entry(maybe_playlist_id.clone()) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let preset = self.effect_settings.preset_for(maybe_playlist_id.as_ref());
+                        match EffectChain::new(&mut self.manager, preset) {
+                            Ok(chain) => entry.insert(chain),
+                            Err(e) => {
+                                println!("Failed to create an effect chain: {e}");
+                                return;
+                            }
+                        }
+                    }
+                };
+                chain.set_pitch_correction(speed.pitch_correction());
                 let handle = util::play_audio(
                     id.clone(),
                     progress_sender,
                     self.audio_sender.clone(),
                     audio_config,
-                    &mut self.manager,
-                    Arc::clone(&last_known_pos_arc),
-                    Arc::clone(&seek_count_arc),
+                    chain.track(),
+                    self.progress_cadence,
+                    &self.library,
                 )
                 .await;
                 match handle {
-                    Ok((handle, audio_duration)) => {
+                    Ok((handle, audio_duration, tracker)) => {
                         let handle_wrapper = AudioHandleWrapper {
                             handle,
                             on_end,
+                            on_near_end: Some(on_near_end),
                             audio_duration,
-                            last_known_pos: last_known_pos_arc,
-                            seek_count: seek_count_arc,
+                            tracker,
                             loop_policy: LoopPolicy::NoLooping,
+                            loop_region: None,
                             on_loop,
                             maybe_playlist_id,
+                            volume,
+                            gain_db,
                         };
                         self.playing_cache.insert(id, handle_wrapper);
                     }
@@ -122,7 +182,18 @@ impl ServiceLogic<AudioMessage> for AudioService {
             }
             AudioMessage::AudioFinished { id, result } => {
                 println!("audio finished");
+                if let Err(e) = &result {
+                    println!("Audio stopped early: {e}");
+                }
                 if let Some(handle) = self.playing_cache.remove(&id) {
+                    // nothing else is playing through this playlist's effects
+                    if !self
+                        .playing_cache
+                        .values()
+                        .any(|w| w.maybe_playlist_id == handle.maybe_playlist_id)
+                    {
+                        self.chains.remove(&handle.maybe_playlist_id);
+                    }
                     let _ = handle.on_end.send(result);
                 }
             }
@@ -130,6 +201,7 @@ impl ServiceLogic<AudioMessage> for AudioService {
                 Some(wrapper) => {
                     let mut guard = wrapper.handle.lock();
                     guard.pause(Tween::default());
+                    let _ = wrapper.tracker.send(TrackerEvent::Paused);
                     let _ = result.send(Ok(()));
                 }
                 None => {
@@ -140,6 +212,7 @@ impl ServiceLogic<AudioMessage> for AudioService {
                 Some(wrapper) => {
                     let mut guard = wrapper.handle.lock();
                     guard.resume(Tween::default());
+                    let _ = wrapper.tracker.send(TrackerEvent::Resumed);
                     let _ = result.send(Ok(()));
                 }
                 None => {
@@ -150,6 +223,7 @@ impl ServiceLogic<AudioMessage> for AudioService {
                 Some(wrapper) => {
                     let mut guard = wrapper.handle.lock();
                     guard.stop(Tween::default());
+                    let _ = wrapper.tracker.send(TrackerEvent::Stopped);
                     let _ = result.send(Ok(()));
                 }
                 None => {
@@ -162,32 +236,13 @@ impl ServiceLogic<AudioMessage> for AudioService {
                 result,
             } => match self.playing_cache.get_mut(&id) {
                 Some(wrapper) => {
-                    // handle all guard stuff before using awaits
-                    let (current_pos, last_known_pos) = {
-                        let mut guard = wrapper.handle.lock();
-
-                        let current_pos = guard.position();
-                        let last_known_pos = wrapper.last_known_pos.load(Ordering::Relaxed);
-
-                        // update the current seek count for looping purposes
-                        wrapper.seek_count.fetch_add(1, Ordering::Relaxed);
-
-                        let seek_secs = percentage * wrapper.audio_duration.as_secs_f64();
-                        guard.seek_to(seek_secs);
-
-                        // update history
-                        wrapper.last_known_pos.store(seek_secs, Ordering::Relaxed);
-                        (current_pos, last_known_pos)
-                    };
-
-                    // cushion added for weird floating point stuff
-                    if current_pos < last_known_pos - 0.5 {
-                        // println!("caught loop in audio seek");
-                        let _ = self
-                            .audio_sender
-                            .send(AudioMessage::AudioLooped { id: id.clone() })
-                            .await;
-                    }
+                    let mut guard = wrapper.handle.lock();
+                    let from = guard.position();
+                    let to = percentage * wrapper.audio_duration.as_secs_f64();
+                    guard.seek_to(to);
+                    // the tracker checks whether it looped on the way
+                    let _ = wrapper.tracker.send(TrackerEvent::Seeked { from, to });
+                    let _ = result.send(Ok(()));
                 }
                 None => {
                     let _ = result.send(Err(anyhow!("Audio not currently playing")));
@@ -200,26 +255,28 @@ impl ServiceLogic<AudioMessage> for AudioService {
             } => match self.playing_cache.get_mut(&id) {
                 Some(wrapper) => {
                     println!("setting loop policy @ audio ({loop_policy:?})");
-                    let mut guard = wrapper.handle.lock();
-                    // act on the loop policy
-                    match loop_policy {
-                        LoopPolicy::NoLooping => {
-                            guard.set_loop_region(None);
-                        }
-                        LoopPolicy::Once => {
-                            guard.set_loop_region(0.0..);
-                        }
-                        LoopPolicy::Infinite => {
-                            guard.set_loop_region(0.0..);
-                        }
-                    }
                     // store it in the wrapper
                     wrapper.loop_policy = loop_policy;
+                    // act on the loop policy; an A–B region keeps looping over it
+                    util::apply_loop(wrapper);
                 }
                 None => {
                     let _ = result.send(Err(anyhow!("Audio not currently playing")));
                 }
             },
+            AudioMessage::SetAudioLoopRegion { id, region, result } => {
+                match self.playing_cache.get_mut(&id) {
+                    Some(wrapper) => {
+                        println!("setting loop region @ audio ({region:?})");
+                        wrapper.loop_region = region;
+                        util::apply_loop(wrapper);
+                        let _ = result.send(Ok(()));
+                    }
+                    None => {
+                        let _ = result.send(Err(anyhow!("Audio not currently playing")));
+                    }
+                }
+            }
             AudioMessage::AudioLooped { id } => {
                 println!("Audio looped");
                 if let Some(wrapper) = self.playing_cache.get_mut(&id) {
@@ -232,27 +289,95 @@ impl ServiceLogic<AudioMessage> for AudioService {
                         })
                         .await;
 
-                    let next_policy = wrapper.loop_policy.clone().looped();
-                    // actually act on that policy
-                    if let LoopPolicy::NoLooping = next_policy {
-                        // stop looping
-                        let mut guard = wrapper.handle.lock();
-                        guard.set_loop_region(None);
+                    // an A–B region counts down first; once it's done, the policy takes over again
+                    match wrapper.loop_region {
+                        Some(region) => wrapper.loop_region = region.looped(),
+                        None => wrapper.loop_policy = wrapper.loop_policy.looped(),
                     }
-
-                    wrapper.loop_policy = next_policy;
+                    // actually act on that policy
+                    util::apply_loop(wrapper);
+                }
+            }
+            AudioMessage::AudioNearEnd { id, ends_at } => {
+                if let Some(wrapper) = self.playing_cache.get_mut(&id)
+                    // a looping track doesn't end
+                    && let LoopPolicy::NoLooping = wrapper.loop_policy
+                    && wrapper.loop_region.is_none()
+                    && let Some(on_near_end) = wrapper.on_near_end.take()
+                {
+                    let _ = on_near_end.send(ends_at);
+                }
+            }
+            AudioMessage::FadeOutAudio { id, tween } => {
+                if let Some(wrapper) = self.playing_cache.get(&id) {
+                    let mut guard = wrapper.handle.lock();
+                    guard.set_volume(Decibels::SILENCE, tween);
                 }
             }
             AudioMessage::SetAudioVolume { id, volume, result } => {
                 if let Some(wrapper) = self.playing_cache.get_mut(&id) {
+                    wrapper.volume = volume;
                     let mut guard = wrapper.handle.lock();
-                    guard.set_volume(util::linear_to_db(volume) as f32, Tween::default());
+                    guard.set_volume(
+                        util::output_db(wrapper.volume, wrapper.gain_db),
+                        Tween::default(),
+                    );
 
                     let _ = result.send(Ok(()));
                 } else {
                     let _ = result.send(Err(anyhow!("Audio not currently playing")));
                 }
             }
+            AudioMessage::SetAudioGain { id, gain_db } => {
+                if let Some(wrapper) = self.playing_cache.get_mut(&id) {
+                    wrapper.gain_db = gain_db;
+                    let mut guard = wrapper.handle.lock();
+                    guard.set_volume(
+                        util::output_db(wrapper.volume, wrapper.gain_db),
+                        Tween::default(),
+                    );
+                }
+            }
+            AudioMessage::SetPlaybackRate {
+                maybe_playlist_id,
+                speed,
+            } => {
+                for wrapper in self
+                    .playing_cache
+                    .values_mut()
+                    .filter(|w| w.maybe_playlist_id == maybe_playlist_id)
+                {
+                    wrapper
+                        .handle
+                        .lock()
+                        .set_playback_rate(speed.rate, Tween::default());
+                    let _ = wrapper.tracker.send(TrackerEvent::RateChanged(speed.rate));
+                }
+                if let Some(chain) = self.chains.get_mut(&maybe_playlist_id) {
+                    chain.set_pitch_correction(speed.pitch_correction());
+                }
+            }
+            AudioMessage::SetProgressCadence { cadence } => {
+                for wrapper in self.playing_cache.values() {
+                    let _ = wrapper.tracker.send(TrackerEvent::CadenceChanged(cadence));
+                }
+                self.progress_cadence = cadence;
+            }
+            AudioMessage::SetLibrary { library } => self.library = library,
+            AudioMessage::SetEffectSettings { settings } => {
+                for (playlist_id, chain) in self.chains.iter_mut() {
+                    chain.apply(settings.preset_for(playlist_id.as_ref()), Tween::default());
+                }
+                self.effect_settings = settings;
+            }
+            AudioMessage::PreviewEffectPreset {
+                playlist_id,
+                preset,
+            } => {
+                if let Some(chain) = self.chains.get_mut(&playlist_id) {
+                    chain.apply(preset.as_ref(), Tween::default());
+                }
+            }
             AudioMessage::GetMusicBrainzClient { result } => {
                 let _ = result.send(self.musicbrainz_client.clone());
             }
@@ -0,0 +1,110 @@
+// The backend kira plays through. Normally that's the sound device, but machines without one
+// (CI, servers, containers) get a null output instead, which keeps time without making sound.
+
+use std::{
+    env,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use kira::backend::{
+    Backend, Renderer,
+    cpal::{CpalBackend, CpalBackendSettings, Error as CpalError},
+};
+
+use crate::service::audio::enums::OutputMode;
+
+pub const NO_AUDIO_FLAG: &str = "--no-audio";
+pub const NO_AUDIO_ENV_VAR: &str = "PEANUT_NO_AUDIO";
+
+// the null output's pretend sample rate
+const NULL_SAMPLE_RATE: u32 = 48000;
+// how often the null output renders
+const NULL_TICK: Duration = Duration::from_millis(10);
+
+/// Works out the output from the command line flag or environment variable.
+pub fn startup_output_mode(args: impl IntoIterator<Item = String>) -> OutputMode {
+    let flagged = args.into_iter().any(|arg| arg == NO_AUDIO_FLAG);
+    let env_set = env::var_os(NO_AUDIO_ENV_VAR).is_some_and(|v| !v.is_empty() && v != "0");
+    if flagged || env_set {
+        OutputMode::Null
+    } else {
+        OutputMode::Device
+    }
+}
+
+/// Either the sound device or the null output.
+pub enum OutputBackend {
+    Device(CpalBackend),
+    Null(NullBackend),
+}
+impl Backend for OutputBackend {
+    type Settings = OutputMode;
+    type Error = CpalError;
+
+    fn setup(mode: OutputMode, internal_buffer_size: usize) -> Result<(Self, u32), CpalError> {
+        match mode {
+            OutputMode::Device => {
+                let (backend, sample_rate) =
+                    CpalBackend::setup(CpalBackendSettings::default(), internal_buffer_size)?;
+                Ok((Self::Device(backend), sample_rate))
+            }
+            OutputMode::Null => Ok((
+                Self::Null(NullBackend::new(internal_buffer_size)),
+                NULL_SAMPLE_RATE,
+            )),
+        }
+    }
+    fn start(&mut self, renderer: Renderer) -> Result<(), CpalError> {
+        match self {
+            Self::Device(backend) => backend.start(renderer),
+            Self::Null(backend) => {
+                backend.start(renderer);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Renders audio on its own thread at the speed it would play, and throws it away.
+pub struct NullBackend {
+    internal_buffer_size: usize,
+    stop_flag: Arc<AtomicBool>,
+}
+impl NullBackend {
+    fn new(internal_buffer_size: usize) -> Self {
+        Self {
+            internal_buffer_size,
+            stop_flag: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    fn start(&mut self, mut renderer: Renderer) {
+        let stop_flag = Arc::clone(&self.stop_flag);
+        // stereo
+        let mut buffer = vec![0.0; self.internal_buffer_size * 2];
+        thread::spawn(move || {
+            let started = Instant::now();
+            let mut frames_rendered: u64 = 0;
+            while !stop_flag.load(Ordering::Relaxed) {
+                thread::sleep(NULL_TICK);
+                // catch up to the clock, so sounds take as long as they would on a device
+                let due = (started.elapsed().as_secs_f64() * NULL_SAMPLE_RATE as f64) as u64;
+                while frames_rendered < due {
+                    let frames = (due - frames_rendered).min(buffer.len() as u64 / 2) as usize;
+                    renderer.on_start_processing();
+                    renderer.process(&mut buffer[..frames * 2], 2);
+                    frames_rendered += frames as u64;
+                }
+            }
+        });
+    }
+}
+impl Drop for NullBackend {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+    }
+}
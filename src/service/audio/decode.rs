@@ -0,0 +1,134 @@
+// Module for reading audio files directly with symphonia (outside of kira playback)
+
+use std::{fs::File, io::ErrorKind, path::Path, time::Duration};
+
+use anyhow::anyhow;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::DecoderOptions,
+    errors::Error as SymphoniaError,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, StandardTagKey, Tag},
+    probe::Hint,
+};
+
+use crate::service::audio::structs::AudioFileInfo;
+
+/// Reads the tags and duration of the given audio file. Blocking; run in a blocking task.
+pub fn read_audio_file_info(path: &Path) -> anyhow::Result<AudioFileInfo> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    // tags can live both before the container (ie. ID3) and inside of it
+    let mut tags: Vec<Tag> = Vec::new();
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        tags.extend(revision.tags().iter().cloned());
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.extend(revision.tags().iter().cloned());
+    }
+
+    let mut info = AudioFileInfo::default();
+    for tag in tags {
+        let value = tag.value.to_string().trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+        match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => info.title = Some(value),
+            Some(StandardTagKey::Artist) if !info.artists.contains(&value) => {
+                info.artists.push(value)
+            }
+            Some(StandardTagKey::Album) => info.album = Some(value),
+            Some(StandardTagKey::AlbumArtist) => info.album_artist = Some(value),
+            _ => {}
+        }
+    }
+
+    // duration from the default track's stream parameters
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        if let (Some(time_base), Some(n_frames)) = (params.time_base, params.n_frames) {
+            let time = time_base.calc_time(n_frames);
+            info.duration =
+                Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac));
+        } else if let (Some(sample_rate), Some(n_frames)) = (params.sample_rate, params.n_frames) {
+            info.duration = Some(Duration::from_secs_f64(
+                n_frames as f64 / sample_rate as f64,
+            ));
+        }
+    }
+
+    Ok(info)
+}
+
+/// Decodes the whole file, handing each decoded packet's interleaved samples to `on_samples`
+/// along with the sample rate and channel count. Blocking; run in a blocking task.
+pub fn decode_samples(
+    path: &Path,
+    mut on_samples: impl FnMut(u32, usize, &[f32]),
+) -> anyhow::Result<()> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?
+        .format;
+    let track = format
+        .default_track()
+        .ok_or(anyhow!("No audio track in {}", path.display()))?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut sample_buffer: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a corrupt packet or two shouldn't fail the whole track
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count();
+        if sample_buffer
+            .as_ref()
+            .is_none_or(|b| b.capacity() < decoded.capacity() * channels)
+        {
+            sample_buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buffer = sample_buffer.as_mut().unwrap();
+        buffer.copy_interleaved_ref(decoded);
+        on_samples(spec.rate, channels, buffer.samples());
+    }
+    Ok(())
+}
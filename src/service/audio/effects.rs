@@ -0,0 +1,119 @@
+// The effect chain audio is played through. Effects can't be added to or removed from a mixer
+// track once it's made, so every chain has all of them, and unused ones are set to do nothing.
+
+mod pitch_shift;
+pub mod sample_tap;
+
+use kira::{
+    AudioManager, Decibels, Mix, Tween,
+    effect::{
+        compressor::{CompressorBuilder, CompressorHandle},
+        eq_filter::{EqFilterBuilder, EqFilterHandle, EqFilterKind},
+        filter::{FilterBuilder, FilterHandle, FilterMode},
+    },
+    track::{TrackBuilder, TrackHandle},
+};
+
+use crate::service::audio::{
+    backend::OutputBackend,
+    effects::pitch_shift::{PitchShiftBuilder, PitchShiftHandle},
+    structs::{EQ_BANDS, EffectPreset},
+};
+
+// width of each equalizer band; about an octave
+const EQ_Q: f64 = 1.41;
+// cutoffs of the filters while they're off; only used so they're valid
+const HIGH_PASS_OFF_HZ: f64 = 20.0;
+const LOW_PASS_OFF_HZ: f64 = 20000.0;
+
+/// A mixer track with a pitch shifter (to undo a playback speed's change in pitch), high pass,
+/// equalizer, low pass and compressor, in that order.
+pub struct EffectChain {
+    track: TrackHandle,
+    pitch_shift: PitchShiftHandle,
+    high_pass: FilterHandle,
+    eq: Vec<EqFilterHandle>,
+    low_pass: FilterHandle,
+    compressor: CompressorHandle,
+}
+impl EffectChain {
+    pub fn new(
+        manager: &mut AudioManager<OutputBackend>,
+        preset: Option<&EffectPreset>,
+    ) -> anyhow::Result<Self> {
+        // removing the chain shouldn't cut off what's still playing through it
+        let mut builder = TrackBuilder::new().persist_until_sounds_finish(true);
+        let pitch_shift = builder.add_effect(PitchShiftBuilder::new(1.0));
+        let high_pass = builder.add_effect(
+            FilterBuilder::new()
+                .mode(FilterMode::HighPass)
+                .cutoff(HIGH_PASS_OFF_HZ)
+                .mix(Mix::DRY),
+        );
+        let eq = EQ_BANDS
+            .iter()
+            .map(|frequency| {
+                builder.add_effect(EqFilterBuilder::new(
+                    EqFilterKind::Bell,
+                    *frequency,
+                    Decibels::IDENTITY,
+                    EQ_Q,
+                ))
+            })
+            .collect();
+        let low_pass = builder.add_effect(
+            FilterBuilder::new()
+                .mode(FilterMode::LowPass)
+                .cutoff(LOW_PASS_OFF_HZ)
+                .mix(Mix::DRY),
+        );
+        let compressor = builder.add_effect(CompressorBuilder::new().mix(Mix::DRY));
+        let mut chain = Self {
+            track: manager.add_sub_track(builder)?,
+            pitch_shift,
+            high_pass,
+            eq,
+            low_pass,
+            compressor,
+        };
+        chain.apply(preset, Tween::default());
+        Ok(chain)
+    }
+    // The track to play audio on
+    pub fn track(&mut self) -> &mut TrackHandle {
+        &mut self.track
+    }
+    // Shifts the pitch of everything on the track; 1 leaves it alone
+    pub fn set_pitch_correction(&mut self, ratio: f64) {
+        self.pitch_shift.set_ratio(ratio);
+    }
+    /// Switches every effect over to the preset's settings. No preset turns them all off.
+    pub fn apply(&mut self, preset: Option<&EffectPreset>, tween: Tween) {
+        let flat = EffectPreset::flat("");
+        let preset = preset.unwrap_or(&flat);
+        for (handle, gain) in self.eq.iter_mut().zip(preset.eq_gains) {
+            handle.set_gain(Decibels(gain as f32), tween);
+        }
+        for (handle, cutoff) in [
+            (&mut self.high_pass, preset.high_pass),
+            (&mut self.low_pass, preset.low_pass),
+        ] {
+            match cutoff {
+                Some(cutoff) => {
+                    handle.set_cutoff(cutoff, tween);
+                    handle.set_mix(Mix::WET, tween);
+                }
+                None => handle.set_mix(Mix::DRY, tween),
+            }
+        }
+        match preset.compressor {
+            Some(compressor) => {
+                self.compressor
+                    .set_threshold(compressor.threshold_db, tween);
+                self.compressor.set_ratio(compressor.ratio, tween);
+                self.compressor.set_mix(Mix::WET, tween);
+            }
+            None => self.compressor.set_mix(Mix::DRY, tween),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// A pitch shifter, used to keep sped up or slowed down audio at its original pitch. Two read
+// heads sweep through a short delay line faster or slower than it's written to, each faded out
+// as it wraps around while the other is at full volume.
+
+use std::{
+    f64::consts::PI,
+    sync::{Arc, atomic::Ordering},
+};
+
+use atomic_float::AtomicF64;
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+
+// length of the delay line the heads sweep through. shorter is smoother for speech, but rougher
+// for low notes
+const WINDOW_SECS: f64 = 0.05;
+
+pub struct PitchShiftBuilder {
+    ratio: f64,
+}
+impl PitchShiftBuilder {
+    // a ratio of 2 is an octave up, 0.5 an octave down
+    pub fn new(ratio: f64) -> Self {
+        Self { ratio }
+    }
+}
+impl EffectBuilder for PitchShiftBuilder {
+    type Handle = PitchShiftHandle;
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let ratio = Arc::new(AtomicF64::new(self.ratio));
+        let effect = PitchShift {
+            ratio: Arc::clone(&ratio),
+            buffer: Vec::new(),
+            write_pos: 0,
+            delay: 0.0,
+            window: 0.0,
+        };
+        (Box::new(effect), PitchShiftHandle { ratio })
+    }
+}
+
+pub struct PitchShiftHandle {
+    ratio: Arc<AtomicF64>,
+}
+impl PitchShiftHandle {
+    pub fn set_ratio(&mut self, ratio: f64) {
+        self.ratio.store(ratio, Ordering::Relaxed);
+    }
+}
+
+struct PitchShift {
+    ratio: Arc<AtomicF64>,
+    buffer: Vec<Frame>,
+    write_pos: usize,
+    // how far the first head is behind the newest frame; the second is half a window further
+    delay: f64,
+    // in frames
+    window: f64,
+}
+impl PitchShift {
+    fn resize(&mut self, sample_rate: u32) {
+        self.window = (WINDOW_SECS * sample_rate as f64).round();
+        // room for the interpolation on either side
+        self.buffer = vec![Frame::ZERO; self.window as usize + 2];
+        self.write_pos = 0;
+        self.delay = 0.0;
+    }
+    // the frame `delay` frames before the newest one, interpolated between frames
+    fn read(&self, delay: f64) -> Frame {
+        let len = self.buffer.len();
+        let pos = self.write_pos as f64 - delay;
+        let index = pos.floor().rem_euclid(len as f64) as usize;
+        let fraction = (pos - pos.floor()) as f32;
+        self.buffer[index] * (1.0 - fraction) + self.buffer[(index + 1) % len] * fraction
+    }
+}
+impl Effect for PitchShift {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.resize(sample_rate);
+    }
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.resize(sample_rate);
+    }
+    fn process(&mut self, input: &mut [Frame], _dt: f64, _info: &Info) {
+        let ratio = self.ratio.load(Ordering::Relaxed);
+        let len = self.buffer.len();
+        for frame in input.iter_mut() {
+            self.buffer[self.write_pos] = *frame;
+            // nothing to shift; pass the audio straight through so it isn't delayed
+            if (ratio - 1.0).abs() > f64::EPSILON {
+                self.delay = (self.delay + 1.0 - ratio).rem_euclid(self.window);
+                let mut out = Frame::ZERO;
+                for offset in [0.0, self.window / 2.0] {
+                    let delay = (self.delay + offset) % self.window;
+                    // the two gains always add up to 1
+                    let gain = (PI * delay / self.window).sin().powi(2);
+                    out += self.read(delay) * gain as f32;
+                }
+                *frame = out;
+            }
+            self.write_pos = (self.write_pos + 1) % len;
+        }
+    }
+}
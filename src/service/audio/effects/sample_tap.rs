@@ -0,0 +1,46 @@
+// Copies the audio passing through it into a `SampleTap` for the visualizer. The audio itself is
+// never touched, so it adds no delay.
+
+use kira::{
+    Frame,
+    effect::{Effect, EffectBuilder},
+    info::Info,
+};
+
+use crate::service::audio::structs::SampleTap;
+
+pub struct SampleTapBuilder {
+    tap: SampleTap,
+}
+impl SampleTapBuilder {
+    pub fn new(tap: SampleTap) -> Self {
+        Self { tap }
+    }
+}
+impl EffectBuilder for SampleTapBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        (Box::new(SampleTapEffect { tap: self.tap }), ())
+    }
+}
+
+struct SampleTapEffect {
+    tap: SampleTap,
+}
+impl Effect for SampleTapEffect {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.tap.set_sample_rate(sample_rate);
+    }
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.tap.set_sample_rate(sample_rate);
+    }
+    fn process(&mut self, input: &mut [Frame], _dt: f64, _info: &Info) {
+        if !self.tap.is_enabled() {
+            return;
+        }
+        for frame in input.iter() {
+            self.tap.push((frame.left + frame.right) / 2.0);
+        }
+    }
+}
@@ -1,13 +1,32 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use kira::{Easing, Tween};
 use musicbrainz_rs::MusicBrainzClient;
 use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::service::{
-    audio::structs::{AudioConfig, AudioProgress},
+    audio::structs::{
+        AudioConfig, AudioProgress, EffectPreset, EffectSettings, LoopRegion, PlaybackSpeed,
+    },
+    file::library::Library,
     id::structs::Id,
     playlist::{PlaylistSender, structs::Album},
 };
 
+/// Where audio is played (see `backend::OutputBackend`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    // the system's default sound device
+    #[default]
+    Device,
+    // nothing; audio still plays in real time, it just isn't heard
+    Null,
+}
+
 #[derive(Debug)]
 pub enum AudioMessage {
     PlayAudio {
@@ -16,6 +35,9 @@ pub enum AudioMessage {
         audio_config: AudioConfig,
         progress_sender: mpsc::Sender<(Id, AudioProgress)>,
         on_end: oneshot::Sender<anyhow::Result<()>>,
+        // Fired once the track is close enough to its end for the next one to start (see
+        // `AudioConfig::end_notice`). Given: when the track will end.
+        on_near_end: oneshot::Sender<Instant>,
         on_loop: PlaylistSender,
     },
     AudioFinished {
@@ -44,17 +66,62 @@ pub enum AudioMessage {
         loop_policy: LoopPolicy,
         result: oneshot::Sender<anyhow::Result<()>>,
     },
+    // Loops part of the audio (an A–B loop) instead of what its loop policy says. None goes
+    // back to the policy.
+    SetAudioLoopRegion {
+        id: Id,
+        region: Option<LoopRegion>,
+        result: oneshot::Sender<anyhow::Result<()>>,
+    },
     AudioLooped {
         id: Id,
     },
+    AudioNearEnd {
+        id: Id,
+        ends_at: Instant,
+    },
+    // Fades the audio out to silence. It keeps playing until it ends on its own.
+    FadeOutAudio {
+        id: Id,
+        tween: Tween,
+    },
     SetAudioVolume {
         id: Id,
         volume: f64,
         result: oneshot::Sender<anyhow::Result<()>>,
     },
+    // Changes the loudness normalization gain of audio that's already playing.
+    SetAudioGain {
+        id: Id,
+        gain_db: f64,
+    },
+    // Changes the speed of everything a playlist is playing (None for audio played outside of
+    // one). Audio played after this should be given the speed in its `AudioConfig`.
+    SetPlaybackRate {
+        maybe_playlist_id: Option<Id>,
+        speed: PlaybackSpeed,
+    },
+    // Replaces the effect presets, switching everything playing over to its (new) preset.
+    SetEffectSettings {
+        settings: EffectSettings,
+    },
+    // Plays a playlist's audio through a preset while it's being edited, without storing it.
+    // No playlist id means audio played outside of a playlist.
+    PreviewEffectPreset {
+        playlist_id: Option<Id>,
+        preset: Option<EffectPreset>,
+    },
     GetMusicBrainzClient {
         result: oneshot::Sender<MusicBrainzClient>,
     },
+    // How often playing audio reports its progress, for everything playing now and after.
+    SetProgressCadence {
+        cadence: ProgressCadence,
+    },
+    // Where track files are played from, after switching libraries.
+    SetLibrary {
+        library: Library,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -83,6 +150,109 @@ impl LoopPolicy {
     }
 }
 
+/// How often a playing sound's progress is reported (see `tracker::PositionTracker`). Loops and
+/// the end of a sound are caught on time either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressCadence {
+    // the window can be seen, so the progress bar should move smoothly
+    #[default]
+    Visible,
+    Hidden,
+}
+impl ProgressCadence {
+    pub fn interval(self) -> Duration {
+        match self {
+            Self::Visible => Duration::from_millis(100),
+            Self::Hidden => Duration::from_secs(1),
+        }
+    }
+}
+
+/// How many more times an A–B loop region (see `structs::LoopRegion`) is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopCount {
+    Times(u32),
+    Forever,
+}
+impl LoopCount {
+    // the counts offered in the player
+    pub const CHOICES: [Self; 5] = [
+        Self::Times(1),
+        Self::Times(2),
+        Self::Times(4),
+        Self::Times(8),
+        Self::Forever,
+    ];
+
+    // the count after the region looped once; None when it's done
+    pub fn looped(self) -> Option<Self> {
+        match self {
+            Self::Times(times) if times > 1 => Some(Self::Times(times - 1)),
+            Self::Times(_) => None,
+            Self::Forever => Some(Self::Forever),
+        }
+    }
+}
+impl fmt::Display for LoopCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Times(1) => write!(f, "Once"),
+            Self::Times(times) => write!(f, "{times} times"),
+            Self::Forever => write!(f, "Forever"),
+        }
+    }
+}
+
+/// How the volume changes over a crossfade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FadeCurve {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+impl FadeCurve {
+    pub const ALL: [Self; 4] = [Self::Linear, Self::EaseIn, Self::EaseOut, Self::EaseInOut];
+
+    pub fn easing(self) -> Easing {
+        match self {
+            Self::Linear => Easing::Linear,
+            Self::EaseIn => Easing::InPowi(2),
+            Self::EaseOut => Easing::OutPowi(2),
+            Self::EaseInOut => Easing::InOutPowi(2),
+        }
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::EaseIn => "Ease in",
+            Self::EaseOut => "Ease out",
+            Self::EaseInOut => "Smooth",
+        }
+    }
+}
+
+/// Which loudness tracks are normalized by. Album mode keeps the differences between tracks on
+/// the same album, so quiet intros stay quiet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalizationMode {
+    Off,
+    #[default]
+    Track,
+    Album,
+}
+impl NormalizationMode {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Track, Self::Album];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Track => "Per track",
+            Self::Album => "Per album",
+        }
+    }
+}
+
 /// Confidence enum used for scrubbing Youtube track titles.
 /// Each variant has a distinct confidence level:
 /// High: has "official" in title, meaning uploader is likely artist
@@ -0,0 +1,233 @@
+// Loudness measurement (ITU-R BS.1770 / EBU R128), used to even out the volume between tracks.
+
+use std::{path::Path, time::Duration};
+
+use anyhow::anyhow;
+
+use crate::service::audio::{decode, structs::Loudness};
+
+// loudness everything is normalized to (ReplayGain 2.0's reference level)
+const TARGET_LUFS: f64 = -18.0;
+// the gain is capped so the loudest peak stays under this
+const MAX_TRUE_PEAK_DB: f64 = -1.0;
+// and so quiet tracks aren't blown up too far
+const MAX_GAIN_DB: f64 = 12.0;
+
+// gating from BS.1770-4
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+// 400ms blocks overlapping by 75%, so they're built out of four 100ms segments
+const SEGMENTS_PER_BLOCK: usize = 4;
+
+// true peak oversampling
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+/// Decodes the whole file and measures its loudness. Blocking; run in a blocking task.
+pub fn analyze_file(path: &Path) -> anyhow::Result<Loudness> {
+    let mut meter: Option<LoudnessMeter> = None;
+    decode::decode_samples(path, |rate, channels, samples| {
+        meter
+            .get_or_insert_with(|| LoudnessMeter::new(rate, channels))
+            .push_interleaved(samples);
+    })?;
+    meter.and_then(|m| m.finish()).ok_or(anyhow!(
+        "{} is silent or too short to measure",
+        path.display()
+    ))
+}
+
+/// The gain that brings audio with the given loudness to the target level.
+pub fn normalization_gain(loudness: &Loudness) -> f64 {
+    (TARGET_LUFS - loudness.integrated_lufs())
+        .min(MAX_TRUE_PEAK_DB - loudness.true_peak_db())
+        .min(MAX_GAIN_DB)
+}
+
+/// Combines the loudness of several tracks (ie. an album) into one, weighting each by its length.
+pub fn combined_loudness<'a>(
+    parts: impl IntoIterator<Item = (&'a Loudness, Duration)>,
+) -> Option<Loudness> {
+    let mut energy = 0.0;
+    let mut total_secs = 0.0;
+    let mut peak = f64::NEG_INFINITY;
+    for (loudness, length) in parts {
+        let secs = length.as_secs_f64().max(1.0);
+        energy += secs * 10f64.powf(loudness.integrated_lufs() / 10.0);
+        total_secs += secs;
+        peak = peak.max(loudness.true_peak_db());
+    }
+    if total_secs == 0.0 {
+        return None;
+    }
+    Some(Loudness::new(10.0 * (energy / total_secs).log10(), peak))
+}
+
+// Transposed direct form II biquad
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z1: f64,
+    z2: f64,
+}
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z1;
+        self.z1 = self.b[1] * x - self.a[0] * y + self.z2;
+        self.z2 = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+// The two stage "K-weighting" filter: a high shelf for the head, then a high pass. Coefficients
+// are worked out for the sample rate, as in BS.1770's reference (which only lists 48kHz).
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let f0 = 1681.974450955533;
+    let gain = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+    [shelf, high_pass]
+}
+
+// Windowed sinc interpolation filter, split into one set of taps per oversampled phase. Each
+// phase is normalized so a constant signal stays the same.
+fn oversampling_phases() -> [[f64; TAPS_PER_PHASE]; OVERSAMPLING] {
+    let len = OVERSAMPLING * TAPS_PER_PHASE;
+    let center = (len - 1) as f64 / 2.0;
+    let mut phases = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+    for (phase, taps) in phases.iter_mut().enumerate() {
+        for (j, tap) in taps.iter_mut().enumerate() {
+            let n = (phase + OVERSAMPLING * j) as f64;
+            let x = (n - center) / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+            };
+            // blackman window
+            let w = n / (len - 1) as f64;
+            let window = 0.42 - 0.5 * (2.0 * std::f64::consts::PI * w).cos()
+                + 0.08 * (4.0 * std::f64::consts::PI * w).cos();
+            *tap = sinc * window;
+        }
+        let sum: f64 = taps.iter().sum();
+        taps.iter_mut().for_each(|t| *t /= sum);
+    }
+    phases
+}
+
+struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    // mean square of each 100ms segment, summed over the channels
+    segments: Vec<f64>,
+    segment_len: usize,
+    segment_pos: usize,
+    segment_sum: f64,
+    phases: [[f64; TAPS_PER_PHASE]; OVERSAMPLING],
+    // the last few samples of each channel, newest first
+    history: Vec<[f64; TAPS_PER_PHASE]>,
+    peak: f64,
+}
+impl LoudnessMeter {
+    fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            channels,
+            filters: vec![k_weighting(rate); channels],
+            segments: Vec::new(),
+            segment_len: (rate as usize / 10).max(1),
+            segment_pos: 0,
+            segment_sum: 0.0,
+            phases: oversampling_phases(),
+            history: vec![[0.0; TAPS_PER_PHASE]; channels],
+            peak: 0.0,
+        }
+    }
+    fn push_interleaved(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let x = *sample as f64;
+                let [shelf, high_pass] = &mut self.filters[channel];
+                let y = high_pass.process(shelf.process(x));
+                self.segment_sum += y * y;
+
+                let history = &mut self.history[channel];
+                history.copy_within(0..TAPS_PER_PHASE - 1, 1);
+                history[0] = x;
+                for taps in self.phases.iter() {
+                    let value: f64 = taps.iter().zip(history.iter()).map(|(t, h)| t * h).sum();
+                    self.peak = self.peak.max(value.abs());
+                }
+                self.peak = self.peak.max(x.abs());
+            }
+            self.segment_pos += 1;
+            if self.segment_pos == self.segment_len {
+                self.segments
+                    .push(self.segment_sum / self.segment_len as f64);
+                self.segment_pos = 0;
+                self.segment_sum = 0.0;
+            }
+        }
+    }
+    // None if there wasn't a single block loud enough to pass the gates
+    fn finish(self) -> Option<Loudness> {
+        let blocks: Vec<f64> = self
+            .segments
+            .windows(SEGMENTS_PER_BLOCK)
+            .map(|w| w.iter().sum::<f64>() / SEGMENTS_PER_BLOCK as f64)
+            .collect();
+        let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        let above_absolute: Vec<f64> = blocks
+            .into_iter()
+            .filter(|z| loudness(*z) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if above_absolute.is_empty() {
+            return None;
+        }
+        let relative_gate = loudness(mean(&above_absolute)) + RELATIVE_GATE_LU;
+        let gated: Vec<f64> = above_absolute
+            .into_iter()
+            .filter(|z| loudness(*z) > relative_gate)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+        Some(Loudness::new(
+            loudness(mean(&gated)),
+            20.0 * self.peak.max(f64::MIN_POSITIVE).log10(),
+        ))
+    }
+}
@@ -0,0 +1,94 @@
+// Finds the silence at the start and end of tracks (ie. quiet intros, or talking before the
+// music starts), so it can be skipped.
+
+use std::{path::Path, time::Duration};
+
+use anyhow::anyhow;
+
+use crate::service::audio::{decode, structs::Trim};
+
+// anything quieter than this counts as silence (-50 dBFS)
+const SILENCE_THRESHOLD: f32 = 0.003;
+// audio is checked in windows this long
+const WINDOW: Duration = Duration::from_millis(50);
+// kept on either side of the sound, so fade ins and tails aren't cut off
+const PADDING: Duration = Duration::from_millis(250);
+// silence shorter than this is part of the track
+const MIN_SILENCE: Duration = Duration::from_secs(1);
+
+/// Decodes the whole file and finds how much of its start and end is silent. Blocking; run in a
+/// blocking task.
+pub fn find_silence(path: &Path) -> anyhow::Result<Trim> {
+    let mut detector: Option<SilenceDetector> = None;
+    decode::decode_samples(path, |rate, channels, samples| {
+        detector
+            .get_or_insert_with(|| SilenceDetector::new(rate, channels))
+            .push_interleaved(samples);
+    })?;
+    detector
+        .and_then(|d| d.finish())
+        .ok_or(anyhow!("{} is silent or empty", path.display()))
+}
+
+struct SilenceDetector {
+    rate: u32,
+    channels: usize,
+    window_frames: usize,
+    // loudest sample in the current window
+    window_peak: f32,
+    frames_in_window: usize,
+    windows: usize,
+    first_sound: Option<usize>,
+    last_sound: Option<usize>,
+}
+impl SilenceDetector {
+    fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            rate,
+            channels,
+            window_frames: ((rate as f64 * WINDOW.as_secs_f64()) as usize).max(1),
+            window_peak: 0.0,
+            frames_in_window: 0,
+            windows: 0,
+            first_sound: None,
+            last_sound: None,
+        }
+    }
+    fn push_interleaved(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            self.window_peak = self.window_peak.max(peak);
+            self.frames_in_window += 1;
+            if self.frames_in_window == self.window_frames {
+                self.end_window();
+            }
+        }
+    }
+    fn end_window(&mut self) {
+        if self.window_peak > SILENCE_THRESHOLD {
+            self.first_sound.get_or_insert(self.windows);
+            self.last_sound = Some(self.windows);
+        }
+        self.windows += 1;
+        self.window_peak = 0.0;
+        self.frames_in_window = 0;
+    }
+    fn finish(mut self) -> Option<Trim> {
+        let total_frames = self.windows * self.window_frames + self.frames_in_window;
+        if self.frames_in_window > 0 {
+            self.end_window();
+        }
+        let to_time = |frames: usize| Duration::from_secs_f64(frames as f64 / self.rate as f64);
+        let total = to_time(total_frames);
+        let sound_start = to_time(self.first_sound? * self.window_frames);
+        let sound_end = to_time((self.last_sound? + 1) * self.window_frames).min(total);
+        let trimmed = |silence: Duration| match silence.saturating_sub(PADDING) {
+            silence if silence >= MIN_SILENCE => silence,
+            _ => Duration::ZERO,
+        };
+        Some(Trim {
+            start: trimmed(sound_start),
+            end: trimmed(total - sound_end),
+        })
+    }
+}
@@ -0,0 +1,126 @@
+// Turns the newest output samples (see `structs::SampleTap`) into the visualizer's spectrum bars
+// and level meter.
+
+use std::f32::consts::PI;
+
+use crate::service::audio::structs::Spectrum;
+
+/// How many samples are analyzed each frame; a power of two for the FFT.
+pub const ANALYSIS_SIZE: usize = 2048;
+pub const SPECTRUM_BANDS: usize = 32;
+// the bands are spread evenly (in pitch) between these
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 16000.0;
+// anything quieter than this is drawn as nothing
+const FLOOR_DB: f32 = -70.0;
+// how far bars can drop each frame, so they fall smoothly instead of flickering
+const FALL_PER_FRAME: f32 = 0.04;
+
+/// Measures `samples` (`ANALYSIS_SIZE` of them, oldest first).
+pub fn analyze(samples: &[f32], sample_rate: u32) -> Spectrum {
+    if samples.is_empty() || sample_rate == 0 {
+        return Spectrum::silent();
+    }
+    let sum_squares: f32 = samples.iter().map(|s| s * s).sum();
+    let rms = (sum_squares / samples.len() as f32).sqrt();
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+    // windowed so the edges of the buffer don't smear into every band
+    let size = samples.len().next_power_of_two();
+    let window =
+        |i: usize| 0.5 - 0.5 * (2.0 * PI * i as f32 / (samples.len() - 1).max(1) as f32).cos();
+    let mut bins: Vec<(f32, f32)> = (0..size)
+        .map(|i| (samples.get(i).map_or(0.0, |s| s * window(i)), 0.0))
+        .collect();
+    fft(&mut bins);
+    // a full scale sine comes out at 0 dB
+    let window_sum: f32 = (0..samples.len()).map(window).sum();
+    let magnitude = |bin: usize| {
+        let (re, im) = bins[bin];
+        (re * re + im * im).sqrt() * 2.0 / window_sum
+    };
+
+    let bin_width = sample_rate as f32 / size as f32;
+    let bands = (0..SPECTRUM_BANDS)
+        .map(|band| {
+            let edge = |band: usize| {
+                MIN_FREQUENCY
+                    * (MAX_FREQUENCY / MIN_FREQUENCY).powf(band as f32 / SPECTRUM_BANDS as f32)
+            };
+            let first = ((edge(band) / bin_width) as usize).clamp(1, size / 2 - 1);
+            let last = ((edge(band + 1) / bin_width) as usize).clamp(first + 1, size / 2);
+            let loudest = (first..last).map(magnitude).fold(0.0f32, f32::max);
+            to_height(loudest)
+        })
+        .collect();
+    Spectrum {
+        bands,
+        level: to_height(rms),
+        peak: to_height(peak),
+    }
+}
+
+impl Spectrum {
+    pub fn silent() -> Self {
+        Self {
+            bands: vec![0.0; SPECTRUM_BANDS],
+            level: 0.0,
+            peak: 0.0,
+        }
+    }
+    // This frame, with anything that dropped since `previous` only falling part of the way
+    pub fn smoothed(mut self, previous: &Spectrum) -> Self {
+        let fall = |new: &mut f32, old: f32| *new = new.max(old - FALL_PER_FRAME);
+        for (new, old) in self.bands.iter_mut().zip(&previous.bands) {
+            fall(new, *old);
+        }
+        fall(&mut self.level, previous.level);
+        fall(&mut self.peak, previous.peak);
+        self
+    }
+}
+
+// an amplitude as 0-1 of the visualizer's height, on a dB scale
+fn to_height(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return 0.0;
+    }
+    let db = 20.0 * amplitude.log10();
+    ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+// in place radix-2 FFT of (real, imaginary) pairs; the length has to be a power of two
+fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+    if n < 2 {
+        return;
+    }
+    // bit reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a_re, a_im) = data[start + k];
+                let (b_re, b_im) = data[start + k + len / 2];
+                let (t_re, t_im) = (b_re * w_re - b_im * w_im, b_re * w_im + b_im * w_re);
+                data[start + k] = (a_re + t_re, a_im + t_im);
+                data[start + k + len / 2] = (a_re - t_re, a_im - t_im);
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -1,59 +1,574 @@
 use std::{
-    sync::{Arc, atomic::AtomicU64},
-    time::Duration,
+    collections::HashMap,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-use atomic_float::AtomicF64;
-use kira::sound::static_sound::StaticSoundHandle;
+use kira::{
+    Decibels, Tween, Value,
+    sound::{
+        FromFileError, IntoOptionalRegion, PlaybackState, static_sound::StaticSoundHandle,
+        streaming::StreamingSoundHandle,
+    },
+};
 use parking_lot::Mutex;
-use tokio::sync::oneshot;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
 
 use crate::service::{
-    audio::enums::{AlbumKind, ExtractorConfidence, LoopPolicy},
+    audio::{
+        enums::{AlbumKind, ExtractorConfidence, FadeCurve, LoopCount, LoopPolicy},
+        tracker::TrackerEvent,
+    },
     id::structs::Id,
     playlist::PlaylistSender,
 };
 
+// how long before a track ends the next one is lined up, when not crossfading. has to cover
+// opening the next file
+const GAPLESS_LEAD: Duration = Duration::from_millis(500);
+
 #[derive(Debug)]
 pub struct AudioConfig {
     start_paused: bool,
     volume: f64,
+    end_notice: Duration,
+    start_delay: Duration,
+    fade_in: Option<Tween>,
+    gain_db: f64,
+    speed: PlaybackSpeed,
+    start_position: Duration,
+    slice: Option<AudioSlice>,
+    trim: Trim,
 }
 impl AudioConfig {
-    pub fn new(start_paused: bool, volume: f64) -> Self {
+    pub fn new(start_paused: bool, volume: f64, end_notice: Duration) -> Self {
         Self {
             start_paused,
             volume,
+            end_notice,
+            start_delay: Duration::ZERO,
+            fade_in: None,
+            gain_db: 0.0,
+            speed: PlaybackSpeed::default(),
+            start_position: Duration::ZERO,
+            slice: None,
+            trim: Trim::default(),
         }
     }
+    // Starts the audio after a delay, so it can be lined up with the end of the previous track.
+    pub fn start_in(mut self, delay: Duration) -> Self {
+        self.start_delay = delay;
+        self
+    }
+    pub fn fade_in(mut self, tween: Tween) -> Self {
+        self.fade_in = Some(tween);
+        self
+    }
+    // Loudness normalization gain, applied on top of the volume.
+    pub fn gain(mut self, gain_db: f64) -> Self {
+        self.gain_db = gain_db;
+        self
+    }
+    pub fn speed(mut self, speed: PlaybackSpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+    // Starts partway into the audio (ie. resuming a previous session)
+    pub fn start_at(mut self, position: Duration) -> Self {
+        self.start_position = position;
+        self
+    }
+    // Only plays part of another track's file (ie. a chapter); positions are relative to the part
+    pub fn slice(mut self, file_id: Id, start: Duration, end: Duration) -> Self {
+        self.slice = Some(AudioSlice {
+            file_id,
+            start,
+            end,
+        });
+        self
+    }
+    // Skips the start and end of the audio; positions are relative to what's left
+    pub fn trim(mut self, trim: Trim) -> Self {
+        self.trim = trim;
+        self
+    }
     pub fn start_paused(&self) -> bool {
         self.start_paused
     }
     pub fn volume(&self) -> f64 {
         self.volume
     }
+    // how long before the end of the audio its `on_near_end` fires
+    pub fn end_notice(&self) -> Duration {
+        self.end_notice
+    }
+    pub fn start_delay(&self) -> Duration {
+        self.start_delay
+    }
+    pub fn fade_in_tween(&self) -> Option<Tween> {
+        self.fade_in
+    }
+    pub fn gain_db(&self) -> f64 {
+        self.gain_db
+    }
+    pub fn playback_speed(&self) -> PlaybackSpeed {
+        self.speed
+    }
+    pub fn start_position(&self) -> Duration {
+        self.start_position
+    }
+    pub fn audio_slice(&self) -> Option<&AudioSlice> {
+        self.slice.as_ref()
+    }
+    pub fn audio_trim(&self) -> Trim {
+        self.trim
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioSlice {
+    pub file_id: Id,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// How fast a playlist plays. Speeding up raises the pitch like a record would, unless
+/// `preserve_pitch` is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackSpeed {
+    pub rate: f64,
+    pub preserve_pitch: bool,
+}
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            preserve_pitch: true,
+        }
+    }
+}
+impl fmt::Display for PlaybackSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x", self.rate)
+    }
+}
+impl PlaybackSpeed {
+    pub const MIN_RATE: f64 = 0.5;
+    pub const MAX_RATE: f64 = 2.0;
+    // the rates offered in the player
+    pub const RATES: [f64; 7] = [0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+
+    pub fn new(rate: f64, preserve_pitch: bool) -> Self {
+        Self {
+            rate: rate.clamp(Self::MIN_RATE, Self::MAX_RATE),
+            preserve_pitch,
+        }
+    }
+    // How much the effect chain has to shift the pitch by to undo the rate's change
+    pub fn pitch_correction(&self) -> f64 {
+        if self.preserve_pitch {
+            1.0 / self.rate
+        } else {
+            1.0
+        }
+    }
+}
+
+/// How one track turns into the next in a playlist. Without a crossfade, the next track starts
+/// right as the previous one ends.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackTransition {
+    pub crossfade: Option<Duration>,
+    pub curve: FadeCurve,
+}
+impl Default for TrackTransition {
+    fn default() -> Self {
+        Self {
+            crossfade: None,
+            curve: FadeCurve::Linear,
+        }
+    }
+}
+impl TrackTransition {
+    // how long before a track ends the next one needs to be started
+    pub fn lead_time(&self) -> Duration {
+        self.crossfade.unwrap_or(GAPLESS_LEAD)
+    }
+}
+
+/// A track's measured loudness (see `loudness::analyze_file`). Stored in hundredths of a dB so
+/// tracks can still be compared and hashed.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct Loudness {
+    integrated_centi_lufs: i32,
+    true_peak_centi_db: i32,
+}
+impl Loudness {
+    pub fn new(integrated_lufs: f64, true_peak_db: f64) -> Self {
+        Self {
+            integrated_centi_lufs: (integrated_lufs * 100.0).round() as i32,
+            true_peak_centi_db: (true_peak_db * 100.0).round() as i32,
+        }
+    }
+    pub fn integrated_lufs(&self) -> f64 {
+        self.integrated_centi_lufs as f64 / 100.0
+    }
+    pub fn true_peak_db(&self) -> f64 {
+        self.true_peak_centi_db as f64 / 100.0
+    }
+}
+
+/// How much of a track's start and end is skipped when it plays (ie. silent intros). Set by hand
+/// or found by `silence::find_silence`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Trim {
+    pub start: Duration,
+    pub end: Duration,
+}
+impl Trim {
+    pub fn is_untrimmed(&self) -> bool {
+        self.start.is_zero() && self.end.is_zero()
+    }
+    // The part of audio `length` long that's left; None when untrimmed or nothing would be left
+    pub fn bounds(&self, length: Duration) -> Option<(Duration, Duration)> {
+        let end = length.checked_sub(self.end)?;
+        (!self.is_untrimmed() && self.start < end).then_some((self.start, end))
+    }
+}
+
+/// A track's audio downsampled for drawing (see `waveform::compute_waveform`). Each point is the
+/// peak and RMS level of an equal part of the track, scaled to 0-255.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Waveform {
+    pub length: Duration,
+    pub peaks: Vec<u8>,
+    pub rms: Vec<u8>,
+}
+impl Waveform {
+    // The points covering `start` to `end` of the track
+    pub fn section(&self, start: Duration, end: Duration) -> (&[u8], &[u8]) {
+        let length = self.length.as_secs_f64();
+        if length <= 0.0 || self.peaks.is_empty() {
+            return (&[], &[]);
+        }
+        let point = |time: Duration| {
+            ((time.as_secs_f64() / length * self.peaks.len() as f64).round() as usize)
+                .min(self.peaks.len())
+        };
+        let (first, last) = (point(start), point(end).max(point(start) + 1));
+        let last = last.min(self.peaks.len());
+        (&self.peaks[first..last], &self.rms[first..last])
+    }
+}
+
+/// One frame of the visualizer (see `spectrum::analyze`). Everything is 0-1 of its height, on a
+/// dB scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    // from low to high frequencies
+    pub bands: Vec<f32>,
+    pub level: f32,
+    pub peak: f32,
+}
+
+/// A part of a track that's played over and over (an A–B loop) instead of the whole track.
+/// Positions are from where the track starts playing, like its progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopRegion {
+    pub start: Duration,
+    pub end: Duration,
+    pub count: LoopCount,
+}
+impl LoopRegion {
+    // shorter loops could be mistaken for the engine's own rounding (see `tracker::LOOP_JUMP`)
+    pub const MIN_LENGTH: Duration = Duration::from_secs(1);
+
+    // The region between two positions, in either order. None if it's too short
+    pub fn new(a: Duration, b: Duration, count: LoopCount) -> Option<Self> {
+        let (start, end) = (a.min(b), a.max(b));
+        (end - start >= Self::MIN_LENGTH).then_some(Self { start, end, count })
+    }
+    pub fn contains(&self, position: Duration) -> bool {
+        (self.start..=self.end).contains(&position)
+    }
+    // The region after it looped once; None when it's done
+    pub fn looped(self) -> Option<Self> {
+        Some(Self {
+            count: self.count.looped()?,
+            ..self
+        })
+    }
+}
+
+/// Centre frequency of each equalizer band, in Hz.
+pub const EQ_BANDS: [f64; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// A named set of effects for the playback chain (see `effects::EffectChain`). Filters and the
+/// compressor are off when None.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectPreset {
+    pub name: String,
+    // gain of each of the `EQ_BANDS`, in dB
+    pub eq_gains: [f64; EQ_BANDS.len()],
+    // cutoffs, in Hz
+    pub high_pass: Option<f64>,
+    pub low_pass: Option<f64>,
+    pub compressor: Option<CompressorSettings>,
+}
+impl EffectPreset {
+    pub fn flat(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            eq_gains: [0.0; EQ_BANDS.len()],
+            high_pass: None,
+            low_pass: None,
+            compressor: None,
+        }
+    }
+    // the presets every library starts with
+    pub fn built_in() -> Vec<Self> {
+        vec![
+            Self::flat("Flat"),
+            Self {
+                eq_gains: [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                ..Self::flat("Bass boost")
+            },
+            Self {
+                eq_gains: [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.5, 3.0, 4.5, 5.0],
+                ..Self::flat("Treble boost")
+            },
+            Self {
+                eq_gains: [-3.0, -2.0, -1.0, 0.0, 1.5, 3.0, 3.0, 2.0, 0.0, -1.0],
+                high_pass: Some(80.0),
+                ..Self::flat("Vocal")
+            },
+            Self {
+                high_pass: Some(60.0),
+                compressor: Some(CompressorSettings {
+                    threshold_db: -24.0,
+                    ratio: 4.0,
+                }),
+                ..Self::flat("Late night")
+            },
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CompressorSettings {
+    pub threshold_db: f64,
+    pub ratio: f64,
+}
+
+/// Every effect preset, the one used by default, and the playlists that use a different one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectSettings {
+    pub presets: Vec<EffectPreset>,
+    // None plays without effects
+    pub active: Option<String>,
+    pub playlist_presets: HashMap<Id, String>,
+}
+impl Default for EffectSettings {
+    fn default() -> Self {
+        Self {
+            presets: EffectPreset::built_in(),
+            active: None,
+            playlist_presets: HashMap::new(),
+        }
+    }
+}
+impl EffectSettings {
+    pub fn preset(&self, name: &str) -> Option<&EffectPreset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+    // The preset a playlist's audio goes through. Audio played outside of a playlist uses the
+    // default one.
+    pub fn preset_for(&self, playlist_id: Option<&Id>) -> Option<&EffectPreset> {
+        playlist_id
+            .and_then(|id| self.playlist_presets.get(id))
+            .or(self.active.as_ref())
+            .and_then(|name| self.preset(name))
+    }
+}
+
+// A playing sound. Tracks are streamed from disk, unless their length can't be read without
+// decoding the whole file (ie. mp3s without a header saying how long they are).
+pub enum SoundHandle {
+    Streaming(StreamingSoundHandle<FromFileError>),
+    Static(StaticSoundHandle),
+}
+impl SoundHandle {
+    pub fn state(&self) -> PlaybackState {
+        match self {
+            Self::Streaming(h) => h.state(),
+            Self::Static(h) => h.state(),
+        }
+    }
+    pub fn position(&self) -> f64 {
+        match self {
+            Self::Streaming(h) => h.position(),
+            Self::Static(h) => h.position(),
+        }
+    }
+    pub fn pause(&mut self, tween: Tween) {
+        match self {
+            Self::Streaming(h) => h.pause(tween),
+            Self::Static(h) => h.pause(tween),
+        }
+    }
+    pub fn resume(&mut self, tween: Tween) {
+        match self {
+            Self::Streaming(h) => h.resume(tween),
+            Self::Static(h) => h.resume(tween),
+        }
+    }
+    pub fn stop(&mut self, tween: Tween) {
+        match self {
+            Self::Streaming(h) => h.stop(tween),
+            Self::Static(h) => h.stop(tween),
+        }
+    }
+    pub fn seek_to(&mut self, position: f64) {
+        match self {
+            Self::Streaming(h) => h.seek_to(position),
+            Self::Static(h) => h.seek_to(position),
+        }
+    }
+    pub fn set_loop_region(&mut self, loop_region: impl IntoOptionalRegion) {
+        match self {
+            Self::Streaming(h) => h.set_loop_region(loop_region),
+            Self::Static(h) => h.set_loop_region(loop_region),
+        }
+    }
+    pub fn set_playback_rate(&mut self, rate: f64, tween: Tween) {
+        match self {
+            Self::Streaming(h) => h.set_playback_rate(rate, tween),
+            Self::Static(h) => h.set_playback_rate(rate, tween),
+        }
+    }
+    pub fn set_volume(&mut self, volume: impl Into<Value<Decibels>>, tween: Tween) {
+        match self {
+            Self::Streaming(h) => h.set_volume(volume, tween),
+            Self::Static(h) => h.set_volume(volume, tween),
+        }
+    }
+    // Returns the error that stopped the stream, if there was one. Static sounds can't fail.
+    pub fn pop_error(&mut self) -> Option<FromFileError> {
+        match self {
+            Self::Streaming(h) => h.pop_error(),
+            Self::Static(_) => None,
+        }
+    }
+}
+
+// how many of the newest output samples the tap keeps; enough for the visualizer's analysis
+const SAMPLE_TAP_CAPACITY: usize = 4096;
+
+/// The newest mixed output samples (mono), written by the audio thread through a tap on the main
+/// track (see `effects::sample_tap`) and read by the visualizer. Nothing locks, so reading can
+/// never hold up the audio; a read racing a write just gets a slightly torn buffer.
+#[derive(Clone)]
+pub struct SampleTap {
+    enabled: Arc<AtomicBool>,
+    sample_rate: Arc<AtomicU32>,
+    // f32 bits
+    samples: Arc<[AtomicU32]>,
+    // where the next sample goes; only ever increases
+    write_pos: Arc<AtomicUsize>,
+}
+impl SampleTap {
+    pub fn new() -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(false)),
+            sample_rate: Arc::new(AtomicU32::new(0)),
+            samples: (0..SAMPLE_TAP_CAPACITY)
+                .map(|_| AtomicU32::new(0))
+                .collect(),
+            write_pos: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+    // Samples are only written while something is reading them
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+    pub fn set_sample_rate(&self, sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+    pub fn push(&self, sample: f32) {
+        let pos = self.write_pos.load(Ordering::Relaxed);
+        self.samples[pos % self.samples.len()].store(sample.to_bits(), Ordering::Relaxed);
+        self.write_pos.store(pos + 1, Ordering::Release);
+    }
+    // Fills `out` with the newest samples, oldest first
+    pub fn read_latest(&self, out: &mut [f32]) {
+        let len = self.samples.len();
+        let end = self.write_pos.load(Ordering::Acquire);
+        let count = out.len().min(len);
+        let start = end.wrapping_sub(count);
+        for (i, sample) in out.iter_mut().enumerate() {
+            *sample = if i < count && end >= count {
+                f32::from_bits(self.samples[(start + i) % len].load(Ordering::Relaxed))
+            } else {
+                0.0
+            };
+        }
+    }
+}
+impl Default for SampleTap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Small wrapper for audio handles; contains other information relevant to the handle.
 pub struct AudioHandleWrapper {
-    pub handle: Arc<Mutex<StaticSoundHandle>>,
+    pub handle: Arc<Mutex<SoundHandle>>,
     pub on_end: oneshot::Sender<anyhow::Result<()>>,
+    // taken once it's fired
+    pub on_near_end: Option<oneshot::Sender<Instant>>,
     pub on_loop: PlaylistSender,
-    pub last_known_pos: Arc<AtomicF64>,
+    // tells the sound's position tracker (see `tracker::PositionTracker`) what's been done to it
+    pub tracker: mpsc::UnboundedSender<TrackerEvent>,
     pub loop_policy: LoopPolicy,
+    // played over and over before the loop policy has any say
+    pub loop_region: Option<LoopRegion>,
     pub audio_duration: Duration,
-    pub seek_count: Arc<AtomicU64>,
     pub maybe_playlist_id: Option<Id>,
+    // kept apart so either can change without knowing the other
+    pub volume: f64,
+    pub gain_db: f64,
 }
 
 #[derive(Debug, Clone)]
 pub struct AudioProgress {
     current: Duration,
     total: Duration,
+    rate: f64,
 }
 impl AudioProgress {
     pub fn new(current: Duration, total: Duration) -> Self {
-        Self { current, total }
+        Self {
+            current,
+            total,
+            rate: 1.0,
+        }
+    }
+    pub fn at_rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
     }
     // gives progress as a decimal (eg. 0.26)
     pub fn progress(&self) -> f32 {
@@ -65,6 +580,13 @@ impl AudioProgress {
     pub fn total(&self) -> &Duration {
         &self.total
     }
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+    // how long until the audio ends, in real time
+    pub fn remaining(&self) -> Duration {
+        self.total.saturating_sub(self.current).div_f64(self.rate)
+    }
     pub fn update_progress(&mut self, progress: f32) {
         self.current = Duration::from_secs_f32(self.total().as_secs_f32() * progress);
     }
@@ -84,3 +606,13 @@ pub struct UpdatedTrackData {
     pub album_kind: AlbumKind,
     pub artists: Vec<String>,
 }
+
+// Information read from an audio file's tags and stream parameters
+#[derive(Debug, Clone, Default)]
+pub struct AudioFileInfo {
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub duration: Option<Duration>,
+}
@@ -0,0 +1,467 @@
+// Keeps track of where a playing sound is up to: what progress to show, when it loops and when
+// it's about to end. The audio service tells it about everything that moves the playhead
+// (seeks, pauses, speed and loop changes), so it never has to guess whether the position going
+// backwards was a loop or a seek. The engine's position is only read when something's due: the
+// next progress update, or the moment the sound should loop or end. Nothing in here reads the
+// clock itself; the time is always passed in.
+
+use std::time::{Duration, Instant};
+
+use kira::sound::PlaybackState;
+
+use crate::service::audio::enums::ProgressCadence;
+
+// seeks are announced, so the position only goes back this far when the sound loops. A–B loops
+// are at least a second long, so this is well clear of the engine's own rounding
+const LOOP_JUMP: f64 = 0.1;
+// how close the engine has to be to where a seek should have put the sound before its position
+// is trusted again
+const SEEK_TOLERANCE: f64 = 0.25;
+// after this long, the engine's position is taken as is, even if the seek never showed up
+const SEEK_TIMEOUT: Duration = Duration::from_secs(1);
+// how long the engine gets to act on a pause, resume, stop or speed change
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+// how often the engine is looked at while it's settling
+const SETTLE_INTERVAL: Duration = Duration::from_millis(20);
+// how long after a predicted loop or end to look, so the engine has gotten there
+const BOUNDARY_MARGIN: Duration = Duration::from_millis(30);
+
+/// Something the audio service did to a sound that changes how its playhead moves.
+#[derive(Debug, Clone, Copy)]
+pub enum TrackerEvent {
+    // `from` is where the sound was right before, so a loop just before the seek isn't lost
+    Seeked { from: f64, to: f64 },
+    Paused,
+    Resumed,
+    Stopped,
+    RateChanged(f64),
+    // the part of the sound that loops (start, end), if any
+    LoopChanged(Option<(f64, f64)>),
+    CadenceChanged(ProgressCadence),
+}
+
+/// What a look at the engine turned up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackerUpdate {
+    Progress(f64),
+    Looped,
+    // how long until the sound ends, in real time
+    NearEnd(Duration),
+}
+
+// a position, and when it was
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    pos: f64,
+    at: Instant,
+}
+
+pub struct PositionTracker {
+    duration: f64,
+    rate: f64,
+    // how long before the end (in real time) to send `NearEnd`
+    end_notice: f64,
+    loop_bounds: Option<(f64, f64)>,
+    cadence: ProgressCadence,
+    // the last position that was trusted
+    last: Option<Sample>,
+    // where a seek should have put the sound, and where it would've been without it
+    pending_seek: Option<(Sample, f64)>,
+    settle_until: Option<Instant>,
+    // whether the playhead moves (or is about to) at all
+    advancing: bool,
+    // whether `NearEnd` was sent since the sound was last far from its end
+    near_end_sent: bool,
+}
+
+impl PositionTracker {
+    pub fn new(
+        duration: Duration,
+        rate: f64,
+        end_notice: Duration,
+        cadence: ProgressCadence,
+    ) -> Self {
+        Self {
+            duration: duration.as_secs_f64(),
+            rate,
+            end_notice: end_notice.as_secs_f64(),
+            loop_bounds: None,
+            cadence,
+            last: None,
+            pending_seek: None,
+            settle_until: None,
+            advancing: true,
+            near_end_sent: false,
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    // Takes in something the audio service did. A seek can reveal a loop that happened since the
+    // engine was last looked at
+    pub fn handle(&mut self, event: TrackerEvent, now: Instant) -> Option<TrackerUpdate> {
+        self.settle_until = Some(now + SETTLE_TIME);
+        match event {
+            TrackerEvent::Seeked { from, to } => {
+                let looped = self.pending_seek.is_none()
+                    && self.last.is_some_and(|last| from < last.pos - LOOP_JUMP);
+                self.pending_seek = Some((Sample { pos: to, at: now }, from));
+                self.last = None;
+                // it could be moving again after being paused at the end
+                self.advancing = true;
+                return looped.then_some(TrackerUpdate::Looped);
+            }
+            TrackerEvent::Resumed => self.advancing = true,
+            TrackerEvent::Paused | TrackerEvent::Stopped => {}
+            TrackerEvent::RateChanged(rate) => self.rate = rate,
+            TrackerEvent::LoopChanged(bounds) => self.loop_bounds = bounds,
+            TrackerEvent::CadenceChanged(cadence) => self.cadence = cadence,
+        }
+        None
+    }
+
+    // Takes in what the engine says. Anything from before the last seek went through is ignored
+    pub fn observe(&mut self, pos: f64, state: PlaybackState, now: Instant) -> Vec<TrackerUpdate> {
+        self.advancing = !matches!(state, PlaybackState::Paused | PlaybackState::Stopped);
+        if self.settle_until.is_some_and(|until| now >= until) {
+            self.settle_until = None;
+        }
+        if let Some((seek, from)) = self.pending_seek {
+            let elapsed = now.saturating_duration_since(seek.at);
+            let moved = if state.is_advancing() {
+                elapsed.as_secs_f64() * self.rate
+            } else {
+                0.0
+            };
+            // the engine may not have gotten to the seek yet, in which case it's still where it
+            // would've been without it
+            let off_seek = (pos - (seek.pos + moved)).abs();
+            let off_before = (pos - (from + moved)).abs();
+            let caught_up = off_seek <= SEEK_TOLERANCE && off_seek <= off_before;
+            if !caught_up && elapsed < SEEK_TIMEOUT {
+                return Vec::new();
+            }
+            self.pending_seek = None;
+        }
+
+        let mut updates = Vec::new();
+        if self.last.is_some_and(|last| pos < last.pos - LOOP_JUMP) {
+            updates.push(TrackerUpdate::Looped);
+        }
+        // in real time; a sped up sound ends sooner than its position says
+        let remaining = ((self.duration - pos) / self.rate).max(0.0);
+        if remaining > self.end_notice {
+            self.near_end_sent = false;
+        } else if !self.near_end_sent && matches!(state, PlaybackState::Playing) {
+            // only while playing, since a paused sound won't end
+            self.near_end_sent = true;
+            updates.push(TrackerUpdate::NearEnd(Duration::from_secs_f64(remaining)));
+        }
+        if self.last.is_none_or(|last| last.pos != pos) {
+            updates.push(TrackerUpdate::Progress(pos));
+        }
+        self.last = Some(Sample { pos, at: now });
+        updates
+    }
+
+    // When the engine should next be looked at, or None if nothing's due until the audio service
+    // does something
+    pub fn next_wake(&self, now: Instant) -> Option<Instant> {
+        if self.pending_seek.is_some() || self.settle_until.is_some() {
+            return Some(now + SETTLE_INTERVAL);
+        }
+        if !self.advancing {
+            return None;
+        }
+        let mut wake = now + self.cadence.interval();
+        if let Some(last) = self.last {
+            // when the playhead gets to a position ahead of it
+            let reaches = |pos: f64| {
+                (pos > last.pos)
+                    .then(|| last.at + Duration::from_secs_f64((pos - last.pos) / self.rate))
+            };
+            // just after it loops or ends
+            let boundary = self.loop_bounds.map_or(self.duration, |(_, end)| end);
+            if let Some(at) = reaches(boundary) {
+                wake = wake.min(at + BOUNDARY_MARGIN);
+            }
+            if !self.near_end_sent
+                && let Some(at) = reaches(self.duration - self.end_notice * self.rate)
+            {
+                wake = wake.min(at);
+            }
+        }
+        // never busy-wait on a prediction that's already passed
+        Some(wake.max(now + SETTLE_INTERVAL))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYING: PlaybackState = PlaybackState::Playing;
+
+    fn ms(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    // 100s long, reporting the end 5s ahead
+    fn tracker(cadence: ProgressCadence) -> PositionTracker {
+        PositionTracker::new(
+            Duration::from_secs(100),
+            1.0,
+            Duration::from_secs(5),
+            cadence,
+        )
+    }
+
+    // lets whatever the last event was settle, so the cadence has a say again
+    fn settle(tracker: &mut PositionTracker, pos: f64, at: Instant) -> Vec<TrackerUpdate> {
+        tracker.observe(pos, PLAYING, at + SETTLE_TIME)
+    }
+
+    #[test]
+    fn progress_is_reported_when_it_changes() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        assert_eq!(
+            tracker.observe(1.0, PLAYING, start),
+            vec![TrackerUpdate::Progress(1.0)]
+        );
+        assert_eq!(tracker.observe(1.0, PLAYING, ms(start, 100)), vec![]);
+        assert_eq!(
+            tracker.next_wake(ms(start, 100)),
+            Some(ms(start, 100) + ProgressCadence::Visible.interval())
+        );
+    }
+
+    #[test]
+    fn paused_sounds_wait_for_the_audio_service() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.observe(1.0, PLAYING, start);
+        tracker.handle(TrackerEvent::Paused, ms(start, 50));
+        // the engine gets a moment to act on it
+        assert_eq!(
+            tracker.next_wake(ms(start, 50)),
+            Some(ms(start, 50) + SETTLE_INTERVAL)
+        );
+        tracker.observe(1.05, PlaybackState::Pausing, ms(start, 100));
+        tracker.observe(1.1, PlaybackState::Paused, ms(start, 400));
+        assert_eq!(tracker.next_wake(ms(start, 400)), None);
+
+        tracker.handle(TrackerEvent::Resumed, ms(start, 5000));
+        assert!(tracker.next_wake(ms(start, 5000)).is_some());
+    }
+
+    #[test]
+    fn stale_position_after_a_seek_back_is_not_a_loop() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.observe(50.0, PLAYING, start);
+        assert_eq!(
+            tracker.handle(
+                TrackerEvent::Seeked {
+                    from: 50.1,
+                    to: 10.0
+                },
+                ms(start, 100)
+            ),
+            None
+        );
+        // the engine hasn't gotten to the seek yet
+        assert_eq!(tracker.observe(50.12, PLAYING, ms(start, 120)), vec![]);
+        assert_eq!(
+            tracker.next_wake(ms(start, 120)),
+            Some(ms(start, 120) + SETTLE_INTERVAL)
+        );
+        // now it has
+        assert_eq!(
+            tracker.observe(10.05, PLAYING, ms(start, 150)),
+            vec![TrackerUpdate::Progress(10.05)]
+        );
+        assert_eq!(
+            tracker.observe(10.5, PLAYING, ms(start, 600)),
+            vec![TrackerUpdate::Progress(10.5)]
+        );
+    }
+
+    #[test]
+    fn small_seek_back_is_not_a_loop() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.observe(50.0, PLAYING, start);
+        tracker.handle(
+            TrackerEvent::Seeked {
+                from: 50.0,
+                to: 49.5,
+            },
+            start,
+        );
+        assert_eq!(
+            tracker.observe(49.52, PLAYING, ms(start, 20)),
+            vec![TrackerUpdate::Progress(49.52)]
+        );
+    }
+
+    #[test]
+    fn loop_wrap_is_reported_once() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Hidden);
+        tracker.handle(TrackerEvent::LoopChanged(Some((10.0, 20.0))), start);
+        settle(&mut tracker, 19.5, start);
+        let at = start + SETTLE_TIME;
+        // looked at right after it's due to loop, ahead of the next progress update
+        assert_eq!(
+            tracker.next_wake(at),
+            Some(at + Duration::from_millis(500) + BOUNDARY_MARGIN)
+        );
+
+        let after = at + Duration::from_millis(600);
+        assert_eq!(
+            tracker.observe(10.1, PLAYING, after),
+            vec![TrackerUpdate::Looped, TrackerUpdate::Progress(10.1)]
+        );
+        assert_eq!(
+            tracker.observe(10.5, PLAYING, after + Duration::from_millis(400)),
+            vec![TrackerUpdate::Progress(10.5)]
+        );
+    }
+
+    #[test]
+    fn loop_just_before_a_seek_is_reported() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.handle(TrackerEvent::LoopChanged(Some((10.0, 20.0))), start);
+        settle(&mut tracker, 19.9, start);
+        // it looped, then was seeked before the engine was looked at again
+        let update = tracker.handle(
+            TrackerEvent::Seeked {
+                from: 10.2,
+                to: 15.0,
+            },
+            start + SETTLE_TIME + Duration::from_millis(300),
+        );
+        assert_eq!(update, Some(TrackerUpdate::Looped));
+    }
+
+    #[test]
+    fn pending_seek_times_out() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.observe(50.0, PLAYING, start);
+        tracker.handle(
+            TrackerEvent::Seeked {
+                from: 50.0,
+                to: 10.0,
+            },
+            start,
+        );
+        // somewhere neither the seek nor the old position would explain
+        assert_eq!(tracker.observe(70.0, PLAYING, ms(start, 500)), vec![]);
+        // given up on; taken as is, and not as a loop
+        assert_eq!(
+            tracker.observe(70.0, PLAYING, start + SEEK_TIMEOUT),
+            vec![TrackerUpdate::Progress(70.0)]
+        );
+    }
+
+    #[test]
+    fn near_end_is_sent_once() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Hidden);
+        assert_eq!(
+            tracker.observe(94.0, PLAYING, start),
+            vec![TrackerUpdate::Progress(94.0)]
+        );
+        // looked at right as the notice is due, ahead of the next progress update
+        assert_eq!(tracker.next_wake(start), Some(ms(start, 1000)));
+        assert_eq!(
+            tracker.observe(95.5, PLAYING, ms(start, 1500)),
+            vec![
+                TrackerUpdate::NearEnd(Duration::from_millis(4500)),
+                TrackerUpdate::Progress(95.5)
+            ]
+        );
+        assert_eq!(
+            tracker.observe(96.0, PLAYING, ms(start, 2000)),
+            vec![TrackerUpdate::Progress(96.0)]
+        );
+        // looked at right after it ends, ahead of the next progress update
+        tracker.observe(99.5, PLAYING, ms(start, 2000));
+        assert_eq!(
+            tracker.next_wake(ms(start, 2000)),
+            Some(ms(start, 2500) + BOUNDARY_MARGIN)
+        );
+
+        // seeking away from the end lets it be sent again
+        tracker.handle(
+            TrackerEvent::Seeked {
+                from: 96.0,
+                to: 50.0,
+            },
+            ms(start, 2000),
+        );
+        tracker.observe(50.0, PLAYING, ms(start, 2000));
+        assert_eq!(
+            tracker.observe(97.0, PLAYING, ms(start, 3000)),
+            vec![
+                TrackerUpdate::NearEnd(Duration::from_secs(3)),
+                TrackerUpdate::Progress(97.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn near_end_waits_while_paused() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        assert_eq!(
+            tracker.observe(98.0, PlaybackState::Paused, start),
+            vec![TrackerUpdate::Progress(98.0)]
+        );
+        tracker.handle(TrackerEvent::Resumed, ms(start, 100));
+        assert_eq!(
+            tracker.observe(98.0, PLAYING, ms(start, 200)),
+            vec![TrackerUpdate::NearEnd(Duration::from_secs(2))]
+        );
+    }
+
+    #[test]
+    fn near_end_is_in_real_time() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.handle(TrackerEvent::RateChanged(2.0), start);
+        // 8s of sound left is 4s at double speed
+        assert_eq!(
+            settle(&mut tracker, 92.0, start),
+            vec![
+                TrackerUpdate::NearEnd(Duration::from_secs(4)),
+                TrackerUpdate::Progress(92.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn cadence_switches() {
+        let start = Instant::now();
+        let mut tracker = tracker(ProgressCadence::Visible);
+        tracker.observe(30.0, PLAYING, start);
+        assert_eq!(tracker.next_wake(start), Some(ms(start, 100)));
+
+        tracker.handle(TrackerEvent::CadenceChanged(ProgressCadence::Hidden), start);
+        // settling first
+        assert_eq!(tracker.next_wake(start), Some(start + SETTLE_INTERVAL));
+        settle(&mut tracker, 30.25, start);
+        let at = start + SETTLE_TIME;
+        assert_eq!(tracker.next_wake(at), Some(at + Duration::from_secs(1)));
+
+        tracker.handle(TrackerEvent::CadenceChanged(ProgressCadence::Visible), at);
+        settle(&mut tracker, 30.5, at);
+        let at = at + SETTLE_TIME;
+        assert_eq!(tracker.next_wake(at), Some(ms(at, 100)));
+    }
+}
@@ -1,18 +1,15 @@
 use std::{
-    sync::{
-        Arc,
-        atomic::{AtomicU64, Ordering},
-    },
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use atomic_float::AtomicF64;
 use kira::{
-    AudioManager, Tween,
+    Tween,
     sound::{
-        PlaybackState,
-        static_sound::{StaticSoundData, StaticSoundHandle},
+        FromFileError, PlaybackState, Region, static_sound::StaticSoundData,
+        streaming::StreamingSoundData,
     },
+    track::TrackHandle,
 };
 use parking_lot::Mutex;
 use tokio::sync::mpsc;
@@ -20,35 +17,85 @@ use tokio::sync::mpsc;
 use crate::service::{
     audio::{
         AudioSender,
-        enums::AudioMessage,
-        structs::{AudioConfig, AudioProgress},
+        enums::{AudioMessage, LoopPolicy, ProgressCadence},
+        structs::{AudioConfig, AudioHandleWrapper, AudioProgress, SoundHandle},
+        tracker::{PositionTracker, TrackerEvent, TrackerUpdate},
     },
-    file,
+    file::{self, library::Library},
     id::structs::Id,
 };
 
-// how often each audio file's heartbeat will poll (delay, in ms)
-const AUDIO_HEARTBEAT_RATE: u64 = 100;
-
 pub async fn play_audio(
     track_id: Id,
     progress_sender: mpsc::Sender<(Id, AudioProgress)>,
     audio_sender: AudioSender,
     audio_config: AudioConfig,
-    audio_manager: &mut AudioManager,
-    last_known_pos_arc: Arc<AtomicF64>,
-    seek_count_arc: Arc<AtomicU64>,
-) -> anyhow::Result<(Arc<Mutex<StaticSoundHandle>>, Duration)> {
-    // get the file path from the track id
-    let path = file::util::track_file_path_from_id(&track_id)?;
-    // do audio things
-    let mut data = tokio::task::spawn_blocking(move || StaticSoundData::from_file(path)).await??;
-    data = data.volume(linear_to_db(audio_config.volume()) as f32);
-    // data = data.volume(-100.0);
-    let duration = data.duration();
-
-    // play the audio and get its handle
-    let mut handle = audio_manager.play(data)?;
+    // the mixer track (ie. effect chain) to play on
+    output: &mut TrackHandle,
+    // how often progress is reported to start with
+    cadence: ProgressCadence,
+    library: &Library,
+) -> anyhow::Result<(
+    Arc<Mutex<SoundHandle>>,
+    Duration,
+    mpsc::UnboundedSender<TrackerEvent>,
+)> {
+    // get the file path from the track id, or from the file the slice is part of
+    let file_id = audio_config
+        .audio_slice()
+        .map_or(&track_id, |slice| &slice.file_id);
+    let path = file::util::track_file_path_from_id(library, file_id)?;
+    // the part of the file that's played, given the file's length
+    let slice = audio_config
+        .audio_slice()
+        .map(|slice| (slice.start, slice.end));
+    let trim = audio_config.audio_trim();
+    let region = move |length: Duration| {
+        slice
+            .or_else(|| trim.bounds(length))
+            .map(|(start, end)| Region::from(start.as_secs_f64()..end.as_secs_f64()))
+    };
+    let volume = output_db(audio_config.volume(), audio_config.gain_db());
+    let start_delay = audio_config.start_delay();
+    let fade_in = audio_config.fade_in_tween();
+    let end_notice = audio_config.end_notice();
+    let rate = audio_config.playback_speed().rate;
+    let start_position = audio_config.start_position().as_secs_f64();
+    // stream the file from disk so playback starts right away, and long tracks don't have to
+    // fit in memory. only opening the file happens here; decoding is done on kira's thread
+    let streaming_data =
+        tokio::task::spawn_blocking(move || (StreamingSoundData::from_file(&path), path)).await?;
+    let (duration, mut handle) = match streaming_data {
+        (Ok(data), _) => {
+            let region = region(data.duration());
+            let data = data
+                .slice(region)
+                .volume(volume)
+                .playback_rate(rate)
+                .start_position(start_position)
+                .start_time(start_delay)
+                .fade_in_tween(fade_in);
+            let duration = data.duration();
+            (duration, SoundHandle::Streaming(output.play(data)?))
+        }
+        // the stream needs to know the track's length upfront; decode the whole file instead
+        (Err(FromFileError::UnknownSampleRate), path) => {
+            println!("Can't stream {}; decoding it into memory", path.display());
+            let data =
+                tokio::task::spawn_blocking(move || StaticSoundData::from_file(path)).await??;
+            let region = region(data.duration());
+            let data = data
+                .slice(region)
+                .volume(volume)
+                .playback_rate(rate)
+                .start_position(start_position)
+                .start_time(start_delay)
+                .fade_in_tween(fade_in);
+            let duration = data.duration();
+            (duration, SoundHandle::Static(output.play(data)?))
+        }
+        (Err(e), _) => return Err(e.into()),
+    };
 
     if audio_config.start_paused() {
         handle.pause(Tween::default());
@@ -58,95 +105,139 @@ pub async fn play_audio(
     let handle_arc = Arc::new(Mutex::new(handle));
     let handle_arc_clone = Arc::clone(&handle_arc);
 
-    // spawn task monitoring audio
+    // spawn task tracking the audio's position
+    let (tracker_sender, mut tracker_events) = mpsc::unbounded_channel();
+    let mut tracker = PositionTracker::new(duration, rate, end_notice, cadence);
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_millis(AUDIO_HEARTBEAT_RATE));
-
-        let mut local_seek_count = seek_count_arc.load(Ordering::Relaxed);
         // if the sender was dropped, then don't try to keep sending
         let mut dropped = false;
+        let mut result = Ok(());
+        // found out from an event, to be sent after the next look at the engine
+        let mut updates = Vec::new();
+        // whether the audio service still has this sound
+        let mut listening = true;
         loop {
-            // wait for next tick
-            interval.tick().await;
-
-            // get the seek count before the audio logic
-            let start_seek_count = seek_count_arc.load(Ordering::Relaxed);
-
-            // run heartbeat logic
-            let (pos, state) = {
-                let guard = handle_arc_clone.lock();
-                (guard.position(), guard.state())
+            let (pos, state, error) = {
+                let mut guard = handle_arc_clone.lock();
+                (guard.position(), guard.state(), guard.pop_error())
             };
 
+            // a stream that fails to decode stops itself
+            if let Some(e) = error {
+                result = Err(anyhow::anyhow!("Failed to stream audio: {e}"));
+            }
+
             // if the audio is stopped, then stop too
             if let PlaybackState::Stopped = state {
                 break;
             }
-
-            // load last known position + seek count after getting audio data
-            let last_known_pos = last_known_pos_arc.load(Ordering::Relaxed);
-            let end_seek_count = seek_count_arc.load(Ordering::Relaxed);
-
-            // ~ loop logic ~
-
-            // if something happened while audio data was read, then don't do anything
-            // not sure how this can happen but ai suggested it
-            if start_seek_count != end_seek_count {
-                local_seek_count = end_seek_count;
-                continue;
+            // the audio service let go of this sound (it was replaced, or the service shut
+            // down), so nothing can control or finish it anymore
+            if !listening {
+                handle_arc_clone.lock().stop(Tween::default());
+                return;
             }
 
-            // if a seek happened between last tick and now; update the values
-            // but don't trigger loop checking or gui updating
-            if start_seek_count != local_seek_count {
-                // apparently the audio engine lags, and this ensures
-                // we don't do anything if the lag is too crazy
-                let lag = (pos - last_known_pos).abs();
-                local_seek_count = start_seek_count;
-
-                if lag > 0.5 {
-                    continue;
-                }
-            } else {
-                // standard loop detection. cushion added for weird floating point stuff
-                if pos < last_known_pos - 0.5 {
-                    let _ = audio_sender
-                        .send(AudioMessage::AudioLooped {
-                            id: track_id.clone(),
-                        })
-                        .await;
-                }
-
-                // update the gui if the position changed and a seek didn't happen last frame
-                if pos != last_known_pos && !dropped {
-                    // send the update
-                    let progress =
-                        AudioProgress::new(Duration::from_secs_f64(pos), duration.clone());
-                    // if the recv was dropped, then stop heartbeating
-                    if let Err(_) = progress_sender.send((track_id.clone(), progress)).await {
-                        dropped = true;
+            // catch up on anything the audio service did since, so a position from before a seek
+            // is never mistaken for one after it
+            let now = Instant::now();
+            while let Ok(event) = tracker_events.try_recv() {
+                updates.extend(tracker.handle(event, now));
+            }
+            updates.extend(tracker.observe(pos, state, now));
+
+            for update in updates.drain(..) {
+                match update {
+                    TrackerUpdate::Looped => {
+                        let _ = audio_sender
+                            .send(AudioMessage::AudioLooped {
+                                id: track_id.clone(),
+                            })
+                            .await;
+                    }
+                    // let the audio service know the end is coming up, so the next track can be
+                    // lined up with it
+                    TrackerUpdate::NearEnd(remaining) => {
+                        let _ = audio_sender
+                            .send(AudioMessage::AudioNearEnd {
+                                id: track_id.clone(),
+                                ends_at: now + remaining,
+                            })
+                            .await;
+                    }
+                    TrackerUpdate::Progress(pos) if !dropped => {
+                        let progress = AudioProgress::new(Duration::from_secs_f64(pos), duration)
+                            .at_rate(tracker.rate());
+                        // if the recv was dropped, then stop sending progress
+                        dropped = progress_sender
+                            .send((track_id.clone(), progress))
+                            .await
+                            .is_err();
                     }
+                    TrackerUpdate::Progress(_) => {}
                 }
             }
 
-            // update last known pos if no seek happened
-            if seek_count_arc.load(Ordering::Relaxed) == start_seek_count {
-                // println!("Updating last known pos.. ({last_known_pos}s), current pos: {pos}s");
-                last_known_pos_arc.store(pos, Ordering::Relaxed);
+            // wait for whatever's due next, or for the audio service to do something
+            let wake = tracker.next_wake(now);
+            let sleep = async {
+                match wake {
+                    Some(at) => tokio::time::sleep_until(at.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                event = tracker_events.recv(), if listening => match event {
+                    Some(event) => updates.extend(tracker.handle(event, Instant::now())),
+                    None => listening = false,
+                },
+                _ = sleep => {}
             }
         }
 
-        // audio ended; send the end signal
-        audio_sender
+        // audio ended; send the end signal, unless it's already been let go of (a newer sound
+        // with the same id would be taken for this one)
+        if tracker_events.is_closed() {
+            return;
+        }
+        let _ = audio_sender
             .send(AudioMessage::AudioFinished {
                 id: track_id,
-                result: Ok(()),
+                result,
             })
-            .await
-            .unwrap();
+            .await;
     });
 
-    Ok((handle_arc, duration))
+    Ok((handle_arc, duration, tracker_sender))
+}
+
+// Loops the sound over its A–B region if it has one, or else all of it unless it isn't looping,
+// and lets its tracker know where it'll jump back
+pub fn apply_loop(wrapper: &AudioHandleWrapper) {
+    let (region, bounds) = match (wrapper.loop_region, wrapper.loop_policy) {
+        (Some(region), _) => {
+            let (start, end) = (region.start.as_secs_f64(), region.end.as_secs_f64());
+            (Some(Region::from(start..end)), Some((start, end)))
+        }
+        (None, LoopPolicy::NoLooping) => (None, None),
+        (None, LoopPolicy::Once | LoopPolicy::Infinite) => (
+            Some(Region::from(0.0..)),
+            Some((0.0, wrapper.audio_duration.as_secs_f64())),
+        ),
+    };
+    wrapper.handle.lock().set_loop_region(region);
+    let _ = wrapper.tracker.send(TrackerEvent::LoopChanged(bounds));
+}
+
+// The user's volume with the normalization gain on top
+pub fn output_db(volume: f64, gain_db: f64) -> f32 {
+    let volume_db = linear_to_db(volume);
+    // silence stays silent
+    if volume_db <= -100.0 {
+        volume_db as f32
+    } else {
+        (volume_db + gain_db) as f32
+    }
 }
 
 pub fn linear_to_db(linear: f64) -> f64 {
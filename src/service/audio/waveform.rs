@@ -0,0 +1,119 @@
+// Builds the waveforms drawn in the player's seek bar, and caches them next to their tracks so
+// each track is only decoded once.
+
+use std::{path::Path, time::Duration};
+
+use anyhow::anyhow;
+
+use crate::service::{
+    audio::{decode, structs::Waveform},
+    file::util::write_atomic,
+};
+
+// audio is first measured in blocks this long, then merged down into points
+const BLOCK: Duration = Duration::from_millis(10);
+// enough points that a chapter of a long mix still gets a usable waveform
+const POINTS_PER_SECOND: f64 = 5.0;
+const MIN_POINTS: usize = 600;
+const MAX_POINTS: usize = 20_000;
+
+/// Loads the waveform cached at `cache_path`, or builds it from the track at `track_path` and
+/// caches it. A cache older than the track is rebuilt. Blocking; run in a blocking task.
+pub fn load_or_compute(track_path: &Path, cache_path: &Path) -> anyhow::Result<Waveform> {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    if let (Some(cached), Some(track)) = (modified(cache_path), modified(track_path))
+        && cached >= track
+    {
+        match std::fs::read(cache_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?))
+        {
+            Ok(waveform) => return Ok(waveform),
+            Err(e) => println!(
+                "Rebuilding unreadable waveform {}: {e}",
+                cache_path.display()
+            ),
+        }
+    }
+    let waveform = compute_waveform(track_path)?;
+    if let Err(e) = write_atomic(cache_path, &serde_json::to_vec(&waveform)?) {
+        println!("Failed to cache waveform {}: {e}", cache_path.display());
+    }
+    Ok(waveform)
+}
+
+/// Decodes the whole file into its waveform. Blocking; run in a blocking task.
+pub fn compute_waveform(path: &Path) -> anyhow::Result<Waveform> {
+    let mut builder: Option<WaveformBuilder> = None;
+    decode::decode_samples(path, |rate, channels, samples| {
+        builder
+            .get_or_insert_with(|| WaveformBuilder::new(rate, channels))
+            .push_interleaved(samples);
+    })?;
+    builder
+        .map(|b| b.finish())
+        .filter(|w| !w.peaks.is_empty())
+        .ok_or(anyhow!("{} has no audio", path.display()))
+}
+
+// peak, sum of squares and frame count of one block
+#[derive(Default)]
+struct Block {
+    peak: f32,
+    sum_squares: f64,
+    frames: usize,
+}
+
+struct WaveformBuilder {
+    rate: u32,
+    channels: usize,
+    block_frames: usize,
+    blocks: Vec<Block>,
+    current: Block,
+}
+impl WaveformBuilder {
+    fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            rate,
+            channels,
+            block_frames: ((rate as f64 * BLOCK.as_secs_f64()) as usize).max(1),
+            blocks: Vec::new(),
+            current: Block::default(),
+        }
+    }
+    fn push_interleaved(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            // channels are mixed down to mono
+            let sample = frame.iter().sum::<f32>() / self.channels as f32;
+            self.current.peak = self.current.peak.max(sample.abs());
+            self.current.sum_squares += (sample as f64).powi(2);
+            self.current.frames += 1;
+            if self.current.frames == self.block_frames {
+                self.blocks.push(std::mem::take(&mut self.current));
+            }
+        }
+    }
+    fn finish(mut self) -> Waveform {
+        if self.current.frames > 0 {
+            self.blocks.push(std::mem::take(&mut self.current));
+        }
+        let total_frames: usize = self.blocks.iter().map(|b| b.frames).sum();
+        let length = Duration::from_secs_f64(total_frames as f64 / self.rate as f64);
+        let points = ((length.as_secs_f64() * POINTS_PER_SECOND) as usize)
+            .clamp(MIN_POINTS, MAX_POINTS)
+            .min(self.blocks.len());
+
+        let scale = |level: f64| (level.min(1.0) * u8::MAX as f64).round() as u8;
+        let (mut peaks, mut rms) = (Vec::with_capacity(points), Vec::with_capacity(points));
+        for i in 0..points {
+            let blocks =
+                &self.blocks[i * self.blocks.len() / points..(i + 1) * self.blocks.len() / points];
+            let peak = blocks.iter().fold(0.0f32, |peak, b| peak.max(b.peak));
+            let frames: usize = blocks.iter().map(|b| b.frames).sum();
+            let sum_squares: f64 = blocks.iter().map(|b| b.sum_squares).sum();
+            peaks.push(scale(peak as f64));
+            rms.push(scale((sum_squares / frames.max(1) as f64).sqrt()));
+        }
+        Waveform { length, peaks, rms }
+    }
+}
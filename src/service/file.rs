@@ -1,5 +1,8 @@
 // no file service here currently, all functionality is provided in service -> file -> util
 
+pub mod db;
 pub mod enums;
+pub mod library;
 pub mod structs;
+pub mod tags;
 pub mod util;
@@ -0,0 +1,994 @@
+// SQLite backed storage for the library (tracks, playlists + their entries, albums).
+// Replaces rewriting the whole json data files on every change.
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use anyhow::anyhow;
+use parking_lot::Mutex;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde_json::json;
+use url::Url;
+
+use crate::service::{
+    audio::{
+        enums::AlbumKind,
+        structs::{EffectSettings, Loudness, PlaybackSpeed, Trim},
+    },
+    file::{
+        library::Library,
+        structs::{DataSize, LoadProblem, LoadReport},
+        util,
+    },
+    id::structs::Id,
+    playlist::structs::{
+        Album, ListeningSession, Playlist, PlaylistMetadata, StorageSettings, Track, TrackIdVec,
+    },
+};
+
+// Schema migrations, in order. The database's `user_version` is the number of migrations that
+// have been applied. Never edit an existing migration, add a new one to the end instead.
+const MIGRATIONS: [&str; 7] = [
+    // 1: initial schema
+    "
+    CREATE TABLE IF NOT EXISTS tracks (
+        id TEXT PRIMARY KEY NOT NULL,
+        source_id TEXT NOT NULL,
+        title TEXT NOT NULL,
+        length_ms INTEGER NOT NULL,
+        artist TEXT NOT NULL,
+        album_kind TEXT NOT NULL,
+        album_id TEXT,
+        download_url TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tracks_title ON tracks (title);
+    CREATE INDEX IF NOT EXISTS tracks_album_id ON tracks (album_id);
+
+    CREATE TABLE IF NOT EXISTS playlists (
+        id TEXT PRIMARY KEY NOT NULL,
+        source_id TEXT NOT NULL,
+        title TEXT NOT NULL,
+        track_count INTEGER NOT NULL,
+        length_ms INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS playlist_entries (
+        playlist_id TEXT NOT NULL REFERENCES playlists (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        track_id TEXT NOT NULL,
+        PRIMARY KEY (playlist_id, position)
+    );
+    CREATE INDEX IF NOT EXISTS playlist_entries_track_id ON playlist_entries (track_id);
+
+    CREATE TABLE IF NOT EXISTS albums (
+        id TEXT PRIMARY KEY NOT NULL,
+        source_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        artists TEXT NOT NULL,
+        img_url TEXT NOT NULL
+    );
+    ",
+    // 2: storage accounting. Rows outlive the audio files, so evicted tracks keep their history.
+    "
+    CREATE TABLE IF NOT EXISTS track_storage (
+        track_id TEXT PRIMARY KEY NOT NULL,
+        size_bytes INTEGER,
+        last_played INTEGER
+    );
+
+    CREATE TABLE IF NOT EXISTS pinned_playlists (
+        playlist_id TEXT PRIMARY KEY NOT NULL REFERENCES playlists (id) ON DELETE CASCADE
+    );
+
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY NOT NULL,
+        value TEXT NOT NULL
+    );
+    ",
+    // 3: loudness normalization. NULL until the track's audio has been analyzed.
+    "
+    ALTER TABLE tracks ADD COLUMN loudness_lufs REAL;
+    ALTER TABLE tracks ADD COLUMN true_peak_db REAL;
+    ",
+    // 4: effect presets. The presets themselves are kept in the settings table.
+    "
+    CREATE TABLE IF NOT EXISTS playlist_effect_presets (
+        playlist_id TEXT PRIMARY KEY NOT NULL REFERENCES playlists (id) ON DELETE CASCADE,
+        preset TEXT NOT NULL
+    );
+    ",
+    // 5: playback speed. Playlists at the normal speed don't have a row.
+    "
+    CREATE TABLE IF NOT EXISTS playlist_playback_speeds (
+        playlist_id TEXT PRIMARY KEY NOT NULL REFERENCES playlists (id) ON DELETE CASCADE,
+        rate REAL NOT NULL,
+        preserve_pitch INTEGER NOT NULL
+    );
+    ",
+    // 6: chapters, as a json list. NULL for tracks without any.
+    "
+    ALTER TABLE tracks ADD COLUMN chapters TEXT;
+    ",
+    // 7: how much of each track's start and end is skipped
+    "
+    ALTER TABLE tracks ADD COLUMN trim_start_ms INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE tracks ADD COLUMN trim_end_ms INTEGER NOT NULL DEFAULT 0;
+    ",
+];
+
+const STORAGE_QUOTA_KEY: &str = "storage_quota_bytes";
+const EFFECT_PRESETS_KEY: &str = "effect_presets";
+const ACTIVE_EFFECT_PRESET_KEY: &str = "active_effect_preset";
+const LAST_SESSION_KEY: &str = "last_session";
+
+/// Library data read from the json files written by older versions.
+#[derive(Default)]
+struct LegacyData {
+    playlists: HashMap<Id, Playlist>,
+    tracks: HashMap<Id, Track>,
+    albums: HashMap<Id, Album>,
+}
+
+/// Handle to the library database. Every write only touches the rows that changed.
+pub struct Database {
+    conn: Mutex<Connection>,
+    library: Library,
+}
+
+impl Database {
+    pub fn open(library: Library) -> anyhow::Result<Self> {
+        let conn = Connection::open(library.database_path())?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            library,
+        })
+    }
+
+    pub fn schema_version(&self) -> anyhow::Result<usize> {
+        let version: i64 = self
+            .conn
+            .lock()
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(version as usize)
+    }
+
+    // Applies every migration that hasn't been applied yet, each in its own transaction.
+    // `legacy` is inserted in the same transaction as the initial schema.
+    fn migrate(&self, mut legacy: Option<LegacyData>) -> anyhow::Result<()> {
+        let version = self.schema_version()?;
+        if version > MIGRATIONS.len() {
+            return Err(anyhow!(
+                "Library schema version {} is newer than this version supports ({})",
+                version,
+                MIGRATIONS.len()
+            ));
+        }
+        let mut conn = self.conn.lock();
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            println!("Migrating library schema to version {}", i + 1);
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            if i == 0
+                && let Some(legacy) = legacy.take()
+            {
+                insert_legacy_data(&tx, &legacy)?;
+            }
+            tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the database to `path`, which must not exist yet. Safe to
+    /// do while the database is open.
+    pub fn snapshot_to(&self, path: &Path) -> anyhow::Result<()> {
+        self.conn
+            .lock()
+            .execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Copies everything in another library database into this one. Rows with the same id are
+    /// updated to the other database's, and merged playlists get the other database's entries.
+    pub fn merge_from(&self, path: &Path) -> anyhow::Result<()> {
+        let conn = self.conn.lock();
+        conn.execute("ATTACH DATABASE ?1 AS other", [path.to_string_lossy()])?;
+        // upserts rather than `INSERT OR REPLACE`, which deletes the old row first and so
+        // cascades to the pinned, preset and speed rows of the playlists being merged. The
+        // `WHERE true` keeps sqlite from reading `ON CONFLICT` as part of the select.
+        let result = conn.execute_batch(
+            "
+            BEGIN;
+            INSERT INTO tracks (id, source_id, title, length_ms, artist, album_kind, album_id,
+                    download_url, loudness_lufs, true_peak_db, chapters, trim_start_ms,
+                    trim_end_ms)
+                SELECT id, source_id, title, length_ms, artist, album_kind, album_id,
+                    download_url, loudness_lufs, true_peak_db, chapters, trim_start_ms,
+                    trim_end_ms
+                FROM other.tracks WHERE true
+                ON CONFLICT(id) DO UPDATE SET
+                    source_id = excluded.source_id,
+                    title = excluded.title,
+                    length_ms = excluded.length_ms,
+                    artist = excluded.artist,
+                    album_kind = excluded.album_kind,
+                    album_id = excluded.album_id,
+                    download_url = excluded.download_url,
+                    loudness_lufs = excluded.loudness_lufs,
+                    true_peak_db = excluded.true_peak_db,
+                    chapters = excluded.chapters,
+                    trim_start_ms = excluded.trim_start_ms,
+                    trim_end_ms = excluded.trim_end_ms;
+            INSERT INTO albums (id, source_id, name, artists, img_url)
+                SELECT id, source_id, name, artists, img_url FROM other.albums WHERE true
+                ON CONFLICT(id) DO UPDATE SET
+                    source_id = excluded.source_id,
+                    name = excluded.name,
+                    artists = excluded.artists,
+                    img_url = excluded.img_url;
+            DELETE FROM playlist_entries WHERE playlist_id IN (SELECT id FROM other.playlists);
+            INSERT INTO playlists (id, source_id, title, track_count, length_ms)
+                SELECT id, source_id, title, track_count, length_ms FROM other.playlists WHERE true
+                ON CONFLICT(id) DO UPDATE SET
+                    source_id = excluded.source_id,
+                    title = excluded.title,
+                    track_count = excluded.track_count,
+                    length_ms = excluded.length_ms;
+            INSERT INTO playlist_entries (playlist_id, position, track_id)
+                SELECT playlist_id, position, track_id FROM other.playlist_entries;
+            INSERT OR IGNORE INTO pinned_playlists (playlist_id)
+                SELECT playlist_id FROM other.pinned_playlists;
+            INSERT OR IGNORE INTO playlist_effect_presets (playlist_id, preset)
+                SELECT playlist_id, preset FROM other.playlist_effect_presets;
+            INSERT OR IGNORE INTO playlist_playback_speeds (playlist_id, rate, preserve_pitch)
+                SELECT playlist_id, rate, preserve_pitch FROM other.playlist_playback_speeds;
+            COMMIT;
+            ",
+        );
+        if result.is_err() {
+            let _ = conn.execute_batch("ROLLBACK");
+        }
+        conn.execute("DETACH DATABASE other", [])?;
+        Ok(result?)
+    }
+
+    // --- LOADING --- //
+
+    pub fn load_tracks(&self, report: &mut LoadReport) -> anyhow::Result<HashMap<Id, Track>> {
+        let mut tracks = HashMap::new();
+        let mut malformed = Vec::new();
+        {
+            let conn = self.conn.lock();
+            let mut stmt = conn.prepare(
+                "SELECT id, source_id, title, length_ms, artist, album_kind, download_url,
+                    loudness_lufs, true_peak_db, chapters, trim_start_ms, trim_end_ms, rowid
+                 FROM tracks",
+            )?;
+            for row in stmt.query_map([], track_from_row)? {
+                match row? {
+                    Ok(track) => {
+                        tracks.insert(track.id().clone(), track);
+                    }
+                    Err(row) => malformed.push(row),
+                }
+            }
+        }
+        for row in malformed {
+            report.push(self.quarantine_row("tracks", row));
+        }
+        Ok(tracks)
+    }
+
+    pub fn load_playlists(&self, report: &mut LoadReport) -> anyhow::Result<HashMap<Id, Playlist>> {
+        let mut playlists = HashMap::new();
+        let mut malformed = Vec::new();
+        let mut malformed_entries = Vec::new();
+        {
+            let conn = self.conn.lock();
+            let mut playlist_stmt = conn.prepare(
+                "SELECT id, source_id, title, track_count, length_ms, rowid FROM playlists",
+            )?;
+            let mut entry_stmt = conn.prepare(
+                "SELECT position, track_id, rowid FROM playlist_entries
+                 WHERE playlist_id = ?1 ORDER BY position",
+            )?;
+            for row in playlist_stmt.query_map([], playlist_from_row)? {
+                let (id_str, metadata) = match row? {
+                    Ok(playlist) => playlist,
+                    Err(mut row) => {
+                        // keep the entries with the row, so the playlist can be pieced back together
+                        let entries: Vec<String> = entry_stmt
+                            .query_map([&row.id], |row| row.get::<_, String>(1))?
+                            .collect::<rusqlite::Result<_>>()?;
+                        row.raw["entries"] = json!(entries);
+                        malformed.push(row);
+                        continue;
+                    }
+                };
+                let mut track_ids = Vec::new();
+                for entry in entry_stmt.query_map([&id_str], |row| entry_from_row(row, &id_str))? {
+                    match entry? {
+                        Ok(track_id) => track_ids.push(track_id),
+                        Err(entry) => malformed_entries.push(entry),
+                    }
+                }
+                playlists.insert(
+                    metadata.id().clone(),
+                    Playlist::new(metadata, TrackIdVec(track_ids)),
+                );
+            }
+        }
+        for row in malformed {
+            report.push(self.quarantine_row("playlists", row));
+        }
+        for entry in malformed_entries {
+            report.push(self.quarantine_row("playlist_entries", entry));
+        }
+        Ok(playlists)
+    }
+
+    pub fn load_albums(&self, report: &mut LoadReport) -> anyhow::Result<HashMap<Id, Album>> {
+        let mut albums = HashMap::new();
+        let mut malformed = Vec::new();
+        {
+            let conn = self.conn.lock();
+            let mut stmt =
+                conn.prepare("SELECT id, source_id, name, artists, img_url, rowid FROM albums")?;
+            for row in stmt.query_map([], album_from_row)? {
+                match row? {
+                    Ok(album) => {
+                        albums.insert(album.id().clone(), album);
+                    }
+                    Err(row) => malformed.push(row),
+                }
+            }
+        }
+        for row in malformed {
+            report.push(self.quarantine_row("albums", row));
+        }
+        Ok(albums)
+    }
+
+    // Moves a row that couldn't be decoded out of its table and into a file in the quarantine
+    // directory, so it isn't hit again on every startup
+    fn quarantine_row(&self, table: &str, row: MalformedRow) -> LoadProblem {
+        println!("Quarantining {} row {}: {}", table, row.id, row.reason);
+        let quarantined_to = (|| {
+            let quarantine_dir = self.library.quarantine_dir_path();
+            std::fs::create_dir_all(&quarantine_dir)?;
+            let destination = quarantine_dir.join(format!(
+                "{}-{}-{}.json",
+                chrono::Local::now().format("%Y%m%d%H%M%S"),
+                table,
+                row.id.replace(',', "_")
+            ));
+            util::write_atomic(
+                &destination,
+                serde_json::to_string_pretty(&row.raw)?.as_bytes(),
+            )?;
+            self.conn.lock().execute(
+                &format!("DELETE FROM {table} WHERE rowid = ?1"),
+                [row.rowid],
+            )?;
+            Ok::<_, anyhow::Error>(destination)
+        })();
+        if let Err(e) = &quarantined_to {
+            println!("Failed to quarantine {} row {}: {e}", table, row.id);
+        }
+        LoadProblem {
+            source: format!("{} row {}", table, row.id),
+            reason: row.reason,
+            quarantined_to: quarantined_to.ok(),
+        }
+    }
+
+    /// Returns the recorded size of each track's audio file, and when each track was last played
+    /// (unix seconds).
+    pub fn load_track_storage(&self) -> anyhow::Result<(HashMap<Id, u64>, HashMap<Id, i64>)> {
+        let conn = self.conn.lock();
+        let mut stmt =
+            conn.prepare("SELECT track_id, size_bytes, last_played FROM track_storage")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+            ))
+        })?;
+        let mut sizes = HashMap::new();
+        let mut last_played = HashMap::new();
+        for row in rows {
+            let (id_str, size, played) = row?;
+            let Ok(id) = Id::from_string(id_str) else {
+                continue;
+            };
+            if let Some(size) = size {
+                sizes.insert(id.clone(), size as u64);
+            }
+            if let Some(played) = played {
+                last_played.insert(id, played);
+            }
+        }
+        Ok((sizes, last_played))
+    }
+
+    pub fn load_storage_settings(&self) -> anyhow::Result<StorageSettings> {
+        let conn = self.conn.lock();
+        let quota = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                [STORAGE_QUOTA_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .and_then(|value| value.parse().ok())
+            .map(DataSize::from_bytes);
+        let mut stmt = conn.prepare("SELECT playlist_id FROM pinned_playlists")?;
+        let pinned_playlists = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter_map(|s| s.ok().and_then(|s| Id::from_string(s).ok()))
+            .collect();
+        Ok(StorageSettings {
+            quota,
+            pinned_playlists,
+        })
+    }
+
+    /// Loads the effect presets. Libraries that never saved any get the built in ones.
+    pub fn load_effect_settings(&self) -> anyhow::Result<EffectSettings> {
+        let conn = self.conn.lock();
+        let setting = |key: &str| {
+            conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()
+        };
+        let mut settings = EffectSettings::default();
+        if let Some(presets) = setting(EFFECT_PRESETS_KEY)? {
+            settings.presets = serde_json::from_str(&presets)?;
+        }
+        settings.active = setting(ACTIVE_EFFECT_PRESET_KEY)?;
+        let mut stmt = conn.prepare("SELECT playlist_id, preset FROM playlist_effect_presets")?;
+        settings.playlist_presets = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .filter_map(|r| {
+                let (id, preset) = r.ok()?;
+                Some((Id::from_string(id).ok()?, preset))
+            })
+            .collect();
+        Ok(settings)
+    }
+
+    pub fn load_playback_speeds(&self) -> anyhow::Result<HashMap<Id, PlaybackSpeed>> {
+        let conn = self.conn.lock();
+        let mut stmt =
+            conn.prepare("SELECT playlist_id, rate, preserve_pitch FROM playlist_playback_speeds")?;
+        let speeds = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            })?
+            .filter_map(|r| {
+                let (id, rate, preserve_pitch) = r.ok()?;
+                Some((
+                    Id::from_string(id).ok()?,
+                    PlaybackSpeed::new(rate, preserve_pitch),
+                ))
+            })
+            .collect();
+        Ok(speeds)
+    }
+
+    /// What was playing when the app was last closed
+    pub fn load_session(&self) -> anyhow::Result<Option<ListeningSession>> {
+        let session = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                [LAST_SESSION_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(match session {
+            Some(session) => Some(serde_json::from_str(&session)?),
+            None => None,
+        })
+    }
+
+    // --- SAVING --- //
+
+    /// Records the size of each track's audio file. None means the track has no file anymore.
+    pub fn save_track_sizes<'a>(
+        &self,
+        sizes: impl IntoIterator<Item = (&'a Id, Option<u64>)>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO track_storage (track_id, size_bytes) VALUES (?1, ?2)
+                 ON CONFLICT (track_id) DO UPDATE SET size_bytes = excluded.size_bytes",
+            )?;
+            for (id, size) in sizes {
+                stmt.execute(params![id.to_string(), size.map(|s| s as i64)])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn save_last_played(&self, id: &Id, last_played: i64) -> anyhow::Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO track_storage (track_id, last_played) VALUES (?1, ?2)
+             ON CONFLICT (track_id) DO UPDATE SET last_played = excluded.last_played",
+            params![id.to_string(), last_played],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_storage_settings(&self, settings: &StorageSettings) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        match &settings.quota {
+            Some(quota) => tx.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![STORAGE_QUOTA_KEY, quota.as_bytes().to_string()],
+            )?,
+            None => tx.execute("DELETE FROM settings WHERE key = ?1", [STORAGE_QUOTA_KEY])?,
+        };
+        tx.execute("DELETE FROM pinned_playlists", [])?;
+        {
+            let mut stmt =
+                tx.prepare_cached("INSERT INTO pinned_playlists (playlist_id) VALUES (?1)")?;
+            for id in settings.pinned_playlists.iter() {
+                stmt.execute([id.to_string()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn save_effect_settings(&self, settings: &EffectSettings) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![
+                EFFECT_PRESETS_KEY,
+                serde_json::to_string(&settings.presets)?
+            ],
+        )?;
+        match &settings.active {
+            Some(active) => tx.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![ACTIVE_EFFECT_PRESET_KEY, active],
+            )?,
+            None => tx.execute(
+                "DELETE FROM settings WHERE key = ?1",
+                [ACTIVE_EFFECT_PRESET_KEY],
+            )?,
+        };
+        tx.execute("DELETE FROM playlist_effect_presets", [])?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO playlist_effect_presets (playlist_id, preset) VALUES (?1, ?2)",
+            )?;
+            for (id, preset) in settings.playlist_presets.iter() {
+                stmt.execute(params![id.to_string(), preset])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn save_playback_speed(
+        &self,
+        playlist_id: &Id,
+        speed: &PlaybackSpeed,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn.lock();
+        if *speed == PlaybackSpeed::default() {
+            conn.execute(
+                "DELETE FROM playlist_playback_speeds WHERE playlist_id = ?1",
+                [playlist_id.to_string()],
+            )?;
+        } else {
+            conn.execute(
+                "INSERT OR REPLACE INTO playlist_playback_speeds (playlist_id, rate, preserve_pitch)
+                 VALUES (?1, ?2, ?3)",
+                params![playlist_id.to_string(), speed.rate, speed.preserve_pitch],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn save_session(&self, session: Option<&ListeningSession>) -> anyhow::Result<()> {
+        let conn = self.conn.lock();
+        match session {
+            Some(session) => conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![LAST_SESSION_KEY, serde_json::to_string(session)?],
+            )?,
+            None => conn.execute("DELETE FROM settings WHERE key = ?1", [LAST_SESSION_KEY])?,
+        };
+        Ok(())
+    }
+
+    pub fn save_track(&self, track: &Track) -> anyhow::Result<()> {
+        self.save_tracks([track])
+    }
+
+    /// Inserts or replaces all the given tracks in a single transaction.
+    pub fn save_tracks<'a>(
+        &self,
+        tracks: impl IntoIterator<Item = &'a Track>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        save_tracks_in(&tx, tracks)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn remove_tracks(&self, ids: &[Id]) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("DELETE FROM tracks WHERE id = ?1")?;
+            for id in ids {
+                stmt.execute([id.to_string()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Inserts or replaces the playlist along with all of its entries.
+    pub fn save_playlist(&self, playlist: &Playlist) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        save_playlist_in(&tx, playlist)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_playlist(&self, id: &Id) -> anyhow::Result<()> {
+        // entries are removed through the foreign key cascade
+        self.conn
+            .lock()
+            .execute("DELETE FROM playlists WHERE id = ?1", [id.to_string()])?;
+        Ok(())
+    }
+
+    pub fn save_album(&self, album: &Album) -> anyhow::Result<()> {
+        save_album_in(&self.conn.lock(), album)
+    }
+
+    pub fn remove_albums(&self, ids: &[Id]) -> anyhow::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("DELETE FROM albums WHERE id = ?1")?;
+            for id in ids {
+                stmt.execute([id.to_string()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Opens the given library's database and brings its schema up to date. The first time it's
+/// opened, the old json data files are migrated into it.
+pub async fn open_library_database(
+    library: &Library,
+    report: &mut LoadReport,
+) -> anyhow::Result<Database> {
+    let library_copy = library.clone();
+    let db = tokio::task::spawn_blocking(move || Database::open(library_copy)).await??;
+    let legacy = if db.schema_version()? == 0 {
+        Some(load_legacy_data(library, report).await)
+    } else {
+        None
+    };
+    db.migrate(legacy)?;
+    Ok(db)
+}
+
+/// Makes sure the library's database (ie. one restored from a backup) is intact, and brings its
+/// schema up to date. Blocking.
+pub fn check_database(library: &Library) -> anyhow::Result<()> {
+    let db = Database::open(library.clone())?;
+    let integrity: String = db
+        .conn
+        .lock()
+        .pragma_query_value(None, "integrity_check", |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(anyhow!("Database is corrupt: {integrity}"));
+    }
+    db.migrate(None)
+}
+
+// Reads the data/*.json files written by older versions. Files that can't be parsed are
+// quarantined, the rest are left where they are so older versions still work.
+async fn load_legacy_data(library: &Library, report: &mut LoadReport) -> LegacyData {
+    let mut legacy = LegacyData::default();
+    match util::load_saved_playlists(library, report).await {
+        Ok(playlists) => legacy.playlists = playlists,
+        Err(e) => println!("Failed to read legacy playlists: {e}"),
+    }
+    if let Ok(path) = util::get_saved_tracks_file_path(library).await
+        && path.is_file()
+    {
+        match util::load_saved_tracks(library).await {
+            Ok(tracks) => legacy.tracks = tracks,
+            Err(e) => report.push(util::quarantine_file(library, &path, e).await),
+        }
+    }
+    if let Ok(path) = util::get_album_data_file_path(library).await
+        && path.is_file()
+    {
+        match util::get_albums(library).await {
+            Ok(albums) => legacy.albums = albums,
+            Err(e) => report.push(util::quarantine_file(library, &path, e).await),
+        }
+    }
+    legacy
+}
+
+fn insert_legacy_data(conn: &Connection, legacy: &LegacyData) -> anyhow::Result<()> {
+    if legacy.playlists.is_empty() && legacy.tracks.is_empty() && legacy.albums.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "Migrating {} playlists, {} tracks and {} albums from json",
+        legacy.playlists.len(),
+        legacy.tracks.len(),
+        legacy.albums.len()
+    );
+    for playlist in legacy.playlists.values() {
+        save_playlist_in(conn, playlist)?;
+    }
+    save_tracks_in(conn, legacy.tracks.values())?;
+    for album in legacy.albums.values() {
+        save_album_in(conn, album)?;
+    }
+    Ok(())
+}
+
+fn save_tracks_in<'a>(
+    conn: &Connection,
+    tracks: impl IntoIterator<Item = &'a Track>,
+) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR REPLACE INTO tracks
+            (id, source_id, title, length_ms, artist, album_kind, album_id, download_url,
+             loudness_lufs, true_peak_db, chapters, trim_start_ms, trim_end_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+    )?;
+    for track in tracks {
+        let album_id = match &track.album_kind {
+            AlbumKind::Album(album) => Some(album.id().to_string()),
+            _ => None,
+        };
+        stmt.execute(params![
+            track.id().to_string(),
+            track.source_id.to_string(),
+            track.title,
+            track.length.as_millis() as i64,
+            serde_json::to_string(&track.artist)?,
+            serde_json::to_string(&track.album_kind)?,
+            album_id,
+            track.download_url.as_str(),
+            track.loudness.as_ref().map(|l| l.integrated_lufs()),
+            track.loudness.as_ref().map(|l| l.true_peak_db()),
+            (!track.chapters.is_empty())
+                .then(|| serde_json::to_string(&track.chapters))
+                .transpose()?,
+            track.trim.start.as_millis() as i64,
+            track.trim.end.as_millis() as i64,
+        ])?;
+    }
+    Ok(())
+}
+
+fn save_playlist_in(conn: &Connection, playlist: &Playlist) -> anyhow::Result<()> {
+    let id = playlist.id().to_string();
+    let metadata = &playlist.metadata;
+    conn.execute(
+        "INSERT INTO playlists (id, source_id, title, track_count, length_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (id) DO UPDATE SET
+            source_id = excluded.source_id,
+            title = excluded.title,
+            track_count = excluded.track_count,
+            length_ms = excluded.length_ms",
+        params![
+            id,
+            metadata.source_id().to_string(),
+            metadata.title,
+            metadata.track_count as i64,
+            metadata.length.as_millis() as i64,
+        ],
+    )?;
+    conn.execute("DELETE FROM playlist_entries WHERE playlist_id = ?1", [&id])?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO playlist_entries (playlist_id, position, track_id) VALUES (?1, ?2, ?3)",
+    )?;
+    for (position, track_id) in playlist.tracks.0.iter().enumerate() {
+        stmt.execute(params![id, position as i64, track_id.to_string()])?;
+    }
+    Ok(())
+}
+
+fn save_album_in(conn: &Connection, album: &Album) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO albums (id, source_id, name, artists, img_url)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            album.id().to_string(),
+            album.source_id.to_string(),
+            album.name,
+            serde_json::to_string(&album.artists)?,
+            album.img_url.as_str(),
+        ],
+    )?;
+    Ok(())
+}
+
+// A row that was read but couldn't be turned into its struct
+struct MalformedRow {
+    id: String,
+    // where it is in its table, since not every table has an id column
+    rowid: i64,
+    raw: serde_json::Value,
+    reason: String,
+}
+
+// Rows are decoded in two steps so one malformed row doesn't fail the whole query
+fn track_from_row(row: &Row) -> rusqlite::Result<Result<Track, MalformedRow>> {
+    let id: String = row.get(0)?;
+    let source_id: String = row.get(1)?;
+    let title: String = row.get(2)?;
+    let length_ms: i64 = row.get(3)?;
+    let artist: String = row.get(4)?;
+    let album_kind: String = row.get(5)?;
+    let download_url: String = row.get(6)?;
+    let loudness_lufs: Option<f64> = row.get(7)?;
+    let true_peak_db: Option<f64> = row.get(8)?;
+    let chapters: Option<String> = row.get(9)?;
+    let trim_start_ms: i64 = row.get(10)?;
+    let trim_end_ms: i64 = row.get(11)?;
+    let rowid: i64 = row.get(12)?;
+    let track = (|| {
+        Ok::<_, anyhow::Error>(Track {
+            title: title.clone(),
+            length: Duration::from_millis(length_ms as u64),
+            artist: serde_json::from_str(&artist)?,
+            album_kind: serde_json::from_str(&album_kind)?,
+            source_id: Id::from_string(source_id.clone())?,
+            dyn_id: Id::from_string(id.clone())?,
+            download_url: Url::parse(&download_url)?,
+            loudness: loudness_lufs
+                .zip(true_peak_db)
+                .map(|(lufs, peak)| Loudness::new(lufs, peak)),
+            trim: Trim {
+                start: Duration::from_millis(trim_start_ms as u64),
+                end: Duration::from_millis(trim_end_ms as u64),
+            },
+            chapters: match &chapters {
+                Some(chapters) => serde_json::from_str(chapters)?,
+                None => Vec::new(),
+            },
+            chapter_of: None,
+        })
+    })();
+    Ok(track.map_err(|e| MalformedRow {
+        raw: json!({
+            "id": id,
+            "source_id": source_id,
+            "title": title,
+            "length_ms": length_ms,
+            "artist": artist,
+            "album_kind": album_kind,
+            "download_url": download_url,
+            "loudness_lufs": loudness_lufs,
+            "true_peak_db": true_peak_db,
+            "chapters": chapters,
+            "trim_start_ms": trim_start_ms,
+            "trim_end_ms": trim_end_ms,
+        }),
+        id,
+        rowid,
+        reason: e.to_string(),
+    }))
+}
+
+fn album_from_row(row: &Row) -> rusqlite::Result<Result<Album, MalformedRow>> {
+    let id: String = row.get(0)?;
+    let source_id: String = row.get(1)?;
+    let name: String = row.get(2)?;
+    let artists: String = row.get(3)?;
+    let img_url: String = row.get(4)?;
+    let rowid: i64 = row.get(5)?;
+    let album = (|| {
+        Ok::<_, anyhow::Error>(Album {
+            name: name.clone(),
+            source_id: Id::from_string(source_id.clone())?,
+            dyn_id: Id::from_string(id.clone())?,
+            artists: serde_json::from_str(&artists)?,
+            img_url: Url::parse(&img_url)?,
+        })
+    })();
+    Ok(album.map_err(|e| MalformedRow {
+        raw: json!({
+            "id": id,
+            "source_id": source_id,
+            "name": name,
+            "artists": artists,
+            "img_url": img_url,
+        }),
+        id,
+        rowid,
+        reason: e.to_string(),
+    }))
+}
+
+// Returns the playlist's id as stored (to look up its entries with) along with its metadata
+fn playlist_from_row(
+    row: &Row,
+) -> rusqlite::Result<Result<(String, PlaylistMetadata), MalformedRow>> {
+    let id: String = row.get(0)?;
+    let source_id: String = row.get(1)?;
+    let title: String = row.get(2)?;
+    let track_count: i64 = row.get(3)?;
+    let length_ms: i64 = row.get(4)?;
+    let rowid: i64 = row.get(5)?;
+    let metadata = (|| {
+        Ok::<_, anyhow::Error>(PlaylistMetadata::new(
+            title.clone(),
+            track_count as u64,
+            Duration::from_millis(length_ms as u64),
+            Id::from_string(source_id.clone())?,
+            Id::from_string(id.clone())?,
+        ))
+    })();
+    Ok(match metadata {
+        Ok(metadata) => Ok((id, metadata)),
+        Err(e) => Err(MalformedRow {
+            raw: json!({
+                "id": id,
+                "source_id": source_id,
+                "title": title,
+                "track_count": track_count,
+                "length_ms": length_ms,
+            }),
+            id,
+            rowid,
+            reason: e.to_string(),
+        }),
+    })
+}
+
+fn entry_from_row(row: &Row, playlist_id: &str) -> rusqlite::Result<Result<Id, MalformedRow>> {
+    let position: i64 = row.get(0)?;
+    let track_id: String = row.get(1)?;
+    let rowid: i64 = row.get(2)?;
+    Ok(Id::from_string(track_id.clone()).map_err(|e| MalformedRow {
+        raw: json!({
+            "playlist_id": playlist_id,
+            "position": position,
+            "track_id": track_id,
+        }),
+        id: format!("{playlist_id}-{position}"),
+        rowid,
+        reason: e.to_string(),
+    }))
+}
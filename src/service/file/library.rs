@@ -0,0 +1,203 @@
+// Where the library (tracks, album covers, database) lives on disk, and switching between
+// several named libraries.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::service::file::util;
+
+const APP_DIR: &str = "peanut";
+const CONFIG_FILENAME: &str = "config.json";
+const DATABASE_FILENAME: &str = "library.sqlite3";
+// where data was stored before library locations were configurable (relative to the project root)
+const LEGACY_OUTPUT_DIR: &str = "output";
+const TRACK_DIR: &str = "track";
+const DATA_DIR: &str = "data";
+const ALBUM_DIR: &str = "album";
+const QUARANTINE_DIR: &str = "quarantine";
+
+pub const LIBRARY_FLAG: &str = "--library";
+pub const LIBRARY_ENV_VAR: &str = "PEANUT_HOME";
+pub const DEFAULT_LIBRARY_NAME: &str = "default";
+
+/// Handle to a library directory. All library paths are derived from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+    name: Arc<str>,
+    root: Arc<Path>,
+}
+impl Library {
+    pub fn new(name: &str, root: PathBuf) -> Self {
+        Self {
+            name: name.into(),
+            root: root.into(),
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    pub fn track_dir_path(&self) -> PathBuf {
+        self.root.join(TRACK_DIR)
+    }
+    pub fn data_dir_path(&self) -> PathBuf {
+        self.root.join(DATA_DIR)
+    }
+    pub fn album_dir_path(&self) -> PathBuf {
+        self.root.join(ALBUM_DIR)
+    }
+    // unreadable library data gets moved here instead of being deleted or ignored
+    pub fn quarantine_dir_path(&self) -> PathBuf {
+        self.data_dir_path().join(QUARANTINE_DIR)
+    }
+    pub fn database_path(&self) -> PathBuf {
+        self.data_dir_path().join(DATABASE_FILENAME)
+    }
+    /// Creates the library's folder structure if it doesn't already exist.
+    pub fn create_dirs(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(self.track_dir_path())?;
+        std::fs::create_dir_all(self.data_dir_path())?;
+        std::fs::create_dir_all(self.album_dir_path())?;
+        Ok(())
+    }
+}
+
+/// The named libraries the user has set up, stored in the config dir.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibraryConfig {
+    // name of the library to open on startup
+    pub active: Option<String>,
+    pub libraries: BTreeMap<String, PathBuf>,
+}
+impl LibraryConfig {
+    pub fn load() -> Self {
+        let Some(path) = config_file_path() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                println!("Ignoring malformed config {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = config_file_path().ok_or(anyhow!("Could not find a config directory"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        util::write_atomic(&path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+    pub fn all(&self) -> Vec<Library> {
+        self.libraries
+            .iter()
+            .map(|(name, root)| Library::new(name, root.clone()))
+            .collect()
+    }
+    pub fn library(&self, name: &str) -> Option<Library> {
+        self.libraries
+            .get(name)
+            .map(|root| Library::new(name, root.clone()))
+    }
+}
+
+/// Works out which library to open on startup. In order of priority: the `--library` command
+/// line flag, the `PEANUT_HOME` env var, the active library in the config, then the default
+/// library in the user's data dir.
+pub fn resolve_startup_library(args: impl IntoIterator<Item = String>) -> anyhow::Result<Library> {
+    if let Some(root) = library_flag(args)? {
+        return Ok(Library::new(DEFAULT_LIBRARY_NAME, root));
+    }
+    if let Some(root) = env::var_os(LIBRARY_ENV_VAR).filter(|v| !v.is_empty()) {
+        return Ok(Library::new(DEFAULT_LIBRARY_NAME, PathBuf::from(root)));
+    }
+    let config = LibraryConfig::load();
+    if let Some(name) = &config.active {
+        match config.library(name) {
+            Some(library) => return Ok(library),
+            None => println!("Active library '{name}' is not in the config, using the default"),
+        }
+    }
+    default_library()
+}
+
+/// The library used when nothing else is configured.
+pub fn default_library() -> anyhow::Result<Library> {
+    // keep using the old output folder if there's already a library in it
+    if let Ok(project_root) = util::get_project_root() {
+        let legacy_root = project_root.join(LEGACY_OUTPUT_DIR);
+        if legacy_root.join(DATA_DIR).is_dir() {
+            return Ok(Library::new(DEFAULT_LIBRARY_NAME, legacy_root));
+        }
+    }
+    let data_dir = data_home().ok_or(anyhow!("Could not find a data directory"))?;
+    Ok(Library::new(DEFAULT_LIBRARY_NAME, data_dir.join(APP_DIR)))
+}
+
+// Gets the value of `--library <path>` or `--library=<path>`
+fn library_flag(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<PathBuf>> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == LIBRARY_FLAG {
+            let path = args.next().ok_or(anyhow!("{LIBRARY_FLAG} needs a path"))?;
+            return Ok(Some(PathBuf::from(path)));
+        }
+        if let Some(path) = arg
+            .strip_prefix(LIBRARY_FLAG)
+            .and_then(|s| s.strip_prefix('='))
+        {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    Some(config_home()?.join(APP_DIR).join(CONFIG_FILENAME))
+}
+
+// $XDG_DATA_HOME, falling back to the platform's usual location
+fn data_home() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME").or_else(|| {
+        if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            home_dir().map(|home| home.join(".local").join("share"))
+        }
+    })
+}
+
+// $XDG_CONFIG_HOME, falling back to the platform's usual location
+fn config_home() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME").or_else(|| {
+        if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            home_dir().map(|home| home.join(".config"))
+        }
+    })
+}
+
+// the spec says relative paths should be ignored
+fn xdg_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
@@ -1,7 +1,45 @@
-use std::path::PathBuf;
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+};
 
 use crate::service::file::enums::SizeUnit;
 
+/// Everything that couldn't be loaded from the library on startup.
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    pub problems: Vec<LoadProblem>,
+}
+impl LoadReport {
+    pub fn push(&mut self, problem: LoadProblem) {
+        self.problems.push(problem);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadProblem {
+    // the file or database row that couldn't be loaded
+    pub source: String,
+    pub reason: String,
+    // where the data was moved to, if it was moved
+    pub quarantined_to: Option<PathBuf>,
+}
+
+/// Metadata embedded into a track's audio file.
+#[derive(Debug, Clone, Default)]
+pub struct TrackTags {
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_artists: Vec<String>,
+    pub musicbrainz_album_id: Option<String>,
+    // jpeg front cover
+    pub cover: Option<Vec<u8>>,
+}
+
 #[derive(Clone)]
 pub struct BinApps {
     pub yt_dlp: PathBuf,
@@ -31,6 +69,12 @@ impl DataSize {
             },
         }
     }
+    pub fn from_bytes(size: u64) -> Self {
+        Self { size }
+    }
+    pub fn as_bytes(&self) -> u64 {
+        self.size
+    }
     pub fn as_kibibytes(&self) -> f64 {
         self.size as f64 / 1024.0
     }
@@ -41,3 +85,17 @@ impl DataSize {
         self.size as f64 / 1048576.0
     }
 }
+impl Display for DataSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // pick the biggest unit that keeps the number readable
+        if self.size >= 1073741824 {
+            write!(f, "{:.2} GiB", self.as_mebibytes() / 1024.0)
+        } else if self.size >= 1048576 {
+            write!(f, "{:.1} MiB", self.as_mebibytes())
+        } else if self.size >= 1024 {
+            write!(f, "{:.1} KiB", self.as_kibibytes())
+        } else {
+            write!(f, "{} B", self.size)
+        }
+    }
+}
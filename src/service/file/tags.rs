@@ -0,0 +1,115 @@
+// Embedding track metadata + album covers into the audio files themselves, so the library shows
+// up properly in other players.
+
+use std::{io::Cursor, path::Path};
+
+use anyhow::Result;
+use id3::TagLike;
+
+use crate::service::{
+    audio::enums::AlbumKind,
+    file::{library::Library, structs::TrackTags, util},
+    id::enums::Platform,
+    playlist::{enums::Artist, structs::Track},
+};
+
+mod mp4;
+mod vorbis;
+
+// Freeform tag names, as written by MusicBrainz Picard
+const MUSICBRAINZ_ALBUM_ID_ID3: &str = "MusicBrainz Album Id";
+const MUSICBRAINZ_ALBUM_ID_VORBIS: &str = "MUSICBRAINZ_ALBUMID";
+const COVER_MIME_TYPE: &str = "image/jpeg";
+
+impl TrackTags {
+    /// Gathers the tags for the track, including the album cover if it's been downloaded.
+    /// Blocking.
+    pub fn from_track(library: &Library, track: &Track) -> Self {
+        let artists = match &track.artist {
+            Artist::Official(artists) => artists.clone(),
+            Artist::Community(artist) => vec![artist.clone()],
+        };
+        let mut tags = Self {
+            title: track.title.clone(),
+            artists,
+            ..Default::default()
+        };
+        if let AlbumKind::Album(album) = &track.album_kind {
+            tags.album = Some(album.name.clone());
+            tags.album_artists = album.artists.clone();
+            if album.id().platform == Platform::MusicBrainz {
+                tags.musicbrainz_album_id = Some(album.id().id.clone());
+            }
+            tags.cover = util::album_filename_from_id(library, album.id())
+                .ok()
+                .and_then(|path| std::fs::read(path).ok());
+        }
+        tags
+    }
+}
+
+/// Writes the track's tags into its audio file. Returns false if the track has no audio file, or
+/// its format can't hold tags (ie. wav). Blocking; run in a blocking task.
+pub fn write_track_tags(library: &Library, track: &Track) -> Result<bool> {
+    let path = util::track_file_path_from_id(library, track.id())?;
+    if !path.is_file() {
+        return Ok(false);
+    }
+    write_tags(&path, &TrackTags::from_track(library, track))
+}
+
+/// Writes the tags into the audio file, picking the tag format from the file's extension.
+/// The file is replaced atomically. Returns false if the format isn't supported.
+pub fn write_tags(path: &Path, tags: &TrackTags) -> Result<bool> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let contents = std::fs::read(path)?;
+    let tagged = match extension.as_str() {
+        "m4a" | "mp4" => mp4::write_tags(&contents, tags)?,
+        "mp3" => write_id3_tags(contents, tags)?,
+        "flac" => vorbis::write_flac_tags(&contents, tags)?,
+        "ogg" | "opus" => vorbis::write_ogg_tags(&contents, tags)?,
+        _ => return Ok(false),
+    };
+    util::write_atomic(path, &tagged)?;
+    Ok(true)
+}
+
+fn write_id3_tags(contents: Vec<u8>, tags: &TrackTags) -> Result<Vec<u8>> {
+    let mut file = Cursor::new(contents);
+    // keep whatever was already there
+    let mut tag = id3::no_tag_ok(id3::Tag::read_from2(&mut file))?.unwrap_or_else(id3::Tag::new);
+    tag.set_title(&tags.title);
+    // id3v2.4 separates multiple values with a null character
+    tag.set_artist(tags.artists.join("\0"));
+    match &tags.album {
+        Some(album) => tag.set_album(album),
+        None => tag.remove_album(),
+    }
+    if tags.album_artists.is_empty() {
+        tag.remove_album_artist();
+    } else {
+        tag.set_album_artist(tags.album_artists.join("\0"));
+    }
+    tag.remove_extended_text(Some(MUSICBRAINZ_ALBUM_ID_ID3), None);
+    if let Some(album_id) = &tags.musicbrainz_album_id {
+        tag.add_frame(id3::frame::ExtendedText {
+            description: MUSICBRAINZ_ALBUM_ID_ID3.to_string(),
+            value: album_id.clone(),
+        });
+    }
+    if let Some(cover) = &tags.cover {
+        tag.remove_picture_by_type(id3::frame::PictureType::CoverFront);
+        tag.add_frame(id3::frame::Picture {
+            mime_type: COVER_MIME_TYPE.to_string(),
+            picture_type: id3::frame::PictureType::CoverFront,
+            description: String::new(),
+            data: cover.clone(),
+        });
+    }
+    tag.write_to_file(&mut file, id3::Version::Id3v24)?;
+    Ok(file.into_inner())
+}
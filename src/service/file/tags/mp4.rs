@@ -0,0 +1,566 @@
+// MP4 (m4a) tags live in iTunes style `moov/udta/meta/ilst` atoms. Rewriting them changes the
+// size of `moov`, so any chunk offsets pointing past it (into `mdat`) have to be shifted too.
+
+use std::ops::Range;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::service::file::{structs::TrackTags, tags::MUSICBRAINZ_ALBUM_ID_ID3};
+
+const FREEFORM_MEAN: &str = "com.apple.iTunes";
+// `data` atom type indicators
+const DATA_TYPE_UTF8: u32 = 1;
+const DATA_TYPE_JPEG: u32 = 13;
+
+const TITLE: &[u8; 4] = b"\xa9nam";
+const ARTIST: &[u8; 4] = b"\xa9ART";
+const ALBUM: &[u8; 4] = b"\xa9alb";
+const ALBUM_ARTIST: &[u8; 4] = b"aART";
+const COVER: &[u8; 4] = b"covr";
+const FREEFORM: &[u8; 4] = b"----";
+
+// atoms that (can) lead to the chunk offset tables
+const OFFSET_CONTAINERS: [&[u8; 4]; 4] = [b"trak", b"mdia", b"minf", b"stbl"];
+
+struct Atom {
+    kind: [u8; 4],
+    // the whole atom, header included
+    range: Range<usize>,
+    body: Range<usize>,
+}
+
+/// Returns the file with its tags replaced.
+pub fn write_tags(contents: &[u8], tags: &TrackTags) -> Result<Vec<u8>> {
+    let atoms = parse_atoms(contents)?;
+    // fragmented files have offsets all over the place; not worth handling
+    if atoms.iter().any(|a| &a.kind == b"moof") {
+        bail!("Fragmented mp4 files can't be tagged");
+    }
+    let moov = atoms
+        .iter()
+        .find(|a| &a.kind == b"moov")
+        .ok_or(anyhow!("No moov atom"))?;
+
+    let moov_body = &contents[moov.body.clone()];
+    let mut new_body = Vec::with_capacity(moov_body.len());
+    let mut udta_found = false;
+    for child in parse_atoms(moov_body)? {
+        if &child.kind == b"udta" {
+            new_body.extend(atom(b"udta", &rewrite_udta(&moov_body[child.body], tags)?));
+            udta_found = true;
+        } else {
+            new_body.extend_from_slice(&moov_body[child.range]);
+        }
+    }
+    if !udta_found {
+        new_body.extend(atom(b"udta", &rewrite_udta(&[], tags)?));
+    }
+    let mut new_moov = atom(b"moov", &new_body);
+
+    // everything after moov moves by however much it grew
+    let delta = new_moov.len() as i64 - moov.range.len() as i64;
+    if delta != 0 {
+        shift_chunk_offsets(&mut new_moov[8..], moov.range.end as u64, delta)?;
+    }
+
+    let mut out = Vec::with_capacity(contents.len() + new_moov.len());
+    for a in atoms {
+        if &a.kind == b"moov" {
+            out.extend_from_slice(&new_moov);
+        } else {
+            out.extend_from_slice(&contents[a.range]);
+        }
+    }
+    Ok(out)
+}
+
+fn rewrite_udta(body: &[u8], tags: &TrackTags) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut meta_found = false;
+    for child in parse_atoms(body)? {
+        if &child.kind == b"meta" {
+            out.extend(atom(b"meta", &rewrite_meta(&body[child.body], tags)?));
+            meta_found = true;
+        } else {
+            out.extend_from_slice(&body[child.range]);
+        }
+    }
+    if !meta_found {
+        out.extend(atom(b"meta", &rewrite_meta(&[], tags)?));
+    }
+    Ok(out)
+}
+
+fn rewrite_meta(body: &[u8], tags: &TrackTags) -> Result<Vec<u8>> {
+    // meta is a full atom; skip its version + flags
+    let children = match body.len() {
+        0 => &[][..],
+        1..4 => bail!("Malformed meta atom"),
+        _ => &body[4..],
+    };
+    let mut out = vec![0; 4];
+    let mut hdlr_found = false;
+    let mut ilst_found = false;
+    for child in parse_atoms(children)? {
+        match &child.kind {
+            b"hdlr" => {
+                hdlr_found = true;
+                out.extend_from_slice(&children[child.range]);
+            }
+            b"ilst" => {
+                ilst_found = true;
+                out.extend(atom(b"ilst", &rewrite_ilst(&children[child.body], tags)?));
+            }
+            _ => out.extend_from_slice(&children[child.range]),
+        }
+    }
+    if !hdlr_found {
+        // version + flags, predefined, handler type, reserved, empty name
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(b"mdirappl");
+        hdlr.extend_from_slice(&[0; 9]);
+        out.splice(4..4, atom(b"hdlr", &hdlr));
+    }
+    if !ilst_found {
+        out.extend(atom(b"ilst", &rewrite_ilst(&[], tags)?));
+    }
+    Ok(out)
+}
+
+fn rewrite_ilst(body: &[u8], tags: &TrackTags) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    // keep any items that aren't being replaced
+    for item in parse_atoms(body)? {
+        let replaced = match &item.kind {
+            TITLE | ARTIST | ALBUM | ALBUM_ARTIST => true,
+            // keep the old cover if there isn't a new one
+            COVER => tags.cover.is_some(),
+            FREEFORM => {
+                freeform_name(&body[item.body.clone()]).as_deref() == Some(MUSICBRAINZ_ALBUM_ID_ID3)
+            }
+            _ => false,
+        };
+        if !replaced {
+            out.extend_from_slice(&body[item.range]);
+        }
+    }
+
+    out.extend(text_item(TITLE, &tags.title));
+    if !tags.artists.is_empty() {
+        out.extend(text_item(ARTIST, &tags.artists.join(", ")));
+    }
+    if let Some(album) = &tags.album {
+        out.extend(text_item(ALBUM, album));
+    }
+    if !tags.album_artists.is_empty() {
+        out.extend(text_item(ALBUM_ARTIST, &tags.album_artists.join(", ")));
+    }
+    if let Some(album_id) = &tags.musicbrainz_album_id {
+        let mut freeform = full_atom(b"mean", FREEFORM_MEAN.as_bytes());
+        freeform.extend(full_atom(b"name", MUSICBRAINZ_ALBUM_ID_ID3.as_bytes()));
+        freeform.extend(data_atom(DATA_TYPE_UTF8, album_id.as_bytes()));
+        out.extend(atom(FREEFORM, &freeform));
+    }
+    if let Some(cover) = &tags.cover {
+        out.extend(atom(COVER, &data_atom(DATA_TYPE_JPEG, cover)));
+    }
+    Ok(out)
+}
+
+// The name of a `----` item, ie. "MusicBrainz Album Id"
+fn freeform_name(body: &[u8]) -> Option<String> {
+    let name = parse_atoms(body)
+        .ok()?
+        .into_iter()
+        .find(|a| &a.kind == b"name")?;
+    let name = body.get(name.body)?.get(4..)?;
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+// Adds `delta` to every chunk offset at or past `after`
+fn shift_chunk_offsets(bytes: &mut [u8], after: u64, delta: i64) -> Result<()> {
+    for child in parse_atoms(bytes)? {
+        let body = &mut bytes[child.body];
+        match &child.kind {
+            b"stco" => shift_offset_table(body, 4, after, delta)?,
+            b"co64" => shift_offset_table(body, 8, after, delta)?,
+            kind if OFFSET_CONTAINERS.contains(&kind) => shift_chunk_offsets(body, after, delta)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn shift_offset_table(body: &mut [u8], width: usize, after: u64, delta: i64) -> Result<()> {
+    // version + flags, then the entry count
+    let count = body
+        .get(4..8)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+        .ok_or(anyhow!("Malformed chunk offset table"))?;
+    if body.len() < 8 + count * width {
+        bail!("Malformed chunk offset table");
+    }
+    for entry in body[8..8 + count * width].chunks_exact_mut(width) {
+        let offset = match width {
+            4 => u32::from_be_bytes(entry.try_into().unwrap()) as u64,
+            _ => u64::from_be_bytes(entry.try_into().unwrap()),
+        };
+        if offset < after {
+            continue;
+        }
+        let shifted = offset
+            .checked_add_signed(delta)
+            .ok_or(anyhow!("Chunk offset out of range"))?;
+        match width {
+            4 => {
+                let shifted = u32::try_from(shifted)
+                    .map_err(|_| anyhow!("Chunk offset no longer fits in stco"))?;
+                entry.copy_from_slice(&shifted.to_be_bytes());
+            }
+            _ => entry.copy_from_slice(&shifted.to_be_bytes()),
+        }
+    }
+    Ok(())
+}
+
+fn parse_atoms(bytes: &[u8]) -> Result<Vec<Atom>> {
+    let mut atoms = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let header = bytes
+            .get(pos..pos + 8)
+            .ok_or(anyhow!("Truncated atom header"))?;
+        let size = u32::from_be_bytes(header[0..4].try_into().unwrap());
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+        let (header_len, size) = match size {
+            // extends to the end of the file
+            0 => (8, bytes.len() - pos),
+            // 64 bit size follows the type
+            1 => {
+                let large_size = bytes
+                    .get(pos + 8..pos + 16)
+                    .ok_or(anyhow!("Truncated atom header"))?;
+                (
+                    16,
+                    u64::from_be_bytes(large_size.try_into().unwrap()) as usize,
+                )
+            }
+            n => (8, n as usize),
+        };
+        if size < header_len || bytes.len() - pos < size {
+            bail!(
+                "Malformed {} atom",
+                String::from_utf8_lossy(&kind).into_owned()
+            );
+        }
+        atoms.push(Atom {
+            kind,
+            range: pos..pos + size,
+            body: pos + header_len..pos + size,
+        });
+        pos += size;
+    }
+    Ok(atoms)
+}
+
+fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 8);
+    out.extend_from_slice(&((body.len() + 8) as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    out
+}
+
+// An atom with (zeroed) version + flags before its body
+fn full_atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut full_body = vec![0; 4];
+    full_body.extend_from_slice(body);
+    atom(kind, &full_body)
+}
+
+fn data_atom(data_type: u32, payload: &[u8]) -> Vec<u8> {
+    let mut body = data_type.to_be_bytes().to_vec();
+    // locale
+    body.extend_from_slice(&[0; 4]);
+    body.extend_from_slice(payload);
+    atom(b"data", &body)
+}
+
+fn text_item(kind: &[u8; 4], text: &str) -> Vec<u8> {
+    atom(kind, &data_atom(DATA_TYPE_UTF8, text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MDAT_PAYLOAD: &[u8] = b"audio frames go here";
+
+    fn tags() -> TrackTags {
+        TrackTags {
+            title: "Song".to_string(),
+            artists: vec!["One".to_string(), "Two".to_string()],
+            album: Some("Album".to_string()),
+            album_artists: vec!["One".to_string()],
+            musicbrainz_album_id: Some("0000-1111".to_string()),
+            cover: None,
+        }
+    }
+
+    // A chunk offset table with a single entry
+    fn offset_table(kind: &[u8; 4], offset: u64) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        match kind {
+            b"stco" => body.extend_from_slice(&(offset as u32).to_be_bytes()),
+            _ => body.extend_from_slice(&offset.to_be_bytes()),
+        }
+        atom(kind, &body)
+    }
+
+    fn trak(table: &[u8]) -> Vec<u8> {
+        atom(
+            b"trak",
+            &atom(b"mdia", &atom(b"minf", &atom(b"stbl", table))),
+        )
+    }
+
+    // ftyp, then moov (holding `udta` and a track pointing at the mdat payload) and mdat, in
+    // that order unless `mdat_first`
+    fn file(table_kind: &[u8; 4], udta: Option<Vec<u8>>, mdat_first: bool) -> Vec<u8> {
+        let ftyp = atom(b"ftyp", b"M4A \0\0\0\0");
+        let mdat = atom(b"mdat", MDAT_PAYLOAD);
+        let moov = |offset: u64| {
+            let mut body = trak(&offset_table(table_kind, offset));
+            if let Some(udta) = &udta {
+                body.extend(atom(b"udta", udta));
+            }
+            atom(b"moov", &body)
+        };
+        let mut out = ftyp.clone();
+        if mdat_first {
+            out.extend(&mdat);
+            out.extend(moov(ftyp.len() as u64 + 8));
+        } else {
+            // the offset depends on moov's size, which doesn't depend on the offset
+            let moov_len = moov(0).len();
+            out.extend(moov((ftyp.len() + moov_len + 8) as u64));
+            out.extend(&mdat);
+        }
+        out
+    }
+
+    fn child<'a>(bytes: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+        let found = parse_atoms(bytes)
+            .unwrap()
+            .into_iter()
+            .find(|a| &a.kind == kind)?;
+        Some(&bytes[found.body])
+    }
+
+    fn moov(file: &[u8]) -> &[u8] {
+        child(file, b"moov").expect("moov")
+    }
+
+    fn chunk_offset(file: &[u8]) -> u64 {
+        let stbl = [b"trak", b"mdia", b"minf", b"stbl"]
+            .into_iter()
+            .fold(moov(file), |bytes, kind| child(bytes, kind).unwrap());
+        if let Some(stco) = child(stbl, b"stco") {
+            u32::from_be_bytes(stco[8..12].try_into().unwrap()) as u64
+        } else {
+            let co64 = child(stbl, b"co64").unwrap();
+            u64::from_be_bytes(co64[8..16].try_into().unwrap())
+        }
+    }
+
+    fn meta(file: &[u8]) -> &[u8] {
+        let udta = child(moov(file), b"udta").expect("udta");
+        // skip the version + flags
+        &child(udta, b"meta").expect("meta")[4..]
+    }
+
+    // The kinds of the ilst items, in order, with the text (or bytes) of their data atoms
+    fn items(file: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        let ilst = child(meta(file), b"ilst").expect("ilst");
+        parse_atoms(ilst)
+            .unwrap()
+            .into_iter()
+            .map(|item| {
+                let data = child(&ilst[item.body], b"data").unwrap();
+                // skip the type indicator and locale
+                (item.kind, data[8..].to_vec())
+            })
+            .collect()
+    }
+
+    fn text(file: &[u8], kind: &[u8; 4]) -> Option<String> {
+        items(file)
+            .into_iter()
+            .find(|(k, _)| k == kind)
+            .map(|(_, data)| String::from_utf8(data).unwrap())
+    }
+
+    fn payload_at_offset(file: &[u8]) -> &[u8] {
+        let offset = chunk_offset(file) as usize;
+        &file[offset..offset + MDAT_PAYLOAD.len()]
+    }
+
+    #[test]
+    fn stco_offsets_follow_the_audio() {
+        let original = file(b"stco", None, false);
+        assert_eq!(payload_at_offset(&original), MDAT_PAYLOAD);
+
+        let tagged = write_tags(&original, &tags()).unwrap();
+        assert!(tagged.len() > original.len());
+        assert_eq!(
+            chunk_offset(&tagged),
+            chunk_offset(&original) + (tagged.len() - original.len()) as u64
+        );
+        assert_eq!(payload_at_offset(&tagged), MDAT_PAYLOAD);
+    }
+
+    #[test]
+    fn co64_offsets_follow_the_audio() {
+        let original = file(b"co64", None, false);
+        let tagged = write_tags(&original, &tags()).unwrap();
+        assert_eq!(payload_at_offset(&tagged), MDAT_PAYLOAD);
+
+        // and back when the tags shrink
+        let retagged = write_tags(
+            &tagged,
+            &TrackTags {
+                title: "S".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(retagged.len() < tagged.len());
+        assert_eq!(payload_at_offset(&retagged), MDAT_PAYLOAD);
+    }
+
+    #[test]
+    fn offsets_before_moov_are_left_alone() {
+        let original = file(b"stco", None, true);
+        let tagged = write_tags(&original, &tags()).unwrap();
+        assert_eq!(chunk_offset(&tagged), chunk_offset(&original));
+        assert_eq!(payload_at_offset(&tagged), MDAT_PAYLOAD);
+    }
+
+    #[test]
+    fn missing_udta_is_created() {
+        let tagged = write_tags(&file(b"stco", None, false), &tags()).unwrap();
+        // other players need the handler to read the tags
+        let hdlr = child(meta(&tagged), b"hdlr").expect("hdlr");
+        assert_eq!(&hdlr[8..12], b"mdir");
+        assert_eq!(text(&tagged, TITLE).as_deref(), Some("Song"));
+        assert_eq!(text(&tagged, ARTIST).as_deref(), Some("One, Two"));
+        assert_eq!(text(&tagged, ALBUM).as_deref(), Some("Album"));
+        assert_eq!(text(&tagged, ALBUM_ARTIST).as_deref(), Some("One"));
+        let ilst = child(meta(&tagged), b"ilst").unwrap();
+        let freeform = parse_atoms(ilst)
+            .unwrap()
+            .into_iter()
+            .find(|a| &a.kind == FREEFORM)
+            .unwrap();
+        assert_eq!(
+            freeform_name(&ilst[freeform.body]).as_deref(),
+            Some(MUSICBRAINZ_ALBUM_ID_ID3)
+        );
+    }
+
+    #[test]
+    fn missing_meta_is_created() {
+        // udta with something else in it, but no meta
+        let udta = atom(b"\xa9xyz", b"kept");
+        let tagged = write_tags(&file(b"stco", Some(udta.clone()), false), &tags()).unwrap();
+        let new_udta = child(moov(&tagged), b"udta").unwrap();
+        assert!(new_udta.starts_with(&udta));
+        assert_eq!(text(&tagged, TITLE).as_deref(), Some("Song"));
+        assert_eq!(payload_at_offset(&tagged), MDAT_PAYLOAD);
+    }
+
+    #[test]
+    fn missing_ilst_is_created() {
+        // meta with just a handler
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(b"mdirappl");
+        hdlr.extend_from_slice(&[0; 9]);
+        let meta_body = [vec![0; 4], atom(b"hdlr", &hdlr)].concat();
+        let udta = atom(b"meta", &meta_body);
+        let tagged = write_tags(&file(b"stco", Some(udta), false), &tags()).unwrap();
+        // the existing handler is kept rather than a second one added
+        let handlers = parse_atoms(meta(&tagged))
+            .unwrap()
+            .into_iter()
+            .filter(|a| &a.kind == b"hdlr")
+            .count();
+        assert_eq!(handlers, 1);
+        assert_eq!(text(&tagged, TITLE).as_deref(), Some("Song"));
+    }
+
+    #[test]
+    fn other_items_are_kept() {
+        let first = write_tags(
+            &file(b"stco", None, false),
+            &TrackTags {
+                title: "Old".to_string(),
+                cover: Some(b"old cover".to_vec()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // an item that isn't one of ours, ie. the track number
+        let extra = atom(b"trkn", &data_atom(0, &[0, 0, 0, 3, 0, 9, 0, 0]));
+        let other = splice_item(&first, &extra);
+        assert_eq!(payload_at_offset(&other), MDAT_PAYLOAD);
+        let retagged = write_tags(&other, &tags()).unwrap();
+
+        let kinds: Vec<[u8; 4]> = items(&retagged).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(kinds.iter().filter(|k| *k == TITLE).count(), 1);
+        assert_eq!(kinds.iter().filter(|k| *k == FREEFORM).count(), 1);
+        assert!(kinds.contains(b"trkn"));
+        assert_eq!(text(&retagged, TITLE).as_deref(), Some("Song"));
+        // there's no new cover, so the old one stays
+        let cover = items(&retagged).into_iter().find(|(k, _)| k == COVER);
+        assert_eq!(cover.map(|(_, data)| data), Some(b"old cover".to_vec()));
+        assert_eq!(payload_at_offset(&retagged), MDAT_PAYLOAD);
+    }
+
+    #[test]
+    fn tagging_twice_changes_nothing() {
+        let once = write_tags(&file(b"stco", None, false), &tags()).unwrap();
+        let twice = write_tags(&once, &tags()).unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn fragmented_files_are_refused() {
+        let mut fragmented = file(b"stco", None, false);
+        fragmented.extend(atom(b"moof", &[]));
+        assert!(write_tags(&fragmented, &tags()).is_err());
+    }
+
+    // Adds an item to the start of the file's ilst, fixing up the sizes (and offsets) around it
+    fn splice_item(file: &[u8], item: &[u8]) -> Vec<u8> {
+        let grow = |container: &[u8], kind: &[u8; 4], inner: &dyn Fn(&[u8]) -> Vec<u8>| {
+            let mut out = Vec::new();
+            for a in parse_atoms(container).unwrap() {
+                if &a.kind == kind {
+                    out.extend(atom(kind, &inner(&container[a.body])));
+                } else {
+                    out.extend_from_slice(&container[a.range]);
+                }
+            }
+            out
+        };
+        let ilst = |body: &[u8]| [item, body].concat();
+        let meta = |body: &[u8]| [&body[..4], &grow(&body[4..], b"ilst", &ilst)].concat();
+        let udta = |body: &[u8]| grow(body, b"meta", &meta);
+        let moov = |body: &[u8]| {
+            let mut body = grow(body, b"udta", &udta);
+            shift_chunk_offsets(&mut body, 0, item.len() as i64).unwrap();
+            body
+        };
+        grow(file, b"moov", &moov)
+    }
+}
@@ -0,0 +1,256 @@
+// Vorbis comments, used by flac and ogg (vorbis + opus) files
+
+use std::{collections::HashMap, io::Cursor};
+
+use anyhow::{Result, anyhow, bail};
+use base64::Engine;
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+
+use crate::service::file::{
+    structs::TrackTags,
+    tags::{COVER_MIME_TYPE, MUSICBRAINZ_ALBUM_ID_VORBIS},
+};
+
+const VENDOR: &str = "peanut";
+const FLAC_MAGIC: &[u8] = b"fLaC";
+const VORBIS_COMMENT_MAGIC: &[u8] = b"\x03vorbis";
+const OPUS_TAGS_MAGIC: &[u8] = b"OpusTags";
+// flac metadata block types
+const BLOCK_STREAMINFO: u8 = 0;
+const BLOCK_VORBIS_COMMENT: u8 = 4;
+const BLOCK_PICTURE: u8 = 6;
+const PICTURE_TYPE_FRONT_COVER: u32 = 3;
+// ogg files don't have picture blocks, so they're base64'd into a comment instead
+const PICTURE_COMMENT_KEY: &str = "METADATA_BLOCK_PICTURE";
+const REPLACED_KEYS: [&str; 5] = [
+    "TITLE",
+    "ARTIST",
+    "ALBUM",
+    "ALBUMARTIST",
+    MUSICBRAINZ_ALBUM_ID_VORBIS,
+];
+
+struct VorbisComment {
+    vendor: String,
+    // `KEY=value`
+    comments: Vec<String>,
+}
+impl VorbisComment {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let read_string = |pos: &mut usize| -> Result<String> {
+            let len = read_u32_le(bytes, *pos)? as usize;
+            let s = bytes
+                .get(*pos + 4..*pos + 4 + len)
+                .ok_or(anyhow!("Truncated vorbis comment"))?;
+            *pos += 4 + len;
+            Ok(String::from_utf8_lossy(s).into_owned())
+        };
+        let vendor = read_string(&mut pos)?;
+        let count = read_u32_le(bytes, pos)?;
+        pos += 4;
+        let comments = (0..count)
+            .map(|_| read_string(&mut pos))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { vendor, comments })
+    }
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.vendor.as_bytes());
+        out.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for comment in &self.comments {
+            out.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            out.extend_from_slice(comment.as_bytes());
+        }
+        out
+    }
+    fn new() -> Self {
+        Self {
+            vendor: VENDOR.to_string(),
+            comments: Vec::new(),
+        }
+    }
+    // `cover_in_comment` is for formats without picture blocks (ogg)
+    fn apply(&mut self, tags: &TrackTags, cover_in_comment: bool) {
+        let replace_cover = cover_in_comment && tags.cover.is_some();
+        self.comments.retain(|comment| {
+            let key = comment
+                .split_once('=')
+                .map(|(key, _)| key.to_uppercase())
+                .unwrap_or_default();
+            let replaced = REPLACED_KEYS.contains(&key.as_str())
+                || (replace_cover && key == PICTURE_COMMENT_KEY);
+            !replaced
+        });
+        self.comments.push(format!("TITLE={}", tags.title));
+        for artist in &tags.artists {
+            self.comments.push(format!("ARTIST={artist}"));
+        }
+        if let Some(album) = &tags.album {
+            self.comments.push(format!("ALBUM={album}"));
+        }
+        for artist in &tags.album_artists {
+            self.comments.push(format!("ALBUMARTIST={artist}"));
+        }
+        if let Some(album_id) = &tags.musicbrainz_album_id {
+            self.comments
+                .push(format!("{MUSICBRAINZ_ALBUM_ID_VORBIS}={album_id}"));
+        }
+        if let Some(cover) = tags.cover.as_ref().filter(|_| cover_in_comment) {
+            let picture = base64::engine::general_purpose::STANDARD.encode(picture_block(cover));
+            self.comments
+                .push(format!("{PICTURE_COMMENT_KEY}={picture}"));
+        }
+    }
+}
+
+/// Returns the flac file with its tags replaced.
+pub fn write_flac_tags(contents: &[u8], tags: &TrackTags) -> Result<Vec<u8>> {
+    if !contents.starts_with(FLAC_MAGIC) {
+        bail!("Not a flac file");
+    }
+    // (type, body) of each metadata block
+    let mut blocks = Vec::new();
+    let mut pos = FLAC_MAGIC.len();
+    loop {
+        let header = contents
+            .get(pos..pos + 4)
+            .ok_or(anyhow!("Truncated flac metadata"))?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let body = contents
+            .get(pos + 4..pos + 4 + len)
+            .ok_or(anyhow!("Truncated flac metadata"))?;
+        blocks.push((block_type, body));
+        pos += 4 + len;
+        if is_last {
+            break;
+        }
+    }
+    let audio = &contents[pos..];
+
+    let mut comment = match blocks.iter().find(|(t, _)| *t == BLOCK_VORBIS_COMMENT) {
+        Some((_, body)) => VorbisComment::parse(body)?,
+        None => VorbisComment::new(),
+    };
+    comment.apply(tags, false);
+    let comment = comment.encode();
+    let picture = tags.cover.as_ref().map(|cover| picture_block(cover));
+
+    let mut new_blocks: Vec<(u8, &[u8])> = blocks
+        .into_iter()
+        .filter(|(block_type, body)| match *block_type {
+            BLOCK_VORBIS_COMMENT => false,
+            BLOCK_PICTURE => {
+                picture.is_none() || read_u32_be(body, 0).ok() != Some(PICTURE_TYPE_FRONT_COVER)
+            }
+            _ => true,
+        })
+        .collect();
+    // streaminfo has to stay first
+    let comment_index = match new_blocks.first() {
+        Some((BLOCK_STREAMINFO, _)) => 1,
+        _ => 0,
+    };
+    new_blocks.insert(comment_index, (BLOCK_VORBIS_COMMENT, &comment));
+    if let Some(picture) = &picture {
+        new_blocks.push((BLOCK_PICTURE, picture));
+    }
+
+    let mut out = FLAC_MAGIC.to_vec();
+    let block_count = new_blocks.len();
+    for (i, (block_type, body)) in new_blocks.into_iter().enumerate() {
+        if body.len() >= 1 << 24 {
+            bail!("Flac metadata block too large");
+        }
+        let last_flag = if i + 1 == block_count { 0x80 } else { 0 };
+        out.push(block_type | last_flag);
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+    }
+    out.extend_from_slice(audio);
+    Ok(out)
+}
+
+/// Returns the ogg (vorbis or opus) file with its tags replaced. The file is re-paged packet by
+/// packet, keeping the original page boundaries + granule positions.
+pub fn write_ogg_tags(contents: &[u8], tags: &TrackTags) -> Result<Vec<u8>> {
+    let mut reader = PacketReader::new(Cursor::new(contents));
+    let mut writer = PacketWriter::new(Vec::with_capacity(contents.len()));
+    // packet index within each logical stream; the comment header is always the second packet
+    let mut packet_indices: HashMap<u32, usize> = HashMap::new();
+    let mut comment_found = false;
+    while let Some(packet) = reader.read_packet()? {
+        let serial = packet.stream_serial();
+        let absgp = packet.absgp_page();
+        let end_info = if packet.last_in_stream() {
+            PacketWriteEndInfo::EndStream
+        } else if packet.last_in_page() {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        let index = packet_indices.entry(serial).or_insert(0);
+        let mut data = packet.data;
+        if *index == 1
+            && let Some(magic) = [VORBIS_COMMENT_MAGIC, OPUS_TAGS_MAGIC]
+                .into_iter()
+                .find(|magic| data.starts_with(magic))
+        {
+            let mut comment = VorbisComment::parse(&data[magic.len()..])?;
+            comment.apply(tags, true);
+            data = magic.to_vec();
+            data.extend(comment.encode());
+            // vorbis ends the comment header with a framing bit
+            if magic == VORBIS_COMMENT_MAGIC {
+                data.push(1);
+            }
+            comment_found = true;
+        }
+        *index += 1;
+        writer.write_packet(data.into_boxed_slice(), serial, end_info, absgp)?;
+    }
+    if !comment_found {
+        bail!("No vorbis or opus comment header");
+    }
+    Ok(writer.into_inner())
+}
+
+// A flac picture block holding the front cover
+fn picture_block(cover: &[u8]) -> Vec<u8> {
+    let (width, height) = image::ImageReader::new(Cursor::new(cover))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+        .unwrap_or((0, 0));
+    let mut out = Vec::new();
+    out.extend_from_slice(&PICTURE_TYPE_FRONT_COVER.to_be_bytes());
+    out.extend_from_slice(&(COVER_MIME_TYPE.len() as u32).to_be_bytes());
+    out.extend_from_slice(COVER_MIME_TYPE.as_bytes());
+    // no description
+    out.extend_from_slice(&0u32.to_be_bytes());
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    // colour depth, indexed colour count
+    out.extend_from_slice(&24u32.to_be_bytes());
+    out.extend_from_slice(&0u32.to_be_bytes());
+    out.extend_from_slice(&(cover.len() as u32).to_be_bytes());
+    out.extend_from_slice(cover);
+    out
+}
+
+fn read_u32_le(bytes: &[u8], pos: usize) -> Result<u32> {
+    let b = bytes
+        .get(pos..pos + 4)
+        .ok_or(anyhow!("Truncated vorbis comment"))?;
+    Ok(u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u32_be(bytes: &[u8], pos: usize) -> Result<u32> {
+    let b = bytes
+        .get(pos..pos + 4)
+        .ok_or(anyhow!("Truncated flac picture"))?;
+    Ok(u32::from_be_bytes(b.try_into().unwrap()))
+}
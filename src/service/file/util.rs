@@ -1,26 +1,28 @@
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::service::id::structs::Id;
 use crate::service::playlist::enums::MediaType;
 use crate::service::playlist::structs::{Album, Playlist, Track};
 
-use super::structs::BinApps;
+use super::library::Library;
+use super::structs::{BinApps, LoadProblem, LoadReport};
 use anyhow::anyhow;
 use image::ImageFormat;
 use reqwest::Client;
 use tokio::fs::{self};
 
-const OUTPUT_DIR: &str = "output";
-const TRACK_DIR: &str = "track";
-const DATA_DIR: &str = "data";
-const ALBUM_DIR: &str = "album";
 const TRACK_DATA_FILENAME: &str = "tracks";
 const ALBUM_DATA_FILENAME: &str = "albums";
 
 const TRACK_EXTENSION: &str = "m4a";
+// other extensions tracks may be stored with (ie. imported local files)
+const ALTERNATE_TRACK_EXTENSIONS: [&str; 4] = ["mp3", "flac", "ogg", "wav"];
+// cached waveforms are kept next to their tracks
+const WAVEFORM_EXTENSION: &str = "waveform";
 const DATA_EXTENSION: &str = "json";
 const ALBUM_EXTENSION: &str = "jpeg";
 
@@ -65,51 +67,71 @@ pub fn get_bin_app_paths() -> BinApps {
     }
 }
 
-pub fn output_dir_path() -> anyhow::Result<PathBuf> {
-    Ok(get_project_root()?.join(OUTPUT_DIR))
-}
-
-pub fn track_dir_path() -> anyhow::Result<PathBuf> {
-    Ok(output_dir_path()?.join(TRACK_DIR))
-}
-
-pub fn data_dir_path() -> anyhow::Result<PathBuf> {
-    Ok(output_dir_path()?.join(DATA_DIR))
+/// Returns the path of the given track's audio file. If the track was stored with a
+/// non-default extension, that file is returned instead.
+pub fn track_file_path_from_id(library: &Library, id: &Id) -> anyhow::Result<PathBuf> {
+    let track_path = track_file_path_with_extension(library, id, TRACK_EXTENSION)?;
+    if !track_path.is_file() {
+        for ext in ALTERNATE_TRACK_EXTENSIONS {
+            let alternate_path = track_path.with_extension(ext);
+            if alternate_path.is_file() {
+                return Ok(alternate_path);
+            }
+        }
+    }
+    Ok(track_path)
 }
 
-pub fn album_dir_path() -> anyhow::Result<PathBuf> {
-    Ok(output_dir_path()?.join(ALBUM_DIR))
-}
-pub fn track_file_path_from_id(id: &Id) -> anyhow::Result<PathBuf> {
+pub fn track_file_path_with_extension(
+    library: &Library,
+    id: &Id,
+    extension: &str,
+) -> anyhow::Result<PathBuf> {
     let MediaType::Track = id.media_type else {
         return Err(anyhow!("Id provided was not a track id"));
     };
-    let mut track_path = track_dir_path()?.join(id.to_string());
-    track_path.set_extension(TRACK_EXTENSION);
+    let mut track_path = library.track_dir_path().join(id.to_string());
+    track_path.set_extension(extension);
     Ok(track_path)
 }
 
-pub fn playlist_file_path_from_id(id: &Id) -> anyhow::Result<PathBuf> {
+/// Whether the given extension is one that tracks can be stored (and played) with.
+pub fn is_supported_track_extension(extension: &str) -> bool {
+    let extension = extension.to_lowercase();
+    extension == TRACK_EXTENSION || ALTERNATE_TRACK_EXTENSIONS.contains(&extension.as_str())
+}
+
+pub fn waveform_path_from_id(library: &Library, id: &Id) -> anyhow::Result<PathBuf> {
+    track_file_path_with_extension(library, id, WAVEFORM_EXTENSION)
+}
+
+pub fn playlist_file_path_from_id(library: &Library, id: &Id) -> anyhow::Result<PathBuf> {
     let MediaType::Playlist = id.media_type else {
         return Err(anyhow!("Id provided was not a playlist id"));
     };
-    let mut playlist_path = data_dir_path()?.join(id.to_string());
+    let mut playlist_path = library.data_dir_path().join(id.to_string());
     playlist_path.set_extension(DATA_EXTENSION);
     Ok(playlist_path)
 }
 
-pub fn track_file_exists(id: &Id) -> bool {
-    matches!(track_file_path_from_id(&id), Ok(_))
+pub fn track_file_exists(library: &Library, id: &Id) -> bool {
+    track_file_path_from_id(library, id).is_ok()
 }
 
-pub async fn get_downloaded_tracks() -> anyhow::Result<HashSet<Id>> {
+pub async fn get_downloaded_tracks(library: &Library) -> anyhow::Result<HashSet<Id>> {
     // get the track data dir
-    let track_dir = track_dir_path()?;
+    let track_dir = library.track_dir_path();
     // go through the directory and search for valid playlist files and add any successful files to a vec
     let mut track_ids = HashSet::new();
     let mut paths = fs::read_dir(track_dir).await?;
     while let Some(path) = paths.next_entry().await.ok().flatten() {
-        if path.path().is_file() {
+        // only audio files; the track dir also holds cached waveforms
+        if path.path().is_file()
+            && path
+                .path()
+                .extension()
+                .is_some_and(|ext| is_supported_track_extension(&ext.to_string_lossy()))
+        {
             // check to see if the name of the file is a valid track id (removing filename)
             let path = path.path().with_extension("");
             let file_name = path.file_stem();
@@ -129,9 +151,14 @@ pub async fn get_downloaded_tracks() -> anyhow::Result<HashSet<Id>> {
     Ok(track_ids)
 }
 
-pub async fn load_saved_playlists() -> anyhow::Result<HashMap<Id, Playlist>> {
+/// Loads the playlist json files written by older versions. Files that can't be parsed are
+/// quarantined and added to the report.
+pub async fn load_saved_playlists(
+    library: &Library,
+    report: &mut LoadReport,
+) -> anyhow::Result<HashMap<Id, Playlist>> {
     // get the data dir
-    let data_dir = data_dir_path()?;
+    let data_dir = library.data_dir_path();
     // go through the directory and search for valid playlist files and add any successful files to a vec
     let mut playlists = HashMap::new();
     let mut paths = fs::read_dir(data_dir).await?;
@@ -146,13 +173,15 @@ pub async fn load_saved_playlists() -> anyhow::Result<HashMap<Id, Playlist>> {
                     // valid id, check if it is a playlist id
                     if let MediaType::Playlist = id.media_type {
                         // valid file, let's have a look at its contents
-                        let maybe_contents = tokio::fs::read_to_string(path.path()).await;
-                        if let Ok(contents) = maybe_contents {
-                            let maybe_playlist: Result<Playlist, serde_json::Error> =
-                                serde_json::from_str(&contents);
-                            if let Ok(playlist) = maybe_playlist {
+                        let parsed = tokio::fs::read_to_string(path.path())
+                            .await
+                            .map_err(anyhow::Error::from)
+                            .and_then(|contents| Ok(serde_json::from_str::<Playlist>(&contents)?));
+                        match parsed {
+                            Ok(playlist) => {
                                 playlists.insert(playlist.id().clone(), playlist);
                             }
+                            Err(e) => report.push(quarantine_file(library, &path.path(), e).await),
                         }
                     }
                 }
@@ -162,23 +191,23 @@ pub async fn load_saved_playlists() -> anyhow::Result<HashMap<Id, Playlist>> {
     Ok(playlists)
 }
 
-pub async fn get_saved_tracks_file_path() -> anyhow::Result<PathBuf> {
-    let mut tracks_file_path = data_dir_path()?;
+pub async fn get_saved_tracks_file_path(library: &Library) -> anyhow::Result<PathBuf> {
+    let mut tracks_file_path = library.data_dir_path();
     tracks_file_path.push(TRACK_DATA_FILENAME);
     tracks_file_path.set_extension(DATA_EXTENSION);
     Ok(tracks_file_path)
 }
 
-pub async fn get_album_data_file_path() -> anyhow::Result<PathBuf> {
-    let mut albums_file_path = data_dir_path()?;
+pub async fn get_album_data_file_path(library: &Library) -> anyhow::Result<PathBuf> {
+    let mut albums_file_path = library.data_dir_path();
     albums_file_path.push(ALBUM_DATA_FILENAME);
     albums_file_path.set_extension(DATA_EXTENSION);
     Ok(albums_file_path)
 }
 
 // Gets all albums. Note that albums that are in this list should be downloaded.
-pub async fn get_albums() -> anyhow::Result<HashMap<Id, Album>> {
-    let path = get_album_data_file_path().await?;
+pub async fn get_albums(library: &Library) -> anyhow::Result<HashMap<Id, Album>> {
+    let path = get_album_data_file_path(library).await?;
     let file_text = fs::read_to_string(path).await?;
     let albums_vec: Vec<Album> = serde_json::from_str(&file_text)?;
     let mut map = HashMap::new();
@@ -188,16 +217,16 @@ pub async fn get_albums() -> anyhow::Result<HashMap<Id, Album>> {
     Ok(map)
 }
 
-pub fn album_filename_from_id(album_id: &Id) -> anyhow::Result<PathBuf> {
-    let mut album_dir_path = album_dir_path()?;
+pub fn album_filename_from_id(library: &Library, album_id: &Id) -> anyhow::Result<PathBuf> {
+    let mut album_dir_path = library.album_dir_path();
     album_dir_path.push(album_id.to_string());
     album_dir_path.set_extension(ALBUM_EXTENSION);
     Ok(album_dir_path)
 }
 
-pub async fn load_saved_tracks() -> anyhow::Result<HashMap<Id, Track>> {
+pub async fn load_saved_tracks(library: &Library) -> anyhow::Result<HashMap<Id, Track>> {
     // get the tracks file path
-    let tracks_file_path = get_saved_tracks_file_path().await?;
+    let tracks_file_path = get_saved_tracks_file_path(library).await?;
 
     let file_contents = fs::read_to_string(tracks_file_path).await?;
     let tracks: Vec<Track> = serde_json::from_str(&file_contents)?;
@@ -208,11 +237,130 @@ pub async fn load_saved_tracks() -> anyhow::Result<HashMap<Id, Track>> {
     Ok(hashmap)
 }
 
+pub async fn delete_track_file(library: &Library, id: &Id) -> anyhow::Result<()> {
+    let path = track_file_path_from_id(library, id)?;
+    fs::remove_file(path).await?;
+    // the waveform may not have been made yet
+    let _ = fs::remove_file(waveform_path_from_id(library, id)?).await;
+    Ok(())
+}
+
+pub async fn delete_album_cover(library: &Library, album_id: &Id) -> anyhow::Result<()> {
+    let path = album_filename_from_id(library, album_id)?;
+    fs::remove_file(path).await?;
+    Ok(())
+}
+
+/// Returns the size of the file at the given path in bytes, or 0 if it doesn't exist.
+pub async fn file_size(path: PathBuf) -> u64 {
+    match fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Moves an unreadable file into the quarantine directory. Returns the problem to report.
+pub async fn quarantine_file(library: &Library, path: &Path, reason: anyhow::Error) -> LoadProblem {
+    println!("Quarantining {}: {reason}", path.display());
+    let quarantined_to = async {
+        let quarantine_dir = library.quarantine_dir_path();
+        fs::create_dir_all(&quarantine_dir).await?;
+        let file_name = path
+            .file_name()
+            .ok_or(anyhow!("path has no file name"))?
+            .to_string_lossy();
+        let destination = quarantine_dir.join(format!(
+            "{}-{}",
+            chrono::Local::now().format("%Y%m%d%H%M%S"),
+            file_name
+        ));
+        fs::rename(path, &destination).await?;
+        Ok::<PathBuf, anyhow::Error>(destination)
+    }
+    .await;
+    if let Err(e) = &quarantined_to {
+        println!("Failed to quarantine {}: {e}", path.display());
+    }
+    LoadProblem {
+        source: path.display().to_string(),
+        reason: reason.to_string(),
+        quarantined_to: quarantined_to.ok(),
+    }
+}
+
+// temporary sibling path used while writing a file. Unique to this write, so two writes to the
+// same file (or two copies of the app) never share one
+fn temp_path_for(path: &Path) -> PathBuf {
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+// Makes a rename into the directory durable. Windows can't open directories, and doesn't need to
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        std::fs::File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Writes the file to a temporary path and renames it over the destination, so a crash
+/// mid-write never leaves a half written file behind. Blocking.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp_path = temp_path_for(path);
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result?;
+    sync_parent(path)
+}
+
+/// Like `write_atomic`, but copies an existing file. Blocking.
+pub fn copy_atomic(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let temp_path = temp_path_for(destination);
+    let result = (|| {
+        std::fs::copy(source, &temp_path)?;
+        std::fs::File::open(&temp_path)?.sync_all()?;
+        std::fs::rename(&temp_path, destination)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result?;
+    sync_parent(destination)
+}
+
 pub fn track_output_extension() -> &'static str {
     TRACK_EXTENSION
 }
 
-pub async fn download_album(album: &Album, client: &Client) -> anyhow::Result<()> {
+pub async fn download_album(
+    library: &Library,
+    album: &Album,
+    client: &Client,
+) -> anyhow::Result<()> {
     // download raw image bytes
     let response = client
         .get(album.img_url.clone())
@@ -220,7 +368,7 @@ pub async fn download_album(album: &Album, client: &Client) -> anyhow::Result<()
         .await?
         .error_for_status()?;
     let bytes = response.bytes().await?.to_vec();
-    let album_filename = album_filename_from_id(&album.id())?;
+    let album_filename = album_filename_from_id(library, album.id())?;
 
     // spawn heavy stuff in separate thread
     tokio::task::spawn_blocking(move || {
@@ -228,12 +376,12 @@ pub async fn download_album(album: &Album, client: &Client) -> anyhow::Result<()
         let format = image::guess_format(&bytes)?;
         if format == ImageFormat::Jpeg {
             // save the file as is
-            let mut file = std::fs::File::create(album_filename.clone())?;
-            file.write_all(&bytes)?;
+            write_atomic(&album_filename, &bytes)?;
         } else {
             let img = image::load_from_memory(&bytes)?;
-            let mut file = std::fs::File::create(album_filename)?;
-            img.write_to(&mut file, ImageFormat::Jpeg)?;
+            let mut jpeg = std::io::Cursor::new(Vec::new());
+            img.write_to(&mut jpeg, ImageFormat::Jpeg)?;
+            write_atomic(&album_filename, jpeg.get_ref())?;
         }
         Ok::<(), anyhow::Error>(())
     })
@@ -1,27 +1,50 @@
 use iced::event::Status;
 use iced::keyboard::key;
-use iced::{Element, Event, Task, event, keyboard};
+use iced::{Element, Event, Task, event, keyboard, window};
 use iced::{Subscription, Theme};
 use indexmap::IndexMap;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
 use url::Url;
 
-use crate::service::audio::enums::LoopPolicy;
-use crate::service::audio::structs::AudioProgress;
-use crate::service::gui::enums::{Action, DownloadState, PlayingState};
+use crate::service::audio::enums::{LoopCount, LoopPolicy, NormalizationMode, ProgressCadence};
+use crate::service::audio::spectrum;
+use crate::service::audio::structs::{
+    AudioProgress, EffectSettings, LoopRegion, PlaybackSpeed, SampleTap, Spectrum, TrackTransition,
+};
+use crate::service::file::library::Library;
+use crate::service::gui::enums::{Action, DownloadState, PlayingState, SleepTimerMode};
 use crate::service::gui::structs::{
-    GeneralCache, GuiCommunication, GuiManagement, GuiSettings, HomeAlbumsWidgetData,
-    HomePlaylistsWidgetData, HomeTracksWidgetData, IdCounter, PlaylistInitData, PlaylistInitId,
-    PlaylistInitIdCounter, PlaylistRenderData, TaskId,
+    Alarm, GeneralCache, GuiCommunication, GuiManagement, GuiSettings, HomeAlbumsWidgetData,
+    HomePlaylistsWidgetData, HomeTracksWidgetData, IdCounter, LocalImportData, PlaylistInitData,
+    PlaylistInitId, PlaylistInitIdCounter, PlaylistRenderData, SleepTimer, TaskId,
 };
 use crate::service::gui::util::delay_task;
+use crate::service::gui::widgets::modal::backup::BackupModal;
+use crate::service::gui::widgets::modal::delete_playlist::DeletePlaylistModal;
+use crate::service::gui::widgets::modal::effects::{EffectsModal, EffectsModalMsg};
+use crate::service::gui::widgets::modal::export_playlist::{
+    ExportPlaylistModal, ExportPlaylistModalMsg,
+};
+use crate::service::gui::widgets::modal::import_archive::ImportArchiveModal;
+use crate::service::gui::widgets::modal::import_folder::ImportFolderModal;
+use crate::service::gui::widgets::modal::import_playlist_file::ImportPlaylistFileModal;
+use crate::service::gui::widgets::modal::import_preview::ImportPreviewModal;
+use crate::service::gui::widgets::modal::libraries::{LibrariesModal, LibrariesModalMsg};
+use crate::service::gui::widgets::modal::load_report::LoadReportModal;
 use crate::service::gui::widgets::modal::new_playlist::NewPlaylistModal;
+use crate::service::gui::widgets::modal::playback::PlaybackModal;
+use crate::service::gui::widgets::modal::sleep_timer::SleepTimerModal;
+use crate::service::gui::widgets::modal::storage::{StorageModal, StorageModalMsg};
+use crate::service::gui::widgets::modal::sync::SyncModal;
+use crate::service::gui::widgets::modal::track_menu::{TrackMenuModal, TrackMenuModalMsg};
+use crate::service::gui::widgets::modal::{Modal, ModalMessage};
 use crate::service::id::structs::Id;
 use crate::service::playlist::PlaylistSender;
-use crate::service::playlist::enums::{PlaylistInitStatus, PlaylistMessage};
-use crate::service::playlist::structs::Tracklist;
+use crate::service::playlist::enums::{LocalImportStatus, PlaylistInitStatus, PlaylistMessage};
+use crate::service::playlist::structs::{TrackOrder, Tracklist};
 use crate::util::sync::ReceiverHandle;
 use builders::{home, player};
 use enums::{EventMessage, Message, Page};
@@ -36,6 +59,14 @@ mod widgets;
 
 const RECENT_PLAYLIST_SIZE: usize = 3;
 const ALBUM_DISPLAY_SIZE: usize = 3;
+// how often sleep timers and alarms are checked
+const TIMER_TICK: Duration = Duration::from_secs(1);
+// how long a sleep timer takes to fade out
+const SLEEP_FADE: Duration = Duration::from_secs(10);
+// how often the listening session is saved (it's also saved on close)
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+// the visualizer's frame rate (30 fps)
+const VISUALIZER_FRAME: Duration = Duration::from_millis(33);
 
 struct App {
     communication: GuiCommunication,
@@ -47,6 +78,7 @@ struct App {
     general_cache: GeneralCache,
     playlist_render_data: IndexMap<Id, PlaylistRenderData>,
     playlist_init_data: IndexMap<PlaylistInitId, PlaylistInitData>,
+    local_import_data: IndexMap<TaskId, LocalImportData>,
     theme: Theme,
 }
 
@@ -54,6 +86,8 @@ struct App {
 struct GuiFlags {
     event_receiver: ReceiverHandle<EventMessage>,
     playlist_sender: PlaylistSender,
+    sample_tap: SampleTap,
+    library: Library,
 }
 
 impl App {
@@ -63,6 +97,7 @@ impl App {
             playlist_sender: flags.playlist_sender,
             active_tasks: HashMap::new(),
             event_bus: flags.event_receiver,
+            sample_tap: flags.sample_tap,
         };
         let management = GuiManagement {
             id_counter: IdCounter::new(),
@@ -79,11 +114,30 @@ impl App {
             downloading_track_data: IndexMap::new(),
             all_playlist_metadata: Vec::new(),
             recent_playlists: VecDeque::with_capacity(RECENT_PLAYLIST_SIZE),
+            storage_usage: None,
+            effect_settings: EffectSettings::default(),
+            playback_speeds: HashMap::new(),
+            sleep_timer: None,
+            alarm: None,
+            alarm_starting: None,
+            restoring_session: None,
+            saved_session: None,
             active_modal: None,
+            spectrum: Spectrum::silent(),
+            progress_cadence: ProgressCadence::default(),
+            library: flags.library,
+        };
+        let settings = GuiSettings {
+            volume: 1.0,
+            transition: TrackTransition::default(),
+            normalization: NormalizationMode::default(),
+            split_chapters: false,
+            trim_silence: false,
+            visualizer: false,
         };
-        let settings = GuiSettings { volume: 1.0 };
         let playlist_render_data = IndexMap::new();
         let playlist_init_data = IndexMap::new();
+        let local_import_data = IndexMap::new();
         let theme = Theme::Dark;
         (
             Self {
@@ -96,6 +150,7 @@ impl App {
                 settings,
                 playlist_render_data,
                 playlist_init_data,
+                local_import_data,
                 theme,
             },
             Task::perform(
@@ -164,10 +219,17 @@ impl App {
                                     // replace the current track with the new one
                                     println!("updating playlist @ gui");
                                     render_data.owned_playlist.tracks.0[pos] = track;
-                                    // update tracklist
-                                    render_data
+                                    // update tracklist, keeping it split into chapters if it was
+                                    let split = render_data
                                         .current_tracklist
-                                        .replace_tracks(render_data.owned_playlist.tracks.clone());
+                                        .iter()
+                                        .any(|t| t.chapter_of.is_some());
+                                    let tracks = if split {
+                                        render_data.owned_playlist.tracks.split_chapters()
+                                    } else {
+                                        render_data.owned_playlist.tracks.clone()
+                                    };
+                                    render_data.current_tracklist.replace_tracks(tracks);
                                 }
                             }
                         }
@@ -209,6 +271,58 @@ impl App {
                             }
                         }
                     }
+                    EventMessage::PlaylistDeleted {
+                        id,
+                        deleted_track_files,
+                        deleted_albums,
+                    } => {
+                        println!("playlist deleted");
+                        util::remove_deleted_playlist(self, &id);
+                        for track_id in deleted_track_files.iter() {
+                            self.general_cache.downloaded_tracks.remove(track_id);
+                        }
+                        self.general_cache
+                            .all_albums
+                            .retain(|a| !deleted_albums.contains(a.id()));
+                    }
+                    EventMessage::DownloadedTracksAdded(tracks) => {
+                        self.general_cache.downloaded_tracks.extend(tracks);
+                    }
+                    EventMessage::TrackFilesEvicted(ids) => {
+                        for id in ids.iter() {
+                            self.general_cache.downloaded_tracks.remove(id);
+                        }
+                    }
+                    EventMessage::StorageUsageUpdated(usage) => {
+                        self.general_cache.storage_usage = Some(usage);
+                    }
+                    EventMessage::LibraryChanged(library) => {
+                        println!("library changed to {}", library.name());
+                        util::clear_library_data(self);
+                        self.general_cache.library = library;
+                    }
+                    EventMessage::EffectSettingsUpdated(settings) => {
+                        self.general_cache.effect_settings = settings;
+                    }
+                    EventMessage::PlaybackSpeedsUpdated(speeds) => {
+                        self.general_cache.playback_speeds = speeds;
+                    }
+                    EventMessage::SessionLoaded(session) => {
+                        if let Some(metadata) = self
+                            .general_cache
+                            .all_playlist_metadata
+                            .iter()
+                            .find(|m| *m.id() == session.playlist_id)
+                            .cloned()
+                        {
+                            self.general_cache.saved_session = Some(session.clone());
+                            self.general_cache.restoring_session = Some(session);
+                            return Task::done(Message::PlaylistSelect(metadata));
+                        }
+                    }
+                    EventMessage::LibraryLoadReport(report) => {
+                        self.general_cache.active_modal = Some(LoadReportModal::new(report).into());
+                    }
                 };
                 Task::none()
             }
@@ -290,33 +404,78 @@ impl App {
                 )
             }
             Message::PlaylistSelectAccepted(owned_playlist) => {
+                // chaptered tracks can be played as a track per chapter
+                let tracks = if self.settings.split_chapters {
+                    owned_playlist.tracks.split_chapters()
+                } else {
+                    owned_playlist.tracks.clone()
+                };
+                // a restored session starts where it left off, in the same order
+                let restored = self
+                    .general_cache
+                    .restoring_session
+                    .as_ref()
+                    .filter(|session| session.playlist_id == *owned_playlist.metadata.id())
+                    .and_then(|session| {
+                        let order =
+                            TrackOrder::from_saved(session.order.clone(), tracks.track_count())?;
+                        let tracklist = Tracklist::new(order, tracks.clone()).ok()?;
+                        ((session.resume.track_index as usize) < tracklist.order().length())
+                            .then_some((tracklist, session.resume))
+                    });
+                // the playlist changed since; just load it normally
+                if restored.is_none()
+                    && self
+                        .general_cache
+                        .restoring_session
+                        .as_ref()
+                        .is_some_and(|session| session.playlist_id == *owned_playlist.metadata.id())
+                {
+                    self.general_cache.restoring_session = None;
+                }
+                let (tracklist, resume) = restored.unzip();
+                let tracklist = match tracklist {
+                    None if self.settings.split_chapters => {
+                        Some(Tracklist::from_tracks_vec(tracks))
+                    }
+                    tracklist => tracklist,
+                };
+
                 // change the page + set the current playlist + start 'playing' the playlist
                 let task = Task::perform(
                     util::play_playlist(
                         owned_playlist.metadata.id().clone(),
                         self.management.id_counter.next(),
                         self.communication.playlist_sender.clone(),
-                        None,
+                        tracklist.clone(),
                         self.settings.volume,
+                        self.settings.transition,
+                        resume,
                     ),
                     |handle| Message::TaskStarted {
                         handle: handle.unwrap(),
                     },
                 );
 
-                let current_tracklist = Tracklist::from_owned_playlist_ref(&owned_playlist);
+                let current_tracklist = tracklist
+                    .unwrap_or_else(|| Tracklist::from_owned_playlist_ref(&owned_playlist));
                 let new_metadata = owned_playlist.metadata.clone();
                 let render_data = PlaylistRenderData {
                     playlist_id: owned_playlist.metadata.id().clone(),
                     current_track: None,
                     owned_playlist: owned_playlist,
                     playing_track_loop_policy: LoopPolicy::NoLooping,
+                    loop_region: None,
+                    loop_mark: None,
+                    loop_count: LoopCount::Forever,
                     playing_track_progress: AudioProgress::new(Duration::ZERO, Duration::ZERO),
                     current_tracklist,
                     playing_state: PlayingState::None,
                     download_state: DownloadState::Idle,
                     scroll_offset: 0.0,
                     track_search_text: String::new(),
+                    waveform: None,
+                    waveform_hover: None,
                 };
 
                 util::handle_playlist_load(self, Some(render_data), new_metadata);
@@ -401,11 +560,12 @@ impl App {
                     Action::ShufflePlaylist { playlist_id } => {
                         println!("shuffle playlist on gui end");
                         let playlist_sender_clone = self.communication.playlist_sender.clone();
+                        let tracklist = util::chapter_tracklist(self, &playlist_id);
                         Task::perform(
                             util::shuffle_playlist(
                                 playlist_id.clone(),
                                 playlist_sender_clone,
-                                None,
+                                tracklist,
                             ),
                             |result| {
                                 if let Ok(tracklist) = result {
@@ -422,11 +582,12 @@ impl App {
                     Action::OrganizePlaylist { playlist_id } => {
                         println!("organize playlist on gui end");
                         let playlist_sender_clone = self.communication.playlist_sender.clone();
+                        let tracklist = util::chapter_tracklist(self, &playlist_id);
                         Task::perform(
                             util::organize_playlist(
                                 playlist_id.clone(),
                                 playlist_sender_clone,
-                                None,
+                                tracklist,
                             ),
                             |result| {
                                 if let Ok(tracklist) = result {
@@ -481,6 +642,31 @@ impl App {
                             |_result| Message::TrackAudioResumeResult { playlist_id },
                         )
                     }
+                    Action::NextChapter { playlist_id } => {
+                        // chapters are timed from the start of the file, before it's trimmed
+                        let target = self.playlist_render_data.get(&playlist_id).and_then(|r| {
+                            let track = r.current_track.as_ref()?;
+                            let current = *r.playing_track_progress.current() + track.trim.start;
+                            let start = track.next_chapter_start(current)?;
+                            Some(start.saturating_sub(track.trim.start))
+                        });
+                        match target {
+                            Some(start) => util::seek_current_track(self, playlist_id, start),
+                            None => Task::none(),
+                        }
+                    }
+                    Action::PreviousChapter { playlist_id } => {
+                        let target = self.playlist_render_data.get(&playlist_id).and_then(|r| {
+                            let track = r.current_track.as_ref()?;
+                            let current = *r.playing_track_progress.current() + track.trim.start;
+                            let start = track.previous_chapter_start(current)?;
+                            Some(start.saturating_sub(track.trim.start))
+                        });
+                        match target {
+                            Some(start) => util::seek_current_track(self, playlist_id, start),
+                            None => Task::none(),
+                        }
+                    }
                     Action::PlayTrack {
                         playlist_id,
                         track_index,
@@ -567,6 +753,56 @@ impl App {
                             Task::none()
                         }
                     }
+                    Action::MarkLoopStart { playlist_id } => {
+                        if let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id) {
+                            render_data.loop_mark =
+                                Some(*render_data.playing_track_progress.current());
+                        }
+                        Task::none()
+                    }
+                    Action::MarkLoopEnd { playlist_id } => {
+                        let region = self.playlist_render_data.get(&playlist_id).and_then(|r| {
+                            let current = *r.playing_track_progress.current();
+                            LoopRegion::new(r.loop_mark?, current, r.loop_count)
+                        });
+                        match region {
+                            Some(region) => util::set_loop_region(self, playlist_id, Some(region)),
+                            None => Task::none(),
+                        }
+                    }
+                    Action::SelectLoopRegion {
+                        playlist_id,
+                        start,
+                        end,
+                    } => {
+                        let region = self.playlist_render_data.get(&playlist_id).and_then(|r| {
+                            let total = r.playing_track_progress.total();
+                            LoopRegion::new(total.mul_f32(start), total.mul_f32(end), r.loop_count)
+                        });
+                        match region {
+                            Some(region) => util::set_loop_region(self, playlist_id, Some(region)),
+                            None => Task::none(),
+                        }
+                    }
+                    Action::SetLoopCount { playlist_id, count } => {
+                        let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id)
+                        else {
+                            return Task::none();
+                        };
+                        render_data.loop_count = count;
+                        // a region that's already looping starts counting again
+                        match render_data.loop_region {
+                            Some(region) => util::set_loop_region(
+                                self,
+                                playlist_id,
+                                Some(LoopRegion { count, ..region }),
+                            ),
+                            None => Task::none(),
+                        }
+                    }
+                    Action::ClearLoopRegion { playlist_id } => {
+                        util::set_loop_region(self, playlist_id, None)
+                    }
                     Action::SetVolume { volume } => {
                         // if the volume here is different, send a req
                         if self.settings.volume != volume {
@@ -582,6 +818,27 @@ impl App {
                         }
                         Task::none()
                     }
+                    Action::DeletePlaylist { playlist_id } => {
+                        let playlist_sender_clone = self.communication.playlist_sender.clone();
+                        Task::perform(
+                            util::request_playlist_deletion_info(
+                                playlist_id,
+                                playlist_sender_clone,
+                            ),
+                            |result| match result {
+                                Ok(Some(info)) => Message::PlaylistDeletionInfoReceived(info),
+                                _ => {
+                                    println!("Failed to get playlist deletion info");
+                                    Message::None
+                                }
+                            },
+                        )
+                    }
+                    Action::ExportPlaylist { playlist_id } => {
+                        self.general_cache.active_modal =
+                            Some(ExportPlaylistModal::new(playlist_id).into());
+                        Task::none()
+                    }
                 }
             }
             Message::DownloadPlaylistStarted {
@@ -633,13 +890,21 @@ impl App {
                 Task::none()
             }
             Message::TrackAudioProgress {
-                id: _,
+                id,
                 progress,
                 maybe_playlist_id,
             } => {
                 if let Some(playlist_id) = maybe_playlist_id
                     && let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id)
                 {
+                    // the previous track can still be playing while it fades out
+                    if render_data
+                        .current_track
+                        .as_ref()
+                        .is_some_and(|t| *t.id() != id)
+                    {
+                        return Task::none();
+                    }
                     // if the user is currently seeking (includes single clicks), ignore this
                     if matches!(render_data.playing_state, PlayingState::Seeking)
                         || matches!(render_data.playing_state, PlayingState::Paused)
@@ -664,27 +929,118 @@ impl App {
                         } else {
                             render_data.playing_state = PlayingState::Playing;
                         }
+                        // chapter tracks are only in the tracklist
+                        // an A–B loop was only for the track before
+                        render_data.loop_region = None;
+                        render_data.loop_mark = None;
                         if let Some(track) = render_data
-                            .owned_playlist
-                            .tracks
-                            .0
+                            .current_tracklist
                             .iter()
+                            .chain(render_data.owned_playlist.tracks.0.iter())
                             .find(|t| *t.id() == id)
                         {
                             render_data.current_track = Some(track.clone());
                         }
                     }
+                    // the seek bar's waveform; chapters of the same file share it
+                    let waveform_file_id =
+                        self.playlist_render_data
+                            .get_mut(&pid)
+                            .and_then(|render_data| {
+                                let file_id = render_data.current_track.as_ref()?.file_id().clone();
+                                if render_data
+                                    .waveform
+                                    .as_ref()
+                                    .is_some_and(|(loaded_id, _)| *loaded_id == file_id)
+                                {
+                                    return None;
+                                }
+                                render_data.waveform = None;
+                                Some(file_id)
+                            });
+                    let waveform_task = match waveform_file_id {
+                        Some(file_id) => {
+                            let playlist_id = pid.clone();
+                            Task::perform(
+                                util::get_waveform(
+                                    file_id.clone(),
+                                    self.communication.playlist_sender.clone(),
+                                ),
+                                move |result| match result {
+                                    Ok(waveform) => Message::WaveformLoaded {
+                                        playlist_id,
+                                        file_id,
+                                        waveform: Arc::new(waveform),
+                                    },
+                                    Err(e) => {
+                                        println!("Failed to load the waveform: {e}");
+                                        Message::None
+                                    }
+                                },
+                            )
+                        }
+                        None => Task::none(),
+                    };
+                    // a restored session's first track; show where it was left off, since
+                    // progress isn't updated while paused
+                    if let Some(session) = self
+                        .general_cache
+                        .restoring_session
+                        .take_if(|session| session.playlist_id == pid)
+                        && let Some(render_data) = self.playlist_render_data.get_mut(&pid)
+                        && let Some(track) = &render_data.current_track
+                        && start_paused
+                    {
+                        let rate = self
+                            .general_cache
+                            .playback_speeds
+                            .get(&pid)
+                            .map_or(1.0, |speed| speed.rate);
+                        render_data.playing_track_progress =
+                            AudioProgress::new(session.resume.position, track.length).at_rate(rate);
+                    }
+                    if start_paused {
+                        // a sleep timer stopping after the last track
+                        if self
+                            .general_cache
+                            .sleep_timer
+                            .as_ref()
+                            .is_some_and(|timer| {
+                                timer.playlist_id == pid
+                                    && matches!(timer.mode, SleepTimerMode::EndOfTrack)
+                            })
+                        {
+                            self.general_cache.sleep_timer = None;
+                        }
+                        // an alarm loaded the playlist; start it
+                        if self.general_cache.alarm_starting.as_ref() == Some(&pid) {
+                            self.general_cache.alarm_starting = None;
+                            return Task::batch([
+                                waveform_task,
+                                Task::done(Message::Action(Action::ResumeTrack {
+                                    playlist_id: pid,
+                                })),
+                            ]);
+                        }
+                    }
+                    return waveform_task;
                 }
                 Task::none()
             }
             Message::TrackAudioEnd {
-                id: _,
+                id,
                 maybe_playlist_id,
             } => {
                 println!("track audio end");
-                // remove the loop policy from the playlist this was in if it exists
+                // remove the loop policy from the playlist this was in if it exists. if the next
+                // track already started (gapless/crossfade), it's not this one's to reset
                 if let Some(pid) = &maybe_playlist_id {
-                    if let Some(render_data) = self.playlist_render_data.get_mut(pid) {
+                    if let Some(render_data) = self.playlist_render_data.get_mut(pid)
+                        && render_data
+                            .current_track
+                            .as_ref()
+                            .is_none_or(|t| *t.id() == id)
+                    {
                         render_data.playing_state = PlayingState::None;
                         render_data.playing_track_loop_policy = LoopPolicy::NoLooping;
                         render_data.current_track = None;
@@ -715,6 +1071,21 @@ impl App {
                 if let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id) {
                     render_data.playing_state = PlayingState::Unloaded;
                 }
+                // the restored session never got going
+                if self
+                    .general_cache
+                    .restoring_session
+                    .as_ref()
+                    .is_some_and(|session| session.playlist_id == playlist_id)
+                {
+                    self.general_cache.restoring_session = None;
+                }
+                // nothing left for the sleep timer to stop
+                if let Some(timer) = &self.general_cache.sleep_timer
+                    && timer.playlist_id == playlist_id
+                {
+                    return util::cancel_sleep_timer(self);
+                }
                 Task::none()
             }
             Message::TrackLooped {
@@ -724,8 +1095,14 @@ impl App {
                 if let Some(pid) = maybe_playlist_id
                     && let Some(render_data) = self.playlist_render_data.get_mut(&pid)
                 {
-                    render_data.playing_track_loop_policy =
-                        render_data.playing_track_loop_policy.looped();
+                    // mirrors the audio service; an A–B region counts down before the policy
+                    match render_data.loop_region {
+                        Some(region) => render_data.loop_region = region.looped(),
+                        None => {
+                            render_data.playing_track_loop_policy =
+                                render_data.playing_track_loop_policy.looped()
+                        }
+                    }
                 }
                 Task::none()
             }
@@ -823,10 +1200,827 @@ impl App {
                 self.general_cache.active_modal = Some(NewPlaylistModal::new().into());
                 Task::none()
             }
+            Message::PlaylistDeletionInfoReceived(info) => {
+                self.general_cache.active_modal = Some(DeletePlaylistModal::new(info).into());
+                Task::none()
+            }
+            Message::ConfirmPlaylistDeletion {
+                playlist_id,
+                delete_files,
+            } => {
+                let playlist_sender = self.communication.playlist_sender.clone();
+                Task::perform(
+                    util::delete_playlist(playlist_id, delete_files, playlist_sender),
+                    |r| {
+                        if let Err(e) = r {
+                            println!("An error occured while deleting the playlist: {e}")
+                        }
+                        Message::None
+                    },
+                )
+            }
+            Message::ImportFolder => {
+                self.general_cache.active_modal = Some(ImportFolderModal::new().into());
+                Task::none()
+            }
+            Message::LocalImportSubmit { path, options } => {
+                println!("importing folder: {}", path.display());
+                let task_id = self.management.id_counter.next();
+                Task::perform(
+                    util::import_local_folder(
+                        path,
+                        options,
+                        task_id,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    move |r| match r {
+                        Ok(handle) => Message::LocalImportTaskStarted {
+                            task_id,
+                            action: if options.dry_run {
+                                "Scanning Folder"
+                            } else {
+                                "Importing Folder"
+                            },
+                            handle,
+                        },
+                        Err(e) => {
+                            println!("something went wrong when submitting the import: {e}");
+                            Message::None
+                        }
+                    },
+                )
+            }
+            Message::ImportArchive => {
+                self.general_cache.active_modal = Some(ImportArchiveModal::new().into());
+                Task::none()
+            }
+            Message::YtDlpArchiveImportSubmit {
+                archive_path,
+                media_dir,
+                transfer_mode,
+            } => {
+                println!("importing yt-dlp archive: {}", archive_path.display());
+                let task_id = self.management.id_counter.next();
+                Task::perform(
+                    util::import_ytdlp_archive(
+                        archive_path,
+                        media_dir,
+                        transfer_mode,
+                        task_id,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    move |r| match r {
+                        Ok(handle) => Message::LocalImportTaskStarted {
+                            task_id,
+                            action: "Importing Archive",
+                            handle,
+                        },
+                        Err(e) => {
+                            println!("something went wrong when submitting the import: {e}");
+                            Message::None
+                        }
+                    },
+                )
+            }
+            Message::ImportPlaylistFile => {
+                self.general_cache.active_modal = Some(ImportPlaylistFileModal::new().into());
+                Task::none()
+            }
+            Message::PlaylistFileImportSubmit {
+                path,
+                transfer_mode,
+            } => {
+                println!("importing playlist file: {}", path.display());
+                let task_id = self.management.id_counter.next();
+                Task::perform(
+                    util::import_playlist_file(
+                        path,
+                        transfer_mode,
+                        task_id,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    move |r| match r {
+                        Ok(handle) => Message::LocalImportTaskStarted {
+                            task_id,
+                            action: "Importing Playlist",
+                            handle,
+                        },
+                        Err(e) => {
+                            println!("something went wrong when submitting the import: {e}");
+                            Message::None
+                        }
+                    },
+                )
+            }
+            Message::ExportPlaylistSubmit {
+                playlist_id,
+                destination,
+                format,
+                path_style,
+                current_order,
+            } => {
+                let tracklist = if current_order {
+                    self.playlist_render_data
+                        .get(&playlist_id)
+                        .map(|d| d.current_tracklist.clone())
+                } else {
+                    None
+                };
+                Task::perform(
+                    util::export_playlist(
+                        playlist_id,
+                        tracklist,
+                        destination,
+                        format,
+                        path_style,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    |r| match r {
+                        Ok(count) => {
+                            println!("exported {count} tracks");
+                            Message::HideModal
+                        }
+                        Err(e) => Message::ModalMessage(ModalMessage::ExportPlaylist(
+                            ExportPlaylistModalMsg::ExportFailed(e.to_string()),
+                        )),
+                    },
+                )
+            }
+            Message::LocalImportTaskStarted {
+                task_id,
+                action,
+                handle,
+            } => {
+                self.communication.active_tasks.insert(task_id, handle);
+                let import_data = LocalImportData {
+                    action,
+                    found_file_count: 0,
+                    current_file: None,
+                    total_file_count: None,
+                };
+                self.local_import_data.insert(task_id, import_data);
+                Task::none()
+            }
+            Message::LocalImportStatus { status, id } => {
+                let import_data = match self.local_import_data.get_mut(&id) {
+                    Some(d) => d,
+                    None => return Task::none(),
+                };
+                match status {
+                    LocalImportStatus::Scanning { found } => {
+                        import_data.found_file_count = found;
+                        Task::none()
+                    }
+                    LocalImportStatus::Progress { current, total } => {
+                        import_data.current_file = Some(current);
+                        import_data.total_file_count = Some(total);
+                        Task::none()
+                    }
+                    LocalImportStatus::Preview(preview) => {
+                        self.local_import_data.swap_remove(&id);
+                        Task::done(Message::LocalImportPreviewReceived(preview))
+                    }
+                    LocalImportStatus::Complete { added, skipped } => {
+                        if !skipped.is_empty() {
+                            self.general_cache.active_modal =
+                                Some(LoadReportModal::skipped_imports(skipped).into());
+                        }
+                        for m in added {
+                            // re-imports replace the old playlist
+                            self.general_cache
+                                .all_playlist_metadata
+                                .retain(|o| o.id() != m.id());
+                            self.general_cache.all_playlist_metadata.push(m);
+                        }
+                        util::sort_playlist_metadata(&mut self.general_cache.all_playlist_metadata);
+                        delay_task(
+                            Duration::from_secs(1),
+                            Message::RemoveLocalImportData { task_id: id },
+                        )
+                    }
+                    LocalImportStatus::SyncComplete(summary) => {
+                        println!(
+                            "sync done: {} written, {} deleted, {} unchanged, {} failed",
+                            summary.written, summary.deleted, summary.unchanged, summary.failed
+                        );
+                        delay_task(
+                            Duration::from_secs(1),
+                            Message::RemoveLocalImportData { task_id: id },
+                        )
+                    }
+                    LocalImportStatus::BackupComplete(path) => {
+                        println!("backup written to {}", path.display());
+                        delay_task(
+                            Duration::from_secs(1),
+                            Message::RemoveLocalImportData { task_id: id },
+                        )
+                    }
+                    LocalImportStatus::RestoreComplete => {
+                        println!("restore done");
+                        delay_task(
+                            Duration::from_secs(1),
+                            Message::RemoveLocalImportData { task_id: id },
+                        )
+                    }
+                    LocalImportStatus::Fail(e) => {
+                        println!("received msg that local import failed: {e}");
+                        delay_task(
+                            Duration::from_secs(1),
+                            Message::RemoveLocalImportData { task_id: id },
+                        )
+                    }
+                }
+            }
+            Message::LocalImportPreviewReceived(preview) => {
+                self.general_cache.active_modal = Some(ImportPreviewModal::new(preview).into());
+                Task::none()
+            }
+            Message::OpenSync => {
+                let playlists = self
+                    .general_cache
+                    .all_playlist_metadata
+                    .iter()
+                    .map(|m| (m.id().clone(), m.title.clone()))
+                    .collect();
+                self.general_cache.active_modal = Some(SyncModal::new(playlists).into());
+                Task::none()
+            }
+            Message::SyncSubmit {
+                playlist_ids,
+                destination,
+                options,
+            } => {
+                println!("syncing to: {}", destination.display());
+                let task_id = self.management.id_counter.next();
+                Task::perform(
+                    util::sync_playlists(
+                        playlist_ids,
+                        destination,
+                        options,
+                        task_id,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    move |r| match r {
+                        Ok(handle) => Message::LocalImportTaskStarted {
+                            task_id,
+                            action: "Syncing",
+                            handle,
+                        },
+                        Err(e) => {
+                            println!("something went wrong when submitting the sync: {e}");
+                            Message::None
+                        }
+                    },
+                )
+            }
+            Message::ToggleVisualizer => {
+                self.settings.visualizer = !self.settings.visualizer;
+                self.communication
+                    .sample_tap
+                    .set_enabled(self.settings.visualizer);
+                self.general_cache.spectrum = Spectrum::silent();
+                Task::none()
+            }
+            Message::VisualizerFrame => {
+                let tap = &self.communication.sample_tap;
+                let mut samples = [0.0; spectrum::ANALYSIS_SIZE];
+                tap.read_latest(&mut samples);
+                self.general_cache.spectrum = spectrum::analyze(&samples, tap.sample_rate())
+                    .smoothed(&self.general_cache.spectrum);
+                Task::none()
+            }
+            Message::SetProgressCadence { cadence } => {
+                if cadence == self.general_cache.progress_cadence {
+                    return Task::none();
+                }
+                self.general_cache.progress_cadence = cadence;
+                Task::perform(
+                    util::set_progress_cadence(cadence, self.communication.playlist_sender.clone()),
+                    |result| {
+                        if let Err(e) = result {
+                            println!("Failed to set the progress cadence: {e}");
+                        }
+                        Message::None
+                    },
+                )
+            }
+            Message::OpenPlaybackSettings => {
+                self.general_cache.active_modal = Some(
+                    PlaybackModal::new(
+                        self.settings.transition,
+                        self.settings.normalization,
+                        self.settings.split_chapters,
+                        self.settings.trim_silence,
+                    )
+                    .into(),
+                );
+                Task::none()
+            }
+            Message::PlaybackSettingsSubmit {
+                transition,
+                normalization,
+                split_chapters,
+                trim_silence,
+            } => {
+                self.settings.transition = transition;
+                self.settings.normalization = normalization;
+                self.settings.split_chapters = split_chapters;
+                self.settings.trim_silence = trim_silence;
+                Task::batch(vec![
+                    Task::perform(
+                        util::update_track_transition(
+                            transition,
+                            self.communication.playlist_sender.clone(),
+                        ),
+                        |r| {
+                            if let Err(e) = r {
+                                println!("failed to update the track transition: {e}");
+                            }
+                            Message::None
+                        },
+                    ),
+                    Task::perform(
+                        util::update_normalization(
+                            normalization,
+                            self.communication.playlist_sender.clone(),
+                        ),
+                        |r| {
+                            if let Err(e) = r {
+                                println!("failed to update loudness normalization: {e}");
+                            }
+                            Message::None
+                        },
+                    ),
+                    Task::perform(
+                        util::update_silence_trimming(
+                            trim_silence,
+                            self.communication.playlist_sender.clone(),
+                        ),
+                        |r| {
+                            if let Err(e) = r {
+                                println!("failed to update silence trimming: {e}");
+                            }
+                            Message::None
+                        },
+                    ),
+                ])
+            }
+            Message::WaveformLoaded {
+                playlist_id,
+                file_id,
+                waveform,
+            } => {
+                // the track may have changed while it loaded
+                if let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id)
+                    && render_data
+                        .current_track
+                        .as_ref()
+                        .is_some_and(|track| *track.file_id() == file_id)
+                {
+                    render_data.waveform = Some((file_id, waveform));
+                }
+                Task::none()
+            }
+            Message::WaveformHovered {
+                playlist_id,
+                progress,
+            } => {
+                if let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id) {
+                    render_data.waveform_hover = progress;
+                }
+                Task::none()
+            }
+            Message::OpenTrackMenu { track } => {
+                self.general_cache.active_modal = Some(TrackMenuModal::new(track).into());
+                Task::none()
+            }
+            Message::DetectTrackSilence { id } => Task::perform(
+                util::detect_track_silence(id, self.communication.playlist_sender.clone()),
+                |r| {
+                    Message::ModalMessage(ModalMessage::TrackMenu(match r {
+                        Ok(trim) => TrackMenuModalMsg::SilenceDetected(trim),
+                        Err(e) => TrackMenuModalMsg::DetectFailed(e.to_string()),
+                    }))
+                },
+            ),
+            Message::TrackTrimSubmit { track } => Task::perform(
+                util::update_track(track, self.communication.playlist_sender.clone()),
+                |r| {
+                    if let Err(e) = r {
+                        println!("failed to update the track's trim: {e}");
+                    }
+                    Message::None
+                },
+            ),
+            Message::SetPlaybackSpeed { playlist_id, speed } => {
+                if speed == PlaybackSpeed::default() {
+                    self.general_cache.playback_speeds.remove(&playlist_id);
+                } else {
+                    self.general_cache
+                        .playback_speeds
+                        .insert(playlist_id.clone(), speed);
+                }
+                Task::perform(
+                    util::update_playback_speed(
+                        playlist_id,
+                        speed,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    |r| {
+                        if let Err(e) = r {
+                            println!("failed to update the playback speed: {e}");
+                        }
+                        Message::None
+                    },
+                )
+            }
+            Message::OpenSleepTimer { playlist_id } => {
+                let cache = &self.general_cache;
+                let mode = cache
+                    .sleep_timer
+                    .as_ref()
+                    .filter(|timer| timer.playlist_id == playlist_id)
+                    .map(|timer| timer.mode);
+                let alarm = cache
+                    .alarm
+                    .as_ref()
+                    .filter(|alarm| *alarm.playlist.id() == playlist_id)
+                    .map(|alarm| alarm.at.time());
+                self.general_cache.active_modal =
+                    Some(SleepTimerModal::new(playlist_id, mode, alarm).into());
+                Task::none()
+            }
+            Message::SleepTimerSubmit {
+                playlist_id,
+                mode,
+                alarm,
+            } => {
+                // there's only ever one timer; setting one replaces any other playlist's
+                let cancel = if mode.is_some()
+                    || self
+                        .general_cache
+                        .sleep_timer
+                        .as_ref()
+                        .is_some_and(|timer| timer.playlist_id == playlist_id)
+                {
+                    util::cancel_sleep_timer(self)
+                } else {
+                    Task::none()
+                };
+                let mut start = Task::none();
+                if let Some(mode) = mode {
+                    if let SleepTimerMode::EndOfTrack = mode {
+                        start = Task::perform(
+                            util::set_pause_after_current_track(
+                                playlist_id.clone(),
+                                true,
+                                self.communication.playlist_sender.clone(),
+                            ),
+                            |r| {
+                                if let Err(e) = r {
+                                    println!("failed to stop after the current track: {e}");
+                                }
+                                Message::None
+                            },
+                        );
+                    }
+                    self.general_cache.sleep_timer =
+                        Some(SleepTimer::new(playlist_id.clone(), mode));
+                }
+
+                let metadata = self
+                    .playlist_render_data
+                    .get(&playlist_id)
+                    .map(|render_data| render_data.owned_playlist.metadata.clone());
+                match (alarm, metadata) {
+                    (Some(time), Some(metadata)) => {
+                        self.general_cache.alarm = Some(Alarm::new(metadata, time));
+                    }
+                    _ => {
+                        if self
+                            .general_cache
+                            .alarm
+                            .as_ref()
+                            .is_some_and(|alarm| *alarm.playlist.id() == playlist_id)
+                        {
+                            self.general_cache.alarm = None;
+                        }
+                    }
+                }
+                // `cancel` has to run first, or it could undo the new timer
+                cancel.chain(start)
+            }
+            Message::TimerTick => {
+                let mut tasks = Vec::new();
+
+                // alarm
+                if let Some(alarm) = &self.general_cache.alarm
+                    && chrono::Local::now() >= alarm.at
+                {
+                    let metadata = alarm.playlist.clone();
+                    let playlist_id = metadata.id().clone();
+                    self.general_cache.alarm = None;
+                    match self
+                        .playlist_render_data
+                        .get(&playlist_id)
+                        .map(|render_data| &render_data.playing_state)
+                    {
+                        Some(PlayingState::Paused) => {
+                            tasks.push(Task::done(Message::Action(Action::ResumeTrack {
+                                playlist_id,
+                            })));
+                        }
+                        // already playing
+                        Some(
+                            PlayingState::Playing | PlayingState::Seeking | PlayingState::None,
+                        ) => {}
+                        // load it; it's played once its first track is ready
+                        Some(PlayingState::Unloaded) | None => {
+                            self.general_cache.alarm_starting = Some(playlist_id);
+                            tasks.push(Task::done(Message::PlaylistSelect(metadata)));
+                        }
+                    }
+                }
+
+                // sleep timer
+                if let Some(timer) = &mut self.general_cache.sleep_timer
+                    && let Some(render_data) = self.playlist_render_data.get(&timer.playlist_id)
+                    // paused time doesn't count
+                    && let PlayingState::Playing = render_data.playing_state
+                    && timer.fading.is_none()
+                {
+                    let playlist_id = timer.playlist_id.clone();
+                    let playlist_sender = self.communication.playlist_sender.clone();
+                    let track_remaining = render_data.playing_track_progress.remaining();
+                    // a looping track doesn't end, so there's nothing to fade into
+                    let track_ends =
+                        matches!(render_data.playing_track_loop_policy, LoopPolicy::NoLooping)
+                            && render_data.loop_region.is_none();
+                    let on_last_track = render_data.current_track.as_ref().is_some_and(|track| {
+                        render_data
+                            .current_tracklist
+                            .iter()
+                            .last()
+                            .is_some_and(|last| last.id() == track.id())
+                    });
+                    let fade = match timer.mode {
+                        SleepTimerMode::After(_) => {
+                            timer.remaining = timer.remaining.saturating_sub(TIMER_TICK);
+                            (timer.remaining <= SLEEP_FADE).then_some(timer.remaining)
+                        }
+                        SleepTimerMode::EndOfTrack => {
+                            (track_ends && track_remaining <= SLEEP_FADE).then_some(track_remaining)
+                        }
+                        SleepTimerMode::EndOfPlaylist => {
+                            (track_ends && on_last_track && track_remaining <= SLEEP_FADE)
+                                .then_some(track_remaining)
+                        }
+                    };
+                    if let Some(fade) = fade {
+                        let fade = fade.max(TIMER_TICK);
+                        // the others end by themselves once the track does
+                        let (task, handle) = if let SleepTimerMode::After(_) = timer.mode {
+                            Task::perform(
+                                util::fade_out_and_pause(
+                                    playlist_id.clone(),
+                                    fade,
+                                    self.settings.volume,
+                                    playlist_sender,
+                                ),
+                                move |r| {
+                                    if let Err(e) = r {
+                                        println!("sleep timer failed to pause: {e}");
+                                    }
+                                    Message::SleepTimerFinished { playlist_id }
+                                },
+                            )
+                            .abortable()
+                        } else {
+                            Task::perform(
+                                util::fade_out_current_track(playlist_id, fade, playlist_sender),
+                                |r| {
+                                    if let Err(e) = r {
+                                        println!("sleep timer failed to fade out: {e}");
+                                    }
+                                    Message::None
+                                },
+                            )
+                            .abortable()
+                        };
+                        timer.fading = Some(handle);
+                        tasks.push(task);
+                    }
+                }
+                Task::batch(tasks)
+            }
+            Message::SleepTimerFinished { playlist_id } => {
+                if let Some(render_data) = self.playlist_render_data.get_mut(&playlist_id) {
+                    render_data.playing_state = PlayingState::Paused;
+                }
+                if self
+                    .general_cache
+                    .sleep_timer
+                    .as_ref()
+                    .is_some_and(|timer| timer.playlist_id == playlist_id)
+                {
+                    self.general_cache.sleep_timer = None;
+                }
+                Task::none()
+            }
+            Message::SaveSession => {
+                // don't overwrite the session being restored before it's loaded
+                if self.general_cache.restoring_session.is_some() {
+                    return Task::none();
+                }
+                let session = util::current_session(self);
+                if session == self.general_cache.saved_session {
+                    return Task::none();
+                }
+                self.general_cache.saved_session = session.clone();
+                Task::perform(
+                    util::save_session(session, self.communication.playlist_sender.clone()),
+                    |r| {
+                        if let Err(e) = r {
+                            println!("failed to save the session: {e}");
+                        }
+                        Message::None
+                    },
+                )
+            }
+            Message::OpenEffects { playlist_id } => {
+                self.general_cache.active_modal = Some(
+                    EffectsModal::new(playlist_id, self.general_cache.effect_settings.clone())
+                        .into(),
+                );
+                Task::none()
+            }
+            Message::PreviewEffectPreset {
+                playlist_id,
+                preset,
+            } => Task::perform(
+                util::preview_effect_preset(
+                    Some(playlist_id),
+                    preset,
+                    self.communication.playlist_sender.clone(),
+                ),
+                |r| {
+                    if let Err(e) = r {
+                        println!("failed to preview the effect preset: {e}");
+                    }
+                    Message::None
+                },
+            ),
+            Message::EffectSettingsSubmit { settings } => {
+                self.general_cache.effect_settings = settings.clone();
+                let playlist_sender = self.communication.playlist_sender.clone();
+                Task::perform(
+                    util::update_effect_settings(settings, playlist_sender),
+                    |r| match r {
+                        Ok(_) => Message::HideModal,
+                        Err(e) => Message::ModalMessage(ModalMessage::Effects(
+                            EffectsModalMsg::SubmitFailed(e.to_string()),
+                        )),
+                    },
+                )
+            }
+            Message::OpenStorage => {
+                let playlists = self
+                    .general_cache
+                    .all_playlist_metadata
+                    .iter()
+                    .map(|m| (m.id().clone(), m.title.clone()))
+                    .collect();
+                self.general_cache.active_modal = Some(
+                    StorageModal::new(playlists, self.general_cache.storage_usage.as_ref()).into(),
+                );
+                Task::none()
+            }
+            Message::StorageSettingsSubmit { settings } => {
+                let playlist_sender = self.communication.playlist_sender.clone();
+                Task::perform(
+                    util::update_storage_settings(settings, playlist_sender),
+                    |r| match r {
+                        Ok(_) => Message::HideModal,
+                        Err(e) => Message::ModalMessage(ModalMessage::Storage(
+                            StorageModalMsg::SubmitFailed(e.to_string()),
+                        )),
+                    },
+                )
+            }
+            Message::OpenBackup => {
+                self.general_cache.active_modal = Some(BackupModal::new().into());
+                Task::none()
+            }
+            Message::BackupSubmit {
+                destination,
+                include_audio,
+            } => {
+                println!("backing up to: {}", destination.display());
+                let task_id = self.management.id_counter.next();
+                Task::perform(
+                    util::backup_library(
+                        destination,
+                        include_audio,
+                        task_id,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    move |r| match r {
+                        Ok(handle) => Message::LocalImportTaskStarted {
+                            task_id,
+                            action: "Backing up",
+                            handle,
+                        },
+                        Err(e) => {
+                            println!("something went wrong when submitting the backup: {e}");
+                            Message::None
+                        }
+                    },
+                )
+            }
+            Message::RestoreSubmit { archive, mode } => {
+                println!("restoring from: {}", archive.display());
+                let task_id = self.management.id_counter.next();
+                Task::perform(
+                    util::restore_library(
+                        archive,
+                        mode,
+                        task_id,
+                        self.communication.playlist_sender.clone(),
+                    ),
+                    move |r| match r {
+                        Ok(handle) => Message::LocalImportTaskStarted {
+                            task_id,
+                            action: "Restoring",
+                            handle,
+                        },
+                        Err(e) => {
+                            println!("something went wrong when submitting the restore: {e}");
+                            Message::None
+                        }
+                    },
+                )
+            }
+            Message::OpenLibraries => {
+                let playlist_sender = self.communication.playlist_sender.clone();
+                Task::perform(util::request_libraries(playlist_sender), |r| match r {
+                    Ok((libraries, active)) => Message::LibrariesReceived { libraries, active },
+                    Err(e) => {
+                        println!("Failed to get libraries: {e}");
+                        Message::None
+                    }
+                })
+            }
+            Message::LibrariesReceived { libraries, active } => {
+                self.general_cache.active_modal =
+                    Some(LibrariesModal::new(libraries, active).into());
+                Task::none()
+            }
+            Message::AddLibrary { name, path } => {
+                let playlist_sender = self.communication.playlist_sender.clone();
+                Task::perform(
+                    util::add_library(name, path, playlist_sender),
+                    |r| match r {
+                        // reopen the modal with the new library in it
+                        Ok(_) => Message::OpenLibraries,
+                        Err(e) => Message::ModalMessage(ModalMessage::Libraries(
+                            LibrariesModalMsg::ActionFailed(e.to_string()),
+                        )),
+                    },
+                )
+            }
+            Message::SwitchLibrary(name) => {
+                let playlist_sender = self.communication.playlist_sender.clone();
+                Task::perform(util::switch_library(name, playlist_sender), |r| match r {
+                    Ok(_) => Message::HideModal,
+                    Err(e) => Message::ModalMessage(ModalMessage::Libraries(
+                        LibrariesModalMsg::ActionFailed(e.to_string()),
+                    )),
+                })
+            }
+            Message::RemoveLocalImportData { task_id } => {
+                self.local_import_data.swap_remove(&task_id);
+                Task::none()
+            }
             Message::HideModal => {
+                // put back whatever the effects modal was previewing
+                let revert = match &self.general_cache.active_modal {
+                    Some(Modal::Effects(m)) => {
+                        let playlist_id = m.playlist_id().clone();
+                        let preset = self
+                            .general_cache
+                            .effect_settings
+                            .preset_for(Some(&playlist_id))
+                            .cloned();
+                        Task::done(Message::PreviewEffectPreset {
+                            playlist_id,
+                            preset,
+                        })
+                    }
+                    _ => Task::none(),
+                };
                 // Note: this clears all the modal data
                 util::hide_modal(self);
-                Task::none()
+                revert
             }
             Message::SystemEvent(e) => {
                 // println!("got event: {e:?}");
@@ -836,8 +2030,36 @@ impl App {
                         ..
                     }) => {
                         // Hide modal
-                        util::hide_modal(self);
-                        Task::none()
+                        Task::done(Message::HideModal)
+                    }
+                    Event::Window(window::Event::CloseRequested) => {
+                        // save where playback is up to before closing
+                        let save = if self.general_cache.restoring_session.is_some() {
+                            Task::none()
+                        } else {
+                            Task::perform(
+                                util::save_session(
+                                    util::current_session(self),
+                                    self.communication.playlist_sender.clone(),
+                                ),
+                                |r| {
+                                    if let Err(e) = r {
+                                        println!("failed to save the session: {e}");
+                                    }
+                                    Message::None
+                                },
+                            )
+                        };
+                        save.chain(iced::exit())
+                    }
+                    // a focused window can be seen; otherwise it might've been minimized
+                    Event::Window(window::Event::Focused) => {
+                        Task::done(Message::SetProgressCadence {
+                            cadence: ProgressCadence::Visible,
+                        })
+                    }
+                    Event::Window(window::Event::Unfocused | window::Event::Resized(_)) => {
+                        util::window_cadence()
                     }
                     _ => Task::none(),
                 }
@@ -870,7 +2092,18 @@ impl App {
                 None
             }
         });
-        Subscription::batch(vec![bus, tasks, event])
+        let session = iced::time::every(SESSION_SAVE_INTERVAL).map(|_| Message::SaveSession);
+        let mut subscriptions = vec![bus, tasks, event, session];
+        // only tick while there's a timer to count down
+        if self.general_cache.sleep_timer.is_some() || self.general_cache.alarm.is_some() {
+            subscriptions.push(iced::time::every(TIMER_TICK).map(|_| Message::TimerTick));
+        }
+        // the visualizer only runs while it can be seen
+        if self.settings.visualizer && matches!(self.management.current_page, Page::Player { .. }) {
+            subscriptions
+                .push(iced::time::every(VISUALIZER_FRAME).map(|_| Message::VisualizerFrame));
+        }
+        Subscription::batch(subscriptions)
     }
     fn theme(&self) -> Theme {
         self.theme.clone()
@@ -893,6 +2126,8 @@ impl GuiService {
         &self,
         playlist_sender: PlaylistSender,
         event_bus_rx: mpsc::Receiver<EventMessage>,
+        sample_tap: SampleTap,
+        library: Library,
     ) -> iced::Result {
         let mut id_counter = IdCounter::new();
         let event_recv_id = id_counter.next();
@@ -900,6 +2135,8 @@ impl GuiService {
         let flags = GuiFlags {
             playlist_sender,
             event_receiver: ReceiverHandle::new(event_recv_id, event_bus_rx),
+            sample_tap,
+            library,
         };
 
         let icon_font_data = include_bytes!(concat!(
@@ -913,7 +2150,7 @@ impl GuiService {
                 .theme(App::theme)
                 .title("peanut")
                 .font(icon_font_data)
-                .exit_on_close_request(true);
+                .exit_on_close_request(false);
 
         application.run()
     }
@@ -1,28 +1,38 @@
 use super::App;
-use crate::service::audio::enums::AlbumKind;
+use crate::service::audio::enums::{AlbumKind, LoopCount};
+use crate::service::audio::structs::PlaybackSpeed;
 use crate::service::file;
 use crate::service::file::enums::TrackDownloadState;
-use crate::service::gui::enums::{Action, DownloadState, Message, Page, PlayingState};
+use crate::service::gui::enums::{
+    Action, DownloadState, Message, Page, PlayingState, SleepTimerMode,
+};
 use crate::service::gui::icons::{self};
 use crate::service::gui::styling::AppTheme;
 use crate::service::gui::util::{self, format_duration};
 use crate::service::gui::widgets;
 use crate::service::gui::widgets::button::{
-    default_button, default_text_button, invisible_button, invisible_button_padded, track_button,
+    default_button, default_text_button, invisible_button, invisible_button_padded,
+    secondary_text_button, track_button,
 };
 use crate::service::gui::widgets::notification::NotificationRenderData;
 use crate::service::gui::widgets::notification::download::download_notification_list;
+use crate::service::gui::widgets::notification::import::local_import_notification_list;
 use crate::service::gui::widgets::notification::init::initialization_notification_list;
 use crate::service::gui::widgets::notification::playing::playing_notification_list;
 use crate::service::gui::widgets::page::build_page;
-use crate::service::gui::widgets::rule::{default_horizontal_rule, in_between_rule};
+use crate::service::gui::widgets::rule::{
+    default_horizontal_rule, in_between_rule, secondary_vertical_rule,
+};
 use crate::service::gui::widgets::scrollable::virtualized_vertical_scrollable;
 use crate::service::gui::widgets::text::{
     default_text, icon_text, left_menu_bold_text, left_menu_sub_text, secondary_text, title_text,
 };
 use crate::service::playlist::enums::Artist;
 use crate::service::playlist::structs::{Album, PlaylistMetadata, Track};
-use iced::widget::{Column, Image, Row, column, container, row, space, text};
+use iced::widget::{
+    Column, Image, Row, checkbox, column, container, mouse_area, pick_list, row, space, stack,
+    text, tooltip,
+};
 use iced::{Alignment, Element, Length, Padding, Theme};
 use widgets::container::{
     home_menu_widget_container, main_content as main_content_container,
@@ -30,6 +40,8 @@ use widgets::container::{
 };
 use widgets::slider::default_slider;
 use widgets::text_input::default_text_input;
+use widgets::visualizer::default_visualizer;
+use widgets::waveform::default_waveform;
 
 // page factory functions
 const HOME_WIDGET_SPACING: f32 = 10.0;
@@ -44,6 +56,19 @@ pub fn home(app: &App) -> Element<'_, Message> {
     let title_txt = title_text("Home", theme, true, true);
 
     let new_playlist = default_text_button("New", theme).on_press(Message::NewPlaylist);
+    let import_folder = secondary_text_button("Import", theme).on_press(Message::ImportFolder);
+    let libraries = secondary_text_button("Libraries", theme).on_press(Message::OpenLibraries);
+    let sync = secondary_text_button("Sync", theme).on_press(Message::OpenSync);
+    let backup = secondary_text_button("Backup", theme).on_press(Message::OpenBackup);
+    let storage_usage = app.general_cache.storage_usage.as_ref();
+    let storage_label = match storage_usage {
+        Some(usage) => match &usage.settings.quota {
+            Some(quota) => format!("Storage: {} / {}", usage.total, quota),
+            None => format!("Storage: {}", usage.total),
+        },
+        None => String::from("Storage"),
+    };
+    let storage = secondary_text_button(storage_label, theme).on_press(Message::OpenStorage);
     // let playlist_url = default_text_input(
     //     "Youtube playlist URL",
     //     &app.home_playlists_widget_data.search_text,
@@ -70,7 +95,8 @@ pub fn home(app: &App) -> Element<'_, Message> {
             default_text("Name", theme, true, true).width(Length::FillPortion(5)),
             default_text("Track Count", theme, true, true).width(Length::FillPortion(2)),
             default_text("Length", theme, true, true).width(Length::FillPortion(1)),
-            space().width(Length::FillPortion(2)),
+            default_text("Size", theme, true, true).width(Length::FillPortion(1)),
+            space().width(Length::FillPortion(1)),
         ]
         .spacing(4.0);
 
@@ -89,7 +115,31 @@ pub fn home(app: &App) -> Element<'_, Message> {
                             true
                         )
                         .width(Length::FillPortion(1)),
-                        space().width(Length::FillPortion(2)),
+                        default_text(
+                            storage_usage
+                                .and_then(|u| u.playlists.get(metadata.id()))
+                                .map(|size| size.to_string())
+                                .unwrap_or_default(),
+                            theme,
+                            true,
+                            true
+                        )
+                        .width(Length::FillPortion(1)),
+                        container(
+                            invisible_button(
+                                icon_text(
+                                    icons::CROSS,
+                                    theme.stylesheet().default_text(true, true)
+                                ),
+                                theme
+                            )
+                            .on_press(Message::Action(
+                                Action::DeletePlaylist {
+                                    playlist_id: metadata.id().clone(),
+                                }
+                            ))
+                        )
+                        .align_right(Length::FillPortion(1)),
                     ]
                     .width(Length::Fill)
                     .spacing(4.0),
@@ -137,7 +187,15 @@ pub fn home(app: &App) -> Element<'_, Message> {
     let playlists_header = row![
         title_text("Playlists", theme, true, true),
         space().width(Length::Fill),
-        new_playlist
+        row![
+            storage,
+            backup,
+            sync,
+            libraries,
+            import_folder,
+            new_playlist
+        ]
+        .spacing(10)
     ];
 
     let track_count = app.general_cache.all_tracks.len();
@@ -305,6 +363,10 @@ pub fn home(app: &App) -> Element<'_, Message> {
         &app.playlist_init_data,
         theme,
     ));
+    notifs.append(&mut local_import_notification_list(
+        &app.local_import_data,
+        theme,
+    ));
     notifs.append(&mut playing_notification_list(
         &app.playlist_render_data,
         theme,
@@ -407,10 +469,28 @@ pub fn player(app: &App) -> Element<'_, Message> {
             }
         });
 
+    let export =
+        secondary_text_button("Export", theme).on_press(Message::Action(Action::ExportPlaylist {
+            playlist_id: current_playlist_id.clone(),
+        }));
+
+    let playback = secondary_text_button("Playback", theme).on_press(Message::OpenPlaybackSettings);
+    let effects = secondary_text_button("Effects", theme).on_press(Message::OpenEffects {
+        playlist_id: current_playlist_id.clone(),
+    });
+
+    let visualizer_toggle =
+        secondary_text_button("Visualizer", theme).on_press(Message::ToggleVisualizer);
+
     let playlist_info_search = row![
         title.width(Length::Fill),
+        visualizer_toggle,
+        playback,
+        effects,
+        export,
         search_bar.width(Length::Fixed(300.0))
-    ];
+    ]
+    .spacing(10.0);
 
     // create header for tracks
     const TRACK_CAGEGORY_SPACING: f32 = 2.0;
@@ -450,11 +530,14 @@ pub fn player(app: &App) -> Element<'_, Message> {
                 Artist::Official(names) => names.join(", "),
             };
             let track_length = util::format_duration(&track.length);
-            let track_downloaded = app.general_cache.downloaded_tracks.contains(&track.id());
+            let track_downloaded = app
+                .general_cache
+                .downloaded_tracks
+                .contains(track.file_id());
             let track_downloading = app
                 .general_cache
                 .downloading_track_data
-                .contains_key(track.id());
+                .contains_key(track.file_id());
             let track_download_state = if track_downloading {
                 TrackDownloadState::Downloading
             } else if track_downloaded {
@@ -463,7 +546,7 @@ pub fn player(app: &App) -> Element<'_, Message> {
                 TrackDownloadState::NotDownloaded
             };
             // create the button
-            track_button(
+            let track_entry = track_button(
                 row![
                     // Track index text
                     default_text(
@@ -502,8 +585,20 @@ pub fn player(app: &App) -> Element<'_, Message> {
             .on_press(Message::Action(Action::PlayTrack {
                 playlist_id: playlist_id_clone.clone(),
                 track_index: *index as u64,
-            }))
-            .into()
+            }));
+            // right click for the track's menu; a chapter's is the menu of its whole track
+            let menu_track = current_render_data
+                .owned_playlist
+                .tracks
+                .0
+                .iter()
+                .find(|t| t.id() == track.file_id())
+                .unwrap_or(track);
+            mouse_area(track_entry)
+                .on_right_press(Message::OpenTrackMenu {
+                    track: menu_track.clone(),
+                })
+                .into()
         };
 
     // let track_closure = move |_relative_index: usize,
@@ -558,6 +653,56 @@ pub fn player(app: &App) -> Element<'_, Message> {
 
     let volume_interface = row![volume_text, volume_bar].spacing(5.0);
 
+    // playback speed
+    let speed = app
+        .general_cache
+        .playback_speeds
+        .get(&current_playlist_id)
+        .copied()
+        .unwrap_or_default();
+    let speed_picker = pick_list(
+        PlaybackSpeed::RATES.map(|rate| PlaybackSpeed::new(rate, speed.preserve_pitch)),
+        Some(speed),
+        {
+            let pid = current_playlist_id.clone();
+            move |speed| Message::SetPlaybackSpeed {
+                playlist_id: pid.clone(),
+                speed,
+            }
+        },
+    );
+    let keep_pitch = checkbox(speed.preserve_pitch)
+        .label("Keep pitch")
+        .on_toggle({
+            let pid = current_playlist_id.clone();
+            move |preserve_pitch| Message::SetPlaybackSpeed {
+                playlist_id: pid.clone(),
+                speed: PlaybackSpeed::new(speed.rate, preserve_pitch),
+            }
+        });
+    let speed_interface = row![speed_picker, keep_pitch]
+        .spacing(5.0)
+        .align_y(Alignment::Center);
+
+    // sleep timer + alarm, with how long's left
+    let mut timer_label = match &app.general_cache.sleep_timer {
+        Some(timer) if timer.playlist_id == current_playlist_id => match timer.mode {
+            SleepTimerMode::After(_) => format!("Sleep {}", format_duration(&timer.remaining)),
+            SleepTimerMode::EndOfTrack => String::from("Sleep after track"),
+            SleepTimerMode::EndOfPlaylist => String::from("Sleep after playlist"),
+        },
+        _ => String::from("Sleep"),
+    };
+    if let Some(alarm) = &app.general_cache.alarm
+        && *alarm.playlist.id() == current_playlist_id
+    {
+        timer_label.push_str(&format!(" · Alarm {}", alarm.at.format("%H:%M")));
+    }
+    let timer_button =
+        secondary_text_button(timer_label, theme).on_press(Message::OpenSleepTimer {
+            playlist_id: current_playlist_id.clone(),
+        });
+
     // buttons
 
     let download_button = if let DownloadState::Downloding = current_render_data.download_state {
@@ -581,6 +726,52 @@ pub fn player(app: &App) -> Element<'_, Message> {
             playlist_id: current_playlist_id.clone(),
         }),
     );
+    // A–B loop: mark A, then B, to loop between them
+    let mark_a_button = secondary_text_button(
+        if current_render_data.loop_mark.is_some() {
+            "A ✓"
+        } else {
+            "A"
+        },
+        theme,
+    )
+    .on_press(Message::Action(Action::MarkLoopStart {
+        playlist_id: current_playlist_id.clone(),
+    }));
+    let mut mark_b_button = secondary_text_button("B", theme);
+    if current_render_data.loop_mark.is_some() {
+        mark_b_button = mark_b_button.on_press(Message::Action(Action::MarkLoopEnd {
+            playlist_id: current_playlist_id.clone(),
+        }));
+    }
+    let loop_count_picker = pick_list(
+        LoopCount::CHOICES,
+        Some(
+            current_render_data
+                .loop_region
+                .map_or(current_render_data.loop_count, |region| region.count),
+        ),
+        {
+            let pid = current_playlist_id.clone();
+            move |count| {
+                Message::Action(Action::SetLoopCount {
+                    playlist_id: pid.clone(),
+                    count,
+                })
+            }
+        },
+    );
+    let mut loop_region_controls = row![mark_a_button, mark_b_button, loop_count_picker]
+        .spacing(5.0)
+        .align_y(Alignment::Center);
+    if current_render_data.loop_region.is_some() || current_render_data.loop_mark.is_some() {
+        loop_region_controls =
+            loop_region_controls.push(secondary_text_button("Clear loop", theme).on_press(
+                Message::Action(Action::ClearLoopRegion {
+                    playlist_id: current_playlist_id.clone(),
+                }),
+            ));
+    }
     let shuffle_button = default_button(icon_text(icons::SHUFFLE, default_text_style), theme)
         .on_press(Message::Action(Action::ShufflePlaylist {
             playlist_id: current_playlist_id.clone(),
@@ -621,23 +812,126 @@ pub fn player(app: &App) -> Element<'_, Message> {
     let total_time = current_render_data.playing_track_progress.total();
 
     let current_txt = text(format_duration(current_time));
-    let total_txt = text(format_duration(total_time));
+    let total_txt = if current_render_data.playing_track_progress.rate() == 1.0 {
+        text(format_duration(total_time))
+    } else {
+        // how long it'll really take at this speed
+        text(format!(
+            "{} ({} left)",
+            format_duration(total_time),
+            format_duration(&current_render_data.playing_track_progress.remaining())
+        ))
+    };
 
     let current_playlist_id_clone = current_playlist_id.clone();
-    let progress_bar_slider = default_slider(
-        0.0..=100.0,
-        current_render_data.playing_track_progress.progress() * 100.0,
-        move |progress| {
-            Message::Action(Action::SeekAudio {
-                playlist_id: current_playlist_id_clone.clone(),
-                progress: progress / 100.0,
-            })
-        },
-        theme,
-    )
-    .on_release(Message::Action(Action::StopSeekingAudio {
+    let on_seek = move |progress: f32| {
+        Message::Action(Action::SeekAudio {
+            playlist_id: current_playlist_id_clone.clone(),
+            progress,
+        })
+    };
+    let on_release = Message::Action(Action::StopSeekingAudio {
         playlist_id: current_playlist_id.clone(),
-    }));
+    });
+    // the track's waveform once it's loaded, showing just the part that plays
+    let progress_bar_slider: Element<'_, Message> = match (
+        &current_render_data.current_track,
+        &current_render_data.waveform,
+    ) {
+        (Some(track), Some((_, waveform))) => {
+            let (start, end) = track.played_section(waveform.length);
+            let (peaks, rms) = waveform.section(start, end);
+            let pid = current_playlist_id.clone();
+            let select_pid = current_playlist_id.clone();
+            let total = total_time.as_secs_f32();
+            let region = current_render_data
+                .loop_region
+                .filter(|_| total > 0.0)
+                .map(|region| {
+                    (
+                        region.start.as_secs_f32() / total,
+                        region.end.as_secs_f32() / total,
+                    )
+                });
+            let waveform_bar = default_waveform(
+                peaks,
+                rms,
+                current_render_data.playing_track_progress.progress(),
+                on_seek,
+                theme,
+            )
+            .on_release(on_release)
+            .on_hover(move |progress| Message::WaveformHovered {
+                playlist_id: pid.clone(),
+                progress,
+            })
+            // shift-drag to loop part of the track
+            .on_select(move |start, end| {
+                Message::Action(Action::SelectLoopRegion {
+                    playlist_id: select_pid.clone(),
+                    start,
+                    end,
+                })
+            })
+            .region(region);
+            let hover_time = current_render_data
+                .waveform_hover
+                .map(|progress| format_duration(&total_time.mul_f32(progress)))
+                .unwrap_or_default();
+            tooltip(
+                waveform_bar,
+                home_menu_widget_container(default_text(hover_time, theme, false, true), theme),
+                tooltip::Position::FollowCursor,
+            )
+            .into()
+        }
+        _ => default_slider(
+            0.0..=100.0,
+            current_render_data.playing_track_progress.progress() * 100.0,
+            move |progress| on_seek(progress / 100.0),
+            theme,
+        )
+        .on_release(on_release)
+        .into(),
+    };
+
+    // chapter markers, drawn over the progress bar where each chapter starts
+    let chapters = current_render_data
+        .current_track
+        .as_ref()
+        .map(|track| track.chapters.as_slice())
+        .unwrap_or_default();
+    // progress is within the trimmed part of the track
+    let trim_start = current_render_data
+        .current_track
+        .as_ref()
+        .map(|track| track.trim.start.as_secs_f64())
+        .unwrap_or_default();
+    let total_secs = total_time.as_secs_f64();
+    let progress_bar_slider: Element<'_, Message> = if chapters.len() > 1 && total_secs > 0.0 {
+        let mut bounds: Vec<f64> = chapters
+            .iter()
+            .skip(1)
+            .map(|chapter| chapter.start.as_secs_f64() - trim_start)
+            .filter(|start| *start > 0.0 && *start < total_secs)
+            .collect();
+        bounds.insert(0, 0.0);
+        bounds.push(total_secs);
+        let markers = Row::with_children(bounds.windows(2).enumerate().map(|(i, bound)| {
+            // out of 1000, so short chapters still get their own spot
+            let portion = ((bound[1] - bound[0]) / total_secs * 1000.0)
+                .round()
+                .max(1.0) as u16;
+            let mut segment = Row::new().width(Length::FillPortion(portion));
+            if i > 0 {
+                segment = segment.push(secondary_vertical_rule(2.0, theme));
+            }
+            segment.into()
+        }));
+        stack![progress_bar_slider, markers].into()
+    } else {
+        progress_bar_slider
+    };
 
     let progress_bar = row![current_txt, progress_bar_slider, total_txt];
 
@@ -650,8 +944,38 @@ pub fn player(app: &App) -> Element<'_, Message> {
         next_button,
         shuffle_button,
         loop_button,
-    ];
-    let core_and_progress = column![progress_bar, core_buttons].align_x(Alignment::Center);
+        loop_region_controls,
+    ]
+    .align_y(Alignment::Center);
+    let mut core_and_progress = column![progress_bar, core_buttons].align_x(Alignment::Center);
+    // skip between chapters of the current track
+    if let Some(track) = &current_render_data.current_track
+        && track.chapters.len() > 1
+    {
+        let chapter_title = track
+            .chapter_at(*current_time + track.trim.start)
+            .map(|i| track.chapters[i].title.clone())
+            .unwrap_or_default();
+        let previous_chapter = secondary_text_button("Previous chapter", theme).on_press(
+            Message::Action(Action::PreviousChapter {
+                playlist_id: current_playlist_id.clone(),
+            }),
+        );
+        let next_chapter = secondary_text_button("Next chapter", theme).on_press(Message::Action(
+            Action::NextChapter {
+                playlist_id: current_playlist_id.clone(),
+            },
+        ));
+        core_and_progress = core_and_progress.push(
+            row![
+                previous_chapter,
+                default_text(chapter_title, theme, false, true),
+                next_chapter
+            ]
+            .spacing(10.0)
+            .align_y(Alignment::Center),
+        );
+    }
 
     let lower_middle = container(core_and_progress).width(Length::FillPortion(1));
 
@@ -662,7 +986,8 @@ pub fn player(app: &App) -> Element<'_, Message> {
         // album cover first
         match &track.album_kind {
             AlbumKind::Album(album) => {
-                let album_cover_path = file::util::album_filename_from_id(&album.id());
+                let album_cover_path =
+                    file::util::album_filename_from_id(&app.general_cache.library, album.id());
                 if let Ok(path) = album_cover_path {
                     // make image with static size
                     let img = Image::new(path)
@@ -700,8 +1025,15 @@ pub fn player(app: &App) -> Element<'_, Message> {
             .align_y(Alignment::Center);
     }
 
-    let right_controls =
-        row![space().width(Length::Fill), volume_interface].width(Length::FillPortion(1));
+    let right_controls = row![
+        space().width(Length::Fill),
+        timer_button,
+        speed_interface,
+        volume_interface
+    ]
+    .spacing(15.0)
+    .align_y(Alignment::Center)
+    .width(Length::FillPortion(1));
     let controls = row![left_controls, lower_middle, right_controls]
         .align_y(Alignment::Center)
         .padding(Padding::new(5.0));
@@ -712,7 +1044,17 @@ pub fn player(app: &App) -> Element<'_, Message> {
 
     // put it all together
 
-    let playlist_and_track_data = column![upper_info, tracklist];
+    const VISUALIZER_HEIGHT: f32 = 80.0;
+    let mut playlist_and_track_data = column![upper_info, tracklist];
+    if app.settings.visualizer {
+        playlist_and_track_data = playlist_and_track_data.push(
+            menu_content_container(
+                default_visualizer(&app.general_cache.spectrum, VISUALIZER_HEIGHT, theme),
+                theme,
+            )
+            .padding(Padding::new(8.0)),
+        );
+    }
     let upper_portion = row![left_menu, playlist_and_track_data];
     let page = container(column![upper_portion, lower_controls]);
 
@@ -1,20 +1,37 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 use iced::{Event, Theme, widget::scrollable::Viewport};
 use tokio::sync::mpsc;
 
 use crate::{
     service::{
-        audio::structs::AudioProgress,
+        audio::{
+            enums::{LoopCount, NormalizationMode, ProgressCadence},
+            structs::{
+                AudioProgress, EffectPreset, EffectSettings, PlaybackSpeed, TrackTransition,
+                Waveform,
+            },
+        },
+        file::{library::Library, structs::LoadReport},
         gui::{
             structs::{PlaylistInitId, TaskId},
             widgets::modal::ModalMessage,
         },
         id::structs::Id,
         playlist::{
-            enums::PlaylistInitStatus,
+            enums::{
+                ImportTransferMode, LocalImportStatus, PlaylistFileFormat, PlaylistInitStatus,
+                PlaylistPathStyle, RestoreMode,
+            },
             structs::{
-                Album, OwnedPlaylist, PlaylistMetadata, Track, TrackDownloadData, Tracklist,
+                Album, ListeningSession, LocalImportOptions, LocalImportPreview, OwnedPlaylist,
+                PlaylistDeletionInfo, PlaylistMetadata, StorageSettings, StorageUsage, SyncOptions,
+                Track, TrackDownloadData, Tracklist,
             },
         },
     },
@@ -157,25 +174,254 @@ pub enum Message {
     },
     ModalMessage(ModalMessage),
     HideModal,
+    // Deletion info for a playlist was received; show the confirmation modal.
+    PlaylistDeletionInfoReceived(PlaylistDeletionInfo),
+    // The user confirmed a playlist deletion in the modal.
+    ConfirmPlaylistDeletion {
+        playlist_id: Id,
+        delete_files: bool,
+    },
+    // Import folder button pressed; show the import modal.
+    ImportFolder,
+    // A local folder import was submitted (in a modal). Provides the folder and import options.
+    LocalImportSubmit {
+        path: PathBuf,
+        options: LocalImportOptions,
+    },
+    // A local import task started. Provides the id and the receiver handle relevant to the task.
+    LocalImportTaskStarted {
+        task_id: TaskId,
+        action: &'static str,
+        handle: ReceiverHandle<Message>,
+    },
+    // fired when there's new info about a local import
+    LocalImportStatus {
+        status: LocalImportStatus,
+        id: TaskId,
+    },
+    // Import archive button pressed (in the import modal); show the archive import modal.
+    ImportArchive,
+    // A yt-dlp archive import was submitted (in a modal). Provides the archive, media folder and transfer mode.
+    YtDlpArchiveImportSubmit {
+        archive_path: PathBuf,
+        media_dir: Option<PathBuf>,
+        transfer_mode: ImportTransferMode,
+    },
+    // Playlist file button pressed (in the import modal); show the playlist file import modal.
+    ImportPlaylistFile,
+    // A playlist file import was submitted (in a modal). Provides the file and transfer mode.
+    PlaylistFileImportSubmit {
+        path: PathBuf,
+        transfer_mode: ImportTransferMode,
+    },
+    // A playlist export was submitted (in a modal). If `current_order` is set, the playlist's
+    // current (ie. shuffled) order is exported instead of the saved one.
+    ExportPlaylistSubmit {
+        playlist_id: Id,
+        destination: PathBuf,
+        format: PlaylistFileFormat,
+        path_style: PlaylistPathStyle,
+        current_order: bool,
+    },
+    // A dry run import finished; show what would be imported.
+    LocalImportPreviewReceived(LocalImportPreview),
+    RemoveLocalImportData {
+        task_id: TaskId,
+    },
+    // Sync button pressed; show the sync modal.
+    OpenSync,
+    // A sync to a folder was submitted (in the sync modal).
+    SyncSubmit {
+        playlist_ids: Vec<Id>,
+        destination: PathBuf,
+        options: SyncOptions,
+    },
+    // Backup button pressed; show the backup modal.
+    OpenBackup,
+    // A backup was submitted (in the backup modal).
+    BackupSubmit {
+        destination: PathBuf,
+        include_audio: bool,
+    },
+    // A restore was submitted (in the backup modal).
+    RestoreSubmit {
+        archive: PathBuf,
+        mode: RestoreMode,
+    },
+    // Storage button pressed; show the storage modal.
+    OpenStorage,
+    // New storage settings were submitted (in the storage modal).
+    StorageSettingsSubmit {
+        settings: StorageSettings,
+    },
+    // Playback button pressed (on the player page); show the playback modal.
+    OpenPlaybackSettings,
+    // New playback settings were submitted (in the playback modal).
+    PlaybackSettingsSubmit {
+        transition: TrackTransition,
+        normalization: NormalizationMode,
+        split_chapters: bool,
+        trim_silence: bool,
+    },
+    // A different speed was picked (on the player page).
+    SetPlaybackSpeed {
+        playlist_id: Id,
+        speed: PlaybackSpeed,
+    },
+    // Sleep button pressed (on the player page); show the sleep timer modal.
+    OpenSleepTimer {
+        playlist_id: Id,
+    },
+    // A sleep timer and/or alarm was submitted (in the sleep timer modal). None cancels them.
+    SleepTimerSubmit {
+        playlist_id: Id,
+        mode: Option<SleepTimerMode>,
+        alarm: Option<chrono::NaiveTime>,
+    },
+    // Once a second while there's a sleep timer or alarm.
+    TimerTick,
+    // Every few seconds; saves the listening session if it changed.
+    SaveSession,
+    // A sleep timer faded its playlist out and paused it.
+    SleepTimerFinished {
+        playlist_id: Id,
+    },
+    // A track was right clicked (in a tracklist); show its menu.
+    OpenTrackMenu {
+        track: Track,
+    },
+    // Detect silence pressed (in the track menu).
+    DetectTrackSilence {
+        id: Id,
+    },
+    // A track's trim was changed (in the track menu).
+    TrackTrimSubmit {
+        track: Track,
+    },
+    // The waveform of a playlist's current track was loaded. Provided: the id of its audio file.
+    WaveformLoaded {
+        playlist_id: Id,
+        file_id: Id,
+        waveform: Arc<Waveform>,
+    },
+    // The cursor moved over the waveform (on the player page); None when it left.
+    WaveformHovered {
+        playlist_id: Id,
+        progress: Option<f32>,
+    },
+    // Visualizer button pressed (on the player page); shows or hides the visualizer.
+    ToggleVisualizer,
+    // Time to draw the visualizer's next frame.
+    VisualizerFrame,
+    // The window was minimized or came back; playing audio reports its progress less often while
+    // it can't be seen.
+    SetProgressCadence {
+        cadence: ProgressCadence,
+    },
+    // Effects button pressed (on the player page); show the effects modal.
+    OpenEffects {
+        playlist_id: Id,
+    },
+    // A preset is being edited (in the effects modal); hear it on the playlist right away.
+    PreviewEffectPreset {
+        playlist_id: Id,
+        preset: Option<EffectPreset>,
+    },
+    // New effect presets were submitted (in the effects modal).
+    EffectSettingsSubmit {
+        settings: EffectSettings,
+    },
+    // Libraries button pressed; get the libraries to show in the libraries modal.
+    OpenLibraries,
+    LibrariesReceived {
+        libraries: Vec<Library>,
+        active: Library,
+    },
+    // A new library was submitted (in the libraries modal).
+    AddLibrary {
+        name: String,
+        path: PathBuf,
+    },
+    // A library was picked to switch to (in the libraries modal). Given: its name.
+    SwitchLibrary(String),
 }
 
 #[derive(Debug, Clone)]
 pub enum Action {
     // In the player menu, the home button was activated.
     Home,
-    DownloadPlaylist { playlist_id: Id },
-    StopPlaylistDownload { playlist_id: Id },
-    OrganizePlaylist { playlist_id: Id },
-    ShufflePlaylist { playlist_id: Id },
-    PreviousTrack { playlist_id: Id },
-    ResumeTrack { playlist_id: Id },
-    PauseTrack { playlist_id: Id },
-    NextTrack { playlist_id: Id },
-    LoopTrack { playlist_id: Id },
-    PlayTrack { playlist_id: Id, track_index: u64 },
-    SeekAudio { playlist_id: Id, progress: f32 },
-    StopSeekingAudio { playlist_id: Id },
-    SetVolume { volume: f64 },
+    DownloadPlaylist {
+        playlist_id: Id,
+    },
+    StopPlaylistDownload {
+        playlist_id: Id,
+    },
+    OrganizePlaylist {
+        playlist_id: Id,
+    },
+    ShufflePlaylist {
+        playlist_id: Id,
+    },
+    PreviousTrack {
+        playlist_id: Id,
+    },
+    ResumeTrack {
+        playlist_id: Id,
+    },
+    PauseTrack {
+        playlist_id: Id,
+    },
+    NextTrack {
+        playlist_id: Id,
+    },
+    PreviousChapter {
+        playlist_id: Id,
+    },
+    NextChapter {
+        playlist_id: Id,
+    },
+    LoopTrack {
+        playlist_id: Id,
+    },
+    // A–B loops: mark A and B at the current position, or drag the region out on the waveform
+    MarkLoopStart {
+        playlist_id: Id,
+    },
+    MarkLoopEnd {
+        playlist_id: Id,
+    },
+    SelectLoopRegion {
+        playlist_id: Id,
+        start: f32,
+        end: f32,
+    },
+    SetLoopCount {
+        playlist_id: Id,
+        count: LoopCount,
+    },
+    ClearLoopRegion {
+        playlist_id: Id,
+    },
+    PlayTrack {
+        playlist_id: Id,
+        track_index: u64,
+    },
+    SeekAudio {
+        playlist_id: Id,
+        progress: f32,
+    },
+    StopSeekingAudio {
+        playlist_id: Id,
+    },
+    SetVolume {
+        volume: f64,
+    },
+    DeletePlaylist {
+        playlist_id: Id,
+    },
+    ExportPlaylist {
+        playlist_id: Id,
+    },
 }
 
 // represents each possible major page the gui can be
@@ -193,6 +439,34 @@ pub enum DownloadState {
     StopPending,
 }
 
+/// When a sleep timer pauses its playlist.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SleepTimerMode {
+    // after this much playing time
+    After(Duration),
+    EndOfTrack,
+    EndOfPlaylist,
+}
+impl SleepTimerMode {
+    // the choices offered in the sleep timer modal
+    pub const ALL: [Self; 7] = [
+        Self::After(Duration::from_secs(15 * 60)),
+        Self::After(Duration::from_secs(30 * 60)),
+        Self::After(Duration::from_secs(45 * 60)),
+        Self::After(Duration::from_secs(60 * 60)),
+        Self::After(Duration::from_secs(90 * 60)),
+        Self::EndOfTrack,
+        Self::EndOfPlaylist,
+    ];
+    pub fn label(&self) -> String {
+        match self {
+            Self::After(duration) => format!("In {} minutes", duration.as_secs() / 60),
+            Self::EndOfTrack => String::from("At the end of this track"),
+            Self::EndOfPlaylist => String::from("At the end of the playlist"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum PlayingState {
     Playing,
@@ -223,6 +497,32 @@ pub enum EventMessage {
         tracks_added: Option<HashMap<Id, Track>>,
         tracks_removed: Option<Vec<Id>>,
     },
+    // A playlist was deleted. Given: its id, the track files that were deleted with it,
+    // and the albums whose covers were deleted.
+    PlaylistDeleted {
+        id: Id,
+        deleted_track_files: Vec<Id>,
+        deleted_albums: Vec<Id>,
+    },
+    // Tracks had their audio added to the library without being downloaded (e.g. imported).
+    // Given: the ids of the tracks.
+    DownloadedTracksAdded(HashSet<Id>),
+    // Some library data couldn't be loaded on startup and was quarantined
+    LibraryLoadReport(LoadReport),
+    // Track audio was deleted to stay under the storage quota (the metadata is kept).
+    // Given: the ids of the tracks.
+    TrackFilesEvicted(Vec<Id>),
+    // The library's storage usage or settings changed.
+    StorageUsageUpdated(StorageUsage),
+    // The library's effect presets were loaded.
+    EffectSettingsUpdated(EffectSettings),
+    // The library's playlist speeds were loaded. Only has playlists not at the normal speed.
+    PlaybackSpeedsUpdated(HashMap<Id, PlaybackSpeed>),
+    // What was playing when the app was last closed, if its playlist is still around.
+    SessionLoaded(ListeningSession),
+    // A different library was opened. Everything cached from the old one should be dropped,
+    // the new library's data is sent right after.
+    LibraryChanged(Library),
 }
 
 pub type EventSender = mpsc::Sender<EventMessage>;
@@ -1,19 +1,33 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
 
+use chrono::{DateTime, Local, NaiveTime};
+use iced::task::Handle;
 use indexmap::IndexMap;
 
 use crate::{
     service::{
-        audio::{enums::LoopPolicy, structs::AudioProgress},
+        audio::{
+            enums::{LoopCount, LoopPolicy, NormalizationMode, ProgressCadence},
+            structs::{
+                AudioProgress, EffectSettings, LoopRegion, PlaybackSpeed, SampleTap, Spectrum,
+                TrackTransition, Waveform,
+            },
+        },
+        file::library::Library,
         gui::{
-            enums::{DownloadState, EventMessage, Message, Page, PlayingState},
+            enums::{DownloadState, EventMessage, Message, Page, PlayingState, SleepTimerMode},
             widgets::modal::Modal,
         },
         id::structs::Id,
         playlist::{
             PlaylistSender,
             structs::{
-                Album, OwnedPlaylist, PlaylistMetadata, Track, TrackDownloadData, Tracklist,
+                Album, ListeningSession, OwnedPlaylist, PlaylistMetadata, StorageUsage, Track,
+                TrackDownloadData, Tracklist,
             },
         },
     },
@@ -75,9 +89,19 @@ pub struct GuiCommunication {
     pub playlist_sender: PlaylistSender,
     pub active_tasks: HashMap<TaskId, ReceiverHandle<Message>>,
     pub event_bus: ReceiverHandle<EventMessage>,
+    // what's playing, copied out of the audio service for the visualizer
+    pub sample_tap: SampleTap,
 }
 pub struct GuiSettings {
     pub volume: f64,
+    pub transition: TrackTransition,
+    pub normalization: NormalizationMode,
+    // whether chaptered tracks play as a track per chapter; takes effect when a playlist starts
+    pub split_chapters: bool,
+    // whether silence is trimmed off of newly downloaded tracks
+    pub trim_silence: bool,
+    // whether the visualizer is shown (on the player page)
+    pub visualizer: bool,
 }
 pub struct GuiManagement {
     pub id_counter: IdCounter,
@@ -113,18 +137,100 @@ pub struct GeneralCache {
     // Playlist caching
     pub recent_playlists: VecDeque<PlaylistMetadata>,
     pub all_playlist_metadata: Vec<PlaylistMetadata>,
+
+    // Storage; None until the library's been measured
+    pub storage_usage: Option<StorageUsage>,
+
+    // Effects, as last saved
+    pub effect_settings: EffectSettings,
+    // Speed of each playlist not at the normal speed
+    pub playback_speeds: HashMap<Id, PlaybackSpeed>,
+
+    // Timers
+    pub sleep_timer: Option<SleepTimer>,
+    pub alarm: Option<Alarm>,
+    // the playlist an alarm is loading; played once its first track is ready
+    pub alarm_starting: Option<Id>,
+
+    // Listening session
+    // the last session, until its playlist is loaded again
+    pub restoring_session: Option<ListeningSession>,
+    // what was last saved, so unchanged sessions aren't saved again
+    pub saved_session: Option<ListeningSession>,
+
+    // Visualizer
+    pub spectrum: Spectrum,
+
+    // how often playing audio reports its progress; slower while the window is minimized
+    pub progress_cadence: ProgressCadence,
+
+    // the library that's open, for finding album covers
+    pub library: Library,
+}
+/// Fades out and pauses a playlist (see `SleepTimerMode`).
+pub struct SleepTimer {
+    pub playlist_id: Id,
+    pub mode: SleepTimerMode,
+    // playing time left; only counts down while the playlist is playing, so pausing doesn't
+    // use the timer up
+    pub remaining: Duration,
+    // set once the fade out has started
+    pub fading: Option<Handle>,
+}
+impl SleepTimer {
+    pub fn new(playlist_id: Id, mode: SleepTimerMode) -> Self {
+        let remaining = match mode {
+            SleepTimerMode::After(duration) => duration,
+            _ => Duration::ZERO,
+        };
+        Self {
+            playlist_id,
+            mode,
+            remaining,
+            fading: None,
+        }
+    }
+}
+/// Starts playing a playlist at a time of day.
+pub struct Alarm {
+    pub playlist: PlaylistMetadata,
+    pub at: DateTime<Local>,
+}
+impl Alarm {
+    // The next time the clock reads `time`; today if it's still to come, otherwise tomorrow
+    pub fn new(playlist: PlaylistMetadata, time: NaiveTime) -> Self {
+        let now = Local::now();
+        let at = (0..=2)
+            .filter_map(|days| {
+                (now.date_naive() + chrono::Days::new(days))
+                    .and_time(time)
+                    .and_local_timezone(Local)
+                    .earliest()
+            })
+            .find(|at| *at > now)
+            .unwrap_or(now);
+        Self { playlist, at }
+    }
 }
 pub struct PlaylistRenderData {
     pub playlist_id: Id,
     pub current_track: Option<Track>,
     pub playing_track_progress: AudioProgress,
     pub playing_track_loop_policy: LoopPolicy,
+    // an A–B loop over part of the current track, and where A is while B hasn't been marked yet
+    pub loop_region: Option<LoopRegion>,
+    pub loop_mark: Option<Duration>,
+    // how many times A–B loops play
+    pub loop_count: LoopCount,
     pub owned_playlist: OwnedPlaylist,
     pub current_tracklist: Tracklist,
     pub playing_state: PlayingState,
     pub download_state: DownloadState,
     pub scroll_offset: f32,
     pub track_search_text: String,
+    // the waveform of the current track's file (by its id), and how far along it the cursor is
+    pub waveform: Option<(Id, Arc<Waveform>)>,
+    pub waveform_hover: Option<f32>,
 }
 pub struct PlaylistInitData {
     pub platform_display_id: Option<String>,
@@ -132,3 +238,10 @@ pub struct PlaylistInitData {
     pub total_track_count: Option<u32>,
     pub name: Option<String>,
 }
+pub struct LocalImportData {
+    // shown in the notification, e.g. "Importing Folder"
+    pub action: &'static str,
+    pub found_file_count: u32,
+    pub current_file: Option<u32>,
+    pub total_file_count: Option<u32>,
+}
@@ -125,6 +125,25 @@ impl SliderStyle {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct WaveformStyle {
+    pub played_color: Color,
+    pub unplayed_color: Color,
+    // the peaks are drawn fainter, behind the RMS
+    pub peak_alpha: f32,
+    // the line under the cursor
+    pub hover_color: Color,
+    // behind an A–B loop region
+    pub region_color: Color,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VisualizerStyle {
+    pub bar_color: Color,
+    pub level_color: Color,
+    pub peak_color: Color,
+}
+
 pub struct TextInputStyle {
     bg: Background,
     border_color: Color,
@@ -331,6 +350,22 @@ impl Stylesheet {
             width: 6.0,
         }
     }
+    pub fn default_waveform(&self) -> WaveformStyle {
+        WaveformStyle {
+            played_color: self.interactable_color,
+            unplayed_color: self.secondary_text_color.scale_alpha(0.6),
+            peak_alpha: 0.45,
+            hover_color: self.main_text_color,
+            region_color: self.interactable_color.scale_alpha(0.25),
+        }
+    }
+    pub fn default_visualizer(&self) -> VisualizerStyle {
+        VisualizerStyle {
+            bar_color: self.interactable_color,
+            level_color: self.secondary_text_color,
+            peak_color: self.main_text_color,
+        }
+    }
     pub fn default_text_input(&self) -> TextInputStyle {
         TextInputStyle {
             bg: self.accent_color_bg,
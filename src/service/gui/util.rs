@@ -1,18 +1,28 @@
 use std::cmp::Ordering;
 use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
 use std::time::Duration;
 
-use iced::Task;
+use iced::{Task, window};
 use tokio::sync::{mpsc, oneshot};
 
-use crate::service::audio::enums::{AlbumKind, LoopPolicy};
+use crate::service::audio::enums::{AlbumKind, LoopPolicy, NormalizationMode, ProgressCadence};
+use crate::service::audio::structs::{
+    EffectPreset, EffectSettings, LoopRegion, PlaybackSpeed, TrackTransition, Trim, Waveform,
+};
+use crate::service::file::library::Library;
 use crate::service::gui::App;
-use crate::service::gui::enums::Page;
+use crate::service::gui::enums::{Page, PlayingState, SleepTimerMode};
 use crate::service::gui::structs::{PlaylistRenderData, TaskId};
 use crate::service::id::structs::Id;
 use crate::service::playlist::PlaylistSender;
-use crate::service::playlist::enums::{Artist, PlaylistMessage};
-use crate::service::playlist::structs::{Album, OwnedPlaylist, PlaylistMetadata, Track, Tracklist};
+use crate::service::playlist::enums::{
+    Artist, ImportTransferMode, PlaylistFileFormat, PlaylistMessage, PlaylistPathStyle, RestoreMode,
+};
+use crate::service::playlist::structs::{
+    Album, ListeningSession, LocalImportOptions, OwnedPlaylist, PlaylistDeletionInfo,
+    PlaylistMetadata, ResumePoint, StorageSettings, SyncOptions, Track, Tracklist,
+};
 use crate::util::sync::ReceiverHandle;
 
 use super::enums::Message;
@@ -120,6 +130,8 @@ pub async fn play_playlist(
     playlist_sender: PlaylistSender,
     tracklist: Option<Tracklist>,
     volume: f64,
+    transition: TrackTransition,
+    resume: Option<ResumePoint>,
 ) -> anyhow::Result<ReceiverHandle<Message>> {
     // create a receiver handle for progress updates
     let (tx, rx) = mpsc::channel(100);
@@ -131,6 +143,8 @@ pub async fn play_playlist(
             tracklist,
             data_sender: tx,
             volume,
+            transition,
+            resume,
         })
         .await?;
     Ok(handle)
@@ -170,6 +184,53 @@ pub async fn resume_current_playlist_track(
     Ok(())
 }
 
+pub async fn fade_out_current_track(
+    playlist_id: Id,
+    fade: Duration,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::FadeOutCurrentTrack {
+            playlist_id,
+            fade,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+
+pub async fn set_pause_after_current_track(
+    playlist_id: Id,
+    enabled: bool,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::PauseAfterCurrentTrack {
+            playlist_id,
+            enabled,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+
+// Fades the playlist out, pauses it, then puts the volume back for when it's resumed
+pub async fn fade_out_and_pause(
+    playlist_id: Id,
+    fade: Duration,
+    volume: f64,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    fade_out_current_track(playlist_id.clone(), fade, playlist_sender.clone()).await?;
+    tokio::time::sleep(fade).await;
+    pause_current_playlist_track(playlist_id, playlist_sender.clone()).await?;
+    update_volume_in_playlist_service(volume, playlist_sender).await
+}
+
 pub async fn skip_current_playlist_track(
     playlist_id: Id,
     playlist_sender: PlaylistSender,
@@ -238,6 +299,22 @@ pub async fn set_playlist_loop_policy(
     Ok(())
 }
 
+pub async fn set_playlist_loop_region(
+    playlist_id: Id,
+    region: Option<LoopRegion>,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::SetPlaylistLoopRegion {
+            playlist_id,
+            region,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await?
+}
+
 pub async fn update_volume_in_playlist_service(
     volume: f64,
     playlist_sender: PlaylistSender,
@@ -254,6 +331,168 @@ pub async fn update_volume_in_playlist_service(
     Ok(())
 }
 
+pub async fn update_track_transition(
+    transition: TrackTransition,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::UpdateTrackTransition {
+            transition,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+
+pub async fn update_normalization(
+    mode: NormalizationMode,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::UpdateNormalization {
+            mode,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+
+pub async fn update_silence_trimming(
+    enabled: bool,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::UpdateSilenceTrimming {
+            enabled,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+
+pub async fn detect_track_silence(id: Id, playlist_sender: PlaylistSender) -> anyhow::Result<Trim> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::DetectTrackSilence {
+            id,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await?
+}
+
+pub async fn get_waveform(id: Id, playlist_sender: PlaylistSender) -> anyhow::Result<Waveform> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::GetWaveform {
+            id,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await?
+}
+
+pub async fn update_track(track: Track, playlist_sender: PlaylistSender) -> anyhow::Result<()> {
+    playlist_sender
+        .send(PlaylistMessage::UpdateTrack {
+            playlist_id: None,
+            track,
+            restart_audio: false,
+            restart_download: false,
+        })
+        .await?;
+    Ok(())
+}
+
+pub async fn update_playback_speed(
+    playlist_id: Id,
+    speed: PlaybackSpeed,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::UpdatePlaybackSpeed {
+            playlist_id,
+            speed,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn save_session(
+    session: Option<ListeningSession>,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::SaveSession {
+            session,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn update_effect_settings(
+    settings: EffectSettings,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::UpdateEffectSettings {
+            settings,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn preview_effect_preset(
+    playlist_id: Option<Id>,
+    preset: Option<EffectPreset>,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    playlist_sender
+        .send(PlaylistMessage::PreviewEffectPreset {
+            playlist_id,
+            preset,
+        })
+        .await?;
+    Ok(())
+}
+pub async fn set_progress_cadence(
+    cadence: ProgressCadence,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    playlist_sender
+        .send(PlaylistMessage::SetProgressCadence { cadence })
+        .await?;
+    Ok(())
+}
+// Whether the window can be seen; a minimized one can't. Platforms that can't tell count as seen
+pub fn window_cadence() -> Task<Message> {
+    window::latest()
+        .then(|id| match id {
+            Some(id) => window::is_minimized(id),
+            None => Task::done(None),
+        })
+        .map(|minimized| {
+            let cadence = if minimized == Some(true) {
+                ProgressCadence::Hidden
+            } else {
+                ProgressCadence::Visible
+            };
+            Message::SetProgressCadence { cadence }
+        })
+}
+
 pub fn format_duration(duration: &Duration) -> String {
     let total_seconds = duration.as_secs();
     let mins = total_seconds / 60;
@@ -415,6 +654,371 @@ pub async fn stop_playlist(playlist_id: Id, playlist_sender: PlaylistSender) ->
     let _ = rx.await??;
     Ok(())
 }
+pub async fn request_playlist_deletion_info(
+    playlist_id: Id,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<Option<PlaylistDeletionInfo>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::GetPlaylistDeletionInfo {
+            id: playlist_id,
+            result_sender: tx,
+        })
+        .await?;
+    Ok(rx.await?)
+}
+pub async fn delete_playlist(
+    playlist_id: Id,
+    delete_files: bool,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::DeletePlaylist {
+            id: playlist_id,
+            delete_files,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn request_libraries(
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<(Vec<Library>, Library)> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::GetLibraries { result_sender: tx })
+        .await?;
+    Ok(rx.await?)
+}
+pub async fn add_library(
+    name: String,
+    path: PathBuf,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::AddLibrary {
+            name,
+            path,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn update_storage_settings(
+    settings: StorageSettings,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::UpdateStorageSettings {
+            settings,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn switch_library(name: String, playlist_sender: PlaylistSender) -> anyhow::Result<()> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::SwitchLibrary {
+            name,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await??;
+    Ok(())
+}
+pub async fn import_local_folder(
+    path: PathBuf,
+    options: LocalImportOptions,
+    task_id: TaskId,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<ReceiverHandle<Message>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ImportLocalFolder {
+            path,
+            options,
+            task_id,
+            reply_stream: tx,
+        })
+        .await?;
+    let raw_recv = rx.await?;
+    Ok(ReceiverHandle::new(task_id, raw_recv))
+}
+pub async fn import_ytdlp_archive(
+    archive_path: PathBuf,
+    media_dir: Option<PathBuf>,
+    transfer_mode: ImportTransferMode,
+    task_id: TaskId,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<ReceiverHandle<Message>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ImportYtDlpArchive {
+            archive_path,
+            media_dir,
+            transfer_mode,
+            task_id,
+            reply_stream: tx,
+        })
+        .await?;
+    let raw_recv = rx.await?;
+    Ok(ReceiverHandle::new(task_id, raw_recv))
+}
+pub async fn import_playlist_file(
+    path: PathBuf,
+    transfer_mode: ImportTransferMode,
+    task_id: TaskId,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<ReceiverHandle<Message>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ImportPlaylistFile {
+            path,
+            transfer_mode,
+            task_id,
+            reply_stream: tx,
+        })
+        .await?;
+    let raw_recv = rx.await?;
+    Ok(ReceiverHandle::new(task_id, raw_recv))
+}
+pub async fn sync_playlists(
+    playlist_ids: Vec<Id>,
+    destination: PathBuf,
+    options: SyncOptions,
+    task_id: TaskId,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<ReceiverHandle<Message>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::SyncPlaylists {
+            playlist_ids,
+            destination,
+            options,
+            task_id,
+            reply_stream: tx,
+        })
+        .await?;
+    let raw_recv = rx.await?;
+    Ok(ReceiverHandle::new(task_id, raw_recv))
+}
+pub async fn backup_library(
+    destination: PathBuf,
+    include_audio: bool,
+    task_id: TaskId,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<ReceiverHandle<Message>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::BackupLibrary {
+            destination,
+            include_audio,
+            task_id,
+            reply_stream: tx,
+        })
+        .await?;
+    let raw_recv = rx.await?;
+    Ok(ReceiverHandle::new(task_id, raw_recv))
+}
+pub async fn restore_library(
+    archive: PathBuf,
+    mode: RestoreMode,
+    task_id: TaskId,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<ReceiverHandle<Message>> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::RestoreLibrary {
+            archive,
+            mode,
+            task_id,
+            reply_stream: tx,
+        })
+        .await?;
+    let raw_recv = rx.await?;
+    Ok(ReceiverHandle::new(task_id, raw_recv))
+}
+// Returns how many tracks were exported
+pub async fn export_playlist(
+    id: Id,
+    tracklist: Option<Tracklist>,
+    destination: PathBuf,
+    format: PlaylistFileFormat,
+    path_style: PlaylistPathStyle,
+    playlist_sender: PlaylistSender,
+) -> anyhow::Result<usize> {
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ExportPlaylist {
+            id,
+            tracklist,
+            destination,
+            format,
+            path_style,
+            result_sender: tx,
+        })
+        .await?;
+    rx.await?
+}
+pub fn remove_deleted_playlist(app: &mut App, playlist_id: &Id) {
+    app.general_cache
+        .all_playlist_metadata
+        .retain(|m| m.id() != playlist_id);
+    app.general_cache
+        .recent_playlists
+        .retain(|m| m.id() != playlist_id);
+    app.playlist_render_data.swap_remove(playlist_id);
+    // if the deleted playlist is open, go back home
+    if let Page::Player { playlist_id: pid } = &app.management.current_page
+        && pid == playlist_id
+    {
+        app.management.current_page = Page::Home;
+    }
+}
+// What's playing (or else the open playlist, or else anything loaded), as a session to resume
+pub fn current_session(app: &App) -> Option<ListeningSession> {
+    let loaded = |render_data: &&PlaylistRenderData| {
+        render_data.current_track.is_some()
+            && !matches!(render_data.playing_state, PlayingState::Unloaded)
+    };
+    let open = match &app.management.current_page {
+        Page::Player { playlist_id } => app.playlist_render_data.get(playlist_id).filter(loaded),
+        Page::Home => None,
+    };
+    let render_data = app
+        .playlist_render_data
+        .values()
+        .filter(loaded)
+        .find(|r| matches!(r.playing_state, PlayingState::Playing))
+        .or(open)
+        .or_else(|| app.playlist_render_data.values().find(loaded))?;
+    let track = render_data.current_track.as_ref()?;
+    let track_index = render_data
+        .current_tracklist
+        .iter()
+        .position(|t| t.id() == track.id())?;
+    Some(ListeningSession {
+        playlist_id: render_data.playlist_id.clone(),
+        order: render_data.current_tracklist.order().order().clone(),
+        resume: ResumePoint {
+            track_index: track_index as u64,
+            position: *render_data.playing_track_progress.current(),
+        },
+    })
+}
+// Seeks the playlist's current track to `position` (ie. a chapter's start) and plays from there
+pub fn seek_current_track(app: &mut App, playlist_id: Id, position: Duration) -> Task<Message> {
+    let Some(render_data) = app.playlist_render_data.get_mut(&playlist_id) else {
+        return Task::none();
+    };
+    let total = render_data.playing_track_progress.total().as_secs_f32();
+    if total == 0.0 {
+        return Task::none();
+    }
+    let progress = (position.as_secs_f32() / total).min(1.0);
+    render_data.playing_track_progress.update_progress(progress);
+    Task::perform(
+        resume_current_playlist_track(
+            playlist_id.clone(),
+            app.communication.playlist_sender.clone(),
+            Some(progress),
+        ),
+        |_result| Message::TrackAudioResumeResult { playlist_id },
+    )
+}
+// Loops part of the playlist's current track, or stops looping it. Playback jumps to the start
+// of the region unless it's already in it
+pub fn set_loop_region(
+    app: &mut App,
+    playlist_id: Id,
+    region: Option<LoopRegion>,
+) -> Task<Message> {
+    let Some(render_data) = app.playlist_render_data.get_mut(&playlist_id) else {
+        return Task::none();
+    };
+    render_data.loop_region = region;
+    render_data.loop_mark = None;
+    let current = *render_data.playing_track_progress.current();
+    let task = Task::perform(
+        set_playlist_loop_region(
+            playlist_id.clone(),
+            region,
+            app.communication.playlist_sender.clone(),
+        ),
+        |result| {
+            if let Err(e) = result {
+                println!("Failed to set the loop region: {e}");
+            }
+            Message::None
+        },
+    );
+    match region {
+        Some(region) if !region.contains(current) => {
+            task.chain(seek_current_track(app, playlist_id, region.start))
+        }
+        _ => task,
+    }
+}
+// The playlist's tracklist if it's split into chapters, which the playlist service can't rebuild
+// from its cache
+pub fn chapter_tracklist(app: &App, playlist_id: &Id) -> Option<Tracklist> {
+    app.playlist_render_data
+        .get(playlist_id)
+        .map(|render_data| &render_data.current_tracklist)
+        .filter(|tracklist| tracklist.iter().any(|track| track.chapter_of.is_some()))
+        .cloned()
+}
+// Drops everything cached from the open library (ie. when switching to another one)
+pub fn clear_library_data(app: &mut App) {
+    let cache = &mut app.general_cache;
+    cache.downloaded_tracks.clear();
+    cache.downloading_track_data.clear();
+    cache.all_tracks.clear();
+    cache.all_albums.clear();
+    cache.recent_playlists.clear();
+    cache.all_playlist_metadata.clear();
+    cache.storage_usage = None;
+    cache.effect_settings = EffectSettings::default();
+    cache.playback_speeds.clear();
+    if let Some(fading) = cache.sleep_timer.take().and_then(|timer| timer.fading) {
+        fading.abort();
+    }
+    cache.alarm = None;
+    cache.alarm_starting = None;
+    cache.restoring_session = None;
+    cache.saved_session = None;
+    app.playlist_render_data.clear();
+    app.management.current_page = Page::Home;
+}
+// Stops the sleep timer, putting back anything it's already done to its playlist
+pub fn cancel_sleep_timer(app: &mut App) -> Task<Message> {
+    let Some(timer) = app.general_cache.sleep_timer.take() else {
+        return Task::none();
+    };
+    let playlist_sender = app.communication.playlist_sender.clone();
+    let mut tasks = Vec::new();
+    if let Some(fading) = timer.fading {
+        fading.abort();
+        tasks.push(Task::perform(
+            update_volume_in_playlist_service(app.settings.volume, playlist_sender.clone()),
+            |_| Message::None,
+        ));
+    }
+    if let SleepTimerMode::EndOfTrack = timer.mode {
+        tasks.push(Task::perform(
+            set_pause_after_current_track(timer.playlist_id, false, playlist_sender),
+            |_| Message::None,
+        ));
+    }
+    Task::batch(tasks)
+}
 pub fn hide_modal(app: &mut App) {
     app.general_cache.active_modal = None
 }
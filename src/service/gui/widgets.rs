@@ -9,3 +9,5 @@ pub mod scrollable;
 pub mod slider;
 pub mod text;
 pub mod text_input;
+pub mod visualizer;
+pub mod waveform;
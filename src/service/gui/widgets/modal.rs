@@ -1,7 +1,8 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, time::Duration};
 
 use iced::{
     Element, Length, Padding, Task, Theme,
+    task::Handle,
     widget::{column, container, mouse_area, opaque, space},
 };
 
@@ -9,11 +10,43 @@ use crate::service::gui::{
     enums::Message,
     widgets::{
         container::{default_modal_background_container, default_modal_container},
-        modal::new_playlist::{NewPlaylistModal, NewPlaylistModalMsg},
+        modal::{
+            backup::{BackupModal, BackupModalMsg},
+            delete_playlist::{DeletePlaylistModal, DeletePlaylistModalMsg},
+            effects::{EffectsModal, EffectsModalMsg},
+            export_playlist::{ExportPlaylistModal, ExportPlaylistModalMsg},
+            import_archive::{ImportArchiveModal, ImportArchiveModalMsg},
+            import_folder::{ImportFolderModal, ImportFolderModalMsg},
+            import_playlist_file::{ImportPlaylistFileModal, ImportPlaylistFileModalMsg},
+            import_preview::{ImportPreviewModal, ImportPreviewModalMsg},
+            libraries::{LibrariesModal, LibrariesModalMsg},
+            load_report::{LoadReportModal, LoadReportModalMsg},
+            new_playlist::{NewPlaylistModal, NewPlaylistModalMsg},
+            playback::{PlaybackModal, PlaybackModalMsg},
+            sleep_timer::{SleepTimerModal, SleepTimerModalMsg},
+            storage::{StorageModal, StorageModalMsg},
+            sync::{SyncModal, SyncModalMsg},
+            track_menu::{TrackMenuModal, TrackMenuModalMsg},
+        },
     },
 };
 
+pub mod backup;
+pub mod delete_playlist;
+pub mod effects;
+pub mod export_playlist;
+pub mod import_archive;
+pub mod import_folder;
+pub mod import_playlist_file;
+pub mod import_preview;
+pub mod libraries;
+pub mod load_report;
 pub mod new_playlist;
+pub mod playback;
+pub mod sleep_timer;
+pub mod storage;
+pub mod sync;
+pub mod track_menu;
 
 #[derive(Debug, Clone)]
 enum AbstractModalMessage<Local, Global> {
@@ -64,9 +97,47 @@ trait AbstractModal<Global: 'static>: Into<Modal> {
     }
 }
 
+// Sends `remove` back to the modal a few seconds after an error is shown, so the error goes
+// away on its own. Showing another error restarts the delay.
+fn remove_error_after_delay<Local: Send + 'static>(
+    timer_handle: &mut Option<Handle>,
+    remove: Local,
+) -> Task<AbstractModalMessage<Local, Message>> {
+    // if there was previously a timer remove it
+    if let Some(h) = timer_handle.take() {
+        h.abort();
+    }
+    // start delay to remove error
+    let (t, h) = Task::perform(
+        async move {
+            tokio::time::sleep(Duration::from_secs(3)).await;
+            remove
+        },
+        AbstractModalMessage::Local,
+    )
+    .abortable();
+    *timer_handle = Some(h);
+    t
+}
+
 #[derive(Debug, Clone)]
 pub enum ModalMessage {
     NewPlaylist(NewPlaylistModalMsg),
+    DeletePlaylist(DeletePlaylistModalMsg),
+    ImportFolder(ImportFolderModalMsg),
+    ImportArchive(ImportArchiveModalMsg),
+    ImportPreview(ImportPreviewModalMsg),
+    LoadReport(LoadReportModalMsg),
+    Libraries(LibrariesModalMsg),
+    ImportPlaylistFile(ImportPlaylistFileModalMsg),
+    ExportPlaylist(ExportPlaylistModalMsg),
+    Sync(SyncModalMsg),
+    Backup(BackupModalMsg),
+    Storage(StorageModalMsg),
+    Playback(PlaybackModalMsg),
+    Effects(EffectsModalMsg),
+    SleepTimer(SleepTimerModalMsg),
+    TrackMenu(TrackMenuModalMsg),
     HideModal,
 }
 
@@ -82,6 +153,21 @@ enum ModalFillAmount {
 #[derive(Debug, Clone)]
 pub enum Modal {
     NewPlaylist(NewPlaylistModal),
+    DeletePlaylist(DeletePlaylistModal),
+    ImportFolder(ImportFolderModal),
+    ImportArchive(ImportArchiveModal),
+    ImportPreview(ImportPreviewModal),
+    LoadReport(LoadReportModal),
+    Libraries(LibrariesModal),
+    ImportPlaylistFile(ImportPlaylistFileModal),
+    ExportPlaylist(ExportPlaylistModal),
+    Sync(SyncModal),
+    Backup(BackupModal),
+    Storage(StorageModal),
+    Playback(PlaybackModal),
+    Effects(EffectsModal),
+    SleepTimer(SleepTimerModal),
+    TrackMenu(Box<TrackMenuModal>),
 }
 impl Modal {
     pub fn view(&self, theme: &Theme) -> Element<'_, Message> {
@@ -92,6 +178,82 @@ impl Modal {
                 }
                 AbstractModalMessage::Global(g) => g,
             }),
+            Self::DeletePlaylist(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::DeletePlaylist(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::ImportFolder(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::ImportFolder(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::ImportArchive(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::ImportArchive(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::ImportPreview(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::ImportPreview(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::LoadReport(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::LoadReport(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::Libraries(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Libraries(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::ImportPlaylistFile(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::ImportPlaylistFile(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::ExportPlaylist(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::ExportPlaylist(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::Sync(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Sync(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::Backup(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Backup(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::Storage(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Storage(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::Playback(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Playback(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::Effects(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Effects(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::SleepTimer(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::SleepTimer(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            Self::TrackMenu(m) => m.build(theme).map(|abstract_msg| match abstract_msg {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::TrackMenu(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
         };
         opaque(mouse_area(main_modal_content).on_press(Message::HideModal))
     }
@@ -105,6 +267,94 @@ impl Modal {
                     AbstractModalMessage::Global(g) => g,
                 })
             }
+            (Modal::DeletePlaylist(w), ModalMessage::DeletePlaylist(m)) => {
+                w.update(m).map(|bm| match bm {
+                    AbstractModalMessage::Local(l) => {
+                        Message::ModalMessage(ModalMessage::DeletePlaylist(l))
+                    }
+                    AbstractModalMessage::Global(g) => g,
+                })
+            }
+            (Modal::ImportFolder(w), ModalMessage::ImportFolder(m)) => {
+                w.update(m).map(|bm| match bm {
+                    AbstractModalMessage::Local(l) => {
+                        Message::ModalMessage(ModalMessage::ImportFolder(l))
+                    }
+                    AbstractModalMessage::Global(g) => g,
+                })
+            }
+            (Modal::ImportArchive(w), ModalMessage::ImportArchive(m)) => {
+                w.update(m).map(|bm| match bm {
+                    AbstractModalMessage::Local(l) => {
+                        Message::ModalMessage(ModalMessage::ImportArchive(l))
+                    }
+                    AbstractModalMessage::Global(g) => g,
+                })
+            }
+            (Modal::ImportPreview(w), ModalMessage::ImportPreview(m)) => {
+                w.update(m).map(|bm| match bm {
+                    AbstractModalMessage::Local(l) => {
+                        Message::ModalMessage(ModalMessage::ImportPreview(l))
+                    }
+                    AbstractModalMessage::Global(g) => g,
+                })
+            }
+            (Modal::LoadReport(w), ModalMessage::LoadReport(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::LoadReport(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::Libraries(w), ModalMessage::Libraries(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Libraries(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::ImportPlaylistFile(w), ModalMessage::ImportPlaylistFile(m)) => {
+                w.update(m).map(|bm| match bm {
+                    AbstractModalMessage::Local(l) => {
+                        Message::ModalMessage(ModalMessage::ImportPlaylistFile(l))
+                    }
+                    AbstractModalMessage::Global(g) => g,
+                })
+            }
+            (Modal::ExportPlaylist(w), ModalMessage::ExportPlaylist(m)) => {
+                w.update(m).map(|bm| match bm {
+                    AbstractModalMessage::Local(l) => {
+                        Message::ModalMessage(ModalMessage::ExportPlaylist(l))
+                    }
+                    AbstractModalMessage::Global(g) => g,
+                })
+            }
+            (Modal::Sync(w), ModalMessage::Sync(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Sync(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::Backup(w), ModalMessage::Backup(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Backup(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::Storage(w), ModalMessage::Storage(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Storage(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::Playback(w), ModalMessage::Playback(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Playback(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::Effects(w), ModalMessage::Effects(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::Effects(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::SleepTimer(w), ModalMessage::SleepTimer(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => {
+                    Message::ModalMessage(ModalMessage::SleepTimer(l))
+                }
+                AbstractModalMessage::Global(g) => g,
+            }),
+            (Modal::TrackMenu(w), ModalMessage::TrackMenu(m)) => w.update(m).map(|bm| match bm {
+                AbstractModalMessage::Local(l) => Message::ModalMessage(ModalMessage::TrackMenu(l)),
+                AbstractModalMessage::Global(g) => g,
+            }),
             _ => Task::none(),
         }
     }
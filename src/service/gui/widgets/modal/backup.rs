@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            rule::default_horizontal_rule,
+            text::{error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    playlist::enums::RestoreMode,
+};
+
+#[derive(Debug, Clone)]
+pub enum BackupModalMsg {
+    BackupPathTextUpdate(String),
+    ToggleIncludeAudio(bool),
+    RestorePathTextUpdate(String),
+    ToggleReplace(bool),
+    CheckBackupSubmit,
+    CheckRestoreSubmit,
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupModal {
+    backup_path_text: String,
+    include_audio: bool,
+    restore_path_text: String,
+    replace: bool,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for BackupModal {
+    type ModalMsg = BackupModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let backup_title = title_text("Back Up Library", theme, true, true);
+        let backup_description = secondary_text(
+            "Writes the library's playlists, tracks and album covers to a .tar.gz archive.",
+            theme,
+            true,
+            true,
+        );
+        let backup_path_box = default_text_input(
+            "Archive path (ie. library.tar.gz)",
+            &self.backup_path_text,
+            theme,
+        )
+        .on_input(|s| Local(BackupModalMsg::BackupPathTextUpdate(s)))
+        .on_paste(|s| Local(BackupModalMsg::BackupPathTextUpdate(s)))
+        .on_submit(Local(BackupModalMsg::CheckBackupSubmit));
+        let include_audio_box = checkbox(self.include_audio)
+            .label("Include the audio files")
+            .on_toggle(|b| Local(BackupModalMsg::ToggleIncludeAudio(b)));
+        let backup = default_text_button("Back Up", theme)
+            .on_press(Local(BackupModalMsg::CheckBackupSubmit));
+
+        let restore_title = title_text("Restore Library", theme, true, true);
+        let restore_description = secondary_text(
+            "Restores a backup into the open library. Playback and downloads have to be stopped first.",
+            theme,
+            true,
+            true,
+        );
+        let restore_path_box = default_text_input("Archive path", &self.restore_path_text, theme)
+            .on_input(|s| Local(BackupModalMsg::RestorePathTextUpdate(s)))
+            .on_paste(|s| Local(BackupModalMsg::RestorePathTextUpdate(s)))
+            .on_submit(Local(BackupModalMsg::CheckRestoreSubmit));
+        let replace_box = checkbox(self.replace)
+            .label("Replace the library instead of merging into it")
+            .on_toggle(|b| Local(BackupModalMsg::ToggleReplace(b)));
+        let restore = default_text_button("Restore", theme)
+            .on_press(Local(BackupModalMsg::CheckRestoreSubmit));
+
+        let error = match &self.error {
+            Some(et) => error_text(format!("Error: {}", et), theme, true, true),
+            None => error_text("", theme, true, true),
+        };
+        let close = secondary_text_button("Close", theme).on_press(Global(Message::HideModal));
+        container(
+            column![
+                backup_title,
+                backup_description,
+                backup_path_box,
+                row![include_audio_box, space().width(Length::Fill), backup].spacing(10),
+                default_horizontal_rule(1, theme),
+                restore_title,
+                restore_description,
+                restore_path_box,
+                row![replace_box, space().width(Length::Fill), restore].spacing(10),
+                error,
+                row![space().width(Length::Fill), close]
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            BackupModalMsg::BackupPathTextUpdate(s) => {
+                self.backup_path_text = s;
+                Task::none()
+            }
+            BackupModalMsg::ToggleIncludeAudio(b) => {
+                self.include_audio = b;
+                Task::none()
+            }
+            BackupModalMsg::RestorePathTextUpdate(s) => {
+                self.restore_path_text = s;
+                Task::none()
+            }
+            BackupModalMsg::ToggleReplace(b) => {
+                self.replace = b;
+                Task::none()
+            }
+            BackupModalMsg::CheckBackupSubmit => {
+                let destination = PathBuf::from(self.backup_path_text.trim());
+                if self.backup_path_text.trim().is_empty() || destination.is_dir() {
+                    return self.show_error("Enter a file path for the archive");
+                }
+                if !destination
+                    .parent()
+                    .is_some_and(|p| p.as_os_str().is_empty() || p.is_dir())
+                {
+                    return self.show_error("Folder does not exist");
+                }
+                Task::batch(vec![
+                    Task::done(Global(Message::BackupSubmit {
+                        destination,
+                        include_audio: self.include_audio,
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            BackupModalMsg::CheckRestoreSubmit => {
+                let archive = PathBuf::from(self.restore_path_text.trim());
+                if !archive.is_file() {
+                    return self.show_error("Archive does not exist");
+                }
+                let mode = if self.replace {
+                    RestoreMode::Replace
+                } else {
+                    RestoreMode::Merge
+                };
+                Task::batch(vec![
+                    Task::done(Global(Message::RestoreSubmit { archive, mode })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            BackupModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<BackupModal> for Modal {
+    fn from(modal: BackupModal) -> Self {
+        Modal::Backup(modal)
+    }
+}
+impl BackupModal {
+    pub fn new() -> Self {
+        Self {
+            backup_path_text: String::new(),
+            include_audio: false,
+            restore_path_text: String::new(),
+            replace: false,
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+    fn show_error(&mut self, e: &str) -> Task<AbstractModalMessage<BackupModalMsg, Message>> {
+        self.error = Some(String::from(e));
+        remove_error_after_delay(&mut self.error_timer_handle, BackupModalMsg::RemoveError)
+    }
+}
@@ -0,0 +1,117 @@
+use iced::{
+    Element, Length, Padding, Task,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column,
+            },
+            text::{default_text, secondary_text, title_text},
+        },
+    },
+    playlist::structs::PlaylistDeletionInfo,
+};
+
+#[derive(Debug, Clone)]
+pub enum DeletePlaylistModalMsg {
+    ToggleDeleteFiles(bool),
+    Confirm,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeletePlaylistModal {
+    info: PlaylistDeletionInfo,
+    delete_files: bool,
+}
+impl AbstractModal<Message> for DeletePlaylistModal {
+    type ModalMsg = DeletePlaylistModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Delete Playlist", theme, true, true);
+        let description = default_text(
+            format!(
+                "Are you sure you want to delete \"{}\"?",
+                self.info.metadata.title
+            ),
+            theme,
+            true,
+            true,
+        );
+        let track_info = secondary_text(
+            format!(
+                "{} track(s) and {} album(s) aren't used by any other playlist.",
+                self.info.unreferenced_tracks.len(),
+                self.info.unreferenced_albums.len()
+            ),
+            theme,
+            true,
+            true,
+        );
+        let delete_files_box = checkbox(self.delete_files)
+            .label(format!(
+                "Also delete unused audio and album covers (frees {})",
+                self.info.freed_size
+            ))
+            .on_toggle(|b| Local(DeletePlaylistModalMsg::ToggleDeleteFiles(b)));
+
+        let confirm =
+            default_text_button("Delete", theme).on_press(Local(DeletePlaylistModalMsg::Confirm));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, confirm].spacing(10);
+        container(
+            column![
+                title,
+                description,
+                track_info,
+                delete_files_box,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            DeletePlaylistModalMsg::ToggleDeleteFiles(b) => {
+                self.delete_files = b;
+                Task::none()
+            }
+            DeletePlaylistModalMsg::Confirm => Task::batch(vec![
+                Task::done(Global(Message::ConfirmPlaylistDeletion {
+                    playlist_id: self.info.metadata.id().clone(),
+                    delete_files: self.delete_files,
+                })),
+                Task::done(Global(Message::HideModal)),
+            ]),
+        }
+    }
+}
+impl From<DeletePlaylistModal> for Modal {
+    fn from(modal: DeletePlaylistModal) -> Self {
+        Modal::DeletePlaylist(modal)
+    }
+}
+impl DeletePlaylistModal {
+    pub fn new(info: PlaylistDeletionInfo) -> Self {
+        Self {
+            info,
+            delete_files: false,
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::fmt;
+
+use iced::{
+    Alignment, Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, pick_list, row, space},
+};
+
+use crate::service::{
+    audio::structs::{CompressorSettings, EQ_BANDS, EffectPreset, EffectSettings},
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            slider::{default_slider, default_vertical_slider},
+            text::{default_text, error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    id::structs::Id,
+};
+
+// limits of the controls
+const MAX_EQ_GAIN_DB: f64 = 12.0;
+const HIGH_PASS_RANGE: (f64, f64) = (20.0, 300.0);
+const LOW_PASS_RANGE: (f64, f64) = (2000.0, 20000.0);
+const THRESHOLD_RANGE: (f64, f64) = (-40.0, 0.0);
+const RATIO_RANGE: (f64, f64) = (1.0, 10.0);
+const EQ_SLIDER_HEIGHT: f32 = 120.0;
+
+// What a preset picker can be set to
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetChoice {
+    // no effects
+    Off,
+    // whatever the default preset is
+    Default,
+    Preset(String),
+}
+impl fmt::Display for PresetChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "No effects"),
+            Self::Default => write!(f, "Same as default"),
+            Self::Preset(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EffectsModalMsg {
+    SelectDefault(PresetChoice),
+    SelectPlaylistPreset(PresetChoice),
+    SelectEditing(PresetChoice),
+    SetBand(usize, f64),
+    ToggleHighPass(bool),
+    SetHighPass(f64),
+    ToggleLowPass(bool),
+    SetLowPass(f64),
+    ToggleCompressor(bool),
+    SetThreshold(f64),
+    SetRatio(f64),
+    NewPresetTextUpdate(String),
+    AddPreset,
+    DeletePreset,
+    CheckSubmit,
+    // The presets couldn't be saved
+    SubmitFailed(String),
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct EffectsModal {
+    // the playlist whose page the modal was opened from
+    playlist_id: Id,
+    settings: EffectSettings,
+    // index of the preset shown in the editor
+    editing: usize,
+    new_preset_text: String,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for EffectsModal {
+    type ModalMsg = EffectsModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Effects", theme, true, true);
+        let description = secondary_text(
+            "Everything plays through the default preset, unless a playlist picks its own. Changes can be heard right away.",
+            theme,
+            true,
+            true,
+        );
+        let preset_choices: Vec<PresetChoice> = self
+            .settings
+            .presets
+            .iter()
+            .map(|p| PresetChoice::Preset(p.name.clone()))
+            .collect();
+
+        let default_choice = match &self.settings.active {
+            Some(name) => PresetChoice::Preset(name.clone()),
+            None => PresetChoice::Off,
+        };
+        let default_picker = pick_list(
+            [PresetChoice::Off]
+                .into_iter()
+                .chain(preset_choices.iter().cloned())
+                .collect::<Vec<_>>(),
+            Some(default_choice),
+            |c| Local(EffectsModalMsg::SelectDefault(c)),
+        );
+        let playlist_choice = match self.settings.playlist_presets.get(&self.playlist_id) {
+            Some(name) => PresetChoice::Preset(name.clone()),
+            None => PresetChoice::Default,
+        };
+        let playlist_picker = pick_list(
+            [PresetChoice::Default]
+                .into_iter()
+                .chain(preset_choices.iter().cloned())
+                .collect::<Vec<_>>(),
+            Some(playlist_choice),
+            |c| Local(EffectsModalMsg::SelectPlaylistPreset(c)),
+        );
+        let pickers = column![
+            row![
+                default_text("Default", theme, false, true),
+                space().width(Length::Fill),
+                default_picker
+            ]
+            .align_y(Alignment::Center),
+            row![
+                default_text("This playlist", theme, false, true),
+                space().width(Length::Fill),
+                playlist_picker
+            ]
+            .align_y(Alignment::Center),
+        ]
+        .spacing(6.0);
+
+        let mut editor = column![].spacing(10.0);
+        if let Some(preset) = self.settings.presets.get(self.editing) {
+            let editing_picker = pick_list(
+                preset_choices,
+                Some(PresetChoice::Preset(preset.name.clone())),
+                |c| Local(EffectsModalMsg::SelectEditing(c)),
+            );
+            let bands = row(EQ_BANDS.iter().zip(preset.eq_gains).enumerate().map(
+                |(i, (frequency, gain))| {
+                    let label = if *frequency >= 1000.0 {
+                        format!("{}k", frequency / 1000.0)
+                    } else {
+                        format!("{}", frequency)
+                    };
+                    column![
+                        default_vertical_slider(
+                            -MAX_EQ_GAIN_DB..=MAX_EQ_GAIN_DB,
+                            gain,
+                            move |g| Local(EffectsModalMsg::SetBand(i, g)),
+                            theme,
+                        )
+                        .step(0.5)
+                        .height(Length::Fixed(EQ_SLIDER_HEIGHT)),
+                        secondary_text(label, theme, false, false),
+                    ]
+                    .align_x(Alignment::Center)
+                    .spacing(4.0)
+                    .width(Length::Fill)
+                    .into()
+                },
+            ));
+
+            let high_pass = checkbox(preset.high_pass.is_some())
+                .label(match preset.high_pass {
+                    Some(hz) => format!("Cut lows below {:.0} Hz", hz),
+                    None => String::from("Cut lows"),
+                })
+                .on_toggle(|b| Local(EffectsModalMsg::ToggleHighPass(b)));
+            let low_pass = checkbox(preset.low_pass.is_some())
+                .label(match preset.low_pass {
+                    Some(hz) => format!("Cut highs above {:.0} Hz", hz),
+                    None => String::from("Cut highs"),
+                })
+                .on_toggle(|b| Local(EffectsModalMsg::ToggleLowPass(b)));
+            let compressor = checkbox(preset.compressor.is_some())
+                .label("Compress (evens out loud and quiet parts)")
+                .on_toggle(|b| Local(EffectsModalMsg::ToggleCompressor(b)));
+            let mut filters = column![high_pass].spacing(6.0);
+            if let Some(hz) = preset.high_pass {
+                filters = filters.push(default_slider(
+                    HIGH_PASS_RANGE.0..=HIGH_PASS_RANGE.1,
+                    hz,
+                    |hz| Local(EffectsModalMsg::SetHighPass(hz)),
+                    theme,
+                ));
+            }
+            filters = filters.push(low_pass);
+            if let Some(hz) = preset.low_pass {
+                filters = filters.push(default_slider(
+                    LOW_PASS_RANGE.0..=LOW_PASS_RANGE.1,
+                    hz,
+                    |hz| Local(EffectsModalMsg::SetLowPass(hz)),
+                    theme,
+                ));
+            }
+            filters = filters.push(compressor);
+            if let Some(c) = preset.compressor {
+                filters = filters
+                    .push(secondary_text(
+                        format!("Threshold: {:.0} dB", c.threshold_db),
+                        theme,
+                        true,
+                        true,
+                    ))
+                    .push(default_slider(
+                        THRESHOLD_RANGE.0..=THRESHOLD_RANGE.1,
+                        c.threshold_db,
+                        |t| Local(EffectsModalMsg::SetThreshold(t)),
+                        theme,
+                    ))
+                    .push(secondary_text(
+                        format!("Ratio: {:.1}:1", c.ratio),
+                        theme,
+                        true,
+                        true,
+                    ))
+                    .push(
+                        default_slider(
+                            RATIO_RANGE.0..=RATIO_RANGE.1,
+                            c.ratio,
+                            |r| Local(EffectsModalMsg::SetRatio(r)),
+                            theme,
+                        )
+                        .step(0.5),
+                    );
+            }
+            editor = editor
+                .push(
+                    row![
+                        default_text("Edit preset", theme, false, true),
+                        space().width(Length::Fill),
+                        editing_picker
+                    ]
+                    .align_y(Alignment::Center),
+                )
+                .push(bands)
+                .push(filters);
+        }
+
+        let new_preset_box = default_text_input("New preset name", &self.new_preset_text, theme)
+            .on_input(|s| Local(EffectsModalMsg::NewPresetTextUpdate(s)))
+            .on_paste(|s| Local(EffectsModalMsg::NewPresetTextUpdate(s)))
+            .on_submit(Local(EffectsModalMsg::AddPreset));
+        let add = secondary_text_button("Copy", theme).on_press(Local(EffectsModalMsg::AddPreset));
+        let mut delete = secondary_text_button("Delete", theme);
+        if !self.settings.presets.is_empty() {
+            delete = delete.on_press(Local(EffectsModalMsg::DeletePreset));
+        }
+        let preset_row = row![new_preset_box, add, delete].spacing(10);
+
+        let error = match &self.error {
+            Some(et) => error_text(format!("Error: {}", et), theme, true, true),
+            None => error_text("", theme, true, true),
+        };
+        let save = default_text_button("Save", theme).on_press(Local(EffectsModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, save].spacing(10);
+        container(
+            column![
+                title,
+                description,
+                pickers,
+                editor,
+                preset_row,
+                error,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(520.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            EffectsModalMsg::SelectDefault(choice) => {
+                self.settings.active = match choice {
+                    PresetChoice::Preset(name) => Some(name),
+                    _ => None,
+                };
+                self.preview_selected()
+            }
+            EffectsModalMsg::SelectPlaylistPreset(choice) => {
+                match choice {
+                    PresetChoice::Preset(name) => {
+                        self.settings
+                            .playlist_presets
+                            .insert(self.playlist_id.clone(), name);
+                    }
+                    _ => {
+                        self.settings.playlist_presets.remove(&self.playlist_id);
+                    }
+                }
+                self.preview_selected()
+            }
+            EffectsModalMsg::SelectEditing(choice) => {
+                if let PresetChoice::Preset(name) = choice
+                    && let Some(i) = self.settings.presets.iter().position(|p| p.name == name)
+                {
+                    self.editing = i;
+                }
+                Task::none()
+            }
+            EffectsModalMsg::SetBand(i, gain) => self.edit(|p| p.eq_gains[i] = gain),
+            EffectsModalMsg::ToggleHighPass(b) => self.edit(|p| p.high_pass = b.then_some(80.0)),
+            EffectsModalMsg::SetHighPass(hz) => self.edit(|p| p.high_pass = Some(hz)),
+            EffectsModalMsg::ToggleLowPass(b) => self.edit(|p| p.low_pass = b.then_some(12000.0)),
+            EffectsModalMsg::SetLowPass(hz) => self.edit(|p| p.low_pass = Some(hz)),
+            EffectsModalMsg::ToggleCompressor(b) => self.edit(|p| {
+                p.compressor = b.then_some(CompressorSettings {
+                    threshold_db: -20.0,
+                    ratio: 3.0,
+                })
+            }),
+            EffectsModalMsg::SetThreshold(threshold_db) => self.edit(|p| {
+                if let Some(c) = &mut p.compressor {
+                    c.threshold_db = threshold_db;
+                }
+            }),
+            EffectsModalMsg::SetRatio(ratio) => self.edit(|p| {
+                if let Some(c) = &mut p.compressor {
+                    c.ratio = ratio;
+                }
+            }),
+            EffectsModalMsg::NewPresetTextUpdate(s) => {
+                self.new_preset_text = s;
+                Task::none()
+            }
+            EffectsModalMsg::AddPreset => {
+                let name = self.new_preset_text.trim().to_string();
+                if name.is_empty() {
+                    return self.show_error("Name the new preset first");
+                }
+                if self.settings.preset(&name).is_some() {
+                    return self.show_error("There's already a preset with that name");
+                }
+                // start from whatever's being edited
+                let preset = match self.settings.presets.get(self.editing) {
+                    Some(preset) => EffectPreset {
+                        name,
+                        ..preset.clone()
+                    },
+                    None => EffectPreset::flat(name),
+                };
+                self.settings.presets.push(preset);
+                self.editing = self.settings.presets.len() - 1;
+                self.new_preset_text.clear();
+                Task::none()
+            }
+            EffectsModalMsg::DeletePreset => {
+                if self.editing >= self.settings.presets.len() {
+                    return Task::none();
+                }
+                let removed = self.settings.presets.remove(self.editing);
+                // anything using it goes back to the default / no effects
+                if self.settings.active.as_ref() == Some(&removed.name) {
+                    self.settings.active = None;
+                }
+                self.settings
+                    .playlist_presets
+                    .retain(|_, name| *name != removed.name);
+                self.editing = 0;
+                self.preview_selected()
+            }
+            EffectsModalMsg::CheckSubmit => {
+                // the modal is closed once the presets are saved
+                Task::done(Global(Message::EffectSettingsSubmit {
+                    settings: self.settings.clone(),
+                }))
+            }
+            EffectsModalMsg::SubmitFailed(e) => self.show_error(&e),
+            EffectsModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<EffectsModal> for Modal {
+    fn from(modal: EffectsModal) -> Self {
+        Modal::Effects(modal)
+    }
+}
+impl EffectsModal {
+    pub fn new(playlist_id: Id, settings: EffectSettings) -> Self {
+        // start on the preset the playlist is using
+        let editing = settings
+            .preset_for(Some(&playlist_id))
+            .and_then(|preset| settings.presets.iter().position(|p| p == preset))
+            .unwrap_or(0);
+        Self {
+            playlist_id,
+            settings,
+            editing,
+            new_preset_text: String::new(),
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+    pub fn playlist_id(&self) -> &Id {
+        &self.playlist_id
+    }
+    // Changes the preset being edited, and plays the playlist through it
+    fn edit(
+        &mut self,
+        f: impl FnOnce(&mut EffectPreset),
+    ) -> Task<AbstractModalMessage<EffectsModalMsg, Message>> {
+        let Some(preset) = self.settings.presets.get_mut(self.editing) else {
+            return Task::none();
+        };
+        f(preset);
+        Task::done(Global(Message::PreviewEffectPreset {
+            playlist_id: self.playlist_id.clone(),
+            preset: Some(preset.clone()),
+        }))
+    }
+    // Plays the playlist through the preset it would use with the current choices
+    fn preview_selected(&self) -> Task<AbstractModalMessage<EffectsModalMsg, Message>> {
+        Task::done(Global(Message::PreviewEffectPreset {
+            playlist_id: self.playlist_id.clone(),
+            preset: self.settings.preset_for(Some(&self.playlist_id)).cloned(),
+        }))
+    }
+    fn show_error(&mut self, e: &str) -> Task<AbstractModalMessage<EffectsModalMsg, Message>> {
+        self.error = Some(String::from(e));
+        remove_error_after_delay(&mut self.error_timer_handle, EffectsModalMsg::RemoveError)
+    }
+}
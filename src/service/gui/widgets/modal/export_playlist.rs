@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    id::structs::Id,
+    playlist::enums::{PlaylistFileFormat, PlaylistPathStyle},
+};
+
+#[derive(Debug, Clone)]
+pub enum ExportPlaylistModalMsg {
+    PathTextUpdate(String),
+    ToggleRelativePaths(bool),
+    ToggleCurrentOrder(bool),
+    // Make sure the destination has a supported extension, and if it does, export
+    CheckSubmit,
+    // The export failed. Given: the error
+    ExportFailed(String),
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportPlaylistModal {
+    playlist_id: Id,
+    path_text: String,
+    relative_paths: bool,
+    current_order: bool,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for ExportPlaylistModal {
+    type ModalMsg = ExportPlaylistModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Export Playlist", theme, true, true);
+        let description = secondary_text(
+            "Writes the downloaded tracks to a playlist file. The format (m3u8, xspf or pls) comes from the file extension.",
+            theme,
+            true,
+            true,
+        );
+        let path_box = default_text_input("Playlist file path", &self.path_text, theme)
+            .on_input(|s| Local(ExportPlaylistModalMsg::PathTextUpdate(s)))
+            .on_paste(|s| Local(ExportPlaylistModalMsg::PathTextUpdate(s)))
+            .on_submit(Local(ExportPlaylistModalMsg::CheckSubmit));
+        let mut path_data = column![path_box].spacing(4.0);
+        if let Some(et) = &self.error {
+            path_data = path_data.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            path_data = path_data.push(error_text("", theme, true, true))
+        }
+        let relative_box = checkbox(self.relative_paths)
+            .label("Use paths relative to the playlist file")
+            .on_toggle(|b| Local(ExportPlaylistModalMsg::ToggleRelativePaths(b)));
+        let current_order_box = checkbox(self.current_order)
+            .label("Use the current (shuffled) order")
+            .on_toggle(|b| Local(ExportPlaylistModalMsg::ToggleCurrentOrder(b)));
+
+        let export = default_text_button("Export", theme)
+            .on_press(Local(ExportPlaylistModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, export].spacing(10);
+        container(
+            column![
+                title,
+                description,
+                path_data,
+                relative_box,
+                current_order_box,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            ExportPlaylistModalMsg::PathTextUpdate(s) => {
+                self.path_text = s;
+                Task::none()
+            }
+            ExportPlaylistModalMsg::ToggleRelativePaths(b) => {
+                self.relative_paths = b;
+                Task::none()
+            }
+            ExportPlaylistModalMsg::ToggleCurrentOrder(b) => {
+                self.current_order = b;
+                Task::none()
+            }
+            ExportPlaylistModalMsg::CheckSubmit => {
+                let destination = PathBuf::from(self.path_text.trim());
+                let Some(format) = PlaylistFileFormat::from_path(&destination) else {
+                    return Task::done(Local(ExportPlaylistModalMsg::ExportFailed(String::from(
+                        "The file needs a m3u8, m3u, xspf or pls extension",
+                    ))));
+                };
+                if !destination.parent().is_some_and(|p| p.is_dir()) {
+                    return Task::done(Local(ExportPlaylistModalMsg::ExportFailed(String::from(
+                        "Folder does not exist",
+                    ))));
+                }
+                let path_style = if self.relative_paths {
+                    PlaylistPathStyle::Relative
+                } else {
+                    PlaylistPathStyle::Absolute
+                };
+                // the modal is closed once the export succeeds
+                Task::done(Global(Message::ExportPlaylistSubmit {
+                    playlist_id: self.playlist_id.clone(),
+                    destination,
+                    format,
+                    path_style,
+                    current_order: self.current_order,
+                }))
+            }
+            ExportPlaylistModalMsg::ExportFailed(e) => {
+                self.error = Some(e);
+                remove_error_after_delay(
+                    &mut self.error_timer_handle,
+                    ExportPlaylistModalMsg::RemoveError,
+                )
+            }
+            ExportPlaylistModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<ExportPlaylistModal> for Modal {
+    fn from(modal: ExportPlaylistModal) -> Self {
+        Modal::ExportPlaylist(modal)
+    }
+}
+impl ExportPlaylistModal {
+    pub fn new(playlist_id: Id) -> Self {
+        Self {
+            playlist_id,
+            path_text: String::new(),
+            relative_paths: false,
+            current_order: false,
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+}
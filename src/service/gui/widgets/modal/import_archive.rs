@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    playlist::enums::ImportTransferMode,
+};
+
+#[derive(Debug, Clone)]
+pub enum ImportArchiveModalMsg {
+    ArchiveTextUpdate(String),
+    MediaDirTextUpdate(String),
+    ToggleHardLink(bool),
+    PathError(String),
+    RemoveError,
+    // Make sure the archive (and media folder if given) exist, and if they do, close the modal
+    CheckSubmit,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportArchiveModal {
+    archive_text: String,
+    media_dir_text: String,
+    hard_link: bool,
+    path_error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for ImportArchiveModal {
+    type ModalMsg = ImportArchiveModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Import yt-dlp Archive", theme, true, true);
+        let description = secondary_text(
+            "Imports the downloads listed in a --download-archive file so they won't be downloaded again.",
+            theme,
+            true,
+            true,
+        );
+        let archive_box =
+            default_text_input("Download archive file path", &self.archive_text, theme)
+                .on_input(|s| Local(ImportArchiveModalMsg::ArchiveTextUpdate(s)))
+                .on_paste(|s| Local(ImportArchiveModalMsg::ArchiveTextUpdate(s)))
+                .on_submit(Local(ImportArchiveModalMsg::CheckSubmit));
+        let media_dir_box = default_text_input(
+            "Media folder path (defaults to the archive's folder)",
+            &self.media_dir_text,
+            theme,
+        )
+        .on_input(|s| Local(ImportArchiveModalMsg::MediaDirTextUpdate(s)))
+        .on_paste(|s| Local(ImportArchiveModalMsg::MediaDirTextUpdate(s)))
+        .on_submit(Local(ImportArchiveModalMsg::CheckSubmit));
+        let mut path_data = column![archive_box, media_dir_box].spacing(4.0);
+        if let Some(et) = &self.path_error {
+            path_data = path_data.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            path_data = path_data.push(error_text("", theme, true, true))
+        }
+        let hard_link_box = checkbox(self.hard_link)
+            .label("Hard link files instead of copying")
+            .on_toggle(|b| Local(ImportArchiveModalMsg::ToggleHardLink(b)));
+
+        let import = default_text_button("Import", theme)
+            .on_press(Local(ImportArchiveModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, import].spacing(10);
+        container(column![title, description, path_data, hard_link_box, buttons_row].spacing(10.0))
+            .width(Length::Fixed(400.0))
+            .padding(Padding::new(20.0))
+            .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            ImportArchiveModalMsg::ArchiveTextUpdate(s) => {
+                self.archive_text = s;
+                Task::none()
+            }
+            ImportArchiveModalMsg::MediaDirTextUpdate(s) => {
+                self.media_dir_text = s;
+                Task::none()
+            }
+            ImportArchiveModalMsg::ToggleHardLink(b) => {
+                self.hard_link = b;
+                Task::none()
+            }
+            ImportArchiveModalMsg::CheckSubmit => {
+                let archive_path = PathBuf::from(self.archive_text.trim());
+                if !archive_path.is_file() {
+                    return Task::done(Local(ImportArchiveModalMsg::PathError(String::from(
+                        "Archive file does not exist",
+                    ))));
+                }
+                let media_dir = match self.media_dir_text.trim() {
+                    "" => None,
+                    s => Some(PathBuf::from(s)),
+                };
+                if media_dir.as_ref().is_some_and(|d| !d.is_dir()) {
+                    return Task::done(Local(ImportArchiveModalMsg::PathError(String::from(
+                        "Media folder does not exist",
+                    ))));
+                }
+                let transfer_mode = if self.hard_link {
+                    ImportTransferMode::HardLink
+                } else {
+                    ImportTransferMode::Copy
+                };
+                Task::batch(vec![
+                    Task::done(Global(Message::YtDlpArchiveImportSubmit {
+                        archive_path,
+                        media_dir,
+                        transfer_mode,
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            ImportArchiveModalMsg::PathError(e) => {
+                self.path_error = Some(e);
+                remove_error_after_delay(
+                    &mut self.error_timer_handle,
+                    ImportArchiveModalMsg::RemoveError,
+                )
+            }
+            ImportArchiveModalMsg::RemoveError => {
+                self.path_error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<ImportArchiveModal> for Modal {
+    fn from(modal: ImportArchiveModal) -> Self {
+        Modal::ImportArchive(modal)
+    }
+}
+impl ImportArchiveModal {
+    pub fn new() -> Self {
+        Self {
+            archive_text: String::new(),
+            media_dir_text: String::new(),
+            hard_link: false,
+            path_error: None,
+            error_timer_handle: None,
+        }
+    }
+}
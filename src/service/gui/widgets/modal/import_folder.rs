@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{error_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    playlist::{
+        enums::{ImportGrouping, ImportTransferMode},
+        structs::LocalImportOptions,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum ImportFolderModalMsg {
+    PathTextUpdate(String),
+    ToggleGroupByAlbum(bool),
+    ToggleHardLink(bool),
+    PathError(String),
+    RemoveError,
+    // Make sure the folder exists, and if it does, close the modal. Given: whether this is a dry run
+    CheckSubmitPath { dry_run: bool },
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportFolderModal {
+    path_text: String,
+    group_by_album: bool,
+    hard_link: bool,
+    path_error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for ImportFolderModal {
+    type ModalMsg = ImportFolderModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Import Folder", theme, true, true);
+        let path_box = default_text_input("Music folder path", &self.path_text, theme)
+            .on_input(|s| Local(ImportFolderModalMsg::PathTextUpdate(s)))
+            .on_paste(|s| Local(ImportFolderModalMsg::PathTextUpdate(s)))
+            .on_submit(Local(ImportFolderModalMsg::CheckSubmitPath {
+                dry_run: true,
+            }));
+        let mut path_data = column![path_box];
+        if let Some(et) = &self.path_error {
+            path_data = path_data.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            path_data = path_data.push(error_text("", theme, true, true))
+        }
+        let group_by_album_box = checkbox(self.group_by_album)
+            .label("Group by album tag instead of folder")
+            .on_toggle(|b| Local(ImportFolderModalMsg::ToggleGroupByAlbum(b)));
+        let hard_link_box = checkbox(self.hard_link)
+            .label("Hard link files instead of copying")
+            .on_toggle(|b| Local(ImportFolderModalMsg::ToggleHardLink(b)));
+
+        let preview = secondary_text_button("Preview", theme).on_press(Local(
+            ImportFolderModalMsg::CheckSubmitPath { dry_run: true },
+        ));
+        let import = default_text_button("Import", theme).on_press(Local(
+            ImportFolderModalMsg::CheckSubmitPath { dry_run: false },
+        ));
+        let archive =
+            secondary_text_button("yt-dlp Archive", theme).on_press(Global(Message::ImportArchive));
+        let playlist_file = secondary_text_button("Playlist File", theme)
+            .on_press(Global(Message::ImportPlaylistFile));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![
+            archive,
+            playlist_file,
+            space().width(Length::Fill),
+            cancel,
+            preview,
+            import
+        ]
+        .spacing(10);
+        container(
+            column![
+                title,
+                path_data,
+                group_by_album_box,
+                hard_link_box,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            ImportFolderModalMsg::PathTextUpdate(s) => {
+                self.path_text = s;
+                Task::none()
+            }
+            ImportFolderModalMsg::ToggleGroupByAlbum(b) => {
+                self.group_by_album = b;
+                Task::none()
+            }
+            ImportFolderModalMsg::ToggleHardLink(b) => {
+                self.hard_link = b;
+                Task::none()
+            }
+            ImportFolderModalMsg::CheckSubmitPath { dry_run } => {
+                let path = PathBuf::from(self.path_text.trim());
+                if !path.is_dir() {
+                    return Task::done(Local(ImportFolderModalMsg::PathError(String::from(
+                        "Folder does not exist",
+                    ))));
+                }
+                Task::batch(vec![
+                    Task::done(Global(Message::LocalImportSubmit {
+                        path,
+                        options: self.options(dry_run),
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            ImportFolderModalMsg::PathError(e) => {
+                self.path_error = Some(e);
+                remove_error_after_delay(
+                    &mut self.error_timer_handle,
+                    ImportFolderModalMsg::RemoveError,
+                )
+            }
+            ImportFolderModalMsg::RemoveError => {
+                self.path_error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<ImportFolderModal> for Modal {
+    fn from(modal: ImportFolderModal) -> Self {
+        Modal::ImportFolder(modal)
+    }
+}
+impl ImportFolderModal {
+    pub fn new() -> Self {
+        Self {
+            path_text: String::new(),
+            group_by_album: false,
+            hard_link: false,
+            path_error: None,
+            error_timer_handle: None,
+        }
+    }
+    fn options(&self, dry_run: bool) -> LocalImportOptions {
+        LocalImportOptions {
+            grouping: if self.group_by_album {
+                ImportGrouping::AlbumTag
+            } else {
+                ImportGrouping::Folder
+            },
+            transfer_mode: if self.hard_link {
+                ImportTransferMode::HardLink
+            } else {
+                ImportTransferMode::Copy
+            },
+            dry_run,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    playlist::enums::{ImportTransferMode, PlaylistFileFormat},
+};
+
+#[derive(Debug, Clone)]
+pub enum ImportPlaylistFileModalMsg {
+    PathTextUpdate(String),
+    ToggleHardLink(bool),
+    PathError(String),
+    RemoveError,
+    // Make sure the playlist file exists and is a supported format, and if it is, close the modal
+    CheckSubmit,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportPlaylistFileModal {
+    path_text: String,
+    hard_link: bool,
+    path_error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for ImportPlaylistFileModal {
+    type ModalMsg = ImportPlaylistFileModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Import Playlist File", theme, true, true);
+        let description = secondary_text(
+            "Imports a m3u8, xspf or pls playlist. Files that aren't in the library are imported as local tracks.",
+            theme,
+            true,
+            true,
+        );
+        let path_box = default_text_input("Playlist file path", &self.path_text, theme)
+            .on_input(|s| Local(ImportPlaylistFileModalMsg::PathTextUpdate(s)))
+            .on_paste(|s| Local(ImportPlaylistFileModalMsg::PathTextUpdate(s)))
+            .on_submit(Local(ImportPlaylistFileModalMsg::CheckSubmit));
+        let mut path_data = column![path_box].spacing(4.0);
+        if let Some(et) = &self.path_error {
+            path_data = path_data.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            path_data = path_data.push(error_text("", theme, true, true))
+        }
+        let hard_link_box = checkbox(self.hard_link)
+            .label("Hard link files instead of copying")
+            .on_toggle(|b| Local(ImportPlaylistFileModalMsg::ToggleHardLink(b)));
+
+        let import = default_text_button("Import", theme)
+            .on_press(Local(ImportPlaylistFileModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, import].spacing(10);
+        container(column![title, description, path_data, hard_link_box, buttons_row].spacing(10.0))
+            .width(Length::Fixed(400.0))
+            .padding(Padding::new(20.0))
+            .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            ImportPlaylistFileModalMsg::PathTextUpdate(s) => {
+                self.path_text = s;
+                Task::none()
+            }
+            ImportPlaylistFileModalMsg::ToggleHardLink(b) => {
+                self.hard_link = b;
+                Task::none()
+            }
+            ImportPlaylistFileModalMsg::CheckSubmit => {
+                let path = PathBuf::from(self.path_text.trim());
+                if !path.is_file() {
+                    return Task::done(Local(ImportPlaylistFileModalMsg::PathError(String::from(
+                        "Playlist file does not exist",
+                    ))));
+                }
+                if PlaylistFileFormat::from_path(&path).is_none() {
+                    return Task::done(Local(ImportPlaylistFileModalMsg::PathError(String::from(
+                        "Only m3u8, m3u, xspf and pls files are supported",
+                    ))));
+                }
+                let transfer_mode = if self.hard_link {
+                    ImportTransferMode::HardLink
+                } else {
+                    ImportTransferMode::Copy
+                };
+                Task::batch(vec![
+                    Task::done(Global(Message::PlaylistFileImportSubmit {
+                        path,
+                        transfer_mode,
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            ImportPlaylistFileModalMsg::PathError(e) => {
+                self.path_error = Some(e);
+                remove_error_after_delay(
+                    &mut self.error_timer_handle,
+                    ImportPlaylistFileModalMsg::RemoveError,
+                )
+            }
+            ImportPlaylistFileModalMsg::RemoveError => {
+                self.path_error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<ImportPlaylistFileModal> for Modal {
+    fn from(modal: ImportPlaylistFileModal) -> Self {
+        Modal::ImportPlaylistFile(modal)
+    }
+}
+impl ImportPlaylistFileModal {
+    pub fn new() -> Self {
+        Self {
+            path_text: String::new(),
+            hard_link: false,
+            path_error: None,
+            error_timer_handle: None,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use iced::{
+    Element, Length, Padding, Task,
+    widget::{container, row, scrollable, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        util::format_long_duration,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column,
+            },
+            text::{default_text, secondary_text, title_text},
+        },
+    },
+    playlist::structs::{LocalImportOptions, LocalImportPreview},
+};
+
+// playlists list height inside the modal
+const PREVIEW_LIST_HEIGHT: f32 = 200.0;
+
+#[derive(Debug, Clone)]
+pub enum ImportPreviewModalMsg {
+    Confirm,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportPreviewModal {
+    preview: LocalImportPreview,
+}
+impl AbstractModal<Message> for ImportPreviewModal {
+    type ModalMsg = ImportPreviewModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Import Preview", theme, true, true);
+        let track_count: u64 = self.preview.playlists.iter().map(|p| p.track_count).sum();
+        let description = default_text(
+            format!(
+                "{} track(s) in {} playlist(s) from {}",
+                track_count,
+                self.preview.playlists.len(),
+                self.preview.root.display()
+            ),
+            theme,
+            true,
+            true,
+        );
+        let skipped = secondary_text(
+            format!(
+                "{} file(s) couldn't be read and will be skipped.",
+                self.preview.skipped_files
+            ),
+            theme,
+            true,
+            true,
+        );
+        let playlists = scrollable(
+            column(self.preview.playlists.iter().map(|p| {
+                row![
+                    default_text(p.title.clone(), theme, true, true).width(Length::Fill),
+                    secondary_text(
+                        format!(
+                            "{} tracks, {}",
+                            p.track_count,
+                            format_long_duration(&p.length)
+                        ),
+                        theme,
+                        true,
+                        true
+                    )
+                ]
+                .into()
+            }))
+            .spacing(4.0),
+        )
+        .height(Length::Fixed(PREVIEW_LIST_HEIGHT));
+
+        let confirm =
+            default_text_button("Import", theme).on_press(Local(ImportPreviewModalMsg::Confirm));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, confirm].spacing(10);
+        container(column![title, description, skipped, playlists, buttons_row].spacing(10.0))
+            .width(Length::Fixed(400.0))
+            .padding(Padding::new(20.0))
+            .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            ImportPreviewModalMsg::Confirm => Task::batch(vec![
+                Task::done(Global(Message::LocalImportSubmit {
+                    path: self.preview.root.clone(),
+                    options: LocalImportOptions {
+                        dry_run: false,
+                        ..self.preview.options
+                    },
+                })),
+                Task::done(Global(Message::HideModal)),
+            ]),
+        }
+    }
+}
+impl From<ImportPreviewModal> for Modal {
+    fn from(modal: ImportPreviewModal) -> Self {
+        Modal::ImportPreview(modal)
+    }
+}
+impl ImportPreviewModal {
+    pub fn new(preview: LocalImportPreview) -> Self {
+        Self { preview }
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{container, row, scrollable, space},
+};
+
+use crate::service::{
+    file::library::Library,
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{default_text, error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+};
+
+// libraries list height inside the modal
+const LIBRARY_LIST_HEIGHT: f32 = 160.0;
+
+#[derive(Debug, Clone)]
+pub enum LibrariesModalMsg {
+    NameTextUpdate(String),
+    PathTextUpdate(String),
+    // Make sure a name and path were given, and if they were, add the library
+    CheckSubmit,
+    // Switching to or adding a library failed. Given: the error
+    ActionFailed(String),
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct LibrariesModal {
+    libraries: Vec<Library>,
+    active: Library,
+    name_text: String,
+    path_text: String,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for LibrariesModal {
+    type ModalMsg = LibrariesModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Libraries", theme, true, true);
+        let current = secondary_text(
+            format!(
+                "Open: {} ({})",
+                self.active.name(),
+                self.active.root().display()
+            ),
+            theme,
+            true,
+            true,
+        );
+        let libraries = scrollable(
+            column(self.libraries.iter().map(|library| {
+                let mut switch = secondary_text_button("Open", theme);
+                if *library != self.active {
+                    switch =
+                        switch.on_press(Global(Message::SwitchLibrary(library.name().to_string())));
+                }
+                row![
+                    column![
+                        default_text(library.name().to_string(), theme, true, true),
+                        secondary_text(library.root().display().to_string(), theme, true, true),
+                    ]
+                    .width(Length::Fill),
+                    switch
+                ]
+                .spacing(10)
+                .into()
+            }))
+            .spacing(4.0),
+        )
+        .height(Length::Fixed(LIBRARY_LIST_HEIGHT));
+
+        let name_box = default_text_input("New library name", &self.name_text, theme)
+            .on_input(|s| Local(LibrariesModalMsg::NameTextUpdate(s)))
+            .on_paste(|s| Local(LibrariesModalMsg::NameTextUpdate(s)))
+            .on_submit(Local(LibrariesModalMsg::CheckSubmit));
+        let path_box = default_text_input("New library folder path", &self.path_text, theme)
+            .on_input(|s| Local(LibrariesModalMsg::PathTextUpdate(s)))
+            .on_paste(|s| Local(LibrariesModalMsg::PathTextUpdate(s)))
+            .on_submit(Local(LibrariesModalMsg::CheckSubmit));
+        let mut new_library = column![name_box, path_box].spacing(4.0);
+        if let Some(et) = &self.error {
+            new_library = new_library.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            new_library = new_library.push(error_text("", theme, true, true))
+        }
+
+        let add = default_text_button("Add", theme).on_press(Local(LibrariesModalMsg::CheckSubmit));
+        let close = secondary_text_button("Close", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), close, add].spacing(10);
+        container(column![title, current, libraries, new_library, buttons_row].spacing(10.0))
+            .width(Length::Fixed(400.0))
+            .padding(Padding::new(20.0))
+            .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            LibrariesModalMsg::NameTextUpdate(s) => {
+                self.name_text = s;
+                Task::none()
+            }
+            LibrariesModalMsg::PathTextUpdate(s) => {
+                self.path_text = s;
+                Task::none()
+            }
+            LibrariesModalMsg::CheckSubmit => {
+                let name = self.name_text.trim();
+                let path = self.path_text.trim();
+                if name.is_empty() || path.is_empty() {
+                    return Task::done(Local(LibrariesModalMsg::ActionFailed(String::from(
+                        "Both a name and a folder are needed",
+                    ))));
+                }
+                Task::done(Global(Message::AddLibrary {
+                    name: name.to_string(),
+                    path: PathBuf::from(path),
+                }))
+            }
+            LibrariesModalMsg::ActionFailed(e) => {
+                self.error = Some(e);
+                remove_error_after_delay(
+                    &mut self.error_timer_handle,
+                    LibrariesModalMsg::RemoveError,
+                )
+            }
+            LibrariesModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<LibrariesModal> for Modal {
+    fn from(modal: LibrariesModal) -> Self {
+        Modal::Libraries(modal)
+    }
+}
+impl LibrariesModal {
+    pub fn new(libraries: Vec<Library>, active: Library) -> Self {
+        Self {
+            libraries,
+            active,
+            name_text: String::new(),
+            path_text: String::new(),
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use iced::{
+    Element, Length, Padding,
+    widget::{container, row, scrollable, space},
+};
+
+use crate::service::{
+    file::structs::LoadReport,
+    gui::{
+        enums::Message,
+        widgets::{
+            button::default_text_button,
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global},
+                Modal, column,
+            },
+            text::{default_text, error_text, secondary_text, title_text},
+        },
+    },
+};
+
+// problems list height inside the modal
+const PROBLEM_LIST_HEIGHT: f32 = 200.0;
+
+#[derive(Debug, Clone)]
+pub enum LoadReportModalMsg {}
+
+#[derive(Debug, Clone)]
+pub struct LoadReportModal {
+    report: LoadReport,
+    // the library's own data, or things left out of an import
+    from_import: bool,
+}
+impl AbstractModal<Message> for LoadReportModal {
+    type ModalMsg = LoadReportModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let count = self.report.problems.len();
+        let (title, description) = if self.from_import {
+            (
+                "Skipped Imports",
+                format!("{count} item(s) weren't imported."),
+            )
+        } else {
+            (
+                "Library Problems",
+                format!("{count} item(s) in the library couldn't be loaded and were moved aside."),
+            )
+        };
+        let title = title_text(title, theme, true, true);
+        let description = default_text(description, theme, true, true);
+        let problems = scrollable(
+            column(self.report.problems.iter().map(|p| {
+                let mut problem = column![
+                    default_text(p.source.clone(), theme, true, true),
+                    error_text(p.reason.clone(), theme, true, true),
+                ]
+                .spacing(2.0);
+                // nothing is moved when importing
+                if !self.from_import {
+                    let moved_to = match &p.quarantined_to {
+                        Some(path) => format!("Moved to {}", path.display()),
+                        None => String::from("Could not be moved"),
+                    };
+                    problem = problem.push(secondary_text(moved_to, theme, true, true));
+                }
+                problem.into()
+            }))
+            .spacing(8.0),
+        )
+        .height(Length::Fixed(PROBLEM_LIST_HEIGHT));
+
+        let ok = default_text_button("OK", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), ok].spacing(10);
+        container(column![title, description, problems, buttons_row].spacing(10.0))
+            .width(Length::Fixed(400.0))
+            .padding(Padding::new(20.0))
+            .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {}
+    }
+}
+impl From<LoadReportModal> for Modal {
+    fn from(modal: LoadReportModal) -> Self {
+        Modal::LoadReport(modal)
+    }
+}
+impl LoadReportModal {
+    pub fn new(report: LoadReport) -> Self {
+        Self {
+            report,
+            from_import: false,
+        }
+    }
+    pub fn skipped_imports(report: LoadReport) -> Self {
+        Self {
+            report,
+            from_import: true,
+        }
+    }
+}
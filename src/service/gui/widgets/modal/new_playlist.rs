@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use iced::{
     Element, Length, Padding, Task,
     task::Handle,
@@ -14,7 +12,7 @@ use crate::service::gui::{
         modal::{
             AbstractModal,
             AbstractModalMessage::{self, Global, Local},
-            Modal, column,
+            Modal, column, remove_error_after_delay,
         },
         text::{error_text, title_text},
         text_input::default_text_input,
@@ -107,21 +105,10 @@ impl AbstractModal<Message> for NewPlaylistModal {
             }
             NewPlaylistModalMsg::PlaylistURLError(e) => {
                 self.url_error = Some(e);
-                // if there was previously a timer remove it
-                if let Some(h) = &mut self.error_timer_handle {
-                    h.abort();
-                }
-                // start delay to remove error
-                let (t, h) = Task::perform(
-                    async {
-                        tokio::time::sleep(Duration::from_secs(3)).await;
-                        NewPlaylistModalMsg::RemoveError
-                    },
-                    AbstractModalMessage::Local,
+                remove_error_after_delay(
+                    &mut self.error_timer_handle,
+                    NewPlaylistModalMsg::RemoveError,
                 )
-                .abortable();
-                self.error_timer_handle = Some(h);
-                t
             }
             NewPlaylistModalMsg::RemoveError => {
                 self.url_error = None;
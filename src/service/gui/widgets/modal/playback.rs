@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    audio::{
+        enums::{FadeCurve, NormalizationMode},
+        structs::TrackTransition,
+    },
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+};
+
+// longest crossfade allowed, in seconds
+const MAX_CROSSFADE_SECS: f64 = 12.0;
+
+#[derive(Debug, Clone)]
+pub enum PlaybackModalMsg {
+    ToggleCrossfade(bool),
+    CrossfadeTextUpdate(String),
+    SelectCurve(FadeCurve),
+    SelectNormalization(NormalizationMode),
+    ToggleSplitChapters(bool),
+    ToggleTrimSilence(bool),
+    CheckSubmit,
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlaybackModal {
+    crossfade: bool,
+    // in seconds
+    crossfade_text: String,
+    curve: FadeCurve,
+    normalization: NormalizationMode,
+    split_chapters: bool,
+    trim_silence: bool,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for PlaybackModal {
+    type ModalMsg = PlaybackModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Playback", theme, true, true);
+        let description = secondary_text(
+            "Tracks play back to back without gaps. A crossfade blends the end of each track into the start of the next.",
+            theme,
+            true,
+            true,
+        );
+        let crossfade_box = checkbox(self.crossfade)
+            .label("Crossfade between tracks")
+            .on_toggle(|b| Local(PlaybackModalMsg::ToggleCrossfade(b)));
+        let mut crossfade_data = column![crossfade_box].spacing(10.0);
+        if self.crossfade {
+            let seconds_box =
+                default_text_input("Crossfade length (seconds)", &self.crossfade_text, theme)
+                    .on_input(|s| Local(PlaybackModalMsg::CrossfadeTextUpdate(s)))
+                    .on_paste(|s| Local(PlaybackModalMsg::CrossfadeTextUpdate(s)))
+                    .on_submit(Local(PlaybackModalMsg::CheckSubmit));
+            let curves = row(FadeCurve::ALL.into_iter().map(|curve| {
+                checkbox(self.curve == curve)
+                    .label(curve.label())
+                    .on_toggle(move |_| Local(PlaybackModalMsg::SelectCurve(curve)))
+                    .into()
+            }))
+            .spacing(10);
+            crossfade_data = crossfade_data.push(seconds_box).push(curves);
+        }
+        let normalization_label = secondary_text(
+            "Even out the volume between tracks (measured after they download):",
+            theme,
+            true,
+            true,
+        );
+        let normalization_modes = row(NormalizationMode::ALL.into_iter().map(|mode| {
+            checkbox(self.normalization == mode)
+                .label(mode.label())
+                .on_toggle(move |_| Local(PlaybackModalMsg::SelectNormalization(mode)))
+                .into()
+        }))
+        .spacing(10);
+        let split_chapters_box = checkbox(self.split_chapters)
+            .label("Play chapters as separate tracks")
+            .on_toggle(|b| Local(PlaybackModalMsg::ToggleSplitChapters(b)));
+        let trim_silence_box = checkbox(self.trim_silence)
+            .label("Trim the silence off of new downloads")
+            .on_toggle(|b| Local(PlaybackModalMsg::ToggleTrimSilence(b)));
+        let error = match &self.error {
+            Some(et) => error_text(format!("Error: {}", et), theme, true, true),
+            None => error_text("", theme, true, true),
+        };
+
+        let save =
+            default_text_button("Save", theme).on_press(Local(PlaybackModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, save].spacing(10);
+        container(
+            column![
+                title,
+                description,
+                crossfade_data,
+                normalization_label,
+                normalization_modes,
+                split_chapters_box,
+                trim_silence_box,
+                error,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            PlaybackModalMsg::ToggleCrossfade(b) => {
+                self.crossfade = b;
+                Task::none()
+            }
+            PlaybackModalMsg::CrossfadeTextUpdate(s) => {
+                self.crossfade_text = s;
+                Task::none()
+            }
+            PlaybackModalMsg::SelectCurve(curve) => {
+                self.curve = curve;
+                Task::none()
+            }
+            PlaybackModalMsg::SelectNormalization(mode) => {
+                self.normalization = mode;
+                Task::none()
+            }
+            PlaybackModalMsg::ToggleSplitChapters(b) => {
+                self.split_chapters = b;
+                Task::none()
+            }
+            PlaybackModalMsg::ToggleTrimSilence(b) => {
+                self.trim_silence = b;
+                Task::none()
+            }
+            PlaybackModalMsg::CheckSubmit => {
+                let crossfade = if self.crossfade {
+                    match self.crossfade_text.trim().parse::<f64>() {
+                        Ok(secs) if secs > 0.0 && secs <= MAX_CROSSFADE_SECS => {
+                            Some(Duration::from_secs_f64(secs))
+                        }
+                        _ => {
+                            return self.show_error(&format!(
+                                "The crossfade must be between 0 and {} seconds",
+                                MAX_CROSSFADE_SECS
+                            ));
+                        }
+                    }
+                } else {
+                    None
+                };
+                Task::batch(vec![
+                    Task::done(Global(Message::PlaybackSettingsSubmit {
+                        transition: TrackTransition {
+                            crossfade,
+                            curve: self.curve,
+                        },
+                        normalization: self.normalization,
+                        split_chapters: self.split_chapters,
+                        trim_silence: self.trim_silence,
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            PlaybackModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<PlaybackModal> for Modal {
+    fn from(modal: PlaybackModal) -> Self {
+        Modal::Playback(modal)
+    }
+}
+impl PlaybackModal {
+    pub fn new(
+        transition: TrackTransition,
+        normalization: NormalizationMode,
+        split_chapters: bool,
+        trim_silence: bool,
+    ) -> Self {
+        Self {
+            crossfade: transition.crossfade.is_some(),
+            crossfade_text: transition
+                .crossfade
+                .map(|d| d.as_secs_f64().to_string())
+                .unwrap_or(String::from("3")),
+            curve: transition.curve,
+            normalization,
+            split_chapters,
+            trim_silence,
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+    fn show_error(&mut self, e: &str) -> Task<AbstractModalMessage<PlaybackModalMsg, Message>> {
+        self.error = Some(String::from(e));
+        remove_error_after_delay(&mut self.error_timer_handle, PlaybackModalMsg::RemoveError)
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::NaiveTime;
+use iced::{
+    Alignment, Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::{Message, SleepTimerMode},
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{default_text, error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    id::structs::Id,
+};
+
+#[derive(Debug, Clone)]
+pub enum SleepTimerModalMsg {
+    SelectMode(SleepTimerMode, bool),
+    ToggleAlarm(bool),
+    AlarmTextUpdate(String),
+    CheckSubmit,
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct SleepTimerModal {
+    // the playlist whose page the modal was opened from
+    playlist_id: Id,
+    mode: Option<SleepTimerMode>,
+    alarm_enabled: bool,
+    alarm_text: String,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for SleepTimerModal {
+    type ModalMsg = SleepTimerModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Sleep Timer", theme, true, true);
+        let description = secondary_text(
+            "Fades out and pauses this playlist. Paused time doesn't count.",
+            theme,
+            true,
+            true,
+        );
+        let modes = column(SleepTimerMode::ALL.into_iter().map(|mode| {
+            checkbox(self.mode == Some(mode))
+                .label(mode.label())
+                .on_toggle(move |b| Local(SleepTimerModalMsg::SelectMode(mode, b)))
+                .into()
+        }))
+        .spacing(6.0);
+
+        let alarm_toggle = checkbox(self.alarm_enabled)
+            .label("Start this playlist at")
+            .on_toggle(|b| Local(SleepTimerModalMsg::ToggleAlarm(b)));
+        let mut alarm_row = row![alarm_toggle].spacing(10).align_y(Alignment::Center);
+        if self.alarm_enabled {
+            alarm_row = alarm_row.push(
+                default_text_input("HH:MM", &self.alarm_text, theme)
+                    .on_input(|s| Local(SleepTimerModalMsg::AlarmTextUpdate(s)))
+                    .on_paste(|s| Local(SleepTimerModalMsg::AlarmTextUpdate(s)))
+                    .on_submit(Local(SleepTimerModalMsg::CheckSubmit))
+                    .width(Length::Fixed(90.0)),
+            );
+        }
+        let alarm = column![default_text("Alarm", theme, false, true), alarm_row].spacing(6.0);
+
+        let error = match &self.error {
+            Some(et) => error_text(format!("Error: {}", et), theme, true, true),
+            None => error_text("", theme, true, true),
+        };
+        let save =
+            default_text_button("Save", theme).on_press(Local(SleepTimerModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, save].spacing(10);
+        container(column![title, description, modes, alarm, error, buttons_row].spacing(10.0))
+            .width(Length::Fixed(420.0))
+            .padding(Padding::new(20.0))
+            .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            SleepTimerModalMsg::SelectMode(mode, b) => {
+                // unticking the chosen option turns the timer off
+                self.mode = b.then_some(mode);
+                Task::none()
+            }
+            SleepTimerModalMsg::ToggleAlarm(b) => {
+                self.alarm_enabled = b;
+                Task::none()
+            }
+            SleepTimerModalMsg::AlarmTextUpdate(s) => {
+                self.alarm_text = s;
+                Task::none()
+            }
+            SleepTimerModalMsg::CheckSubmit => {
+                let alarm = if self.alarm_enabled {
+                    match NaiveTime::parse_from_str(self.alarm_text.trim(), "%H:%M") {
+                        Ok(time) => Some(time),
+                        Err(_) => return self.show_error("Enter the alarm time as HH:MM"),
+                    }
+                } else {
+                    None
+                };
+                Task::batch([
+                    Task::done(Global(Message::SleepTimerSubmit {
+                        playlist_id: self.playlist_id.clone(),
+                        mode: self.mode,
+                        alarm,
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            SleepTimerModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<SleepTimerModal> for Modal {
+    fn from(modal: SleepTimerModal) -> Self {
+        Modal::SleepTimer(modal)
+    }
+}
+impl SleepTimerModal {
+    pub fn new(playlist_id: Id, mode: Option<SleepTimerMode>, alarm: Option<NaiveTime>) -> Self {
+        Self {
+            playlist_id,
+            mode,
+            alarm_enabled: alarm.is_some(),
+            alarm_text: alarm
+                .map(|time| time.format("%H:%M").to_string())
+                .unwrap_or_default(),
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+    fn show_error(&mut self, e: &str) -> Task<AbstractModalMessage<SleepTimerModalMsg, Message>> {
+        self.error = Some(String::from(e));
+        remove_error_after_delay(
+            &mut self.error_timer_handle,
+            SleepTimerModalMsg::RemoveError,
+        )
+    }
+}
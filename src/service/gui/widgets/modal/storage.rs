@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, scrollable, space},
+};
+
+use crate::service::{
+    file::{enums::SizeUnit, structs::DataSize},
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{default_text, error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    id::structs::Id,
+    playlist::structs::{StorageSettings, StorageUsage},
+};
+
+// playlists list height inside the modal
+const PLAYLIST_LIST_HEIGHT: f32 = 200.0;
+
+#[derive(Debug, Clone)]
+pub enum StorageModalMsg {
+    QuotaTextUpdate(String),
+    TogglePinned(Id, bool),
+    CheckSubmit,
+    // The settings couldn't be saved
+    SubmitFailed(String),
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageModal {
+    // (id, title, size) of every playlist
+    playlists: Vec<(Id, String, DataSize)>,
+    total: DataSize,
+    pinned: HashSet<Id>,
+    // in gigabytes; empty means no quota
+    quota_text: String,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for StorageModal {
+    type ModalMsg = StorageModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Storage", theme, true, true);
+        let usage = default_text(
+            format!("The library's audio takes up {}", self.total),
+            theme,
+            true,
+            true,
+        );
+        let description = secondary_text(
+            "When the library goes over the quota, the audio of the least recently played tracks is deleted. Their info is kept, so they can be downloaded again. Tracks in pinned playlists are never deleted.",
+            theme,
+            true,
+            true,
+        );
+        let quota_box = default_text_input("Quota in GB (empty for none)", &self.quota_text, theme)
+            .on_input(|s| Local(StorageModalMsg::QuotaTextUpdate(s)))
+            .on_paste(|s| Local(StorageModalMsg::QuotaTextUpdate(s)))
+            .on_submit(Local(StorageModalMsg::CheckSubmit));
+        let mut quota_data = column![quota_box].spacing(4.0);
+        if let Some(et) = &self.error {
+            quota_data = quota_data.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            quota_data = quota_data.push(error_text("", theme, true, true))
+        }
+        let playlists = scrollable(
+            column(self.playlists.iter().map(|(id, title, size)| {
+                let id = id.clone();
+                row![
+                    checkbox(self.pinned.contains(&id))
+                        .label(format!("Pin {}", title))
+                        .on_toggle(move |b| Local(StorageModalMsg::TogglePinned(id.clone(), b))),
+                    space().width(Length::Fill),
+                    secondary_text(size.to_string(), theme, true, true),
+                ]
+                .spacing(10)
+                .into()
+            }))
+            .spacing(4.0),
+        )
+        .height(Length::Fixed(PLAYLIST_LIST_HEIGHT));
+
+        let save = default_text_button("Save", theme).on_press(Local(StorageModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, save].spacing(10);
+        container(
+            column![
+                title,
+                usage,
+                description,
+                quota_data,
+                playlists,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            StorageModalMsg::QuotaTextUpdate(s) => {
+                self.quota_text = s;
+                Task::none()
+            }
+            StorageModalMsg::TogglePinned(id, b) => {
+                if b {
+                    self.pinned.insert(id);
+                } else {
+                    self.pinned.remove(&id);
+                }
+                Task::none()
+            }
+            StorageModalMsg::CheckSubmit => {
+                let quota_text = self.quota_text.trim();
+                let quota = if quota_text.is_empty() {
+                    None
+                } else {
+                    match quota_text.parse::<f64>() {
+                        Ok(gb) if gb > 0.0 => Some(DataSize::new(gb * 1000.0, SizeUnit::Megabyte)),
+                        _ => return self.show_error("The quota must be a positive number"),
+                    }
+                };
+                // the modal is closed once the settings are saved
+                Task::done(Global(Message::StorageSettingsSubmit {
+                    settings: StorageSettings {
+                        quota,
+                        pinned_playlists: self.pinned.clone(),
+                    },
+                }))
+            }
+            StorageModalMsg::SubmitFailed(e) => self.show_error(&e),
+            StorageModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<StorageModal> for Modal {
+    fn from(modal: StorageModal) -> Self {
+        Modal::Storage(modal)
+    }
+}
+impl StorageModal {
+    pub fn new(playlists: Vec<(Id, String)>, usage: Option<&StorageUsage>) -> Self {
+        let playlists = playlists
+            .into_iter()
+            .map(|(id, title)| {
+                let size = usage
+                    .and_then(|u| u.playlists.get(&id).cloned())
+                    .unwrap_or(DataSize::from_bytes(0));
+                (id, title, size)
+            })
+            .collect();
+        let quota_text = usage
+            .and_then(|u| u.settings.quota.as_ref())
+            .map(|q| (q.as_bytes() as f64 / 1_000_000_000.0).to_string())
+            .unwrap_or_default();
+        Self {
+            playlists,
+            total: usage
+                .map(|u| u.total.clone())
+                .unwrap_or(DataSize::from_bytes(0)),
+            pinned: usage
+                .map(|u| u.settings.pinned_playlists.clone())
+                .unwrap_or_default(),
+            quota_text,
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+    fn show_error(&mut self, e: &str) -> Task<AbstractModalMessage<StorageModalMsg, Message>> {
+        self.error = Some(String::from(e));
+        remove_error_after_delay(&mut self.error_timer_handle, StorageModalMsg::RemoveError)
+    }
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use iced::{
+    Element, Length, Padding, Task,
+    task::Handle,
+    widget::{checkbox, container, row, scrollable, space},
+};
+
+use crate::service::{
+    gui::{
+        enums::Message,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    id::structs::Id,
+    playlist::{
+        enums::{SyncLayout, SyncTranscode},
+        structs::SyncOptions,
+    },
+};
+
+// playlists list height inside the modal
+const PLAYLIST_LIST_HEIGHT: f32 = 200.0;
+
+#[derive(Debug, Clone)]
+pub enum SyncModalMsg {
+    PathTextUpdate(String),
+    TogglePlaylist(Id, bool),
+    ToggleArtistAlbumLayout(bool),
+    // Converting to one format unchecks the other
+    ToggleTranscode(SyncTranscode, bool),
+    CheckSubmit,
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncModal {
+    // (id, title) of every playlist
+    playlists: Vec<(Id, String)>,
+    selected: HashSet<Id>,
+    path_text: String,
+    artist_album_layout: bool,
+    transcode: Option<SyncTranscode>,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for SyncModal {
+    type ModalMsg = SyncModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text("Sync to Folder", theme, true, true);
+        let description = secondary_text(
+            "Copies the downloaded tracks of the chosen playlists to a folder, like a phone or SD card. Syncing again only copies what changed.",
+            theme,
+            true,
+            true,
+        );
+        let path_box = default_text_input("Destination folder", &self.path_text, theme)
+            .on_input(|s| Local(SyncModalMsg::PathTextUpdate(s)))
+            .on_paste(|s| Local(SyncModalMsg::PathTextUpdate(s)))
+            .on_submit(Local(SyncModalMsg::CheckSubmit));
+        let mut path_data = column![path_box].spacing(4.0);
+        if let Some(et) = &self.error {
+            path_data = path_data.push(error_text(format!("Error: {}", et), theme, true, true))
+        } else {
+            path_data = path_data.push(error_text("", theme, true, true))
+        }
+        let playlists = scrollable(
+            column(self.playlists.iter().map(|(id, title)| {
+                let id = id.clone();
+                checkbox(self.selected.contains(&id))
+                    .label(title.clone())
+                    .on_toggle(move |b| Local(SyncModalMsg::TogglePlaylist(id.clone(), b)))
+                    .into()
+            }))
+            .spacing(4.0),
+        )
+        .height(Length::Fixed(PLAYLIST_LIST_HEIGHT));
+        let layout_box = checkbox(self.artist_album_layout)
+            .label("Group by artist and album instead of by playlist")
+            .on_toggle(|b| Local(SyncModalMsg::ToggleArtistAlbumLayout(b)));
+        let mp3_box = checkbox(self.transcode == Some(SyncTranscode::Mp3))
+            .label("Convert to MP3")
+            .on_toggle(|b| Local(SyncModalMsg::ToggleTranscode(SyncTranscode::Mp3, b)));
+        let opus_box = checkbox(self.transcode == Some(SyncTranscode::Opus))
+            .label("Convert to Opus")
+            .on_toggle(|b| Local(SyncModalMsg::ToggleTranscode(SyncTranscode::Opus, b)));
+
+        let sync = default_text_button("Sync", theme).on_press(Local(SyncModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, sync].spacing(10);
+        container(
+            column![
+                title,
+                description,
+                path_data,
+                playlists,
+                layout_box,
+                row![mp3_box, opus_box].spacing(10),
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(400.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            SyncModalMsg::PathTextUpdate(s) => {
+                self.path_text = s;
+                Task::none()
+            }
+            SyncModalMsg::TogglePlaylist(id, b) => {
+                if b {
+                    self.selected.insert(id);
+                } else {
+                    self.selected.remove(&id);
+                }
+                Task::none()
+            }
+            SyncModalMsg::ToggleArtistAlbumLayout(b) => {
+                self.artist_album_layout = b;
+                Task::none()
+            }
+            SyncModalMsg::ToggleTranscode(transcode, b) => {
+                self.transcode = if b { Some(transcode) } else { None };
+                Task::none()
+            }
+            SyncModalMsg::CheckSubmit => {
+                let destination = PathBuf::from(self.path_text.trim());
+                let error = if self.selected.is_empty() {
+                    Some("Choose at least one playlist")
+                } else if self.path_text.trim().is_empty() {
+                    Some("Choose a destination folder")
+                } else if destination.exists() && !destination.is_dir() {
+                    Some("Destination is not a folder")
+                } else {
+                    None
+                };
+                if let Some(e) = error {
+                    self.error = Some(String::from(e));
+                    return remove_error_after_delay(
+                        &mut self.error_timer_handle,
+                        SyncModalMsg::RemoveError,
+                    );
+                }
+                let layout = if self.artist_album_layout {
+                    SyncLayout::ArtistAlbum
+                } else {
+                    SyncLayout::Playlist
+                };
+                // keep the order the playlists are listed in
+                let playlist_ids = self
+                    .playlists
+                    .iter()
+                    .filter(|(id, _)| self.selected.contains(id))
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                Task::batch(vec![
+                    Task::done(Global(Message::SyncSubmit {
+                        playlist_ids,
+                        destination,
+                        options: SyncOptions {
+                            layout,
+                            transcode: self.transcode,
+                        },
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            SyncModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<SyncModal> for Modal {
+    fn from(modal: SyncModal) -> Self {
+        Modal::Sync(modal)
+    }
+}
+impl SyncModal {
+    pub fn new(playlists: Vec<(Id, String)>) -> Self {
+        Self {
+            playlists,
+            selected: HashSet::new(),
+            path_text: String::new(),
+            artist_album_layout: false,
+            transcode: None,
+            error: None,
+            error_timer_handle: None,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use iced::{
+    Alignment, Element, Length, Padding, Task,
+    task::Handle,
+    widget::{container, row, space},
+};
+
+use crate::service::{
+    audio::structs::Trim,
+    gui::{
+        enums::Message,
+        util::format_duration,
+        widgets::{
+            button::{default_text_button, secondary_text_button},
+            modal::{
+                AbstractModal,
+                AbstractModalMessage::{self, Global, Local},
+                Modal, column, remove_error_after_delay,
+            },
+            text::{default_text, error_text, secondary_text, title_text},
+            text_input::default_text_input,
+        },
+    },
+    playlist::structs::Track,
+};
+
+#[derive(Debug, Clone)]
+pub enum TrackMenuModalMsg {
+    TrimStartTextUpdate(String),
+    TrimEndTextUpdate(String),
+    DetectSilence,
+    SilenceDetected(Trim),
+    DetectFailed(String),
+    ResetTrim,
+    CheckSubmit,
+    RemoveError,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackMenuModal {
+    track: Track,
+    // in seconds
+    trim_start_text: String,
+    trim_end_text: String,
+    detecting: bool,
+    error: Option<String>,
+    error_timer_handle: Option<Handle>,
+}
+impl AbstractModal<Message> for TrackMenuModal {
+    type ModalMsg = TrackMenuModalMsg;
+
+    fn view(
+        &self,
+        theme: &iced::Theme,
+    ) -> Element<'_, AbstractModalMessage<Self::ModalMsg, Message>> {
+        let title = title_text(self.track.title.clone(), theme, true, true);
+        let length = secondary_text(
+            format!("Length: {}", format_duration(&self.track.length)),
+            theme,
+            true,
+            true,
+        );
+
+        let trim_label = default_text("Trim", theme, false, true);
+        let trim_description = secondary_text(
+            "Seconds skipped at the start and end when the track plays.",
+            theme,
+            true,
+            true,
+        );
+        let trim_start = default_text_input("Start", &self.trim_start_text, theme)
+            .on_input(|s| Local(TrackMenuModalMsg::TrimStartTextUpdate(s)))
+            .on_paste(|s| Local(TrackMenuModalMsg::TrimStartTextUpdate(s)))
+            .on_submit(Local(TrackMenuModalMsg::CheckSubmit))
+            .width(Length::Fixed(90.0));
+        let trim_end = default_text_input("End", &self.trim_end_text, theme)
+            .on_input(|s| Local(TrackMenuModalMsg::TrimEndTextUpdate(s)))
+            .on_paste(|s| Local(TrackMenuModalMsg::TrimEndTextUpdate(s)))
+            .on_submit(Local(TrackMenuModalMsg::CheckSubmit))
+            .width(Length::Fixed(90.0));
+        let detect = if self.detecting {
+            secondary_text_button("Detecting..", theme)
+        } else {
+            secondary_text_button("Detect silence", theme)
+                .on_press(Local(TrackMenuModalMsg::DetectSilence))
+        };
+        let reset =
+            secondary_text_button("Reset", theme).on_press(Local(TrackMenuModalMsg::ResetTrim));
+        let trim_row = row![
+            default_text("Start", theme, false, true),
+            trim_start,
+            default_text("End", theme, false, true),
+            trim_end,
+            detect,
+            reset
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let error = match &self.error {
+            Some(et) => error_text(format!("Error: {}", et), theme, true, true),
+            None => error_text("", theme, true, true),
+        };
+        let save =
+            default_text_button("Save", theme).on_press(Local(TrackMenuModalMsg::CheckSubmit));
+        let cancel = secondary_text_button("Cancel", theme).on_press(Global(Message::HideModal));
+        let buttons_row = row![space().width(Length::Fill), cancel, save].spacing(10);
+        container(
+            column![
+                title,
+                length,
+                trim_label,
+                trim_description,
+                trim_row,
+                error,
+                buttons_row
+            ]
+            .spacing(10.0),
+        )
+        .width(Length::Fixed(520.0))
+        .padding(Padding::new(20.0))
+        .into()
+    }
+
+    fn update(
+        &mut self,
+        message: Self::ModalMsg,
+    ) -> iced::Task<AbstractModalMessage<Self::ModalMsg, Message>> {
+        match message {
+            TrackMenuModalMsg::TrimStartTextUpdate(s) => {
+                self.trim_start_text = s;
+                Task::none()
+            }
+            TrackMenuModalMsg::TrimEndTextUpdate(s) => {
+                self.trim_end_text = s;
+                Task::none()
+            }
+            TrackMenuModalMsg::DetectSilence => {
+                self.detecting = true;
+                Task::done(Global(Message::DetectTrackSilence {
+                    id: self.track.id().clone(),
+                }))
+            }
+            TrackMenuModalMsg::SilenceDetected(trim) => {
+                self.detecting = false;
+                self.set_trim_text(trim);
+                Task::none()
+            }
+            TrackMenuModalMsg::DetectFailed(e) => {
+                self.detecting = false;
+                self.show_error(&e)
+            }
+            TrackMenuModalMsg::ResetTrim => {
+                self.set_trim_text(Trim::default());
+                Task::none()
+            }
+            TrackMenuModalMsg::CheckSubmit => {
+                let parse = |text: &str| {
+                    text.trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                        .map(Duration::from_secs_f64)
+                };
+                let (Some(start), Some(end)) =
+                    (parse(&self.trim_start_text), parse(&self.trim_end_text))
+                else {
+                    return self.show_error("Enter the trim in seconds");
+                };
+                // tracks without a known length can't be checked
+                if !self.track.length.is_zero() && start + end >= self.track.length {
+                    return self.show_error("The trim can't cover the whole track");
+                }
+                Task::batch([
+                    Task::done(Global(Message::TrackTrimSubmit {
+                        track: Track {
+                            trim: Trim { start, end },
+                            ..self.track.clone()
+                        },
+                    })),
+                    Task::done(Global(Message::HideModal)),
+                ])
+            }
+            TrackMenuModalMsg::RemoveError => {
+                self.error = None;
+                self.error_timer_handle = None;
+                Task::none()
+            }
+        }
+    }
+}
+impl From<TrackMenuModal> for Modal {
+    fn from(modal: TrackMenuModal) -> Self {
+        Modal::TrackMenu(Box::new(modal))
+    }
+}
+impl TrackMenuModal {
+    pub fn new(track: Track) -> Self {
+        let mut modal = Self {
+            track,
+            trim_start_text: String::new(),
+            trim_end_text: String::new(),
+            detecting: false,
+            error: None,
+            error_timer_handle: None,
+        };
+        modal.set_trim_text(modal.track.trim);
+        modal
+    }
+    fn set_trim_text(&mut self, trim: Trim) {
+        self.trim_start_text = trim.start.as_secs_f64().to_string();
+        self.trim_end_text = trim.end.as_secs_f64().to_string();
+    }
+    fn show_error(&mut self, e: &str) -> Task<AbstractModalMessage<TrackMenuModalMsg, Message>> {
+        self.error = Some(String::from(e));
+        remove_error_after_delay(&mut self.error_timer_handle, TrackMenuModalMsg::RemoveError)
+    }
+}
@@ -3,6 +3,7 @@ use iced::{Element, Padding, Theme};
 use crate::service::gui::{enums::Message, widgets::container::notification_container};
 
 pub mod download;
+pub mod import;
 pub mod init;
 pub mod playing;
 
@@ -0,0 +1,44 @@
+use crate::service::gui::structs::{LocalImportData, TaskId};
+use crate::service::gui::widgets::notification::{NOTIFICATION_PROGRESS_BAR_HEIGHT, Notification};
+use crate::service::gui::widgets::progress_bar::default_progress_bar;
+use crate::service::gui::widgets::text::default_text;
+use iced::Theme;
+use iced::widget::column;
+use indexmap::IndexMap;
+
+pub fn local_import_notification<'a>(
+    local_import_data: &LocalImportData,
+    theme: &Theme,
+) -> Notification<'a> {
+    let action = local_import_data.action;
+    let title_text = if let Some(cf) = local_import_data.current_file
+        && let Some(tf) = local_import_data.total_file_count
+    {
+        format!("{}... ({}/{})", action, cf, tf)
+    } else {
+        format!(
+            "{}... ({} files found)",
+            action, local_import_data.found_file_count
+        )
+    };
+    let title = default_text(title_text, theme, true, true);
+    let bar_progress = {
+        let cf = local_import_data.current_file.unwrap_or(0);
+        let tf = local_import_data.total_file_count.unwrap_or(1).max(1);
+        (cf as f32) / (tf as f32)
+    };
+    let progress = default_progress_bar(0.0..=100.0, bar_progress * 100.0, theme)
+        .girth(NOTIFICATION_PROGRESS_BAR_HEIGHT);
+    let content = column![title, progress].into();
+    Notification { content }
+}
+
+pub fn local_import_notification_list<'a>(
+    local_import_data: &'a IndexMap<TaskId, LocalImportData>,
+    theme: &Theme,
+) -> Vec<Notification<'a>> {
+    local_import_data
+        .iter()
+        .map(|(_, idata)| local_import_notification(idata, theme))
+        .collect()
+}
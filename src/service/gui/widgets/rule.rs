@@ -13,6 +13,9 @@ pub fn default_horizontal_rule<'a>(height: impl Into<Pixels>, theme: &Theme) ->
 pub fn secondary_horizontal_rule<'a>(height: impl Into<Pixels>, theme: &Theme) -> Rule<'a> {
     rule::horizontal(height).style(theme.stylesheet().secondary_rule(6.0).style())
 }
+pub fn secondary_vertical_rule<'a>(width: impl Into<Pixels>, theme: &Theme) -> Rule<'a> {
+    rule::vertical(width).style(theme.stylesheet().secondary_rule(0.0).style())
+}
 pub fn in_between_rule<'a, Message>(
     content: impl Into<Element<'a, Message>>,
     height: impl Into<Pixels>,
@@ -2,7 +2,7 @@ use std::ops::RangeInclusive;
 
 use iced::{
     Theme,
-    widget::{Slider, slider},
+    widget::{Slider, VerticalSlider, slider, vertical_slider},
 };
 
 use crate::service::gui::styling::{AppTheme, SliderStyle};
@@ -33,3 +33,17 @@ where
     let style = theme.stylesheet().default_slider();
     build_slider(range, value, on_change, style)
 }
+
+pub fn default_vertical_slider<'a, T, Message>(
+    range: RangeInclusive<T>,
+    value: T,
+    on_change: impl Fn(T) -> Message + 'a,
+    theme: &Theme,
+) -> VerticalSlider<'a, T, Message>
+where
+    T: Copy + From<u8> + std::cmp::PartialOrd,
+    Message: Clone,
+{
+    let style = theme.stylesheet().default_slider();
+    vertical_slider(range, value, on_change).style(style.style())
+}
@@ -0,0 +1,135 @@
+// The player's visualizer: spectrum bars with a level meter beside them, drawn as quads like the
+// waveform seek bar.
+
+use iced::{
+    Border, Color, Element, Length, Rectangle, Size, Theme,
+    advanced::{Layout, Widget, layout, mouse, renderer, widget::Tree},
+};
+
+use crate::service::{
+    audio::structs::Spectrum,
+    gui::styling::{AppTheme, VisualizerStyle},
+};
+
+const BAR_GAP: f32 = 2.0;
+// the level meter is to the right of the bars
+const METER_WIDTH: f32 = 10.0;
+const METER_GAP: f32 = 12.0;
+// the peak is a line this thick on top of the level
+const PEAK_HEIGHT: f32 = 2.0;
+
+pub struct Visualizer<'a> {
+    spectrum: &'a Spectrum,
+    height: f32,
+    style: VisualizerStyle,
+}
+impl<'a> Visualizer<'a> {
+    pub fn new(spectrum: &'a Spectrum, height: f32, style: VisualizerStyle) -> Self {
+        Self {
+            spectrum,
+            height,
+            style,
+        }
+    }
+}
+
+impl<Message, Renderer> Widget<Message, Theme, Renderer> for Visualizer<'_>
+where
+    Renderer: renderer::Renderer,
+{
+    fn size(&self) -> Size<Length> {
+        Size {
+            width: Length::Fill,
+            height: Length::Fixed(self.height),
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _tree: &mut Tree,
+        _renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        layout::atomic(limits, Length::Fill, self.height)
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let bottom = bounds.y + bounds.height;
+        // a rectangle rising from the bottom
+        let mut fill = |x: f32, width: f32, from: f32, to: f32, color: Color| {
+            let (top, height) = (bottom - to * bounds.height, (to - from) * bounds.height);
+            if height <= 0.0 {
+                return;
+            }
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x,
+                        y: top,
+                        width,
+                        height,
+                    },
+                    border: Border::default(),
+                    ..renderer::Quad::default()
+                },
+                color,
+            );
+        };
+
+        let bands = &self.spectrum.bands;
+        let bars_width = bounds.width - METER_WIDTH - METER_GAP;
+        if !bands.is_empty() && bars_width > 0.0 {
+            let bar_width = (bars_width / bands.len() as f32 - BAR_GAP).max(1.0);
+            for (i, height) in bands.iter().enumerate() {
+                let x = bounds.x + i as f32 * (bar_width + BAR_GAP);
+                fill(x, bar_width, 0.0, *height, self.style.bar_color);
+            }
+        }
+
+        let meter_x = bounds.x + bounds.width - METER_WIDTH;
+        fill(
+            meter_x,
+            METER_WIDTH,
+            0.0,
+            self.spectrum.level,
+            self.style.level_color,
+        );
+        let peak_line = PEAK_HEIGHT / bounds.height.max(1.0);
+        let peak = self.spectrum.peak.max(peak_line);
+        fill(
+            meter_x,
+            METER_WIDTH,
+            peak - peak_line,
+            peak,
+            self.style.peak_color,
+        );
+    }
+}
+
+impl<'a, Message, Renderer> From<Visualizer<'a>> for Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Renderer: renderer::Renderer + 'a,
+{
+    fn from(visualizer: Visualizer<'a>) -> Self {
+        Element::new(visualizer)
+    }
+}
+
+pub fn default_visualizer<'a>(
+    spectrum: &'a Spectrum,
+    height: f32,
+    theme: &Theme,
+) -> Visualizer<'a> {
+    Visualizer::new(spectrum, height, theme.stylesheet().default_visualizer())
+}
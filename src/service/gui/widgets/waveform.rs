@@ -0,0 +1,317 @@
+// A seek bar drawn as a track's waveform (see `audio::waveform`). Iced has no canvas without its
+// `canvas` feature, so the bars are drawn as quads by a widget of its own.
+
+use iced::{
+    Border, Color, Element, Event, Length, Point, Rectangle, Size, Theme,
+    advanced::{
+        Clipboard, Layout, Shell, Widget, layout, mouse, renderer,
+        widget::{Tree, tree},
+    },
+    keyboard,
+};
+
+use crate::service::gui::styling::{AppTheme, WaveformStyle};
+
+// width of each bar and the gap after it
+const BAR_WIDTH: f32 = 2.0;
+const BAR_GAP: f32 = 1.0;
+const HEIGHT: f32 = 24.0;
+
+pub struct WaveformBar<'a, Message> {
+    peaks: &'a [u8],
+    rms: &'a [u8],
+    // how far through the waveform playback is, 0-1
+    progress: f32,
+    on_seek: Box<dyn Fn(f32) -> Message + 'a>,
+    on_release: Option<Message>,
+    on_hover: Option<Box<dyn Fn(Option<f32>) -> Message + 'a>>,
+    on_select: Option<Box<dyn Fn(f32, f32) -> Message + 'a>>,
+    // a highlighted stretch of the waveform, 0-1 from start to end
+    region: Option<(f32, f32)>,
+    style: WaveformStyle,
+}
+impl<'a, Message> WaveformBar<'a, Message> {
+    pub fn new(
+        peaks: &'a [u8],
+        rms: &'a [u8],
+        progress: f32,
+        on_seek: impl Fn(f32) -> Message + 'a,
+        style: WaveformStyle,
+    ) -> Self {
+        Self {
+            peaks,
+            rms,
+            progress: if progress.is_finite() {
+                progress.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+            on_seek: Box::new(on_seek),
+            on_release: None,
+            on_hover: None,
+            on_select: None,
+            region: None,
+            style,
+        }
+    }
+    // Sent when the mouse is let go after seeking
+    pub fn on_release(mut self, on_release: Message) -> Self {
+        self.on_release = Some(on_release);
+        self
+    }
+    // Sent with how far along the cursor is (0-1) as it moves over the bar, and None as it leaves
+    pub fn on_hover(mut self, on_hover: impl Fn(Option<f32>) -> Message + 'a) -> Self {
+        self.on_hover = Some(Box::new(on_hover));
+        self
+    }
+    // Dragging with shift held selects a stretch of the bar instead of seeking; this is sent with
+    // its start and end (0-1) when the mouse is let go
+    pub fn on_select(mut self, on_select: impl Fn(f32, f32) -> Message + 'a) -> Self {
+        self.on_select = Some(Box::new(on_select));
+        self
+    }
+    pub fn region(mut self, region: Option<(f32, f32)>) -> Self {
+        self.region = region;
+        self
+    }
+}
+
+#[derive(Default)]
+struct State {
+    is_dragging: bool,
+    hovered: Option<f32>,
+    modifiers: keyboard::Modifiers,
+    // where a shift-drag started and where it's got to
+    selection: Option<(f32, f32)>,
+}
+
+impl<Message, Renderer> Widget<Message, Theme, Renderer> for WaveformBar<'_, Message>
+where
+    Message: Clone,
+    Renderer: renderer::Renderer,
+{
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size {
+            width: Length::Fill,
+            height: Length::Fixed(HEIGHT),
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _tree: &mut Tree,
+        _renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        layout::atomic(limits, Length::Fill, HEIGHT)
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_mut::<State>();
+        let bounds = layout.bounds();
+        let locate = |position: Point| ((position.x - bounds.x) / bounds.width).clamp(0.0, 1.0);
+
+        match event {
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = *modifiers;
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                if let Some(position) = cursor.position_over(bounds) {
+                    let at = locate(position);
+                    if state.modifiers.shift() && self.on_select.is_some() {
+                        state.selection = Some((at, at));
+                    } else {
+                        shell.publish((self.on_seek)(at));
+                        state.is_dragging = true;
+                    }
+                    shell.capture_event();
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if state.is_dragging =>
+            {
+                if let Some(on_release) = self.on_release.clone() {
+                    shell.publish(on_release);
+                }
+                state.is_dragging = false;
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                if let Some((from, to)) = state.selection.take()
+                    && let Some(on_select) = &self.on_select
+                {
+                    shell.publish(on_select(from.min(to), from.max(to)));
+                    shell.request_redraw();
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. } | mouse::Event::CursorLeft) => {
+                if let Some(position) = cursor.land().position() {
+                    if state.is_dragging {
+                        shell.publish((self.on_seek)(locate(position)));
+                        shell.capture_event();
+                    } else if let Some((_, to)) = &mut state.selection {
+                        *to = locate(position);
+                        shell.capture_event();
+                        shell.request_redraw();
+                    }
+                }
+                let hovered = cursor.position_over(bounds).map(locate);
+                if hovered != state.hovered {
+                    state.hovered = hovered;
+                    if let Some(on_hover) = &self.on_hover {
+                        shell.publish(on_hover(hovered));
+                    }
+                    shell.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<State>();
+        let bounds = layout.bounds();
+        let columns = (bounds.width / (BAR_WIDTH + BAR_GAP)).floor() as usize;
+        if columns == 0 || self.peaks.is_empty() {
+            return;
+        }
+        let mid_y = bounds.y + bounds.height / 2.0;
+        // a selection being dragged out shows instead of the current region
+        let region = state
+            .selection
+            .map(|(from, to)| (from.min(to), from.max(to)))
+            .or(self.region);
+        if let Some((start, end)) = region {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: bounds.x + start * bounds.width,
+                        y: bounds.y,
+                        width: ((end - start) * bounds.width).max(1.0),
+                        height: bounds.height,
+                    },
+                    border: Border::default(),
+                    ..renderer::Quad::default()
+                },
+                self.style.region_color,
+            );
+        }
+        let mut bar = |x: f32, level: u8, color: Color| {
+            // silence still shows as a line
+            let height = (level as f32 / u8::MAX as f32 * bounds.height).max(1.0);
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x,
+                        y: mid_y - height / 2.0,
+                        width: BAR_WIDTH,
+                        height,
+                    },
+                    border: Border::default(),
+                    ..renderer::Quad::default()
+                },
+                color,
+            );
+        };
+        let points = self.peaks.len();
+        for column in 0..columns {
+            // the loudest of the points under this bar
+            let first = column * points / columns;
+            let last = ((column + 1) * points / columns).max(first + 1);
+            let peak = self.peaks[first..last].iter().copied().max().unwrap_or(0);
+            let rms = self.rms[first..last].iter().copied().max().unwrap_or(0);
+
+            let x = bounds.x + column as f32 * (BAR_WIDTH + BAR_GAP);
+            let color = if (column as f32 + 0.5) / columns as f32 <= self.progress {
+                self.style.played_color
+            } else {
+                self.style.unplayed_color
+            };
+            bar(x, peak, color.scale_alpha(self.style.peak_alpha));
+            bar(x, rms, color);
+        }
+        if let Some(hovered) = state.hovered {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: bounds.x + hovered * bounds.width - 0.5,
+                        y: bounds.y,
+                        width: 1.0,
+                        height: bounds.height,
+                    },
+                    border: Border::default(),
+                    ..renderer::Quad::default()
+                },
+                self.style.hover_color,
+            );
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+        if state.is_dragging || state.selection.is_some() || cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+impl<'a, Message, Renderer> From<WaveformBar<'a, Message>> for Element<'a, Message, Theme, Renderer>
+where
+    Message: Clone + 'a,
+    Renderer: renderer::Renderer + 'a,
+{
+    fn from(waveform: WaveformBar<'a, Message>) -> Self {
+        Element::new(waveform)
+    }
+}
+
+pub fn default_waveform<'a, Message>(
+    peaks: &'a [u8],
+    rms: &'a [u8],
+    progress: f32,
+    on_seek: impl Fn(f32) -> Message + 'a,
+    theme: &Theme,
+) -> WaveformBar<'a, Message> {
+    WaveformBar::new(
+        peaks,
+        rms,
+        progress,
+        on_seek,
+        theme.stylesheet().default_waveform(),
+    )
+}
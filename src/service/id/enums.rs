@@ -7,4 +7,6 @@ pub enum Platform {
     Youtube,
     #[strum(serialize = "mb")]
     MusicBrainz,
+    #[strum(serialize = "lc")]
+    Local,
 }
@@ -1,17 +1,37 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use crate::{
     service::{
-        audio::{AudioSender, enums::AudioMessage},
-        file::{self, structs::BinApps},
+        audio::{
+            AudioSender,
+            enums::{AlbumKind, AudioMessage, NormalizationMode},
+            loudness, silence,
+            structs::{EffectSettings, PlaybackSpeed},
+            waveform,
+        },
+        file::{
+            self,
+            db::Database,
+            library::{Library, LibraryConfig},
+            structs::{BinApps, DataSize, LoadReport},
+        },
         gui::enums::{EventMessage, EventSender, Message},
         id::structs::Id,
         playlist::{
+            backup::{backup_library, restore_library},
             download::initialize_playlist,
+            enums::{LocalImportStatus, RestoreMode},
+            import::{import_local_folder, import_ytdlp_archive},
+            playlist_file::{export_playlist_file, import_playlist_file},
             structs::{
-                Album, OwnedPlaylist, PlaylistAudioManager, PlaylistDownloadManager, Track,
-                Tracklist,
+                Album, OwnedPlaylist, PlaylistAudioManager, PlaylistDeletionInfo,
+                PlaylistDownloadManager, StorageSettings, StorageUsage, Track, Tracklist,
             },
+            sync::{Transcoder, sync_playlists},
         },
         process::ProcessSender,
     },
@@ -19,21 +39,27 @@ use crate::{
 };
 use anyhow::anyhow;
 use enums::PlaylistMessage;
+use kira::Tween;
 use musicbrainz_rs::MusicBrainzClient;
 use reqwest::Client;
 use structs::Playlist;
-use tokio::{
-    fs,
-    sync::{mpsc, oneshot},
-};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
 
+mod backup;
 mod download;
 pub mod enums;
+mod import;
+mod playlist_file;
 pub mod structs;
+mod sync;
 mod util;
 
 pub type PlaylistSender = mpsc::Sender<PlaylistMessage>;
 
+// where the database is copied to while a backup is written
+const BACKUP_SNAPSHOT_FILENAME: &str = ".backup.sqlite3";
+
 /// Handles playlist management.
 pub struct PlaylistService {
     event_sender: EventSender,
@@ -46,6 +72,26 @@ pub struct PlaylistService {
     tracks: HashMap<Id, Track>,
     downloaded_tracks: HashSet<Id>,
     albums: HashMap<Id, Album>,
+    // how many playlists reference each track (counting duplicates)
+    track_references: HashMap<Id, usize>,
+    // size of each downloaded track's audio file, and when each track was last played
+    track_sizes: HashMap<Id, u64>,
+    last_played: HashMap<Id, i64>,
+    storage_settings: StorageSettings,
+    // tracks waiting to have their loudness measured, and a way to stop measuring them
+    loudness_analysis: Option<(mpsc::UnboundedSender<Id>, CancellationToken)>,
+    normalization: NormalizationMode,
+    // whether silence is trimmed off of newly downloaded tracks, and the downloads to trim once
+    // they're tagged
+    trim_silence: bool,
+    silence_pending: HashSet<Id>,
+    // tracks whose audio file is being tagged, with the tags to write once that's done (if any)
+    tagging: HashMap<Id, Option<Track>>,
+    // only playlists that don't play at the normal speed
+    playback_speeds: HashMap<Id, PlaybackSpeed>,
+    // where the library lives on disk + persistent storage for the caches above
+    library: Library,
+    database: Option<Arc<Database>>,
 
     bin_files: Option<BinApps>,
     // cache downloaded tracks to prevent re-downloading
@@ -62,42 +108,314 @@ pub struct PlaylistFlags {
     pub process_sender: ProcessSender,
     pub audio_sender: AudioSender,
     pub playlist_sender: PlaylistSender,
+    // the library to open on startup
+    pub library: Library,
 }
 
 impl PlaylistService {
-    pub fn new(flags: PlaylistFlags) -> Self {
-        Self {
-            event_sender: flags.event_sender,
-            process_sender: flags.process_sender,
-            audio_sender: flags.audio_sender,
-            playlists: HashMap::new(),
-            tracks: HashMap::new(),
-            bin_files: None,
-            playlist_sender: flags.playlist_sender,
-            download_managers: HashMap::new(),
-            downloaded_tracks: HashSet::new(),
-            audio_managers: HashMap::new(),
-            download_waiting_tracks: HashMap::new(),
-            musicbrainz_client: None,
-            albums: HashMap::new(),
-            reqwest_client: Client::new(),
+    // Runs a write on the blocking pool, so it doesn't stall the runtime. Fails if the database
+    // is closed, which it is while a library is switched or restored, and stays if the library
+    // can't be opened again
+    async fn write_db(
+        &self,
+        write: impl FnOnce(&Database) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        let database = self
+            .database
+            .clone()
+            .ok_or_else(|| anyhow!("The library's database isn't open"))?;
+        tokio::task::spawn_blocking(move || write(&database)).await?
+    }
+    // Embeds the track's metadata (and album cover) into its audio file in the background. Only
+    // one write to a file runs at a time; tags asked for while one is running are written after it
+    fn tag_track_file(&mut self, track: Track) {
+        if let Some(pending) = self.tagging.get_mut(track.id()) {
+            // only the newest tags need writing
+            *pending = Some(track);
+            return;
         }
+        self.tagging.insert(track.id().clone(), None);
+        let library = self.library.clone();
+        let playlist_sender = self.playlist_sender.clone();
+        tokio::spawn(async move {
+            let id = track.id().clone();
+            let _ = tokio::task::spawn_blocking(move || {
+                if let Err(e) = file::tags::write_track_tags(&library, &track) {
+                    println!("Failed to tag the audio file of {}: {e}", track.title);
+                }
+            })
+            .await;
+            // tagging changes the file's size
+            let _ = playlist_sender
+                .send(PlaylistMessage::TrackFileChanged { id })
+                .await;
+        });
     }
-}
-
-#[async_trait::async_trait]
-impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
-    fn name(&self) -> &'static str {
-        "PlaylistService"
+    // Starts measuring the loudness of queued tracks in the background, one at a time
+    fn start_loudness_analysis(&mut self) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Id>();
+        let cancel_token = CancellationToken::new();
+        let library = self.library.clone();
+        let playlist_sender = self.playlist_sender.clone();
+        let token = cancel_token.clone();
+        tokio::spawn(async move {
+            loop {
+                let id = tokio::select! {
+                    _ = token.cancelled() => break,
+                    id = rx.recv() => match id {
+                        Some(id) => id,
+                        None => break,
+                    },
+                };
+                let Ok(path) = file::util::track_file_path_from_id(&library, &id) else {
+                    continue;
+                };
+                let result =
+                    tokio::task::spawn_blocking(move || loudness::analyze_file(&path)).await;
+                match result {
+                    Ok(Ok(loudness)) => {
+                        if token.is_cancelled() {
+                            break;
+                        }
+                        let _ = playlist_sender
+                            .send(PlaylistMessage::TrackLoudnessAnalyzed { id, loudness })
+                            .await;
+                    }
+                    Ok(Err(e)) => println!("Failed to measure the loudness of {id}: {e}"),
+                    Err(e) => println!("Loudness analysis task failed: {e}"),
+                }
+            }
+        });
+        self.loudness_analysis = Some((tx, cancel_token));
     }
-    async fn on_start(&mut self) -> anyhow::Result<()> {
-        // startup logic
-        // get the yt_dlp and ffmpeg file locations
-        let bin_files = file::util::get_bin_app_paths();
-        self.bin_files = Some(bin_files);
+    // Queues the downloaded tracks that haven't been measured yet for loudness analysis
+    fn queue_loudness_analysis<'a>(&self, ids: impl IntoIterator<Item = &'a Id>) {
+        let Some((sender, _)) = &self.loudness_analysis else {
+            return;
+        };
+        for id in ids {
+            let unmeasured = self
+                .tracks
+                .get(id)
+                .is_some_and(|track| track.loudness.is_none());
+            if unmeasured && self.downloaded_tracks.contains(id) {
+                let _ = sender.send(id.clone());
+            }
+        }
+    }
+    // Finds the silence at the start and end of a downloaded track in the background
+    fn detect_silence(&self, id: Id) {
+        let Ok(path) = file::util::track_file_path_from_id(&self.library, &id) else {
+            return;
+        };
+        let playlist_sender = self.playlist_sender.clone();
+        tokio::spawn(async move {
+            match tokio::task::spawn_blocking(move || silence::find_silence(&path)).await {
+                Ok(Ok(trim)) => {
+                    let _ = playlist_sender
+                        .send(PlaylistMessage::TrackSilenceFound { id, trim })
+                        .await;
+                }
+                Ok(Err(e)) => println!("Failed to find the silence in {id}: {e}"),
+                Err(e) => println!("Silence detection task failed: {e}"),
+            }
+        });
+    }
+    // The normalization gain for a track in the current mode. Tracks that haven't been measured
+    // get none.
+    fn track_gain(&self, id: &Id) -> f64 {
+        let Some(track) = self.tracks.get(id) else {
+            return 0.0;
+        };
+        let loudness = match (self.normalization, &track.album_kind) {
+            (NormalizationMode::Off, _) => None,
+            (NormalizationMode::Album, AlbumKind::Album(album)) => loudness::combined_loudness(
+                self.tracks.values().filter_map(|t| match &t.album_kind {
+                    AlbumKind::Album(a) if a.id() == album.id() => {
+                        t.loudness.as_ref().map(|l| (l, t.length))
+                    }
+                    _ => None,
+                }),
+            ),
+            // singles are their own album
+            (NormalizationMode::Track | NormalizationMode::Album, _) => track.loudness.clone(),
+        };
+        loudness
+            .map(|l| loudness::normalization_gain(&l))
+            .unwrap_or(0.0)
+    }
+    // Looks up the size of each track's audio file, recording any that changed. Tracks that
+    // aren't downloaded are recorded as having no file.
+    async fn record_track_sizes(&mut self, ids: impl IntoIterator<Item = Id>) {
+        let mut changed = Vec::new();
+        for id in ids {
+            let size = match file::util::track_file_path_from_id(&self.library, &id) {
+                Ok(path) if self.downloaded_tracks.contains(&id) => {
+                    Some(file::util::file_size(path).await)
+                }
+                _ => None,
+            };
+            let previous = match size {
+                Some(size) => self.track_sizes.insert(id.clone(), size),
+                None => self.track_sizes.remove(&id),
+            };
+            if previous != size {
+                changed.push((id, size));
+            }
+        }
+        if !changed.is_empty()
+            && let Err(e) = self
+                .write_db(move |db| {
+                    db.save_track_sizes(changed.iter().map(|(id, size)| (id, *size)))
+                })
+                .await
+        {
+            println!("Failed to save track sizes: {e}");
+        }
+    }
+    // Evicts the audio of the least recently played tracks until the library fits in its quota.
+    // Tracks in pinned playlists, playlists that are playing or downloading, and `protected`
+    // are kept. Metadata is never touched, so evicted tracks can be downloaded again.
+    async fn enforce_storage_quota(&mut self, protected: &[Id]) {
+        let Some(quota) = self.storage_settings.quota.as_ref().map(|q| q.as_bytes()) else {
+            return;
+        };
+        let mut total: u64 = self.track_sizes.values().sum();
+        if total <= quota {
+            return;
+        }
+        let candidates = {
+            let mut keep: HashSet<&Id> = protected.iter().collect();
+            // a tag write would put the file straight back
+            keep.extend(self.tagging.keys());
+            for (id, playlist) in self.playlists.iter() {
+                if self.storage_settings.pinned_playlists.contains(id)
+                    || self.download_managers.contains_key(id)
+                    || self.audio_managers.contains_key(id)
+                {
+                    keep.extend(playlist.tracks.0.iter());
+                }
+            }
+            util::eviction_order(
+                &self.downloaded_tracks,
+                &keep,
+                &self.last_played,
+                &self.track_sizes,
+            )
+        };
+        let mut evicted = Vec::new();
+        for id in candidates {
+            if total <= quota {
+                break;
+            }
+            match file::util::delete_track_file(&self.library, &id).await {
+                Ok(_) => {
+                    total -= self.track_sizes.get(&id).copied().unwrap_or(0);
+                    self.downloaded_tracks.remove(&id);
+                    evicted.push(id);
+                }
+                Err(e) => println!("Failed to evict track file: {e}"),
+            }
+        }
+        if total > quota {
+            println!(
+                "Library is still over its storage quota ({} of {}); everything left is pinned or in use",
+                DataSize::from_bytes(total),
+                DataSize::from_bytes(quota)
+            );
+        }
+        if evicted.is_empty() {
+            return;
+        }
+        println!("Evicted {} track(s) to stay under the quota", evicted.len());
+        self.record_track_sizes(evicted.iter().cloned()).await;
+        let _ = self
+            .event_sender
+            .send(EventMessage::TrackFilesEvicted(evicted))
+            .await;
+    }
+    // Sends the library's storage usage to the gui
+    async fn send_storage_usage(&self) {
+        let playlists = self
+            .playlists
+            .iter()
+            .map(|(id, playlist)| {
+                let unique: HashSet<&Id> = playlist.tracks.0.iter().collect();
+                let size = unique
+                    .into_iter()
+                    .filter_map(|track_id| self.track_sizes.get(track_id))
+                    .sum();
+                (id.clone(), DataSize::from_bytes(size))
+            })
+            .collect();
+        let usage = StorageUsage {
+            total: DataSize::from_bytes(self.track_sizes.values().sum()),
+            playlists,
+            settings: self.storage_settings.clone(),
+        };
+        let _ = self
+            .event_sender
+            .send(EventMessage::StorageUsageUpdated(usage))
+            .await;
+    }
+    // The playlist's title, and its downloaded tracks along with their audio files, in the
+    // tracklist's order (or the saved order).
+    fn playlist_export_entries(
+        &self,
+        id: &Id,
+        tracklist: Option<Tracklist>,
+    ) -> anyhow::Result<(String, Vec<(Track, PathBuf)>)> {
+        let playlist = self
+            .playlists
+            .get(id)
+            .ok_or(anyhow!("Playlist does not exist"))?;
+        let tracks: Vec<Track> = match tracklist {
+            Some(tracklist) => tracklist.iter().cloned().collect(),
+            None => playlist
+                .tracks
+                .0
+                .iter()
+                .filter_map(|track_id| self.tracks.get(track_id).cloned())
+                .collect(),
+        };
+        let mut entries = Vec::new();
+        for track in tracks {
+            // only downloaded tracks have a file to point at
+            if !self.downloaded_tracks.contains(track.id()) {
+                continue;
+            }
+            let path = file::util::track_file_path_from_id(&self.library, track.id())?;
+            entries.push((track, path));
+        }
+        if entries.is_empty() {
+            return Err(anyhow!("None of the playlist's tracks are downloaded"));
+        }
+        Ok((playlist.metadata.title.clone(), entries))
+    }
+    // Opens the current library's database and loads everything in it into the caches,
+    // sending it all to the gui
+    async fn load_library(&mut self) -> anyhow::Result<()> {
+        println!(
+            "Using library '{}' at {}",
+            self.library.name(),
+            self.library.root().display()
+        );
+        // tracks are played from whichever library is open
+        let _ = self
+            .audio_sender
+            .send(AudioMessage::SetLibrary {
+                library: self.library.clone(),
+            })
+            .await;
+
+        // open the database
+        // anything that can't be loaded gets quarantined and reported to the gui
+        let mut load_report = LoadReport::default();
+        let database = file::db::open_library_database(&self.library, &mut load_report).await?;
 
         // load existing playlists
-        let playlists = file::util::load_saved_playlists().await.unwrap();
+        let playlists = database.load_playlists(&mut load_report)?;
+        self.track_references = util::count_track_references(&playlists);
         self.playlists = playlists;
         self.event_sender
             .send(EventMessage::InitialPlaylistsInitalized(
@@ -106,18 +424,14 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                     .map(|playlist| playlist.metadata.clone())
                     .collect(),
             ))
-            .await
-            .unwrap();
+            .await?;
 
         // cache downloaded tracks
-        let downloaded_tracks = file::util::get_downloaded_tracks().await.unwrap();
+        let downloaded_tracks = file::util::get_downloaded_tracks(&self.library).await?;
         self.downloaded_tracks = downloaded_tracks;
 
         // cache all tracks
-        let track_cache = match file::util::load_saved_tracks().await {
-            Ok(tracks) => tracks,
-            Err(_) => HashMap::new(),
-        };
+        let track_cache = database.load_tracks(&mut load_report).unwrap_or_default();
         let _ = self
             .event_sender
             .send(EventMessage::TrackCacheUpdated {
@@ -128,18 +442,251 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
         self.tracks = track_cache;
 
         // cache (downloaded) albums
-        let albums = file::util::get_albums().await;
+        let albums = database.load_albums(&mut load_report);
         let album_set = match albums {
             Ok(albums) => albums,
             Err(_) => HashMap::new(),
         };
         self.albums = album_set.clone();
 
+        // storage accounting; re-check every file in case they changed while closed
+        let (track_sizes, last_played) = database.load_track_storage().unwrap_or_default();
+        self.storage_settings = database.load_storage_settings().unwrap_or_default();
+        let database = Arc::new(database);
+        self.database = Some(database.clone());
+        let recorded: Vec<Id> = track_sizes.keys().cloned().collect();
+        self.track_sizes = track_sizes;
+        self.last_played = last_played;
+        let ids: HashSet<Id> = recorded
+            .into_iter()
+            .chain(self.downloaded_tracks.iter().cloned())
+            .collect();
+        self.record_track_sizes(ids).await;
+        self.send_storage_usage().await;
+
+        let effect_settings = database.load_effect_settings().unwrap_or_else(|e| {
+            println!("Failed to load effect presets: {e}");
+            EffectSettings::default()
+        });
+        let _ = self
+            .audio_sender
+            .send(AudioMessage::SetEffectSettings {
+                settings: effect_settings.clone(),
+            })
+            .await;
+        let _ = self
+            .event_sender
+            .send(EventMessage::EffectSettingsUpdated(effect_settings))
+            .await;
+        self.playback_speeds = database.load_playback_speeds().unwrap_or_else(|e| {
+            println!("Failed to load playback speeds: {e}");
+            HashMap::new()
+        });
+        let _ = self
+            .event_sender
+            .send(EventMessage::PlaybackSpeedsUpdated(
+                self.playback_speeds.clone(),
+            ))
+            .await;
+
+        // pick up where the last session left off, if its playlist is still around
+        match database.load_session() {
+            Ok(Some(session)) if self.playlists.contains_key(&session.playlist_id) => {
+                let _ = self
+                    .event_sender
+                    .send(EventMessage::SessionLoaded(session))
+                    .await;
+            }
+            Ok(_) => {}
+            Err(e) => println!("Failed to load the last session: {e}"),
+        }
+
+        // measure anything downloaded before loudness normalization existed
+        self.start_loudness_analysis();
+        self.queue_loudness_analysis(self.downloaded_tracks.iter());
+
         let _ = self
             .event_sender
             .send(EventMessage::DownloadedAlbumsReceived(album_set))
             .await;
 
+        if !load_report.is_empty() {
+            let _ = self
+                .event_sender
+                .send(EventMessage::LibraryLoadReport(load_report))
+                .await;
+        }
+
+        Ok(())
+    }
+    // Closes the database and empties the caches, telling the gui the library changed
+    async fn close_library(&mut self) {
+        if let Some((_, cancel_token)) = self.loudness_analysis.take() {
+            cancel_token.cancel();
+        }
+        self.database = None;
+        self.playlists.clear();
+        self.tracks.clear();
+        self.downloaded_tracks.clear();
+        self.albums.clear();
+        self.track_references.clear();
+        self.track_sizes.clear();
+        self.last_played.clear();
+        self.storage_settings = StorageSettings::default();
+        self.playback_speeds.clear();
+        self.download_waiting_tracks.clear();
+        self.tagging.clear();
+        let _ = self
+            .event_sender
+            .send(EventMessage::LibraryChanged(self.library.clone()))
+            .await;
+    }
+    async fn switch_library(&mut self, name: &str) -> anyhow::Result<()> {
+        if !self.download_managers.is_empty() || !self.audio_managers.is_empty() {
+            return Err(anyhow!(
+                "Stop all playback and downloads before switching libraries"
+            ));
+        }
+        let mut config = LibraryConfig::load();
+        let library = config
+            .library(name)
+            .ok_or(anyhow!("There is no library named '{name}'"))?;
+        // a library that couldn't be opened again can be retried
+        if library == self.library && self.database.is_some() {
+            return Ok(());
+        }
+        library.create_dirs()?;
+
+        // close the current library
+        let previous = std::mem::replace(&mut self.library, library);
+        self.close_library().await;
+
+        let result = self.load_library().await;
+        if let Err(e) = &result {
+            // go back to the library that was open
+            println!("Failed to open library '{name}': {e}");
+            self.library = previous.clone();
+            let _ = self
+                .event_sender
+                .send(EventMessage::LibraryChanged(previous))
+                .await;
+            if let Err(e) = self.load_library().await {
+                // leave it closed rather than half loaded; commands that need the database
+                // fail until a library is opened
+                println!(
+                    "Failed to open library '{}' again: {e}",
+                    self.library.name()
+                );
+                self.close_library().await;
+            }
+        }
+        let _ = self
+            .event_sender
+            .send(EventMessage::DownloadedTracksAdded(
+                self.downloaded_tracks.clone(),
+            ))
+            .await;
+
+        if result.is_ok() {
+            config.active = Some(name.to_string());
+            if let Err(e) = config.save() {
+                println!("Failed to save the active library: {e}");
+            }
+        }
+        result
+    }
+    // Moves an unpacked backup into the library, then loads the library again
+    async fn restore_staged(&mut self, staging: PathBuf, mode: RestoreMode) -> anyhow::Result<()> {
+        if !self.download_managers.is_empty() || !self.audio_managers.is_empty() {
+            return Err(anyhow!("Stop all playback and downloads before restoring"));
+        }
+        self.close_library().await;
+        let library = self.library.clone();
+        let result = match tokio::task::spawn_blocking(move || {
+            backup::apply_restore(&library, &staging, mode)
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(e) => Err(e.into()),
+        };
+        // whatever happened, load what's there now
+        let reloaded = self.load_library().await;
+        if let Err(e) = &reloaded {
+            println!("Failed to open the library after restoring: {e}");
+        }
+        let _ = self
+            .event_sender
+            .send(EventMessage::DownloadedTracksAdded(
+                self.downloaded_tracks.clone(),
+            ))
+            .await;
+        result.and(reloaded)
+    }
+    // Copies the database for a backup. Returns the copy and its schema version.
+    async fn snapshot_database(&self) -> anyhow::Result<(PathBuf, usize)> {
+        let database = self
+            .database
+            .clone()
+            .ok_or(anyhow!("The library isn't open"))?;
+        let snapshot = self.library.data_dir_path().join(BACKUP_SNAPSHOT_FILENAME);
+        tokio::task::spawn_blocking(move || {
+            // a leftover from a backup that didn't finish
+            if snapshot.exists() {
+                std::fs::remove_file(&snapshot)?;
+            }
+            database.snapshot_to(&snapshot)?;
+            Ok((snapshot, database.schema_version()?))
+        })
+        .await?
+    }
+    pub fn new(flags: PlaylistFlags) -> Self {
+        Self {
+            event_sender: flags.event_sender,
+            process_sender: flags.process_sender,
+            audio_sender: flags.audio_sender,
+            playlists: HashMap::new(),
+            tracks: HashMap::new(),
+            bin_files: None,
+            playlist_sender: flags.playlist_sender,
+            download_managers: HashMap::new(),
+            downloaded_tracks: HashSet::new(),
+            audio_managers: HashMap::new(),
+            download_waiting_tracks: HashMap::new(),
+            musicbrainz_client: None,
+            albums: HashMap::new(),
+            track_references: HashMap::new(),
+            track_sizes: HashMap::new(),
+            last_played: HashMap::new(),
+            storage_settings: StorageSettings::default(),
+            loudness_analysis: None,
+            normalization: NormalizationMode::default(),
+            trim_silence: false,
+            silence_pending: HashSet::new(),
+            tagging: HashMap::new(),
+            playback_speeds: HashMap::new(),
+            library: flags.library,
+            database: None,
+            reqwest_client: Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
+    fn name(&self) -> &'static str {
+        "PlaylistService"
+    }
+    async fn on_start(&mut self) -> anyhow::Result<()> {
+        // startup logic
+        // get the yt_dlp and ffmpeg file locations
+        let bin_files = file::util::get_bin_app_paths();
+        self.bin_files = Some(bin_files);
+
+        self.load_library()
+            .await
+            .expect("Failed to open library database");
+
         // get the music brains client
         let (tx, rx) = oneshot::channel();
         let _ = self
@@ -234,22 +781,25 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                     let (playlist, track_vec) = owned_playlist.unpack_to_playlist();
                     let mut new_tracks = HashMap::new();
                     for track in track_vec.0 {
+                        *self.track_references.entry(track.id().clone()).or_insert(0) += 1;
                         // if its not already in the cache, add it
-                        let mut changed = false;
                         if !self.tracks.contains_key(&track.id()) {
                             new_tracks.insert(track.id().clone(), track.clone());
                             self.tracks.insert(track.id().clone(), track);
-                            changed = true;
-                        }
-                        // save the tracklist
-                        if changed {
-                            let tracks_vec: Vec<Track> =
-                                self.tracks.clone().into_values().collect();
-                            let json = serde_json::to_string(&tracks_vec).unwrap();
-                            let path = file::util::get_saved_tracks_file_path().await.unwrap();
-                            fs::write(path, json).await.expect("Failed to save to file");
                         }
                     }
+                    // save only the new tracks, then the playlist that uses them
+                    let saved_tracks: Vec<Track> = new_tracks.values().cloned().collect();
+                    let saved_playlist = playlist.clone();
+                    let saved = self
+                        .write_db(move |db| {
+                            db.save_tracks(&saved_tracks)?;
+                            db.save_playlist(&saved_playlist)
+                        })
+                        .await;
+                    if let Err(e) = saved {
+                        println!("Failed to save playlist: {e}");
+                    }
 
                     // if any tracks changed, notify the gui
                     if new_tracks.len() > 0 {
@@ -264,19 +814,358 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
 
                     // Playlist saving
 
-                    // get playlist json
-                    let playlist_json = serde_json::to_string_pretty(&playlist).unwrap();
                     println!("playlist id in string: {}", playlist.id().to_string());
-                    // write to file
-                    let pth = file::util::playlist_file_path_from_id(&playlist.id());
-                    println!("{pth:?}");
-                    fs::write(pth.unwrap(), playlist_json).await.unwrap();
 
                     // insert playlist into cache
                     self.playlists.insert(playlist.id().clone(), playlist);
+                    self.send_storage_usage().await;
                     result_sender.send(Ok(())).unwrap();
                 }
             }
+            PlaylistMessage::ImportLocalFolder {
+                path,
+                options,
+                task_id,
+                reply_stream,
+            } => {
+                let playlist_sender_copy = self.playlist_sender.clone();
+                let library = self.library.clone();
+                tokio::spawn(async move {
+                    let (t_import_status, r_import_status) = mpsc::channel(100);
+                    reply_stream.send(r_import_status).unwrap();
+
+                    if let Err(e) = import_local_folder(
+                        path,
+                        options,
+                        task_id,
+                        library,
+                        playlist_sender_copy,
+                        &t_import_status,
+                    )
+                    .await
+                    {
+                        println!("local import failed: {e}");
+                        let _ = t_import_status
+                            .send(Message::LocalImportStatus {
+                                status: LocalImportStatus::Fail(e.to_string()),
+                                id: task_id,
+                            })
+                            .await;
+                    }
+                });
+            }
+            PlaylistMessage::ImportYtDlpArchive {
+                archive_path,
+                media_dir,
+                transfer_mode,
+                task_id,
+                reply_stream,
+            } => {
+                let playlist_sender_copy = self.playlist_sender.clone();
+                let library = self.library.clone();
+                tokio::spawn(async move {
+                    let (t_import_status, r_import_status) = mpsc::channel(100);
+                    reply_stream.send(r_import_status).unwrap();
+
+                    if let Err(e) = import_ytdlp_archive(
+                        archive_path,
+                        media_dir,
+                        transfer_mode,
+                        task_id,
+                        library,
+                        playlist_sender_copy,
+                        &t_import_status,
+                    )
+                    .await
+                    {
+                        println!("yt-dlp archive import failed: {e}");
+                        let _ = t_import_status
+                            .send(Message::LocalImportStatus {
+                                status: LocalImportStatus::Fail(e.to_string()),
+                                id: task_id,
+                            })
+                            .await;
+                    }
+                });
+            }
+            PlaylistMessage::ImportPlaylistFile {
+                path,
+                transfer_mode,
+                task_id,
+                reply_stream,
+            } => {
+                let playlist_sender_copy = self.playlist_sender.clone();
+                let library = self.library.clone();
+                tokio::spawn(async move {
+                    let (t_import_status, r_import_status) = mpsc::channel(100);
+                    reply_stream.send(r_import_status).unwrap();
+
+                    if let Err(e) = import_playlist_file(
+                        path,
+                        transfer_mode,
+                        task_id,
+                        library,
+                        playlist_sender_copy,
+                        &t_import_status,
+                    )
+                    .await
+                    {
+                        println!("playlist file import failed: {e}");
+                        let _ = t_import_status
+                            .send(Message::LocalImportStatus {
+                                status: LocalImportStatus::Fail(e.to_string()),
+                                id: task_id,
+                            })
+                            .await;
+                    }
+                });
+            }
+            PlaylistMessage::ExportPlaylist {
+                id,
+                tracklist,
+                destination,
+                format,
+                path_style,
+                result_sender,
+            } => match self.playlist_export_entries(&id, tracklist) {
+                Ok((title, tracks)) => {
+                    tokio::task::spawn_blocking(move || {
+                        let result =
+                            export_playlist_file(&destination, format, path_style, &title, &tracks)
+                                .map(|_| tracks.len());
+                        let _ = result_sender.send(result);
+                    });
+                }
+                Err(e) => {
+                    let _ = result_sender.send(Err(e));
+                }
+            },
+            PlaylistMessage::SyncPlaylists {
+                playlist_ids,
+                destination,
+                options,
+                task_id,
+                reply_stream,
+            } => {
+                // playlists without any downloaded tracks have nothing to sync
+                let playlists = playlist_ids
+                    .iter()
+                    .filter_map(|id| self.playlist_export_entries(id, None).ok())
+                    .collect();
+                let library = self.library.clone();
+                let transcoder = Transcoder {
+                    ffmpeg: self.bin_files.as_ref().unwrap().ffmpeg.clone(),
+                    process_sender: self.process_sender.clone(),
+                };
+                tokio::spawn(async move {
+                    let (t_sync_status, r_sync_status) = mpsc::channel(100);
+                    reply_stream.send(r_sync_status).unwrap();
+
+                    if let Err(e) = sync_playlists(
+                        playlists,
+                        destination,
+                        options,
+                        task_id,
+                        library,
+                        transcoder,
+                        &t_sync_status,
+                    )
+                    .await
+                    {
+                        println!("sync failed: {e}");
+                        let _ = t_sync_status
+                            .send(Message::LocalImportStatus {
+                                status: LocalImportStatus::Fail(e.to_string()),
+                                id: task_id,
+                            })
+                            .await;
+                    }
+                });
+            }
+            PlaylistMessage::BackupLibrary {
+                destination,
+                include_audio,
+                task_id,
+                reply_stream,
+            } => {
+                // snapshot now, so the backup matches the library as it is when it's requested
+                let snapshot = self.snapshot_database().await;
+                let library = self.library.clone();
+                tokio::spawn(async move {
+                    let (t_backup_status, r_backup_status) = mpsc::channel(100);
+                    reply_stream.send(r_backup_status).unwrap();
+
+                    let result = match snapshot {
+                        Ok((snapshot, schema_version)) => {
+                            backup_library(
+                                library,
+                                snapshot,
+                                schema_version,
+                                destination,
+                                include_audio,
+                                task_id,
+                                &t_backup_status,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
+                        println!("backup failed: {e}");
+                        let _ = t_backup_status
+                            .send(Message::LocalImportStatus {
+                                status: LocalImportStatus::Fail(e.to_string()),
+                                id: task_id,
+                            })
+                            .await;
+                    }
+                });
+            }
+            PlaylistMessage::RestoreLibrary {
+                archive,
+                mode,
+                task_id,
+                reply_stream,
+            } => {
+                let playlist_sender_copy = self.playlist_sender.clone();
+                let library = self.library.clone();
+                tokio::spawn(async move {
+                    let (t_restore_status, r_restore_status) = mpsc::channel(100);
+                    reply_stream.send(r_restore_status).unwrap();
+
+                    if let Err(e) = restore_library(
+                        archive,
+                        mode,
+                        task_id,
+                        library,
+                        playlist_sender_copy,
+                        &t_restore_status,
+                    )
+                    .await
+                    {
+                        println!("restore failed: {e}");
+                        let _ = t_restore_status
+                            .send(Message::LocalImportStatus {
+                                status: LocalImportStatus::Fail(e.to_string()),
+                                id: task_id,
+                            })
+                            .await;
+                    }
+                });
+            }
+            PlaylistMessage::RestoreStaged {
+                staging,
+                mode,
+                result_sender,
+            } => {
+                let result = self.restore_staged(staging, mode).await;
+                let _ = result_sender.send(result);
+            }
+            PlaylistMessage::GetTracks { ids, result_sender } => {
+                let tracks = ids
+                    .into_iter()
+                    .filter_map(|id| self.tracks.get(&id).map(|t| (id, t.clone())))
+                    .collect();
+                let _ = result_sender.send(tracks);
+            }
+            PlaylistMessage::GetLibraries { result_sender } => {
+                let _ = result_sender.send((LibraryConfig::load().all(), self.library.clone()));
+            }
+            PlaylistMessage::AddLibrary {
+                name,
+                path,
+                result_sender,
+            } => {
+                let mut config = LibraryConfig::load();
+                config.libraries.insert(name, path);
+                let _ = result_sender.send(config.save());
+            }
+            PlaylistMessage::SwitchLibrary {
+                name,
+                result_sender,
+            } => {
+                let result = self.switch_library(&name).await;
+                let _ = result_sender.send(result);
+            }
+            PlaylistMessage::ImportedPlaylistsDone {
+                playlists,
+                tracks,
+                downloaded_tracks,
+                result_sender,
+            } => {
+                let mut new_tracks = HashMap::new();
+                for track in tracks {
+                    // keep any existing (possibly richer) data for these tracks
+                    if !self.tracks.contains_key(track.id()) {
+                        new_tracks.insert(track.id().clone(), track.clone());
+                        self.tracks.insert(track.id().clone(), track);
+                    }
+                }
+                let mut added = Vec::new();
+                let mut saved_playlists = Vec::new();
+                for owned_playlist in playlists {
+                    let (playlist, track_vec) = owned_playlist.unpack_to_playlist();
+                    // re-importing the same folder replaces the playlist from last time
+                    if let Some(old_playlist) = self.playlists.remove(playlist.id()) {
+                        for track_id in old_playlist.tracks.0.iter() {
+                            if let Some(count) = self.track_references.get_mut(track_id) {
+                                *count = count.saturating_sub(1);
+                            }
+                        }
+                    }
+                    for track in track_vec.0 {
+                        *self.track_references.entry(track.id().clone()).or_insert(0) += 1;
+                        // the fresh copy has none of the loudness, trim, chapters or edits the
+                        // library's copy may have, so a track that's already there stays as is
+                        if !self.tracks.contains_key(track.id()) {
+                            new_tracks.insert(track.id().clone(), track.clone());
+                            self.tracks.insert(track.id().clone(), track);
+                        }
+                    }
+                    saved_playlists.push(playlist.clone());
+                    added.push(playlist.metadata.clone());
+                    self.playlists.insert(playlist.id().clone(), playlist);
+                }
+
+                let saved_tracks: Vec<Track> = new_tracks.values().cloned().collect();
+                let saved = self
+                    .write_db(move |db| {
+                        db.save_tracks(&saved_tracks)?;
+                        for playlist in &saved_playlists {
+                            db.save_playlist(playlist)?;
+                        }
+                        Ok(())
+                    })
+                    .await;
+                if let Err(e) = saved {
+                    println!("Failed to save imported playlists: {e}");
+                }
+                if !new_tracks.is_empty() {
+                    let _ = self
+                        .event_sender
+                        .send(EventMessage::TrackCacheUpdated {
+                            tracks_added: Some(new_tracks),
+                            tracks_removed: None,
+                        })
+                        .await;
+                }
+                let downloaded_tracks: HashSet<Id> = downloaded_tracks.into_iter().collect();
+                self.downloaded_tracks
+                    .extend(downloaded_tracks.iter().cloned());
+                let _ = self
+                    .event_sender
+                    .send(EventMessage::DownloadedTracksAdded(
+                        downloaded_tracks.clone(),
+                    ))
+                    .await;
+                self.record_track_sizes(downloaded_tracks.iter().cloned())
+                    .await;
+                self.queue_loudness_analysis(downloaded_tracks.iter());
+                let protected: Vec<Id> = downloaded_tracks.into_iter().collect();
+                self.enforce_storage_quota(&protected).await;
+                self.send_storage_usage().await;
+                let _ = result_sender.send(added);
+            }
             PlaylistMessage::RequestOwnedPlaylist { id, result_sender } => {
                 if let Some(playlist) = self.playlists.get(&id) {
                     let oplaylist = OwnedPlaylist::with_cache(
@@ -322,6 +1211,7 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                     process_sender,
                     bin_apps,
                     self.musicbrainz_client.clone().unwrap(),
+                    self.library.clone(),
                 );
 
                 self.download_managers.insert(id, (manager, reply_t));
@@ -356,6 +1246,19 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                 if success {
                     // update local downloaded cache
                     self.downloaded_tracks.insert(id.clone());
+                    if self.trim_silence {
+                        self.silence_pending.insert(id.clone());
+                    }
+
+                    // the track's metadata is final by now (identification happens before this)
+                    if let Some(track) = self.tracks.get(&id).cloned() {
+                        self.tag_track_file(track);
+                    } else {
+                        // nothing to tag, so account for the file now
+                        self.record_track_sizes([id.clone()]).await;
+                        self.enforce_storage_quota(std::slice::from_ref(&id)).await;
+                        self.send_storage_usage().await;
+                    }
 
                     // then send any oks to any waiting audio mgrs
                     if let Some(senders) = self.download_waiting_tracks.remove(&id) {
@@ -371,6 +1274,46 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                     .await
                     .unwrap();
             }
+            PlaylistMessage::TrackFileChanged { id } => {
+                if let Some(Some(track)) = self.tagging.remove(&id) {
+                    // the file's tagged again before anything else reads it
+                    self.tag_track_file(track);
+                    return;
+                }
+                // downloaded tracks are measured once they're tagged, so the file isn't being
+                // written while it's read
+                self.queue_loudness_analysis([&id]);
+                if self.silence_pending.remove(&id) {
+                    self.detect_silence(id.clone());
+                }
+                self.record_track_sizes([id.clone()]).await;
+                self.enforce_storage_quota(&[id]).await;
+                self.send_storage_usage().await;
+            }
+            PlaylistMessage::TrackPlayed { id } => {
+                let now = chrono::Utc::now().timestamp();
+                self.last_played.insert(id.clone(), now);
+                if let Err(e) = self.write_db(move |db| db.save_last_played(&id, now)).await {
+                    println!("Failed to save when a track was played: {e}");
+                }
+            }
+            PlaylistMessage::UpdateStorageSettings {
+                settings,
+                result_sender,
+            } => {
+                let saved = settings.clone();
+                if let Err(e) = self
+                    .write_db(move |db| db.save_storage_settings(&saved))
+                    .await
+                {
+                    let _ = result_sender.send(Err(e));
+                    return;
+                }
+                self.storage_settings = settings;
+                self.enforce_storage_quota(&[]).await;
+                self.send_storage_usage().await;
+                let _ = result_sender.send(Ok(()));
+            }
             PlaylistMessage::CheckTrackDownloaded { id, result_sender } => {
                 let downloaded = self.downloaded_tracks.contains(&id);
                 result_sender.send(downloaded).unwrap();
@@ -467,6 +1410,8 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                 tracklist,
                 data_sender,
                 volume,
+                transition,
+                resume,
             } => {
                 // create a playlist audio manager and immediately start playing it.
                 if !self.audio_managers.contains_key(&id) {
@@ -486,7 +1431,8 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                             }
                         }
                     };
-                    let mut mgr = PlaylistAudioManager::new(id.clone(), volume);
+                    let speed = self.playback_speeds.get(&id).copied().unwrap_or_default();
+                    let mut mgr = PlaylistAudioManager::new(id.clone(), volume, transition, speed);
 
                     mgr.run(
                         tracklist,
@@ -494,6 +1440,7 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                         self.playlist_sender.clone(),
                         self.audio_sender.clone(),
                         false,
+                        resume,
                     );
 
                     self.audio_managers.insert(id, (mgr, data_sender));
@@ -537,6 +1484,41 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                         .send(Err(anyhow!("Playlist audio manager does exist for id")));
                 }
             }
+            PlaylistMessage::FadeOutCurrentTrack {
+                playlist_id,
+                fade,
+                result_sender,
+            } => {
+                if let Some((mgr, _)) = self.audio_managers.get(&playlist_id) {
+                    let tween = Tween {
+                        duration: fade,
+                        ..Default::default()
+                    };
+                    for id in mgr.playing_track_ids() {
+                        let _ = self
+                            .audio_sender
+                            .send(AudioMessage::FadeOutAudio { id, tween })
+                            .await;
+                    }
+                    let _ = result_sender.send(Ok(()));
+                } else {
+                    let _ = result_sender
+                        .send(Err(anyhow!("Playlist audio manager does exist for id")));
+                }
+            }
+            PlaylistMessage::PauseAfterCurrentTrack {
+                playlist_id,
+                enabled,
+                result_sender,
+            } => {
+                if let Some((mgr, _)) = self.audio_managers.get_mut(&playlist_id) {
+                    mgr.set_pause_after_current(enabled);
+                    let _ = result_sender.send(Ok(()));
+                } else {
+                    let _ = result_sender
+                        .send(Err(anyhow!("Playlist audio manager does exist for id")));
+                }
+            }
             PlaylistMessage::ResumeCurrentTrack {
                 playlist_id,
                 result_sender,
@@ -679,6 +1661,33 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                     let _ = result_sender.send(Err(anyhow!("Playlist not loaded")));
                 }
             }
+            PlaylistMessage::SetPlaylistLoopRegion {
+                playlist_id,
+                region,
+                result_sender,
+            } => {
+                let track_id = match self.audio_managers.get(&playlist_id) {
+                    Some((mgr, _)) if mgr.loaded_track() => {
+                        mgr.get_current_track().unwrap().id().clone()
+                    }
+                    Some(_) => {
+                        let _ = result_sender.send(Err(anyhow!("No track currently loaded")));
+                        return;
+                    }
+                    None => {
+                        let _ = result_sender.send(Err(anyhow!("Playlist not loaded")));
+                        return;
+                    }
+                };
+                let _ = self
+                    .audio_sender
+                    .send(AudioMessage::SetAudioLoopRegion {
+                        id: track_id,
+                        region,
+                        result: result_sender,
+                    })
+                    .await;
+            }
             PlaylistMessage::TrackLooped {
                 maybe_playlist_id,
                 track_id,
@@ -719,17 +1728,219 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                 }
                 let _ = result_sender.send(Ok(()));
             }
+            PlaylistMessage::UpdateTrackTransition {
+                transition,
+                result_sender,
+            } => {
+                for (mgr, _) in self.audio_managers.values_mut() {
+                    mgr.update_transition(transition);
+                }
+                let _ = result_sender.send(Ok(()));
+            }
+            PlaylistMessage::UpdatePlaybackSpeed {
+                playlist_id,
+                speed,
+                result_sender,
+            } => {
+                let saved_id = playlist_id.clone();
+                if let Err(e) = self
+                    .write_db(move |db| db.save_playback_speed(&saved_id, &speed))
+                    .await
+                {
+                    let _ = result_sender.send(Err(e));
+                    return;
+                }
+                if speed == PlaybackSpeed::default() {
+                    self.playback_speeds.remove(&playlist_id);
+                } else {
+                    self.playback_speeds.insert(playlist_id.clone(), speed);
+                }
+                if let Some((mgr, _)) = self.audio_managers.get_mut(&playlist_id) {
+                    mgr.update_speed(speed);
+                    let _ = self
+                        .audio_sender
+                        .send(AudioMessage::SetPlaybackRate {
+                            maybe_playlist_id: Some(playlist_id),
+                            speed,
+                        })
+                        .await;
+                }
+                let _ = result_sender.send(Ok(()));
+            }
+            PlaylistMessage::SaveSession {
+                session,
+                result_sender,
+            } => {
+                let result = self
+                    .write_db(move |db| db.save_session(session.as_ref()))
+                    .await;
+                let _ = result_sender.send(result);
+            }
+            PlaylistMessage::UpdateNormalization {
+                mode,
+                result_sender,
+            } => {
+                self.normalization = mode;
+                let playing: Vec<Id> = self
+                    .audio_managers
+                    .values()
+                    .flat_map(|(mgr, _)| mgr.playing_track_ids())
+                    .collect();
+                for id in playing {
+                    let gain_db = self.track_gain(&id);
+                    let _ = self
+                        .audio_sender
+                        .send(AudioMessage::SetAudioGain { id, gain_db })
+                        .await;
+                }
+                let _ = result_sender.send(Ok(()));
+            }
+            PlaylistMessage::UpdateEffectSettings {
+                settings,
+                result_sender,
+            } => {
+                let saved = settings.clone();
+                if let Err(e) = self
+                    .write_db(move |db| db.save_effect_settings(&saved))
+                    .await
+                {
+                    let _ = result_sender.send(Err(e));
+                    return;
+                }
+                let _ = self
+                    .audio_sender
+                    .send(AudioMessage::SetEffectSettings { settings })
+                    .await;
+                let _ = result_sender.send(Ok(()));
+            }
+            PlaylistMessage::PreviewEffectPreset {
+                playlist_id,
+                preset,
+            } => {
+                let _ = self
+                    .audio_sender
+                    .send(AudioMessage::PreviewEffectPreset {
+                        playlist_id,
+                        preset,
+                    })
+                    .await;
+            }
+            PlaylistMessage::SetProgressCadence { cadence } => {
+                let _ = self
+                    .audio_sender
+                    .send(AudioMessage::SetProgressCadence { cadence })
+                    .await;
+            }
+            PlaylistMessage::UpdateSilenceTrimming {
+                enabled,
+                result_sender,
+            } => {
+                self.trim_silence = enabled;
+                let _ = result_sender.send(Ok(()));
+            }
+            PlaylistMessage::DetectTrackSilence { id, result_sender } => {
+                let path = match file::util::track_file_path_from_id(&self.library, &id) {
+                    Ok(path) if self.downloaded_tracks.contains(&id) => path,
+                    _ => {
+                        let _ = result_sender.send(Err(anyhow!("The track isn't downloaded")));
+                        return;
+                    }
+                };
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || silence::find_silence(&path))
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|r| r);
+                    let _ = result_sender.send(result);
+                });
+            }
+            PlaylistMessage::GetWaveform { id, result_sender } => {
+                let paths = file::util::track_file_path_from_id(&self.library, &id).and_then(
+                    |track_path| {
+                        Ok((
+                            track_path,
+                            file::util::waveform_path_from_id(&self.library, &id)?,
+                        ))
+                    },
+                );
+                let (track_path, cache_path) = match paths {
+                    Ok(paths) if self.downloaded_tracks.contains(&id) => paths,
+                    _ => {
+                        let _ = result_sender.send(Err(anyhow!("The track isn't downloaded")));
+                        return;
+                    }
+                };
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || {
+                        waveform::load_or_compute(&track_path, &cache_path)
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|r| r);
+                    let _ = result_sender.send(result);
+                });
+            }
+            PlaylistMessage::TrackSilenceFound { id, trim } => {
+                // trims set by hand are kept
+                if let Some(track) = self.tracks.get_mut(&id)
+                    && track.trim.is_untrimmed()
+                    && !trim.is_untrimmed()
+                {
+                    track.trim = trim;
+                    let track = track.clone();
+                    let saved = track.clone();
+                    if let Err(e) = self.write_db(move |db| db.save_track(&saved)).await {
+                        println!("Failed to save the trim of {}: {e}", track.title);
+                    }
+                    let _ = self
+                        .event_sender
+                        .send(EventMessage::TrackUpdated { track })
+                        .await;
+                }
+            }
+            PlaylistMessage::GetTrackGain { id, result_sender } => {
+                let _ = result_sender.send(self.track_gain(&id));
+            }
+            PlaylistMessage::TrackLoudnessAnalyzed { id, loudness } => {
+                if let Some(track) = self.tracks.get_mut(&id) {
+                    track.loudness = Some(loudness);
+                    let track = track.clone();
+                    let title = track.title.clone();
+                    if let Err(e) = self.write_db(move |db| db.save_track(&track)).await {
+                        println!("Failed to save the loudness of {title}: {e}");
+                    }
+                }
+            }
             PlaylistMessage::UpdateTrack {
                 playlist_id,
-                track,
+                mut track,
                 restart_audio,
                 restart_download,
             } => {
                 println!("track updated in playlist service");
                 match playlist_id {
                     None => {
+                        // the gui's copy might be from before the track was measured
+                        if track.loudness.is_none()
+                            && let Some(old_track) = self.tracks.get(track.id())
+                        {
+                            track.loudness = old_track.loudness.clone();
+                        }
+                        // or from before its chapters were found
+                        if track.chapters.is_empty()
+                            && let Some(old_track) = self.tracks.get(track.id())
+                        {
+                            track.chapters = old_track.chapters.clone();
+                        }
                         // replace the track
-                        self.tracks.insert(track.id().clone(), track.clone());
+                        let old_track = self.tracks.insert(track.id().clone(), track.clone());
+
+                        // retag the audio file if anything changed
+                        if old_track.as_ref() != Some(&track)
+                            && self.downloaded_tracks.contains(track.id())
+                        {
+                            self.tag_track_file(track.clone());
+                        }
 
                         // notify the gui
                         let mut hm = HashMap::new();
@@ -742,11 +1953,11 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                             })
                             .await;
 
-                        // save file
-                        let tracks_vec: Vec<Track> = self.tracks.clone().into_values().collect();
-                        let json = serde_json::to_string(&tracks_vec).unwrap();
-                        let path = file::util::get_saved_tracks_file_path().await.unwrap();
-                        fs::write(path, json).await.expect("Failed to save to file");
+                        // save the track
+                        let saved = track.clone();
+                        if let Err(e) = self.write_db(move |db| db.save_track(&saved)).await {
+                            println!("Failed to save track: {e}");
+                        }
 
                         // notify gui
                         let _ = self
@@ -774,10 +1985,12 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                 // if it isn't there, make sure to download it
                 let playlist_sender_clone = self.playlist_sender.clone();
                 let client = self.reqwest_client.clone();
+                let library = self.library.clone();
                 if !self.albums.contains_key(album.id()) {
                     // download the album
                     tokio::spawn(async move {
-                        let maybe_album = file::util::download_album(&album, &client).await;
+                        let maybe_album =
+                            file::util::download_album(&library, &album, &client).await;
                         match maybe_album {
                             Err(e) => {
                                 println!("An error occured while downloading an album cover: {e}");
@@ -796,14 +2009,24 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                 if !self.albums.contains_key(album.id()) {
                     println!("Album download successful");
                     self.albums.insert(album.id().clone(), album.clone());
-                    // save all the album data
-                    let albums_vec: Vec<&Album> = self.albums.values().collect();
-                    let file_text = serde_json::to_string(&albums_vec).unwrap();
-                    tokio::spawn(async {
-                        let album_data_path = file::util::get_album_data_file_path().await.unwrap();
-                        // write to the file
-                        let _ = fs::write(album_data_path, file_text).await;
-                    });
+                    // save the album data
+                    let saved = album.clone();
+                    if let Err(e) = self.write_db(move |db| db.save_album(&saved)).await {
+                        println!("Failed to save album: {e}");
+                    }
+                    // now that there's a cover, retag the downloaded tracks on the album
+                    let album_tracks: Vec<Track> = self
+                        .tracks
+                        .values()
+                        .filter(|t| {
+                            matches!(&t.album_kind, AlbumKind::Album(a) if a.id() == album.id())
+                                && self.downloaded_tracks.contains(t.id())
+                        })
+                        .cloned()
+                        .collect();
+                    for track in album_tracks {
+                        self.tag_track_file(track);
+                    }
                     // tell the gui event manager
                     let _ = self
                         .event_sender
@@ -829,6 +2052,145 @@ impl ServiceLogic<enums::PlaylistMessage> for PlaylistService {
                     let _ = result_sender.send(Err(anyhow!("Playing manager did not exist")));
                 }
             }
+            PlaylistMessage::GetPlaylistDeletionInfo { id, result_sender } => {
+                let Some(playlist) = self.playlists.get(&id) else {
+                    let _ = result_sender.send(None);
+                    return;
+                };
+                let unreferenced_tracks =
+                    util::unreferenced_tracks_after_removal(playlist, &self.track_references);
+                let unreferenced_albums =
+                    util::unreferenced_albums_after_removal(&unreferenced_tracks, &self.tracks);
+
+                // add up the size of everything that would be deleted
+                let mut freed_bytes = 0;
+                for track_id in unreferenced_tracks.iter() {
+                    if self.downloaded_tracks.contains(track_id)
+                        && let Ok(path) =
+                            file::util::track_file_path_from_id(&self.library, track_id)
+                    {
+                        freed_bytes += file::util::file_size(path).await;
+                    }
+                }
+                for album_id in unreferenced_albums.iter() {
+                    if self.albums.contains_key(album_id)
+                        && let Ok(path) =
+                            file::util::album_filename_from_id(&self.library, album_id)
+                    {
+                        freed_bytes += file::util::file_size(path).await;
+                    }
+                }
+
+                let _ = result_sender.send(Some(PlaylistDeletionInfo {
+                    metadata: playlist.metadata.clone(),
+                    unreferenced_tracks,
+                    unreferenced_albums,
+                    freed_size: DataSize::from_bytes(freed_bytes),
+                }));
+            }
+            PlaylistMessage::DeletePlaylist {
+                id,
+                delete_files,
+                result_sender,
+            } => {
+                let Some(playlist) = self.playlists.remove(&id) else {
+                    let _ = result_sender.send(Err(anyhow!("Playlist does not exist")));
+                    return;
+                };
+                println!("Deleting playlist {}", playlist.metadata.title);
+
+                // stop anything currently using this playlist
+                if let Some((mut mgr, _)) = self.download_managers.remove(&id) {
+                    mgr.stop();
+                }
+                if let Some((mut mgr, _)) = self.audio_managers.remove(&id) {
+                    mgr.cancel();
+                }
+
+                let deleted = id.clone();
+                if let Err(e) = self.write_db(move |db| db.delete_playlist(&deleted)).await {
+                    println!("Failed to delete playlist from database: {e}");
+                }
+
+                // decrement track references + collect tracks nothing references anymore
+                let mut removed_tracks =
+                    util::unreferenced_tracks_after_removal(&playlist, &self.track_references);
+                // tracks whose files are kept keep their metadata too, so the files are still
+                // counted against the quota (and can be evicted), and a playlist that adds the
+                // track again picks the file back up
+                if !delete_files {
+                    removed_tracks.retain(|id| !self.downloaded_tracks.contains(id));
+                }
+                let removed_albums =
+                    util::unreferenced_albums_after_removal(&removed_tracks, &self.tracks);
+                for track_id in playlist.tracks.0.iter() {
+                    if let Some(count) = self.track_references.get_mut(track_id) {
+                        *count = count.saturating_sub(1);
+                    }
+                }
+                for track_id in removed_tracks.iter() {
+                    self.track_references.remove(track_id);
+                    self.tracks.remove(track_id);
+                }
+                if !removed_tracks.is_empty() {
+                    let removed = removed_tracks.clone();
+                    if let Err(e) = self.write_db(move |db| db.remove_tracks(&removed)).await {
+                        println!("Failed to save tracks after playlist deletion: {e}");
+                    }
+                    let _ = self
+                        .event_sender
+                        .send(EventMessage::TrackCacheUpdated {
+                            tracks_added: None,
+                            tracks_removed: Some(removed_tracks.clone()),
+                        })
+                        .await;
+                }
+
+                // delete the audio + album covers if requested
+                let mut deleted_track_files = Vec::new();
+                let mut deleted_albums = Vec::new();
+                if delete_files {
+                    for track_id in removed_tracks {
+                        if self.downloaded_tracks.remove(&track_id) {
+                            match file::util::delete_track_file(&self.library, &track_id).await {
+                                Ok(_) => deleted_track_files.push(track_id),
+                                Err(e) => println!("Failed to delete track file: {e}"),
+                            }
+                        }
+                    }
+                    for album_id in removed_albums {
+                        if self.albums.remove(&album_id).is_some() {
+                            if let Err(e) =
+                                file::util::delete_album_cover(&self.library, &album_id).await
+                            {
+                                println!("Failed to delete album cover: {e}");
+                            }
+                            deleted_albums.push(album_id);
+                        }
+                    }
+                    let removed = deleted_albums.clone();
+                    if !removed.is_empty()
+                        && let Err(e) = self.write_db(move |db| db.remove_albums(&removed)).await
+                    {
+                        println!("Failed to save albums after playlist deletion: {e}");
+                    }
+                }
+
+                // the database drops the pin along with the playlist
+                self.storage_settings.pinned_playlists.remove(&id);
+                self.record_track_sizes(deleted_track_files.iter().cloned())
+                    .await;
+                let _ = self
+                    .event_sender
+                    .send(EventMessage::PlaylistDeleted {
+                        id,
+                        deleted_track_files,
+                        deleted_albums,
+                    })
+                    .await;
+                self.send_storage_usage().await;
+                let _ = result_sender.send(Ok(()));
+            }
         }
     }
 }
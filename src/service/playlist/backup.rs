@@ -0,0 +1,385 @@
+// Backing the library up to a single .tar.gz archive, and restoring it from one. Track metadata
+// can be hand corrected, so it can't always be regenerated from the audio files.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow, bail};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::service::{
+    file::{db, library::Library},
+    gui::{enums::Message, structs::TaskId},
+    playlist::{
+        PlaylistSender,
+        enums::{LocalImportStatus, PlaylistMessage, RestoreMode},
+    },
+};
+
+// always the first entry in the archive
+const MANIFEST_NAME: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+// restores are unpacked here first, so a bad archive never touches the library
+const STAGING_DIR: &str = ".restore";
+const RESTORE_LIBRARY_NAME: &str = "restore";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupManifest {
+    format_version: u32,
+    app_version: String,
+    created: String,
+    library_name: String,
+    schema_version: usize,
+    includes_audio: bool,
+    // path in the archive -> size in bytes
+    files: BTreeMap<String, u64>,
+}
+
+/// Writes the library to a .tar.gz archive at `destination`. `snapshot` is a copy of the
+/// database (see `Database::snapshot_to`), and is removed afterwards.
+pub async fn backup_library(
+    library: Library,
+    snapshot: PathBuf,
+    schema_version: usize,
+    destination: PathBuf,
+    include_audio: bool,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    let progress_sender = status_sender.clone();
+    let destination_copy = destination.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let result = write_backup(
+            &library,
+            &snapshot,
+            schema_version,
+            &destination_copy,
+            include_audio,
+            task_id,
+            &progress_sender,
+        );
+        let _ = std::fs::remove_file(&snapshot);
+        result
+    })
+    .await?;
+    result?;
+    let _ = status_sender
+        .send(Message::LocalImportStatus {
+            status: LocalImportStatus::BackupComplete(destination),
+            id: task_id,
+        })
+        .await;
+    Ok(())
+}
+
+/// Validates the archive, then merges it into or replaces the current library.
+pub async fn restore_library(
+    archive: PathBuf,
+    mode: RestoreMode,
+    task_id: TaskId,
+    library: Library,
+    playlist_sender: PlaylistSender,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    let staging = library.root().join(STAGING_DIR);
+    let progress_sender = status_sender.clone();
+    let staging_copy = staging.clone();
+    let extracted = tokio::task::spawn_blocking(move || {
+        extract_backup(&archive, &staging_copy, task_id, &progress_sender)
+    })
+    .await?;
+
+    let result = match extracted {
+        Ok(manifest) => {
+            println!(
+                "restoring backup of '{}' from {}",
+                manifest.library_name, manifest.created
+            );
+            // the playlist service has to close the library while it's being replaced
+            let (tx, rx) = oneshot::channel();
+            playlist_sender
+                .send(PlaylistMessage::RestoreStaged {
+                    staging: staging.clone(),
+                    mode,
+                    result_sender: tx,
+                })
+                .await?;
+            rx.await?
+        }
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    result?;
+    let _ = status_sender
+        .send(Message::LocalImportStatus {
+            status: LocalImportStatus::RestoreComplete,
+            id: task_id,
+        })
+        .await;
+    Ok(())
+}
+
+/// Moves a validated, unpacked backup into the library. The library's database must be closed.
+/// Blocking.
+pub fn apply_restore(library: &Library, staging: &Path, mode: RestoreMode) -> Result<()> {
+    let staged = Library::new(RESTORE_LIBRARY_NAME, staging.to_path_buf());
+    library.create_dirs()?;
+    match mode {
+        RestoreMode::Merge => {
+            db::Database::open(library.clone())?.merge_from(&staged.database_path())?;
+        }
+        RestoreMode::Replace => replace_database(library, &staged)?,
+    }
+    // album covers and audio files are named by id, so the same name is the same thing
+    for (from_dir, to_dir) in [
+        (staged.album_dir_path(), library.album_dir_path()),
+        (staged.track_dir_path(), library.track_dir_path()),
+    ] {
+        let Ok(entries) = std::fs::read_dir(&from_dir) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry?;
+            let destination = to_dir.join(entry.file_name());
+            if mode == RestoreMode::Merge && destination.exists() {
+                continue;
+            }
+            std::fs::rename(entry.path(), destination)?;
+        }
+    }
+    Ok(())
+}
+
+// Swaps the staged database in for the library's. The old one (with its -wal and -shm files) is
+// kept in the quarantine folder, just in case, and is put back if the swap fails.
+fn replace_database(library: &Library, staged: &Library) -> Result<()> {
+    let database_path = library.database_path();
+    let file_name = database_path
+        .file_name()
+        .ok_or(anyhow!("{} is not a file path", database_path.display()))?
+        .to_string_lossy()
+        .into_owned();
+    // get the staged database next to the live one first, so the swap is only renames in one
+    // folder
+    let incoming = database_path.with_file_name(format!(".{}.restore", file_name));
+    std::fs::rename(staged.database_path(), &incoming)?;
+
+    let quarantine_dir = library.quarantine_dir_path();
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+    // (where it was, where it's kept)
+    let mut moved = Vec::new();
+    let result = (|| -> Result<()> {
+        std::fs::create_dir_all(&quarantine_dir)?;
+        for suffix in ["", "-wal", "-shm"] {
+            let path = database_path.with_file_name(format!("{}{}", file_name, suffix));
+            if path.is_file() {
+                let destination = quarantine_dir
+                    .join(format!("{}-pre-restore-{}{}", timestamp, file_name, suffix));
+                std::fs::rename(&path, &destination)?;
+                moved.push((path, destination));
+            }
+        }
+        std::fs::rename(&incoming, &database_path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        for (path, destination) in moved.iter().rev() {
+            if let Err(e) = std::fs::rename(destination, path) {
+                println!("Failed to put back {}: {e}", path.display());
+            }
+        }
+        let _ = std::fs::remove_file(&incoming);
+    }
+    result
+}
+
+fn write_backup(
+    library: &Library,
+    snapshot: &Path,
+    schema_version: usize,
+    destination: &Path,
+    include_audio: bool,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    // (path in the archive, file on disk)
+    let mut files = vec![(
+        archive_name(library, &library.database_path())?,
+        snapshot.to_path_buf(),
+    )];
+    let mut dirs = vec![library.album_dir_path()];
+    if include_audio {
+        dirs.push(library.track_dir_path());
+    }
+    for dir in dirs {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            // skip temp files from unfinished writes
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if path.is_file() && !hidden {
+                files.push((archive_name(library, &path)?, path));
+            }
+        }
+    }
+    let mut manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created: chrono::Local::now().to_rfc3339(),
+        library_name: library.name().to_string(),
+        schema_version,
+        includes_audio: include_audio,
+        files: BTreeMap::new(),
+    };
+    for (name, path) in &files {
+        manifest
+            .files
+            .insert(name.clone(), std::fs::metadata(path)?.len());
+    }
+
+    let file_name = destination
+        .file_name()
+        .ok_or(anyhow!("{} is not a file path", destination.display()))?
+        .to_string_lossy();
+    let temp_path = destination.with_file_name(format!(".{}.tmp", file_name));
+    let result = (|| -> Result<()> {
+        let encoder = GzEncoder::new(File::create(&temp_path)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let manifest = serde_json::to_vec_pretty(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST_NAME, manifest.as_slice())?;
+
+        let total = files.len() as u32;
+        for (i, (name, path)) in files.iter().enumerate() {
+            builder.append_path_with_name(path, name)?;
+            let _ = status_sender.blocking_send(Message::LocalImportStatus {
+                status: LocalImportStatus::Progress {
+                    current: i as u32 + 1,
+                    total,
+                },
+                id: task_id,
+            });
+        }
+        let file = builder.into_inner()?.finish()?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, destination)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// Unpacks the archive into `staging`, checking every entry against the manifest along the way,
+// then checks the database. Blocking.
+fn extract_backup(
+    archive: &Path,
+    staging: &Path,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<BackupManifest> {
+    if staging.exists() {
+        std::fs::remove_dir_all(staging)?;
+    }
+    let staged = Library::new(RESTORE_LIBRARY_NAME, staging.to_path_buf());
+    staged.create_dirs()?;
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+    let mut entries = archive.entries()?;
+    let mut manifest_entry = entries.next().ok_or(anyhow!("The archive is empty"))??;
+    if manifest_entry.path()?.as_ref() != Path::new(MANIFEST_NAME) {
+        bail!("Not a library backup (no manifest)");
+    }
+    let mut manifest = String::new();
+    manifest_entry.read_to_string(&mut manifest)?;
+    let manifest: BackupManifest = serde_json::from_str(&manifest)?;
+    if manifest.format_version > FORMAT_VERSION {
+        bail!(
+            "The backup was made by a newer version (format {})",
+            manifest.format_version
+        );
+    }
+
+    let total = manifest.files.len() as u32;
+    let mut extracted = HashSet::new();
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let name = safe_archive_name(&path)
+            .ok_or(anyhow!("Unexpected file in the backup: {}", path.display()))?;
+        let expected_size = manifest
+            .files
+            .get(&name)
+            .ok_or(anyhow!("{} is not in the backup's manifest", name))?;
+        if !entry.header().entry_type().is_file() || entry.size() != *expected_size {
+            bail!("{} doesn't match the backup's manifest", name);
+        }
+        entry.unpack(staging.join(&path))?;
+        extracted.insert(name);
+        let _ = status_sender.blocking_send(Message::LocalImportStatus {
+            status: LocalImportStatus::Progress {
+                current: extracted.len() as u32,
+                total,
+            },
+            id: task_id,
+        });
+    }
+    if let Some(missing) = manifest
+        .files
+        .keys()
+        .find(|name| !extracted.contains(*name))
+    {
+        bail!("The backup is incomplete ({} is missing)", missing);
+    }
+    if !staged.database_path().is_file() {
+        bail!("The backup has no library database");
+    }
+    db::check_database(&staged)?;
+    Ok(manifest)
+}
+
+// A file's path in the archive, relative to the library root with `/` separators
+fn archive_name(library: &Library, path: &Path) -> Result<String> {
+    let relative = path
+        .strip_prefix(library.root())
+        .map_err(|_| anyhow!("{} is outside the library", path.display()))?;
+    Ok(relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+// Only `<data|album|track>/<file>` entries are allowed, so nothing can be written outside the
+// staging folder.
+fn safe_archive_name(path: &Path) -> Option<String> {
+    let components = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let staged = Library::new(RESTORE_LIBRARY_NAME, PathBuf::new());
+    let allowed_dirs = [
+        staged.data_dir_path(),
+        staged.album_dir_path(),
+        staged.track_dir_path(),
+    ];
+    match components.as_slice() {
+        [dir, _] if allowed_dirs.iter().any(|d| d == Path::new(dir)) => Some(components.join("/")),
+        _ => None,
+    }
+}
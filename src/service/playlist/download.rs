@@ -203,9 +203,14 @@ pub async fn download_track(
         })
         .await;
 
+    // only known once yt-dlp has the video's details
+    let mut chapters = Vec::new();
     while let Some(msg) = rx.recv().await {
         println!("Received msg from download: {msg:?}");
         let line = parse_output(msg, ExtractorContext::Download, Some(track));
+        if let ExtractorLineOut::DownloadTrackData(json) = &line {
+            chapters = json.chapters();
+        }
         let mut err = None;
         if let ExtractorLineOut::Error(e) = &line {
             // check to see if this was actually an error
@@ -244,8 +249,17 @@ pub async fn download_track(
             download_url: track.download_url.clone(),
             source_id: track.source_id.clone(),
             dyn_id: track.dyn_id.clone(),
+            loudness: track.loudness.clone(),
+            trim: track.trim,
+            chapters,
+            chapter_of: None,
         };
         Ok(Some(track))
+    } else if !chapters.is_empty() {
+        Ok(Some(Track {
+            chapters,
+            ..track.clone()
+        }))
     } else {
         Ok(None)
     }
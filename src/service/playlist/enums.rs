@@ -1,4 +1,9 @@
-use std::{collections::HashSet, process::ExitStatus};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
@@ -6,12 +11,23 @@ use tokio::sync::{mpsc, oneshot};
 use url::Url;
 
 use crate::service::{
-    audio::enums::LoopPolicy,
-    gui::{enums::Message, structs::PlaylistInitId},
+    audio::{
+        enums::{LoopPolicy, NormalizationMode, ProgressCadence},
+        structs::{
+            EffectPreset, EffectSettings, LoopRegion, Loudness, PlaybackSpeed, TrackTransition,
+            Trim, Waveform,
+        },
+    },
+    file::{library::Library, structs::LoadReport},
+    gui::{
+        enums::Message,
+        structs::{PlaylistInitId, TaskId},
+    },
     id::structs::Id,
     playlist::structs::{
-        Album, OwnedPlaylist, PlaylistMetadata, Track, TrackDownloadData, TrackDownloadJson,
-        Tracklist,
+        Album, ListeningSession, LocalImportOptions, LocalImportPreview, OwnedPlaylist,
+        PlaylistDeletionInfo, PlaylistMetadata, ResumePoint, StorageSettings, SyncOptions,
+        SyncSummary, Track, TrackDownloadData, TrackDownloadJson, Tracklist,
     },
 };
 
@@ -75,6 +91,9 @@ pub enum PlaylistMessage {
         // Sends individual track progress updates and when the playlist finishes.
         data_sender: mpsc::Sender<Message>,
         volume: f64,
+        transition: TrackTransition,
+        // start partway through (ie. a restored session), paused
+        resume: Option<ResumePoint>,
     },
     PauseCurrentTrack {
         playlist_id: Id,
@@ -85,6 +104,19 @@ pub enum PlaylistMessage {
         seek_location: Option<f32>,
         result_sender: oneshot::Sender<anyhow::Result<()>>,
     },
+    // Fades whatever the playlist is playing down to silence (for sleep timers). The volume comes
+    // back once the playlist's volume is set again.
+    FadeOutCurrentTrack {
+        playlist_id: Id,
+        fade: Duration,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Makes the playlist stop after the current track, by starting the next one paused.
+    PauseAfterCurrentTrack {
+        playlist_id: Id,
+        enabled: bool,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
     SkipCurrentTrack {
         playlist_id: Id,
         result_sender: oneshot::Sender<anyhow::Result<()>>,
@@ -123,6 +155,12 @@ pub enum PlaylistMessage {
         policy: LoopPolicy,
         result_sender: oneshot::Sender<anyhow::Result<()>>,
     },
+    // Loops part of the playlist's current track (an A–B loop); None stops it.
+    SetPlaylistLoopRegion {
+        playlist_id: Id,
+        region: Option<LoopRegion>,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
     TrackLooped {
         maybe_playlist_id: Option<Id>,
         track_id: Id,
@@ -131,6 +169,71 @@ pub enum PlaylistMessage {
         volume: f64,
         result_sender: oneshot::Sender<anyhow::Result<()>>,
     },
+    // Changes the gap/crossfade between tracks for every playing playlist.
+    UpdateTrackTransition {
+        transition: TrackTransition,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Changes (and remembers) how fast a playlist plays, including what it's playing right now.
+    UpdatePlaybackSpeed {
+        playlist_id: Id,
+        speed: PlaybackSpeed,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Remembers what's playing, to pick it up again next launch. None forgets it.
+    SaveSession {
+        session: Option<ListeningSession>,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Changes how tracks are loudness normalized, including the ones playing right now.
+    UpdateNormalization {
+        mode: NormalizationMode,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // The loudness normalization gain for a track about to be played. Given: the gain in dB.
+    GetTrackGain {
+        id: Id,
+        result_sender: oneshot::Sender<f64>,
+    },
+    // A track's audio was analyzed in the background; stores its loudness.
+    TrackLoudnessAnalyzed {
+        id: Id,
+        loudness: Loudness,
+    },
+    // Turns trimming the silence off of newly downloaded tracks on or off.
+    UpdateSilenceTrimming {
+        enabled: bool,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Finds the silence at the start and end of a downloaded track, without saving it.
+    DetectTrackSilence {
+        id: Id,
+        result_sender: oneshot::Sender<anyhow::Result<Trim>>,
+    },
+    // The waveform of a downloaded track, from its cache or decoded (and cached) if there's none.
+    GetWaveform {
+        id: Id,
+        result_sender: oneshot::Sender<anyhow::Result<Waveform>>,
+    },
+    // A new download's silence was found in the background; trims it unless it already is.
+    TrackSilenceFound {
+        id: Id,
+        trim: Trim,
+    },
+    // Saves the effect presets and applies them to everything playing.
+    UpdateEffectSettings {
+        settings: EffectSettings,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Plays a playlist through a preset while it's being edited (see `AudioMessage`).
+    PreviewEffectPreset {
+        playlist_id: Option<Id>,
+        preset: Option<EffectPreset>,
+    },
+    // How often playing audio reports its progress, depending on whether the window can be seen.
+    SetProgressCadence {
+        cadence: ProgressCadence,
+    },
     UpdateTrack {
         // Provide playlist id if the modification is for that playlist only.
         // Otherwise don't provide one
@@ -149,6 +252,123 @@ pub enum PlaylistMessage {
         id: Id,
         result_sender: oneshot::Sender<anyhow::Result<()>>,
     },
+    // Returns what deleting the given playlist would collect (None if the playlist doesn't exist).
+    GetPlaylistDeletionInfo {
+        id: Id,
+        result_sender: oneshot::Sender<Option<PlaylistDeletionInfo>>,
+    },
+    // Deletes the playlist and any track data no other playlist references.
+    // If `delete_files` is set, unreferenced audio files and album covers are deleted too.
+    DeletePlaylist {
+        id: Id,
+        delete_files: bool,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Starts a job importing the audio files in a local folder into the library.
+    ImportLocalFolder {
+        path: PathBuf,
+        options: LocalImportOptions,
+        task_id: TaskId,
+        reply_stream: oneshot::Sender<mpsc::Receiver<Message>>,
+    },
+    // Starts a job importing the media listed in a yt-dlp download archive.
+    // `media_dir` is where the media + info json files are; if None, the archive's folder is used.
+    ImportYtDlpArchive {
+        archive_path: PathBuf,
+        media_dir: Option<PathBuf>,
+        transfer_mode: ImportTransferMode,
+        task_id: TaskId,
+        reply_stream: oneshot::Sender<mpsc::Receiver<Message>>,
+    },
+    // Used by import jobs. Adds the imported playlists (and their tracks) to the library.
+    // `tracks` are imported tracks that don't belong to a playlist, and are only added if not already cached.
+    // `downloaded_tracks` are the tracks whose audio is now present in the track directory.
+    // Returns the metadata of every playlist that was added.
+    ImportedPlaylistsDone {
+        playlists: Vec<OwnedPlaylist>,
+        tracks: Vec<Track>,
+        downloaded_tracks: Vec<Id>,
+        result_sender: oneshot::Sender<Vec<PlaylistMetadata>>,
+    },
+    // Writes the playlist to a playlist file pointing at its downloaded tracks. Uses the given
+    // tracklist's order, or the saved order if None. Returns how many tracks were written.
+    ExportPlaylist {
+        id: Id,
+        tracklist: Option<Tracklist>,
+        destination: PathBuf,
+        format: PlaylistFileFormat,
+        path_style: PlaylistPathStyle,
+        result_sender: oneshot::Sender<anyhow::Result<usize>>,
+    },
+    // Starts a job importing a playlist file (m3u8, xspf or pls) as a playlist.
+    ImportPlaylistFile {
+        path: PathBuf,
+        transfer_mode: ImportTransferMode,
+        task_id: TaskId,
+        reply_stream: oneshot::Sender<mpsc::Receiver<Message>>,
+    },
+    // Returns the cached tracks out of the given ids; unknown ids are left out.
+    GetTracks {
+        ids: Vec<Id>,
+        result_sender: oneshot::Sender<HashMap<Id, Track>>,
+    },
+    // Starts a job mirroring the given playlists' downloaded tracks to a folder (ie. a phone or sd card).
+    SyncPlaylists {
+        playlist_ids: Vec<Id>,
+        destination: PathBuf,
+        options: SyncOptions,
+        task_id: TaskId,
+        reply_stream: oneshot::Sender<mpsc::Receiver<Message>>,
+    },
+    // Starts a job writing the library (and optionally its audio files) to a backup archive.
+    BackupLibrary {
+        destination: PathBuf,
+        include_audio: bool,
+        task_id: TaskId,
+        reply_stream: oneshot::Sender<mpsc::Receiver<Message>>,
+    },
+    // Starts a job restoring a backup archive into the current library.
+    RestoreLibrary {
+        archive: PathBuf,
+        mode: RestoreMode,
+        task_id: TaskId,
+        reply_stream: oneshot::Sender<mpsc::Receiver<Message>>,
+    },
+    // Sent by the restore job once the backup is validated and unpacked to `staging`; closes
+    // the library, moves the backup in, and loads it again.
+    RestoreStaged {
+        staging: PathBuf,
+        mode: RestoreMode,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // A track's audio file was tagged; records its size and enforces the quota.
+    TrackFileChanged {
+        id: Id,
+    },
+    // A track started playing. Used to pick what gets evicted when over the storage quota.
+    TrackPlayed {
+        id: Id,
+    },
+    // Sets the storage quota + pinned playlists, evicting tracks if the library is over the quota.
+    UpdateStorageSettings {
+        settings: StorageSettings,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Returns the named libraries in the config, and the library that's currently open.
+    GetLibraries {
+        result_sender: oneshot::Sender<(Vec<Library>, Library)>,
+    },
+    // Adds (or moves) a named library in the config.
+    AddLibrary {
+        name: String,
+        path: PathBuf,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
+    // Closes the current library and opens the named one. Fails if anything is playing or downloading.
+    SwitchLibrary {
+        name: String,
+        result_sender: oneshot::Sender<anyhow::Result<()>>,
+    },
 }
 
 #[derive(Debug, EnumString, Display, PartialEq, Eq, Hash, Clone, Deserialize, Serialize)]
@@ -183,6 +403,112 @@ pub enum PlaylistInitStatus {
     Duplicate(PlaylistMetadata),
 }
 
+#[derive(Debug, Clone)]
+pub enum LocalImportStatus {
+    Scanning {
+        found: u32,
+    },
+    Progress {
+        current: u32,
+        total: u32,
+    },
+    // Dry run finished; nothing was copied or saved.
+    Preview(LocalImportPreview),
+    // Given: the playlists that were added, and anything that was found but left out
+    Complete {
+        added: Vec<PlaylistMetadata>,
+        skipped: LoadReport,
+    },
+    // A sync to a folder finished
+    SyncComplete(SyncSummary),
+    // A backup archive was written. Given: where it was written to
+    BackupComplete(PathBuf),
+    RestoreComplete,
+    Fail(String),
+}
+
+// Playlist file formats that can be exported and imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFileFormat {
+    M3u8,
+    Xspf,
+    Pls,
+}
+impl PlaylistFileFormat {
+    /// Picks the format from the file's extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "m3u8" | "m3u" => Some(Self::M3u8),
+            "xspf" => Some(Self::Xspf),
+            "pls" => Some(Self::Pls),
+            _ => None,
+        }
+    }
+}
+
+// How track locations are written into exported playlist files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistPathStyle {
+    Absolute,
+    // relative to the folder the playlist file is in
+    Relative,
+}
+
+// How synced files are laid out in the destination folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncLayout {
+    // Artist/Album/Title.ext
+    ArtistAlbum,
+    // Playlist/NN - Title.ext
+    Playlist,
+}
+
+// What synced tracks are converted to. Not converting keeps the library's files as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncTranscode {
+    Mp3,
+    Opus,
+}
+impl SyncTranscode {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+        }
+    }
+    // ffmpeg encoder arguments
+    pub fn codec_args(&self) -> [&'static str; 4] {
+        match self {
+            Self::Mp3 => ["-c:a", "libmp3lame", "-q:a", "2"],
+            Self::Opus => ["-c:a", "libopus", "-b:a", "128k"],
+        }
+    }
+}
+
+// What a restore does with the library that's already there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreMode {
+    // Add the backup to the library; the backup wins where both have the same thing
+    Merge,
+    // Throw away the current library data (moved to quarantine) and use the backup's
+    Replace,
+}
+
+// How imported files are grouped into playlists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportGrouping {
+    Folder,
+    AlbumTag,
+}
+
+// How imported files are put into the track directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportTransferMode {
+    Copy,
+    HardLink,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 pub enum Artist {
     Community(String),
@@ -0,0 +1,639 @@
+// Module for importing audio that already exists on disk into the library
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use tokio::sync::{mpsc, oneshot};
+use url::Url;
+
+use crate::service::{
+    audio::{
+        decode,
+        enums::AlbumKind,
+        structs::{AudioFileInfo, Trim},
+    },
+    file::{
+        self,
+        library::Library,
+        structs::{LoadProblem, LoadReport},
+    },
+    gui::{enums::Message, structs::TaskId},
+    id::{enums::Platform, structs::Id},
+    playlist::{
+        PlaylistSender,
+        enums::{
+            Artist, ImportGrouping, ImportTransferMode, LocalImportStatus, MediaType,
+            PlaylistMessage,
+        },
+        structs::{
+            LocalImportOptions, LocalImportPreview, OwnedPlaylist, PlaylistMetadata, Track,
+            TrackVec, YtDlpInfoJson,
+        },
+    },
+};
+
+// how many found files to wait between scanning updates
+const SCAN_UPDATE_INTERVAL: u32 = 25;
+const UNKNOWN_ALBUM: &str = "Unknown Album";
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+const YOUTUBE_EXTRACTOR: &str = "youtube";
+const INFO_JSON_SUFFIX: &str = ".info.json";
+// containers yt-dlp often leaves audio in that tracks can't be played from (opus isn't decoded)
+const UNPLAYABLE_YTDLP_EXTENSIONS: [&str; 4] = ["opus", "webm", "weba", "aac"];
+
+// A file found while scanning along with the track built from it
+struct ScannedTrack {
+    source_path: PathBuf,
+    track: Track,
+    group_key: String,
+}
+
+pub async fn import_local_folder(
+    root: PathBuf,
+    options: LocalImportOptions,
+    task_id: TaskId,
+    library: Library,
+    playlist_sender: PlaylistSender,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    let root = tokio::fs::canonicalize(&root).await?;
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a folder", root.display()));
+    }
+
+    // scanning + tag reading is all blocking work
+    let scan_root = root.clone();
+    let scan_sender = status_sender.clone();
+    let (scanned, skipped_files) = tokio::task::spawn_blocking(move || {
+        scan_folder(&scan_root, options.grouping, task_id, &scan_sender)
+    })
+    .await??;
+
+    if options.dry_run {
+        let preview = LocalImportPreview {
+            playlists: group_playlists(&root, scanned.iter())
+                .into_iter()
+                .map(|p| p.metadata)
+                .collect(),
+            root,
+            options,
+            skipped_files,
+        };
+        let _ = status_sender
+            .send(Message::LocalImportStatus {
+                status: LocalImportStatus::Preview(preview),
+                id: task_id,
+            })
+            .await;
+        return Ok(());
+    }
+
+    // move the audio into the track directory
+    let files: Vec<(PathBuf, Id)> = scanned
+        .iter()
+        .map(|t| (t.source_path.clone(), t.track.id().clone()))
+        .collect();
+    let downloaded_tracks = transfer_tracks(
+        files,
+        options.transfer_mode,
+        &library,
+        task_id,
+        status_sender,
+    )
+    .await?;
+    // only the tracks whose audio made it into the library
+    let transferred: HashSet<&Id> = downloaded_tracks.iter().collect();
+    let playlists = group_playlists(
+        &root,
+        scanned
+            .iter()
+            .filter(|t| transferred.contains(t.track.id())),
+    );
+
+    // hand everything to the playlist service
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ImportedPlaylistsDone {
+            playlists,
+            tracks: Vec::new(),
+            downloaded_tracks,
+            result_sender: tx,
+        })
+        .await?;
+    let added = rx.await?;
+    let _ = status_sender
+        .send(Message::LocalImportStatus {
+            status: LocalImportStatus::Complete {
+                added,
+                skipped: LoadReport::default(),
+            },
+            id: task_id,
+        })
+        .await;
+    Ok(())
+}
+
+// Groups the tracks into playlists by their group key (BTreeMap to keep the order stable)
+fn group_playlists<'a>(
+    root: &Path,
+    scanned: impl Iterator<Item = &'a ScannedTrack>,
+) -> Vec<OwnedPlaylist> {
+    let mut groups: BTreeMap<&str, Vec<&ScannedTrack>> = BTreeMap::new();
+    for scanned_track in scanned {
+        groups
+            .entry(&scanned_track.group_key)
+            .or_default()
+            .push(scanned_track);
+    }
+    groups
+        .into_iter()
+        .map(|(group_key, tracks)| {
+            let tracks = TrackVec(tracks.into_iter().map(|t| t.track.clone()).collect());
+            let id = Id::new(
+                Platform::Local,
+                MediaType::Playlist,
+                stable_hash(&format!("{}|{}", root.display(), group_key)),
+            );
+            let metadata = PlaylistMetadata::new(
+                group_title(group_key),
+                tracks.track_count() as u64,
+                tracks.total_time(),
+                id.clone(),
+                id,
+            );
+            OwnedPlaylist::new(metadata, tracks)
+        })
+        .collect()
+}
+
+fn scan_folder(
+    root: &Path,
+    grouping: ImportGrouping,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<(Vec<ScannedTrack>, u32)> {
+    // collect every supported audio file first so progress can be reported
+    let mut paths = walk_folder(root, is_supported_audio_file, task_id, status_sender);
+    paths.sort();
+
+    let total = paths.len() as u32;
+    let mut scanned = Vec::new();
+    let mut skipped = 0;
+    for (i, path) in paths.into_iter().enumerate() {
+        let _ = status_sender.blocking_send(Message::LocalImportStatus {
+            status: LocalImportStatus::Progress {
+                current: i as u32 + 1,
+                total,
+            },
+            id: task_id,
+        });
+        match scan_file(root, &path, grouping) {
+            Ok(scanned_track) => scanned.push(scanned_track),
+            Err(e) => {
+                println!("Skipping {}: {e}", path.display());
+                skipped += 1;
+            }
+        }
+    }
+    Ok((scanned, skipped))
+}
+
+fn scan_file(root: &Path, path: &Path, grouping: ImportGrouping) -> Result<ScannedTrack> {
+    let (track, info) = local_track_from_file(path)?;
+
+    let group_key = match grouping {
+        ImportGrouping::Folder => {
+            // group by the folder relative to the import root
+            let parent = path.parent().unwrap_or(root);
+            let relative = parent.strip_prefix(root).unwrap_or(parent);
+            let root_name = root
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if relative.as_os_str().is_empty() {
+                root_name
+            } else {
+                format!("{}/{}", root_name, relative.to_string_lossy())
+            }
+        }
+        ImportGrouping::AlbumTag => info.album.unwrap_or(String::from(UNKNOWN_ALBUM)),
+    };
+
+    Ok(ScannedTrack {
+        source_path: path.to_path_buf(),
+        track,
+        group_key,
+    })
+}
+
+/// Builds a local track from an audio file's tags. The track id comes from the file's path, so
+/// importing the same file again gives the same track. Also returns the tags that were read.
+pub(super) fn local_track_from_file(path: &Path) -> Result<(Track, AudioFileInfo)> {
+    let info = decode::read_audio_file_info(path)?;
+    let url = Url::from_file_path(path).map_err(|_| anyhow!("path could not become a url"))?;
+    let id = Id::new(
+        Platform::Local,
+        MediaType::Track,
+        stable_hash(&path.to_string_lossy()),
+    );
+
+    let title = info.title.clone().unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let artist = if !info.artists.is_empty() {
+        Artist::Official(info.artists.clone())
+    } else if let Some(album_artist) = &info.album_artist {
+        Artist::Official(vec![album_artist.clone()])
+    } else {
+        Artist::Community(String::from(UNKNOWN_ARTIST))
+    };
+    let track = Track {
+        title,
+        length: info.duration.unwrap_or(Duration::ZERO),
+        artist,
+        album_kind: AlbumKind::Unknown,
+        source_id: id.clone(),
+        dyn_id: id,
+        download_url: url,
+        loudness: None,
+        trim: Trim::default(),
+        chapters: Vec::new(),
+        chapter_of: None,
+    };
+    Ok((track, info))
+}
+
+pub async fn import_ytdlp_archive(
+    archive_path: PathBuf,
+    media_dir: Option<PathBuf>,
+    transfer_mode: ImportTransferMode,
+    task_id: TaskId,
+    library: Library,
+    playlist_sender: PlaylistSender,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    let archive = tokio::fs::read_to_string(&archive_path).await?;
+    let archived_ids = parse_download_archive(&archive);
+    if archived_ids.is_empty() {
+        return Err(anyhow!("No youtube entries in {}", archive_path.display()));
+    }
+    let media_dir = match media_dir {
+        Some(dir) => dir,
+        None => archive_path
+            .parent()
+            .map(Path::to_path_buf)
+            .ok_or(anyhow!("Archive has no parent folder"))?,
+    };
+
+    let scan_sender = status_sender.clone();
+    let (matched, mut skipped) = tokio::task::spawn_blocking(move || {
+        match_archive_files(&media_dir, &archived_ids, task_id, &scan_sender)
+    })
+    .await?;
+
+    let files = matched
+        .iter()
+        .map(|(path, track)| (path.clone(), track.id().clone()))
+        .collect();
+    let downloaded_tracks =
+        transfer_tracks(files, transfer_mode, &library, task_id, status_sender).await?;
+    // only the tracks whose audio made it into the library
+    let transferred: HashSet<&Id> = downloaded_tracks.iter().collect();
+    let (tracks, failed): (Vec<Track>, Vec<Track>) = matched
+        .into_iter()
+        .map(|(_, track)| track)
+        .partition(|track| transferred.contains(track.id()));
+    for track in failed {
+        skipped.push(LoadProblem {
+            source: archive_entry_name(&track.id().id),
+            reason: String::from("Its audio file couldn't be copied into the library"),
+            quarantined_to: None,
+        });
+    }
+
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ImportedPlaylistsDone {
+            playlists: Vec::new(),
+            tracks,
+            downloaded_tracks,
+            result_sender: tx,
+        })
+        .await?;
+    let added = rx.await?;
+    let _ = status_sender
+        .send(Message::LocalImportStatus {
+            status: LocalImportStatus::Complete { added, skipped },
+            id: task_id,
+        })
+        .await;
+    Ok(())
+}
+
+fn archive_entry_name(video_id: &str) -> String {
+    format!("{YOUTUBE_EXTRACTOR} {video_id}")
+}
+
+// archive lines look like `youtube <video id>`
+fn parse_download_archive(archive: &str) -> HashSet<String> {
+    archive
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .filter(|(extractor, _)| extractor.eq_ignore_ascii_case(YOUTUBE_EXTRACTOR))
+        .map(|(_, id)| id.trim().to_string())
+        .collect()
+}
+
+// Pairs the archived video ids with their audio files, building tracks from the
+// info json sidecars when there are any and from the file tags otherwise. Also returns why each
+// archived video that couldn't be paired was skipped.
+fn match_archive_files(
+    media_dir: &Path,
+    archived_ids: &HashSet<String>,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> (Vec<(PathBuf, Track)>, LoadReport) {
+    let paths = walk_folder(
+        media_dir,
+        |p| is_supported_audio_file(p) || is_info_json(p) || is_unplayable_ytdlp_audio(p),
+        task_id,
+        status_sender,
+    );
+    let (info_paths, paths): (Vec<PathBuf>, Vec<PathBuf>) =
+        paths.into_iter().partition(|p| is_info_json(p));
+    let (unplayable_paths, audio_paths): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .into_iter()
+        .partition(|p| is_unplayable_ytdlp_audio(p));
+
+    let mut matched: HashMap<String, (PathBuf, Track)> = HashMap::new();
+    let total = info_paths.len() as u32;
+    for (i, info_path) in info_paths.iter().enumerate() {
+        let _ = status_sender.blocking_send(Message::LocalImportStatus {
+            status: LocalImportStatus::Progress {
+                current: i as u32 + 1,
+                total,
+            },
+            id: task_id,
+        });
+        let info: YtDlpInfoJson = match fs::read_to_string(info_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| Ok(serde_json::from_str(&s)?))
+        {
+            Ok(info) => info,
+            Err(e) => {
+                println!("Skipping {}: {e}", info_path.display());
+                continue;
+            }
+        };
+        if !archived_ids.contains(&info.id)
+            || info
+                .extractor_key
+                .as_ref()
+                .is_some_and(|k| !k.eq_ignore_ascii_case(YOUTUBE_EXTRACTOR))
+        {
+            continue;
+        }
+        // yt-dlp names the sidecar after the media file: `<name>.info.json` + `<name>.<ext>`
+        let name = info_path
+            .to_string_lossy()
+            .trim_end_matches(INFO_JSON_SUFFIX)
+            .to_string();
+        let audio_path = audio_paths
+            .iter()
+            .find(|p| p.with_extension("").to_string_lossy() == name)
+            .or_else(|| find_audio_by_id(&audio_paths, &info.id));
+        let Some(audio_path) = audio_path.cloned() else {
+            continue;
+        };
+        let id = info.id.clone();
+        match Track::from_ytdlp_info_json(info) {
+            Ok(track) => {
+                matched.insert(id, (audio_path, track));
+            }
+            Err(e) => println!("Skipping {}: {e}", info_path.display()),
+        }
+    }
+
+    // archived videos that had no info json
+    for video_id in archived_ids.iter() {
+        if matched.contains_key(video_id) {
+            continue;
+        }
+        let Some(audio_path) = find_audio_by_id(&audio_paths, video_id) else {
+            continue;
+        };
+        match track_from_tags(audio_path, video_id) {
+            Ok(track) => {
+                matched.insert(video_id.clone(), (audio_path.clone(), track));
+            }
+            Err(e) => println!("Skipping {}: {e}", audio_path.display()),
+        }
+    }
+
+    let mut skipped = LoadReport::default();
+    let mut unmatched: Vec<&String> = archived_ids
+        .iter()
+        .filter(|id| !matched.contains_key(*id))
+        .collect();
+    unmatched.sort();
+    for video_id in unmatched {
+        let reason = match find_audio_by_id(&unplayable_paths, video_id) {
+            Some(path) => format!(
+                "{} is in a format that can't be played",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            None => String::from("No audio file was found for it"),
+        };
+        skipped.push(LoadProblem {
+            source: archive_entry_name(video_id),
+            reason,
+            quarantined_to: None,
+        });
+    }
+    (matched.into_values().collect(), skipped)
+}
+
+// yt-dlp's default output template puts the id in brackets: `<title> [<id>].<ext>`
+fn find_audio_by_id<'a>(audio_paths: &'a [PathBuf], video_id: &str) -> Option<&'a PathBuf> {
+    let bracketed = format!("[{}]", video_id);
+    audio_paths.iter().find(|p| {
+        p.file_stem()
+            .map(|s| s.to_string_lossy())
+            .is_some_and(|s| s == video_id || s.ends_with(&bracketed))
+    })
+}
+
+fn track_from_tags(path: &Path, video_id: &str) -> Result<Track> {
+    let info = decode::read_audio_file_info(path)?;
+    let id = Id::new(Platform::Youtube, MediaType::Track, video_id.to_string());
+    let title = info.title.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let artist = if !info.artists.is_empty() {
+        Artist::Official(info.artists)
+    } else {
+        Artist::Community(String::from(UNKNOWN_ARTIST))
+    };
+    Ok(Track {
+        title,
+        length: info.duration.unwrap_or(Duration::ZERO),
+        artist,
+        album_kind: AlbumKind::Unknown,
+        source_id: id.clone(),
+        dyn_id: id,
+        download_url: Url::parse(&format!("https://www.youtube.com/watch?v={}", video_id))?,
+        loudness: None,
+        trim: Trim::default(),
+        chapters: Vec::new(),
+        chapter_of: None,
+    })
+}
+
+// Recursively collects every file under `root` that passes `filter`, sending scanning updates.
+// Symlinked folders are followed, but each folder is only walked once, so a link back up the tree
+// can't loop forever.
+fn walk_folder(
+    root: &Path,
+    filter: impl Fn(&Path) -> bool,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut visited = HashSet::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        match fs::canonicalize(&directory) {
+            Ok(canonical) => {
+                if !visited.insert(canonical) {
+                    continue;
+                }
+            }
+            Err(e) => {
+                println!("Failed to resolve directory {}: {e}", directory.display());
+                continue;
+            }
+        }
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("Failed to read directory {}: {e}", directory.display());
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if filter(&path) {
+                paths.push(path);
+                if (paths.len() as u32).is_multiple_of(SCAN_UPDATE_INTERVAL) {
+                    let _ = status_sender.blocking_send(Message::LocalImportStatus {
+                        status: LocalImportStatus::Scanning {
+                            found: paths.len() as u32,
+                        },
+                        id: task_id,
+                    });
+                }
+            }
+        }
+    }
+    paths
+}
+
+pub(super) fn is_supported_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(file::util::is_supported_track_extension)
+}
+
+fn is_unplayable_ytdlp_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| UNPLAYABLE_YTDLP_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+fn is_info_json(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(INFO_JSON_SUFFIX)
+}
+
+// Copies (or links) each file to its track's location. Returns the tracks whose audio is now in place.
+pub(super) async fn transfer_tracks(
+    files: Vec<(PathBuf, Id)>,
+    transfer_mode: ImportTransferMode,
+    library: &Library,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<Vec<Id>> {
+    let total = files.len() as u32;
+    let mut downloaded_tracks = Vec::new();
+    for (i, (source, id)) in files.into_iter().enumerate() {
+        let _ = status_sender
+            .send(Message::LocalImportStatus {
+                status: LocalImportStatus::Progress {
+                    current: i as u32 + 1,
+                    total,
+                },
+                id: task_id,
+            })
+            .await;
+        let extension = source
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or(file::util::track_output_extension())
+            .to_lowercase();
+        let destination = file::util::track_file_path_with_extension(library, &id, &extension)?;
+        // don't touch tracks that already have audio
+        if !destination.is_file() && !file::util::track_file_path_from_id(library, &id)?.is_file() {
+            let source_copy = source.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                transfer_file(&source_copy, &destination, transfer_mode)
+            })
+            .await?;
+            if let Err(e) = result {
+                println!("Failed to import {}: {e}", source.display());
+                continue;
+            }
+        }
+        downloaded_tracks.push(id);
+    }
+    Ok(downloaded_tracks)
+}
+
+fn transfer_file(source: &Path, destination: &Path, mode: ImportTransferMode) -> Result<()> {
+    if let ImportTransferMode::HardLink = mode {
+        // hard links can't cross filesystems; fall back to copying if it fails
+        if fs::hard_link(source, destination).is_ok() {
+            return Ok(());
+        }
+    }
+    file::util::copy_atomic(source, destination)?;
+    Ok(())
+}
+
+// folder groups use the last path component as their title
+fn group_title(group_key: &str) -> String {
+    group_key
+        .rsplit('/')
+        .next()
+        .unwrap_or(group_key)
+        .to_string()
+}
+
+/// 64 bit FNV-1a hash as hex. Used to make ids for local files that stay the same between runs.
+pub fn stable_hash(s: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in s.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
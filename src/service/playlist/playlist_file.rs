@@ -0,0 +1,660 @@
+// Exporting playlists to, and importing them from, m3u8 / xspf / pls playlist files
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use tokio::sync::{mpsc, oneshot};
+use url::Url;
+
+use crate::service::{
+    file::{self, library::Library, structs::LoadReport},
+    gui::{enums::Message, structs::TaskId},
+    id::{enums::Platform, structs::Id},
+    playlist::{
+        PlaylistSender,
+        enums::{
+            ImportTransferMode, LocalImportStatus, MediaType, PlaylistFileFormat, PlaylistMessage,
+            PlaylistPathStyle,
+        },
+        import::{self, stable_hash},
+        structs::{OwnedPlaylist, PlaylistMetadata, Track, TrackVec},
+    },
+};
+
+lazy_static::lazy_static! {
+    static ref RE_XSPF_TRACK: Regex = Regex::new(r"(?s)<track\b[^>]*>(.*?)</track>").unwrap();
+    static ref RE_XSPF_TRACKLIST: Regex = Regex::new(r"(?s)<trackList\b.*</trackList>").unwrap();
+    static ref RE_XSPF_LOCATION: Regex = Regex::new(r"(?s)<location>(.*?)</location>").unwrap();
+    static ref RE_XSPF_TITLE: Regex = Regex::new(r"(?s)<title>(.*?)</title>").unwrap();
+    static ref RE_XSPF_CREATOR: Regex = Regex::new(r"(?s)<creator>(.*?)</creator>").unwrap();
+    // `File1=...`, `Title1=...`, `Length1=...`
+    static ref RE_PLS_ENTRY: Regex = Regex::new(r"(?i)^(file|title|length)(\d+)=(.*)$").unwrap();
+}
+
+// An entry read from a playlist file
+struct PlaylistFileEntry {
+    location: PathBuf,
+    title: Option<String>,
+}
+
+/// Writes the given tracks (with the paths of their audio files) to a playlist file.
+/// Blocking; run in a blocking task.
+pub fn export_playlist_file(
+    destination: &Path,
+    format: PlaylistFileFormat,
+    path_style: PlaylistPathStyle,
+    title: &str,
+    tracks: &[(Track, PathBuf)],
+) -> Result<()> {
+    let playlist_dir = destination
+        .parent()
+        .ok_or(anyhow!("{} has no parent folder", destination.display()))?;
+    let playlist_dir = std::fs::canonicalize(playlist_dir)?;
+    let locations = tracks
+        .iter()
+        .map(|(_, path)| {
+            // the library root isn't necessarily absolute
+            let path = std::fs::canonicalize(path).unwrap_or(path.clone());
+            match path_style {
+                PlaylistPathStyle::Absolute => path,
+                // fall back to absolute paths if there's no way to get there relatively (ie. another drive)
+                PlaylistPathStyle::Relative => relative_path(&playlist_dir, &path).unwrap_or(path),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let contents = render_playlist_file(format, title, tracks, &locations)?;
+    file::util::write_atomic(destination, contents.as_bytes())?;
+    Ok(())
+}
+
+/// Renders the contents of a playlist file. `locations` are the paths written for each track,
+/// either absolute or relative to the playlist file.
+pub fn render_playlist_file(
+    format: PlaylistFileFormat,
+    title: &str,
+    tracks: &[(Track, PathBuf)],
+    locations: &[PathBuf],
+) -> Result<String> {
+    let mut out = String::new();
+    match format {
+        PlaylistFileFormat::M3u8 => {
+            writeln!(out, "#EXTM3U")?;
+            writeln!(out, "#PLAYLIST:{}", title)?;
+            for ((track, _), location) in tracks.iter().zip(locations) {
+                writeln!(
+                    out,
+                    "#EXTINF:{},{} - {}",
+                    track.length.as_secs(),
+                    track.artist.clone().artist(),
+                    track.title
+                )?;
+                writeln!(out, "{}", location.display())?;
+            }
+        }
+        PlaylistFileFormat::Xspf => {
+            writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(
+                out,
+                r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#
+            )?;
+            writeln!(out, "  <title>{}</title>", escape_xml(title))?;
+            writeln!(out, "  <trackList>")?;
+            for ((track, _), location) in tracks.iter().zip(locations) {
+                let location = if location.is_absolute() {
+                    Url::from_file_path(location)
+                        .map_err(|_| anyhow!("{} could not become a url", location.display()))?
+                        .to_string()
+                } else {
+                    encode_uri_path(location)
+                };
+                writeln!(out, "    <track>")?;
+                writeln!(out, "      <location>{}</location>", escape_xml(&location))?;
+                writeln!(out, "      <title>{}</title>", escape_xml(&track.title))?;
+                writeln!(
+                    out,
+                    "      <creator>{}</creator>",
+                    escape_xml(&track.artist.clone().artist())
+                )?;
+                writeln!(
+                    out,
+                    "      <duration>{}</duration>",
+                    track.length.as_millis()
+                )?;
+                writeln!(out, "    </track>")?;
+            }
+            writeln!(out, "  </trackList>")?;
+            writeln!(out, "</playlist>")?;
+        }
+        PlaylistFileFormat::Pls => {
+            writeln!(out, "[playlist]")?;
+            for (i, ((track, _), location)) in tracks.iter().zip(locations).enumerate() {
+                let n = i + 1;
+                writeln!(out, "File{}={}", n, location.display())?;
+                writeln!(
+                    out,
+                    "Title{}={} - {}",
+                    n,
+                    track.artist.clone().artist(),
+                    track.title
+                )?;
+                writeln!(out, "Length{}={}", n, track.length.as_secs())?;
+            }
+            writeln!(out, "NumberOfEntries={}", tracks.len())?;
+            writeln!(out, "Version=2")?;
+        }
+    }
+    Ok(out)
+}
+
+/// Imports a playlist file as a playlist. Entries that point at tracks already in the library
+/// (ie. files exported by peanut) become those tracks; other files are imported as local tracks.
+pub async fn import_playlist_file(
+    path: PathBuf,
+    transfer_mode: ImportTransferMode,
+    task_id: TaskId,
+    library: Library,
+    playlist_sender: PlaylistSender,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    let path = tokio::fs::canonicalize(&path).await?;
+    let format = PlaylistFileFormat::from_path(&path).ok_or(anyhow!(
+        "{} is not a m3u8, xspf or pls file",
+        path.display()
+    ))?;
+    let contents = tokio::fs::read_to_string(&path).await?;
+    let playlist_dir = path
+        .parent()
+        .ok_or(anyhow!("{} has no parent folder", path.display()))?;
+    let (title, entries) = match format {
+        PlaylistFileFormat::M3u8 => parse_m3u8(&contents, playlist_dir),
+        PlaylistFileFormat::Xspf => parse_xspf(&contents, playlist_dir),
+        PlaylistFileFormat::Pls => parse_pls(&contents, playlist_dir),
+    };
+    if entries.is_empty() {
+        return Err(anyhow!("No local files in {}", path.display()));
+    }
+
+    // files named after a track id map back to that track
+    let ids = entries
+        .iter()
+        .filter_map(|e| track_id_from_path(&e.location))
+        .collect();
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::GetTracks {
+            ids,
+            result_sender: tx,
+        })
+        .await?;
+    let known_tracks = rx.await?;
+
+    // tag reading is blocking work
+    let resolve_sender = status_sender.clone();
+    let known_ids: HashSet<Id> = known_tracks.keys().cloned().collect();
+    let (tracks, files) = tokio::task::spawn_blocking(move || {
+        resolve_entries(entries, known_tracks, task_id, &resolve_sender)
+    })
+    .await?;
+    if tracks.is_empty() {
+        return Err(anyhow!(
+            "None of the files in {} could be found",
+            path.display()
+        ));
+    }
+    let downloaded_tracks =
+        import::transfer_tracks(files, transfer_mode, &library, task_id, status_sender).await?;
+    // new tracks are only kept if their file made it into the library
+    let tracks: Vec<Track> = tracks
+        .into_iter()
+        .filter(|t| known_ids.contains(t.id()) || downloaded_tracks.contains(t.id()))
+        .collect();
+    if tracks.is_empty() {
+        return Err(anyhow!(
+            "None of the files in {} could be imported",
+            path.display()
+        ));
+    }
+
+    let title = title.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let id = Id::new(
+        Platform::Local,
+        MediaType::Playlist,
+        stable_hash(&path.to_string_lossy()),
+    );
+    let tracks = TrackVec(tracks);
+    let metadata = PlaylistMetadata::new(
+        title,
+        tracks.track_count() as u64,
+        tracks.total_time(),
+        id.clone(),
+        id,
+    );
+
+    let (tx, rx) = oneshot::channel();
+    playlist_sender
+        .send(PlaylistMessage::ImportedPlaylistsDone {
+            playlists: vec![OwnedPlaylist::new(metadata, tracks)],
+            tracks: Vec::new(),
+            downloaded_tracks,
+            result_sender: tx,
+        })
+        .await?;
+    let added = rx.await?;
+    let _ = status_sender
+        .send(Message::LocalImportStatus {
+            status: LocalImportStatus::Complete {
+                added,
+                skipped: LoadReport::default(),
+            },
+            id: task_id,
+        })
+        .await;
+    Ok(())
+}
+
+// Turns the entries into tracks (in order), along with the files that need to be brought into
+// the library for them.
+fn resolve_entries(
+    entries: Vec<PlaylistFileEntry>,
+    known_tracks: HashMap<Id, Track>,
+    task_id: TaskId,
+    status_sender: &mpsc::Sender<Message>,
+) -> (Vec<Track>, Vec<(PathBuf, Id)>) {
+    let total = entries.len() as u32;
+    let mut tracks = Vec::new();
+    let mut files = Vec::new();
+    for (i, entry) in entries.into_iter().enumerate() {
+        let _ = status_sender.blocking_send(Message::LocalImportStatus {
+            status: LocalImportStatus::Progress {
+                current: i as u32 + 1,
+                total,
+            },
+            id: task_id,
+        });
+        if let Some(track) =
+            track_id_from_path(&entry.location).and_then(|id| known_tracks.get(&id))
+        {
+            // bring the audio along if the library doesn't have it yet
+            if entry.location.is_file() {
+                files.push((entry.location, track.id().clone()));
+            }
+            tracks.push(track.clone());
+            continue;
+        }
+        if !entry.location.is_file() || !import::is_supported_audio_file(&entry.location) {
+            println!(
+                "Skipping {} ({}): not a supported audio file",
+                entry.location.display(),
+                entry.title.as_deref().unwrap_or("untitled")
+            );
+            continue;
+        }
+        match import::local_track_from_file(&entry.location) {
+            Ok((track, _)) => {
+                files.push((entry.location, track.id().clone()));
+                tracks.push(track);
+            }
+            Err(e) => println!("Skipping {}: {e}", entry.location.display()),
+        }
+    }
+    (tracks, files)
+}
+
+fn parse_m3u8(contents: &str, playlist_dir: &Path) -> (Option<String>, Vec<PlaylistFileEntry>) {
+    let mut title = None;
+    let mut entries = Vec::new();
+    // the #EXTINF line applies to the location after it
+    let mut entry_title = None;
+    for line in contents.lines().map(str::trim) {
+        if let Some(playlist_title) = line.strip_prefix("#PLAYLIST:") {
+            title = Some(playlist_title.trim().to_string());
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            entry_title = info.split_once(',').map(|(_, t)| t.trim().to_string());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            if let Some(location) = resolve_location(line, playlist_dir, false) {
+                entries.push(PlaylistFileEntry {
+                    location,
+                    title: entry_title.take(),
+                });
+            }
+            entry_title = None;
+        }
+    }
+    (title, entries)
+}
+
+fn parse_xspf(contents: &str, playlist_dir: &Path) -> (Option<String>, Vec<PlaylistFileEntry>) {
+    // the playlist title is the one outside of the track list
+    let outside_tracks = RE_XSPF_TRACKLIST.replace(contents, "");
+    let title = RE_XSPF_TITLE
+        .captures(&outside_tracks)
+        .map(|c| unescape_xml(c[1].trim()));
+    let entries = RE_XSPF_TRACK
+        .captures_iter(contents)
+        .filter_map(|track| {
+            let track = &track[1];
+            let location = RE_XSPF_LOCATION.captures(track)?;
+            let location = resolve_location(&unescape_xml(location[1].trim()), playlist_dir, true)?;
+            let title = RE_XSPF_TITLE.captures(track).map(|c| {
+                let title = unescape_xml(c[1].trim());
+                match RE_XSPF_CREATOR.captures(track) {
+                    Some(creator) => format!("{} - {}", unescape_xml(creator[1].trim()), title),
+                    None => title,
+                }
+            });
+            Some(PlaylistFileEntry { location, title })
+        })
+        .collect();
+    (title, entries)
+}
+
+fn parse_pls(contents: &str, playlist_dir: &Path) -> (Option<String>, Vec<PlaylistFileEntry>) {
+    // entries are numbered and can be in any order
+    let mut numbered: BTreeMap<u32, (Option<String>, Option<String>)> = BTreeMap::new();
+    for line in contents.lines().map(str::trim) {
+        let Some(captures) = RE_PLS_ENTRY.captures(line) else {
+            continue;
+        };
+        let Ok(n) = captures[2].parse::<u32>() else {
+            continue;
+        };
+        let value = captures[3].trim().to_string();
+        let entry = numbered.entry(n).or_default();
+        match captures[1].to_lowercase().as_str() {
+            "file" => entry.0 = Some(value),
+            "title" => entry.1 = Some(value),
+            _ => {}
+        }
+    }
+    let entries = numbered
+        .into_values()
+        .filter_map(|(location, title)| {
+            let location = resolve_location(&location?, playlist_dir, false)?;
+            Some(PlaylistFileEntry { location, title })
+        })
+        .collect();
+    (None, entries)
+}
+
+// Turns a location in a playlist file into a local path. Remote locations (ie. http) are skipped.
+// `is_uri` is set for formats where relative locations are percent encoded (xspf).
+fn resolve_location(location: &str, playlist_dir: &Path, is_uri: bool) -> Option<PathBuf> {
+    if location.starts_with("file:") {
+        return Url::parse(location).ok()?.to_file_path().ok();
+    }
+    if location.contains("://") {
+        return None;
+    }
+    let path = if is_uri {
+        PathBuf::from(decode_uri_path(location))
+    } else {
+        PathBuf::from(location)
+    };
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        Some(playlist_dir.join(path))
+    }
+}
+
+fn track_id_from_path(path: &Path) -> Option<Id> {
+    let stem = path.file_stem()?.to_string_lossy().into_owned();
+    Id::from_string(stem)
+        .ok()
+        .filter(|id| id.media_type == MediaType::Track)
+}
+
+// Path from `from_dir` to `to`. Both need to be absolute. None if there's no relative path
+// between them (ie. they're on different drives).
+fn relative_path(from_dir: &Path, to: &Path) -> Option<PathBuf> {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    // the root (and drive prefix on windows) has to match
+    if from.first() != to.first() {
+        return None;
+    }
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    Some(relative)
+}
+
+// percent encodes a relative path for use as a uri reference, always using `/` as separator
+fn encode_uri_path(path: &Path) -> String {
+    let joined = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut encoded = String::new();
+    for byte in joined.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+fn decode_uri_path(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::service::{audio::enums::AlbumKind, playlist::enums::Artist};
+
+    fn track(title: &str, artist: &str, seconds: u64) -> Track {
+        let id = Id::new(Platform::Local, MediaType::Track, stable_hash(title));
+        Track {
+            title: title.to_string(),
+            length: Duration::from_secs(seconds),
+            artist: Artist::Community(artist.to_string()),
+            album_kind: AlbumKind::Unknown,
+            source_id: id.clone(),
+            dyn_id: id,
+            download_url: Url::parse("file:///music").unwrap(),
+            loudness: None,
+            trim: Default::default(),
+            chapters: Vec::new(),
+            chapter_of: None,
+        }
+    }
+
+    // Renders the tracks at `locations` and parses them back, as if the file was in `/playlists`
+    fn round_trip(
+        format: PlaylistFileFormat,
+        locations: &[&str],
+    ) -> (Option<String>, Vec<PlaylistFileEntry>) {
+        let tracks: Vec<(Track, PathBuf)> = [
+            track("First & <Best>", "Some \"Band\"", 61),
+            track("Second", "Another Band", 200),
+            track("Third", "Ünïcode Ärtist", 5),
+        ]
+        .into_iter()
+        .map(|t| (t, PathBuf::new()))
+        .collect();
+        let locations: Vec<PathBuf> = locations.iter().map(PathBuf::from).collect();
+        let contents = render_playlist_file(format, "Mix & Match", &tracks, &locations).unwrap();
+        let playlist_dir = Path::new("/playlists");
+        match format {
+            PlaylistFileFormat::M3u8 => parse_m3u8(&contents, playlist_dir),
+            PlaylistFileFormat::Xspf => parse_xspf(&contents, playlist_dir),
+            PlaylistFileFormat::Pls => parse_pls(&contents, playlist_dir),
+        }
+    }
+
+    const LOCATIONS: [&str; 3] = [
+        "/music/First & Best.m4a",
+        "../music/with space/Second #2.mp3",
+        "Third%.flac",
+    ];
+    const RESOLVED: [&str; 3] = [
+        "/music/First & Best.m4a",
+        "/playlists/../music/with space/Second #2.mp3",
+        "/playlists/Third%.flac",
+    ];
+
+    fn locations(entries: &[PlaylistFileEntry]) -> Vec<PathBuf> {
+        entries.iter().map(|e| e.location.clone()).collect()
+    }
+
+    fn titles(entries: &[PlaylistFileEntry]) -> Vec<Option<String>> {
+        entries.iter().map(|e| e.title.clone()).collect()
+    }
+
+    fn expected_titles() -> Vec<Option<String>> {
+        vec![
+            Some("Some \"Band\" - First & <Best>".to_string()),
+            Some("Another Band - Second".to_string()),
+            Some("Ünïcode Ärtist - Third".to_string()),
+        ]
+    }
+
+    #[test]
+    fn m3u8_round_trip() {
+        let (title, entries) = round_trip(PlaylistFileFormat::M3u8, &LOCATIONS);
+        assert_eq!(title.as_deref(), Some("Mix & Match"));
+        assert_eq!(locations(&entries), RESOLVED.map(PathBuf::from).to_vec());
+        assert_eq!(titles(&entries), expected_titles());
+    }
+
+    #[test]
+    fn xspf_round_trip() {
+        let (title, entries) = round_trip(PlaylistFileFormat::Xspf, &LOCATIONS);
+        assert_eq!(title.as_deref(), Some("Mix & Match"));
+        assert_eq!(locations(&entries), RESOLVED.map(PathBuf::from).to_vec());
+        assert_eq!(titles(&entries), expected_titles());
+    }
+
+    #[test]
+    fn pls_round_trip() {
+        // pls has no playlist title; it comes from the file name instead
+        let (title, entries) = round_trip(PlaylistFileFormat::Pls, &LOCATIONS);
+        assert_eq!(title, None);
+        assert_eq!(locations(&entries), RESOLVED.map(PathBuf::from).to_vec());
+        assert_eq!(titles(&entries), expected_titles());
+    }
+
+    #[test]
+    fn xspf_locations_are_urls() {
+        let tracks = vec![(track("Song", "Band", 1), PathBuf::new())];
+        let contents = render_playlist_file(
+            PlaylistFileFormat::Xspf,
+            "Songs",
+            &tracks,
+            &[PathBuf::from("/music/a b/Song & Co.m4a")],
+        )
+        .unwrap();
+        assert!(
+            contents.contains("<location>file:///music/a%20b/Song%20&amp;%20Co.m4a</location>")
+        );
+
+        let relative = render_playlist_file(
+            PlaylistFileFormat::Xspf,
+            "Songs",
+            &tracks,
+            &[PathBuf::from("a b/Song#1.m4a")],
+        )
+        .unwrap();
+        assert!(relative.contains("<location>a%20b/Song%231.m4a</location>"));
+    }
+
+    #[test]
+    fn remote_entries_are_skipped() {
+        let m3u8 = "#EXTM3U\n#EXTINF:10,Radio\nhttp://example.com/stream\n#EXTINF:20,Band - Song\nsong.mp3\n";
+        let (_, entries) = parse_m3u8(m3u8, Path::new("/playlists"));
+        assert_eq!(
+            locations(&entries),
+            vec![PathBuf::from("/playlists/song.mp3")]
+        );
+        // the remote entry's title doesn't carry over to the next entry
+        assert_eq!(titles(&entries), vec![Some("Band - Song".to_string())]);
+    }
+
+    #[test]
+    fn pls_entries_are_read_in_number_order() {
+        let pls = "[playlist]\nFile2=/b.mp3\nTitle1=First\nfile1=/a.mp3\nNumberOfEntries=2\n";
+        let (_, entries) = parse_pls(pls, Path::new("/playlists"));
+        assert_eq!(
+            locations(&entries),
+            vec![PathBuf::from("/a.mp3"), PathBuf::from("/b.mp3")]
+        );
+        assert_eq!(titles(&entries), vec![Some("First".to_string()), None]);
+    }
+
+    #[test]
+    fn exported_track_files_map_back_to_their_tracks() {
+        let id = Id::new(Platform::Youtube, MediaType::Track, "abc".to_string());
+        let path = PathBuf::from(format!("/library/track/{}.m4a", id));
+        assert_eq!(track_id_from_path(&path), Some(id));
+        assert_eq!(track_id_from_path(Path::new("/music/song.m4a")), None);
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative_path(Path::new("/a/b"), Path::new("/a/c/d.m4a")),
+            Some(PathBuf::from("../c/d.m4a"))
+        );
+        assert_eq!(
+            relative_path(Path::new("/a"), Path::new("/a/d.m4a")),
+            Some(PathBuf::from("d.m4a"))
+        );
+    }
+}
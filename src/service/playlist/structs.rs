@@ -1,17 +1,19 @@
 use anyhow::anyhow;
 use atomic_float::AtomicF64;
 use futures::FutureExt;
+use kira::Tween;
 use musicbrainz_rs::MusicBrainzClient;
 use parking_lot::Mutex;
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
     sync::{
         Arc,
         atomic::{AtomicBool, AtomicU64, Ordering},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::sync::{Notify, mpsc, oneshot, watch};
 use tokio_util::sync::CancellationToken;
@@ -21,22 +23,28 @@ use crate::service::{
     audio::{
         AudioSender,
         enums::{AlbumKind, AudioMessage},
-        structs::AudioConfig,
+        structs::{AudioConfig, Loudness, PlaybackSpeed, TrackTransition, Trim},
     },
     file::{
-        self,
+        library::Library,
         structs::{BinApps, DataSize},
     },
     gui::enums::Message,
     id::{enums::Platform, structs::Id},
     playlist::{
         PlaylistSender, download,
-        enums::{Artist, DownloadEndType, ExtractorLineOut, MediaType, PlaylistMessage},
+        enums::{
+            Artist, DownloadEndType, ExtractorLineOut, ImportGrouping, ImportTransferMode,
+            MediaType, PlaylistMessage, SyncLayout, SyncTranscode,
+        },
         util,
     },
     process::ProcessSender,
 };
 
+// how far into a chapter going back restarts it, rather than going to the one before
+const CHAPTER_RESTART_WINDOW: Duration = Duration::from_secs(3);
+
 // --- PLAYLIST STRUCTS --- //
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -48,6 +56,18 @@ impl TrackVec {
     pub fn track_count(&self) -> usize {
         self.0.len()
     }
+    // Swaps every track with chapters for a track per chapter
+    pub fn split_chapters(&self) -> TrackVec {
+        TrackVec(
+            self.0
+                .iter()
+                .flat_map(|track| match track.chapter_tracks() {
+                    chapters if chapters.is_empty() => vec![track.clone()],
+                    chapters => chapters,
+                })
+                .collect(),
+        )
+    }
     pub fn total_time(&self) -> Duration {
         let mut total = Duration::from_secs(0);
         for t in &self.0 {
@@ -94,6 +114,9 @@ impl PlaylistMetadata {
     pub fn id(&self) -> &Id {
         &self.dyn_id
     }
+    pub fn source_id(&self) -> &Id {
+        &self.source_id
+    }
 }
 
 /// Standard `Playlist` struct. Does NOT own its tracks.
@@ -163,6 +186,52 @@ pub struct Track {
     pub source_id: Id,
     pub dyn_id: Id,
     pub download_url: Url,
+    // measured once the audio is downloaded; None until then
+    #[serde(default)]
+    pub loudness: Option<Loudness>,
+    // how much of the start and end is skipped when it plays
+    #[serde(default)]
+    pub trim: Trim,
+    // reported by yt-dlp for long videos (ie. full albums, mixes)
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    // set when this is one chapter of another track, played as a track of its own
+    #[serde(skip)]
+    pub chapter_of: Option<ChapterOf>,
+}
+
+/// A named section of a track.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct Chapter {
+    pub title: String,
+    pub start: Duration,
+    pub end: Duration,
+}
+impl Chapter {
+    // Chapters from yt-dlp's json; ones with no length are dropped
+    pub fn from_ytdlp_chapters(chapters: Option<Vec<YtDlpChapterJson>>) -> Vec<Self> {
+        chapters
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, c)| c.end_time > c.start_time)
+            .map(|(i, c)| Self {
+                title: c.title.unwrap_or_else(|| format!("Chapter {}", i + 1)),
+                start: Duration::from_secs_f64(c.start_time.max(0.0)),
+                end: Duration::from_secs_f64(c.end_time),
+            })
+            .collect()
+    }
+    pub fn length(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+/// Which track a chapter track plays part of.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChapterOf {
+    pub parent: Id,
+    pub chapter: Chapter,
 }
 
 impl Track {
@@ -176,11 +245,102 @@ impl Track {
             source_id: id.clone(),
             dyn_id: id,
             download_url: ptj.url,
+            loudness: None,
+            trim: Trim::default(),
+            chapters: Vec::new(),
+            chapter_of: None,
         }
     }
+    pub fn from_ytdlp_info_json(info: YtDlpInfoJson) -> anyhow::Result<Self> {
+        let url = match info.webpage_url {
+            Some(url) => url,
+            None => Url::parse(&format!("https://www.youtube.com/watch?v={}", info.id))?,
+        };
+        let artist = match (info.artist, info.channel.or(info.uploader)) {
+            (Some(artist), _) => Artist::Official(vec![artist]),
+            (None, Some(channel)) => Artist::Community(channel),
+            (None, None) => Artist::Community(String::from("Unknown Artist")),
+        };
+        let id = Id::new(Platform::Youtube, MediaType::Track, info.id);
+        Ok(Self {
+            title: info.title,
+            length: Duration::from_secs_f64(info.duration.unwrap_or(0.0).max(0.0)),
+            artist,
+            album_kind: AlbumKind::Unknown,
+            source_id: id.clone(),
+            dyn_id: id,
+            download_url: url,
+            loudness: None,
+            trim: Trim::default(),
+            chapters: Chapter::from_ytdlp_chapters(info.chapters),
+            chapter_of: None,
+        })
+    }
     pub fn id(&self) -> &Id {
         &self.dyn_id
     }
+    // The track whose audio file this plays; itself, unless it's a chapter of another track
+    pub fn file_id(&self) -> &Id {
+        self.chapter_of
+            .as_ref()
+            .map_or(&self.dyn_id, |chapter_of| &chapter_of.parent)
+    }
+    // Each chapter as a track of its own. Nothing if there's only one chapter
+    pub fn chapter_tracks(&self) -> Vec<Track> {
+        if self.chapters.len() < 2 {
+            return Vec::new();
+        }
+        self.chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| Track {
+                title: chapter.title.clone(),
+                length: chapter.length(),
+                dyn_id: Id::new(
+                    self.dyn_id.platform.clone(),
+                    self.dyn_id.media_type.clone(),
+                    format!("{}#{}", self.dyn_id.id, i + 1),
+                ),
+                // the track's trim is outside of most chapters
+                trim: Trim::default(),
+                chapters: Vec::new(),
+                chapter_of: Some(ChapterOf {
+                    parent: self.dyn_id.clone(),
+                    chapter: chapter.clone(),
+                }),
+                ..self.clone()
+            })
+            .collect()
+    }
+    // The part of its audio file that plays: its chapter, or what's left after the trim
+    pub fn played_section(&self, file_length: Duration) -> (Duration, Duration) {
+        match &self.chapter_of {
+            Some(chapter_of) => (chapter_of.chapter.start, chapter_of.chapter.end),
+            None => self
+                .trim
+                .bounds(file_length)
+                .unwrap_or((Duration::ZERO, file_length)),
+        }
+    }
+    // The chapter `position` is in
+    pub fn chapter_at(&self, position: Duration) -> Option<usize> {
+        self.chapters.iter().rposition(|c| c.start <= position)
+    }
+    // Where the chapter after `position` starts
+    pub fn next_chapter_start(&self, position: Duration) -> Option<Duration> {
+        let next = self.chapter_at(position).map_or(0, |i| i + 1);
+        self.chapters.get(next).map(|c| c.start)
+    }
+    // Where the chapter `position` is in starts, or the one before if it just started
+    pub fn previous_chapter_start(&self, position: Duration) -> Option<Duration> {
+        let current = self.chapter_at(position)?;
+        let chapter = &self.chapters[current];
+        if position - chapter.start > CHAPTER_RESTART_WINDOW || current == 0 {
+            Some(chapter.start)
+        } else {
+            Some(self.chapters[current - 1].start)
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
@@ -197,6 +357,67 @@ impl Album {
     }
 }
 
+/// Describes what would be collected if a playlist were deleted.
+#[derive(Debug, Clone)]
+pub struct PlaylistDeletionInfo {
+    pub metadata: PlaylistMetadata,
+    // tracks that no other playlist references
+    pub unreferenced_tracks: Vec<Id>,
+    // albums whose covers are only used by the unreferenced tracks
+    pub unreferenced_albums: Vec<Id>,
+    // how much space deleting the unreferenced audio + album covers would free
+    pub freed_size: DataSize,
+}
+
+/// The library's storage quota, and the playlists whose tracks are never evicted to stay under it.
+#[derive(Debug, Clone, Default)]
+pub struct StorageSettings {
+    pub quota: Option<DataSize>,
+    pub pinned_playlists: HashSet<Id>,
+}
+
+/// How much space the library's audio files take up.
+#[derive(Debug, Clone)]
+pub struct StorageUsage {
+    pub total: DataSize,
+    // the downloaded tracks of each playlist (tracks in several playlists count for each)
+    pub playlists: HashMap<Id, DataSize>,
+    pub settings: StorageSettings,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LocalImportOptions {
+    pub grouping: ImportGrouping,
+    pub transfer_mode: ImportTransferMode,
+    // if set, only scan + report what would be imported
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SyncOptions {
+    pub layout: SyncLayout,
+    // None copies the files as they are
+    pub transcode: Option<SyncTranscode>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncSummary {
+    pub written: u32,
+    pub deleted: u32,
+    pub unchanged: u32,
+    pub failed: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalImportPreview {
+    pub root: PathBuf,
+    pub options: LocalImportOptions,
+    // the playlists that would be created
+    pub playlists: Vec<PlaylistMetadata>,
+    // files that looked like audio but couldn't be read
+    pub skipped_files: u32,
+}
+
 #[derive(Debug, Deserialize)]
 // created on playlist initialization
 pub struct PlaylistTrackJson {
@@ -208,9 +429,39 @@ pub struct PlaylistTrackJson {
     id: String,
 }
 
+#[derive(Debug, Deserialize)]
+// `.info.json` sidecar written by yt-dlp's `--write-info-json`
+pub struct YtDlpInfoJson {
+    pub id: String,
+    title: String,
+    duration: Option<f64>,
+    channel: Option<String>,
+    uploader: Option<String>,
+    // only present for music videos
+    artist: Option<String>,
+    webpage_url: Option<Url>,
+    pub extractor_key: Option<String>,
+    chapters: Option<Vec<YtDlpChapterJson>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+// one of the `chapters` in yt-dlp's json
+pub struct YtDlpChapterJson {
+    start_time: f64,
+    end_time: f64,
+    title: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 // created on track download
-pub struct TrackDownloadJson {}
+pub struct TrackDownloadJson {
+    chapters: Option<Vec<YtDlpChapterJson>>,
+}
+impl TrackDownloadJson {
+    pub fn chapters(&self) -> Vec<Chapter> {
+        Chapter::from_ytdlp_chapters(self.chapters.clone())
+    }
+}
 
 #[derive(Debug)]
 pub struct DownloadTrackJson {}
@@ -273,6 +524,16 @@ impl TrackOrder {
     pub fn sort(&mut self) {
         self.index_order.sort_unstable();
     }
+    // An order saved earlier. None if it isn't an order of `length` tracks (ie. the playlist
+    // changed since)
+    pub fn from_saved(index_order: Vec<u64>, length: usize) -> Option<Self> {
+        let mut sorted = index_order.clone();
+        sorted.sort_unstable();
+        sorted
+            .into_iter()
+            .eq(0..length as u64)
+            .then_some(Self { index_order })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -324,6 +585,26 @@ impl Tracklist {
             Some(&self.tracks.0[index])
         }
     }
+    pub fn order(&self) -> &TrackOrder {
+        &self.order
+    }
+}
+
+/// Where in a tracklist to start playing from.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct ResumePoint {
+    // index into the track order, not the playlist
+    pub track_index: u64,
+    pub position: Duration,
+}
+
+/// What was playing when the app was last closed, so it can be picked up again.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ListeningSession {
+    pub playlist_id: Id,
+    // the (maybe shuffled) track order
+    pub order: Vec<u64>,
+    pub resume: ResumePoint,
 }
 
 pub struct PlaylistDownloadManager {
@@ -360,6 +641,7 @@ impl PlaylistDownloadManager {
         process_sender: ProcessSender,
         bin_apps: BinApps,
         musicbrainz_client: MusicBrainzClient,
+        library: Library,
     ) {
         if self.dead() {
             return;
@@ -444,7 +726,7 @@ impl PlaylistDownloadManager {
                     // send the request
                     playlist_sender_clone
                         .send(PlaylistMessage::CheckTrackDownloaded {
-                            id: track.id().clone(),
+                            id: track.file_id().clone(),
                             result_sender: downloaded_t,
                         })
                         .await
@@ -460,7 +742,7 @@ impl PlaylistDownloadManager {
                     // Track Download Start message
                     gui_reply_stream_clone
                         .send(Message::TrackDownloadStarted {
-                            id: track.id().clone(),
+                            id: track.file_id().clone(),
                             data: TrackDownloadData::only_track(track.clone()),
                         })
                         .await
@@ -469,9 +751,9 @@ impl PlaylistDownloadManager {
                     println!("Downloading track {}..", track.title);
                     let maybe_new_track = download::download_track(
                         &track,
-                        file::util::track_dir_path().unwrap(),
+                        library.track_dir_path(),
                         &musicbrainz_client,
-                        track.id().to_string(),
+                        track.file_id().to_string(),
                         bin_apps.clone(),
                         &process_sender,
                         &map_t,
@@ -486,7 +768,7 @@ impl PlaylistDownloadManager {
                             // Track Download End message
                             let _ = playlist_sender_clone
                                 .send(PlaylistMessage::TrackDownloadDone {
-                                    id: track.id().clone(),
+                                    id: track.file_id().clone(),
                                     success: false,
                                 })
                                 .await;
@@ -494,13 +776,16 @@ impl PlaylistDownloadManager {
                         }
                     };
 
-                    // update track logic
-                    if let Some(track) = maybe_new_track {
-                        println!("Found new match for track: {track:?}");
+                    // update track logic. a chapter's file is its whole track's, so the match
+                    // isn't for the chapter
+                    if let Some(new_track) = maybe_new_track
+                        && track.chapter_of.is_none()
+                    {
+                        println!("Found new match for track: {new_track:?}");
                         let _ = playlist_sender_clone
                             .send(PlaylistMessage::UpdateTrack {
                                 playlist_id: None,
-                                track,
+                                track: new_track,
                                 restart_audio: false,
                                 restart_download: false,
                             })
@@ -510,7 +795,7 @@ impl PlaylistDownloadManager {
                     // Track Download End message
                     playlist_sender_clone
                         .send(PlaylistMessage::TrackDownloadDone {
-                            id: track.id().clone(),
+                            id: track.file_id().clone(),
                             success: true,
                         })
                         .await
@@ -612,6 +897,8 @@ pub struct PlaylistAudioManager {
     restart_flag: Arc<AtomicBool>,
     internal_t: Option<watch::Sender<Option<Tracklist>>>,
     current_track_id: Arc<Mutex<Option<Id>>>,
+    // the previous track, while it finishes under the start of the current one
+    fading_track_id: Arc<Mutex<Option<Id>>>,
     current_pos: Arc<AtomicU64>,
     dead: bool,
     running: bool,
@@ -623,9 +910,18 @@ pub struct PlaylistAudioManager {
     playing_flag: Arc<AtomicBool>,
     start_audio_looped: Arc<AtomicBool>,
     volume: Arc<AtomicF64>,
+    transition: Arc<Mutex<TrackTransition>>,
+    speed: Arc<Mutex<PlaybackSpeed>>,
+    // set by a sleep timer; the next track is started paused instead of playing
+    pause_after_current: Arc<AtomicBool>,
 }
 impl PlaylistAudioManager {
-    pub fn new(playlist_id: Id, volume: f64) -> Self {
+    pub fn new(
+        playlist_id: Id,
+        volume: f64,
+        transition: TrackTransition,
+        speed: PlaybackSpeed,
+    ) -> Self {
         Self {
             tracklist: None,
             playlist_id,
@@ -636,6 +932,7 @@ impl PlaylistAudioManager {
             dead: false,
             running: false,
             current_track_id: Arc::new(Mutex::new(None)),
+            fading_track_id: Arc::new(Mutex::new(None)),
             current_pos: Arc::new(AtomicU64::new(0)),
             audio_sender: None,
             start_index: None,
@@ -644,6 +941,9 @@ impl PlaylistAudioManager {
             playing_flag: Arc::new(AtomicBool::new(false)),
             start_audio_looped: Arc::new(AtomicBool::new(false)),
             volume: Arc::new(AtomicF64::new(volume)),
+            transition: Arc::new(Mutex::new(transition)),
+            speed: Arc::new(Mutex::new(speed)),
+            pause_after_current: Arc::new(AtomicBool::new(false)),
         }
     }
     pub fn run(
@@ -655,6 +955,8 @@ impl PlaylistAudioManager {
         playlist_sender: mpsc::Sender<PlaylistMessage>,
         audio_sender: mpsc::Sender<AudioMessage>,
         autoplay_first_track: bool,
+        // where to start, instead of the first track
+        resume: Option<ResumePoint>,
     ) {
         if self.dead() {
             return;
@@ -669,6 +971,9 @@ impl PlaylistAudioManager {
 
         // create the start index watch channel
         let (start_index_t, mut start_index_r) = watch::channel(None);
+        if let Some(resume) = resume {
+            let _ = start_index_t.send(Some(resume.track_index));
+        }
         let arc_start_index = Arc::new(start_index_t);
         self.start_index = Some(Arc::clone(&arc_start_index));
 
@@ -703,6 +1008,7 @@ impl PlaylistAudioManager {
         let gui_reply_stream_clone = gui_progress_sender.clone();
         let cancel_token = self.cancel_token.clone();
         let current_track_id = Arc::clone(&self.current_track_id);
+        let fading_track_id = Arc::clone(&self.fading_track_id);
         let audio_sender = self.audio_sender.clone().unwrap();
         let restart_flag = Arc::clone(&self.restart_flag);
         let current_pos_arc = Arc::clone(&self.current_pos);
@@ -710,10 +1016,15 @@ impl PlaylistAudioManager {
         let stop_waiting_on_track_notify = Arc::clone(&self.stop_waiting_on_track_notify);
         let playing_flag = self.playing_flag.clone();
         let volume_arc = Arc::clone(&self.volume);
+        let transition_arc = Arc::clone(&self.transition);
+        let speed_arc = Arc::clone(&self.speed);
+        let pause_after_current = Arc::clone(&self.pause_after_current);
 
         // spawn async process
         tokio::spawn(async move {
             let mut first_pass = true;
+            // only used if the first track played is the one being resumed
+            let mut resume = resume;
             while internal_r.has_changed().unwrap_or(false) || restart_flag.load(Ordering::Relaxed)
             {
                 println!("starting new loop in audio player");
@@ -734,6 +1045,8 @@ impl PlaylistAudioManager {
                 println!("running playlist playing logic lol");
                 let mut current_pos: i64 = -1;
                 let playlist_length = tracklist.order.length() as u64;
+                // set when the previous track handed off to this one; when it will end
+                let mut previous_ends_at: Option<Instant> = None;
 
                 // check if a specific position was requested
                 if start_index_r.has_changed().unwrap_or(false) {
@@ -767,7 +1080,7 @@ impl PlaylistAudioManager {
                     // send the request
                     if let Err(_) = playlist_sender_clone
                         .send(PlaylistMessage::CheckTrackDownloaded {
-                            id: track.id().clone(),
+                            id: track.file_id().clone(),
                             result_sender: downloaded_t,
                         })
                         .await
@@ -794,7 +1107,7 @@ impl PlaylistAudioManager {
                                 let _ = playlist_sender
                                     .send(PlaylistMessage::IfPlaylistDownloadingWait {
                                         playlist_id: playlist_id.clone(),
-                                        track_id_to_wait: track.id().clone(),
+                                        track_id_to_wait: track.file_id().clone(),
                                         result_sender: tx,
                                     })
                                     .await;
@@ -872,13 +1185,71 @@ impl PlaylistAudioManager {
 
                     println!("Playing track {}..", track.title);
                     // audio playing logic
-                    let start_paused = first_pass && !autoplay_first_track;
+                    let start_paused = (first_pass && !autoplay_first_track)
+                        || pause_after_current.swap(false, Ordering::Relaxed);
                     // immediately change first pass
                     first_pass = false;
-                    let audio_config =
-                        AudioConfig::new(start_paused, volume_arc.load(Ordering::Relaxed));
-                    let (end_t, end_r) = oneshot::channel();
+                    let (gain_t, gain_r) = oneshot::channel();
+                    let _ = playlist_sender
+                        .send(PlaylistMessage::GetTrackGain {
+                            id: track.file_id().clone(),
+                            result_sender: gain_t,
+                        })
+                        .await;
+                    let transition = *transition_arc.lock();
+                    let mut audio_config = AudioConfig::new(
+                        start_paused,
+                        volume_arc.load(Ordering::Relaxed),
+                        transition.lead_time(),
+                    )
+                    .gain(gain_r.await.unwrap_or(0.0))
+                    .speed(*speed_arc.lock());
+                    audio_config = audio_config.trim(track.trim);
+                    if let Some(chapter_of) = &track.chapter_of {
+                        audio_config = audio_config.slice(
+                            chapter_of.parent.clone(),
+                            chapter_of.chapter.start,
+                            chapter_of.chapter.end,
+                        );
+                    }
+                    if let Some(resume) = resume.take()
+                        && resume.track_index == current_pos as u64
+                    {
+                        audio_config = audio_config.start_at(resume.position);
+                    }
+                    // line this track up with the end of the previous one
+                    if let Some(ends_at) = previous_ends_at.take() {
+                        let left = ends_at.saturating_duration_since(Instant::now());
+                        match transition.crossfade {
+                            Some(_) => {
+                                let tween = Tween {
+                                    duration: left,
+                                    easing: transition.curve.easing(),
+                                    ..Default::default()
+                                };
+                                audio_config = audio_config.fade_in(tween);
+                                let previous_id = fading_track_id.lock().clone();
+                                if let Some(previous_id) = previous_id {
+                                    let _ = audio_sender
+                                        .send(AudioMessage::FadeOutAudio {
+                                            id: previous_id,
+                                            tween,
+                                        })
+                                        .await;
+                                }
+                            }
+                            None => audio_config = audio_config.start_in(left),
+                        }
+                    }
+                    let (end_t, mut end_r) = oneshot::channel();
+                    let (near_end_t, mut near_end_r) = oneshot::channel();
 
+                    // remember when it was played, for the storage quota
+                    let _ = playlist_sender
+                        .send(PlaylistMessage::TrackPlayed {
+                            id: track.file_id().clone(),
+                        })
+                        .await;
                     // Track Audio Start message
                     let _ = gui_reply_stream_clone
                         .send(Message::TrackAudioStart {
@@ -893,6 +1264,7 @@ impl PlaylistAudioManager {
                         audio_config,
                         progress_sender: map_t.clone(),
                         on_end: end_t,
+                        on_near_end: near_end_t,
                         on_loop: playlist_sender.clone(),
                         maybe_playlist_id: Some(playlist_id.clone()),
                     };
@@ -908,18 +1280,59 @@ impl PlaylistAudioManager {
                         *guard = Some(track.id().clone());
                     }
 
+                    // only hand off to a different track; the audio service can't play the same
+                    // track twice at once
+                    let can_hand_off = tracklist
+                        .order
+                        .index_order
+                        .get(current_pos as usize + 1)
+                        .and_then(|loc| tracklist.tracks.0.get(*loc as usize))
+                        .is_some_and(|next| next.id() != track.id())
+                        // the next track waits to be started paused
+                        && !pause_after_current.load(Ordering::Relaxed);
+
                     // play audio unless cancelled (audio mgr shut down)
                     tokio::select! {
                         _ = cancel_token.cancelled() => {
                             println!("Playlist audio manager cancelled");
                             break;
                         }
-                        _ = end_r => {
+                        _ = &mut end_r => {
                             println!("Track finished in mgr.");
                             // reset current track
                             let mut guard = current_track_id.lock();
                             *guard = None;
                         }
+                        Ok(ends_at) = &mut near_end_r, if can_hand_off => {
+                            // start the next track now; this one finishes on its own
+                            println!("Track nearly finished in mgr.");
+                            previous_ends_at = Some(ends_at);
+                            *current_track_id.lock() = None;
+                            *fading_track_id.lock() = Some(track.id().clone());
+                            playing_flag.store(false, Ordering::Relaxed);
+                            tokio::spawn({
+                                let id = track.id().clone();
+                                let playlist_id = playlist_id.clone();
+                                let fading_track_id = Arc::clone(&fading_track_id);
+                                let gui_reply_stream = gui_reply_stream_clone.clone();
+                                async move {
+                                    let _ = end_r.await;
+                                    {
+                                        let mut guard = fading_track_id.lock();
+                                        if guard.as_ref() == Some(&id) {
+                                            *guard = None;
+                                        }
+                                    }
+                                    let _ = gui_reply_stream
+                                        .send(Message::TrackAudioEnd {
+                                            id,
+                                            maybe_playlist_id: Some(playlist_id),
+                                        })
+                                        .await;
+                                }
+                            });
+                            continue;
+                        }
                     }
 
                     // this track ended
@@ -968,11 +1381,7 @@ impl PlaylistAudioManager {
         }
 
         if let Some(sender) = &self.audio_sender {
-            let track_id = {
-                let guard = self.current_track_id.lock();
-                guard.clone()
-            };
-            if let Some(track_id) = track_id {
+            for track_id in self.playing_track_ids() {
                 let sender_clone = sender.clone();
                 tokio::spawn(async move {
                     let (tx, _) = oneshot::channel();
@@ -1003,12 +1412,8 @@ impl PlaylistAudioManager {
         }
 
         if let Some(sender) = &self.audio_sender {
-            // get the current track id if it exists
-            let track_id: Option<Id> = {
-                let guard = self.current_track_id.lock();
-                guard.clone()
-            };
-            if let Some(track_id) = track_id {
+            // includes the previous track if it's still fading out
+            for track_id in self.playing_track_ids() {
                 let sender_clone = sender.clone();
                 tokio::spawn(async move {
                     let (tx, _) = oneshot::channel();
@@ -1029,12 +1434,8 @@ impl PlaylistAudioManager {
         }
 
         if let Some(sender) = &self.audio_sender {
-            // get the current track id if it exists
-            let track_id: Option<Id> = {
-                let guard = self.current_track_id.lock();
-                guard.clone()
-            };
-            if let Some(track_id) = track_id {
+            // includes the previous track if it's still fading out
+            for track_id in self.playing_track_ids() {
                 let sender_clone = sender.clone();
                 tokio::spawn(async move {
                     let (tx, _) = oneshot::channel();
@@ -1118,6 +1519,25 @@ impl PlaylistAudioManager {
         // set the flag for future tracks
         self.start_audio_looped.store(loop_audio, Ordering::Relaxed);
     }
+    /// updates how the manager moves between tracks. Applies from the next track on.
+    pub fn update_transition(&mut self, transition: TrackTransition) {
+        *self.transition.lock() = transition;
+    }
+    /// Starts the next track paused, rather than playing it. Cleared once it's started.
+    pub fn set_pause_after_current(&mut self, enabled: bool) {
+        self.pause_after_current.store(enabled, Ordering::Relaxed);
+    }
+    /// updates the speed future tracks are started at. The audio service has to be told to change
+    /// the speed of the ones already playing.
+    pub fn update_speed(&mut self, speed: PlaybackSpeed) {
+        *self.speed.lock() = speed;
+    }
+    // The current track, and the previous one if it's still finishing
+    pub fn playing_track_ids(&self) -> Vec<Id> {
+        let current = self.current_track_id.lock().clone();
+        let fading = self.fading_track_id.lock().clone();
+        current.into_iter().chain(fading).collect()
+    }
     /// updates the manager's internal volume. Note: it does not update
     /// any current track's volume.
     pub fn update_volume(&mut self, volume: f64) {
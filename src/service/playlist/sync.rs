@@ -0,0 +1,426 @@
+// Mirroring playlists to a folder (a phone, sd card, nas...). A manifest in the destination
+// remembers what the last sync wrote, so later syncs only touch what changed.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::service::{
+    audio::enums::AlbumKind,
+    file::{self, library::Library, structs::TrackTags},
+    gui::{enums::Message, structs::TaskId},
+    playlist::{
+        enums::{LocalImportStatus, PlaylistFileFormat, SyncLayout, SyncTranscode},
+        import::stable_hash,
+        playlist_file::render_playlist_file,
+        structs::{SyncOptions, SyncSummary, Track},
+    },
+    process::{ProcessSender, enums::ChildMessage, enums::ProcessMessage},
+};
+
+const MANIFEST_FILENAME: &str = ".peanut-sync.json";
+// keeps paths well under the limits of most filesystems
+const MAX_COMPONENT_LEN: usize = 120;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncManifest {
+    // relative path (`/` separated) -> fingerprint of what was written there
+    files: BTreeMap<String, String>,
+}
+
+// What ends up at a path in the destination
+enum SyncSource {
+    Track { track: Box<Track>, path: PathBuf },
+    PlaylistFile(String),
+}
+
+/// Runs ffmpeg for syncs that convert the tracks
+pub struct Transcoder {
+    pub ffmpeg: PathBuf,
+    pub process_sender: ProcessSender,
+}
+
+struct SyncEntry {
+    relative_path: String,
+    fingerprint: String,
+    source: SyncSource,
+}
+
+/// Mirrors the playlists' downloaded tracks (with the paths of their audio files) to the
+/// destination. Only files that changed since the last sync are written or deleted.
+pub async fn sync_playlists(
+    playlists: Vec<(String, Vec<(Track, PathBuf)>)>,
+    destination: PathBuf,
+    options: SyncOptions,
+    task_id: TaskId,
+    library: Library,
+    transcoder: Transcoder,
+    status_sender: &mpsc::Sender<Message>,
+) -> Result<()> {
+    tokio::fs::create_dir_all(&destination).await?;
+    let manifest_path = destination.join(MANIFEST_FILENAME);
+    let mut manifest = match tokio::fs::read_to_string(&manifest_path).await {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => SyncManifest::default(),
+        Err(e) => return Err(e.into()),
+    };
+    let (entries, skipped) =
+        tokio::task::spawn_blocking(move || plan_sync(playlists, options)).await??;
+    if entries.is_empty() {
+        bail!("None of the playlists have downloaded tracks");
+    }
+
+    let mut summary = SyncSummary {
+        failed: skipped,
+        ..Default::default()
+    };
+    // remove what the last sync wrote that isn't wanted anymore. Files that weren't written by
+    // a sync are never touched.
+    let wanted: HashSet<&str> = entries.iter().map(|e| e.relative_path.as_str()).collect();
+    let stale: Vec<String> = manifest
+        .files
+        .keys()
+        .filter(|path| !wanted.contains(path.as_str()))
+        .cloned()
+        .collect();
+    for relative_path in stale {
+        let path = destination.join(&relative_path);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => summary.deleted += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                println!("Failed to remove {}: {e}", path.display());
+                continue;
+            }
+        }
+        manifest.files.remove(&relative_path);
+        remove_empty_parents(&destination, &path).await;
+    }
+
+    let total = entries.len() as u32;
+    for (i, entry) in entries.into_iter().enumerate() {
+        let path = destination.join(&entry.relative_path);
+        let up_to_date = manifest.files.get(&entry.relative_path) == Some(&entry.fingerprint)
+            && tokio::fs::try_exists(&path).await.unwrap_or(false);
+        if up_to_date {
+            summary.unchanged += 1;
+        } else {
+            match write_entry(&entry, &path, options, &library, &transcoder).await {
+                Ok(()) => {
+                    summary.written += 1;
+                    manifest
+                        .files
+                        .insert(entry.relative_path, entry.fingerprint);
+                }
+                Err(e) => {
+                    println!("Failed to sync {}: {e}", path.display());
+                    summary.failed += 1;
+                }
+            }
+        }
+        let _ = status_sender
+            .send(Message::LocalImportStatus {
+                status: LocalImportStatus::Progress {
+                    current: i as u32 + 1,
+                    total,
+                },
+                id: task_id,
+            })
+            .await;
+    }
+
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    tokio::task::spawn_blocking(move || file::util::write_atomic(&manifest_path, &manifest))
+        .await??;
+    let _ = status_sender
+        .send(Message::LocalImportStatus {
+            status: LocalImportStatus::SyncComplete(summary),
+            id: task_id,
+        })
+        .await;
+    Ok(())
+}
+
+// Works out every file the destination should have, and how many tracks had to be left out.
+// Blocking, as it looks at the audio files.
+fn plan_sync(
+    playlists: Vec<(String, Vec<(Track, PathBuf)>)>,
+    options: SyncOptions,
+) -> Result<(Vec<SyncEntry>, u32)> {
+    let mut entries = Vec::new();
+    // lowercased, as the destination is often a case insensitive filesystem
+    let mut taken = HashSet::new();
+    // the album layout shares tracks between playlists
+    let mut track_paths: BTreeMap<String, String> = BTreeMap::new();
+    // tracks whose audio file couldn't be read
+    let mut skipped = HashSet::new();
+    for (title, tracks) in playlists {
+        let playlist_dir = sanitize_component(&title, "Playlist");
+        let number_width = tracks.len().to_string().len().max(2);
+        // the tracks that made it, and where they are in the destination
+        let mut synced = Vec::new();
+        let mut locations = Vec::new();
+        for (n, (track, path)) in tracks.iter().enumerate() {
+            let track_key = track.id().to_string();
+            if let Some(existing) = track_paths.get(&track_key) {
+                synced.push((track.clone(), path.clone()));
+                locations.push(PathBuf::from(existing));
+                continue;
+            }
+            if skipped.contains(&track_key) {
+                continue;
+            }
+            let metadata = match std::fs::metadata(path).and_then(|m| Ok((m.len(), m.modified()?)))
+            {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    println!("Skipping {} ({}): {e}", track.title, path.display());
+                    skipped.insert(track_key);
+                    continue;
+                }
+            };
+            let extension = match options.transcode {
+                Some(transcode) => transcode.extension().to_string(),
+                None => path
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_default(),
+            };
+            // tracks don't know where they are on their album, so they're numbered in playlist
+            // order (which is the album's order for an album's own playlist)
+            let file_title = format!(
+                "{:0width$} - {}",
+                n + 1,
+                sanitize_component(&track.title, "Untitled"),
+                width = number_width
+            );
+            let relative_path = match options.layout {
+                SyncLayout::ArtistAlbum => {
+                    let (artist, album) = match &track.album_kind {
+                        AlbumKind::Album(album) if !album.artists.is_empty() => {
+                            (album.artists.join(", "), album.name.clone())
+                        }
+                        AlbumKind::Album(album) => {
+                            (track.artist.clone().artist(), album.name.clone())
+                        }
+                        AlbumKind::Single => {
+                            (track.artist.clone().artist(), String::from("Singles"))
+                        }
+                        AlbumKind::Unknown => {
+                            (track.artist.clone().artist(), String::from("Unknown Album"))
+                        }
+                    };
+                    format!(
+                        "{}/{}/{}",
+                        sanitize_component(&artist, "Unknown Artist"),
+                        sanitize_component(&album, "Unknown Album"),
+                        file_title
+                    )
+                }
+                SyncLayout::Playlist => format!("{}/{}", playlist_dir, file_title),
+            };
+            let relative_path = unique_path(&relative_path, &extension, &mut taken);
+            // the playlist layout gives every playlist its own copy
+            if options.layout == SyncLayout::ArtistAlbum {
+                track_paths.insert(track_key, relative_path.clone());
+            }
+            synced.push((track.clone(), path.clone()));
+            locations.push(PathBuf::from(&relative_path));
+
+            let (len, modified) = metadata;
+            let modified = modified
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let fingerprint = format!(
+                "{}|{}|{}|{}",
+                track.id(),
+                len,
+                modified,
+                options.transcode.map(|t| t.extension()).unwrap_or("copy")
+            );
+            entries.push(SyncEntry {
+                relative_path,
+                fingerprint,
+                source: SyncSource::Track {
+                    track: Box::new(track.clone()),
+                    path: path.clone(),
+                },
+            });
+        }
+        if synced.is_empty() {
+            continue;
+        }
+
+        // a playlist file at the root, pointing at the synced files
+        let contents = render_playlist_file(PlaylistFileFormat::M3u8, &title, &synced, &locations)?;
+        let relative_path = unique_path(&playlist_dir, "m3u8", &mut taken);
+        entries.push(SyncEntry {
+            relative_path,
+            fingerprint: stable_hash(&contents),
+            source: SyncSource::PlaylistFile(contents),
+        });
+    }
+    Ok((entries, skipped.len() as u32))
+}
+
+async fn write_entry(
+    entry: &SyncEntry,
+    path: &Path,
+    options: SyncOptions,
+    library: &Library,
+    transcoder: &Transcoder,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    match (&entry.source, options.transcode) {
+        (SyncSource::PlaylistFile(contents), _) => {
+            let path = path.to_path_buf();
+            let contents = contents.clone();
+            tokio::task::spawn_blocking(move || {
+                file::util::write_atomic(&path, contents.as_bytes())
+            })
+            .await??;
+        }
+        (SyncSource::Track { path: source, .. }, None) => {
+            let source = source.clone();
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || file::util::copy_atomic(&source, &path)).await??;
+        }
+        (
+            SyncSource::Track {
+                track,
+                path: source,
+            },
+            Some(transcode),
+        ) => {
+            transcode_track(track, source, path, transcode, library, transcoder).await?;
+        }
+    }
+    Ok(())
+}
+
+// Converts the track with ffmpeg, then tags the result the same way library files are tagged
+async fn transcode_track(
+    track: &Track,
+    source: &Path,
+    destination: &Path,
+    transcode: SyncTranscode,
+    library: &Library,
+    transcoder: &Transcoder,
+) -> Result<()> {
+    // ffmpeg picks the container from the extension, so the temp file keeps it
+    let file_name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path =
+        destination.with_file_name(format!(".{}.part.{}", file_name, transcode.extension()));
+    let mut args: Vec<OsString> = ["-y", "-hide_banner", "-loglevel", "error", "-i"]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+    args.push(source.as_os_str().to_os_string());
+    // drop video (cover art) streams and the old tags; tags are written afterwards
+    args.extend(["-map", "0:a", "-map_metadata", "-1"].map(OsString::from));
+    args.extend(transcode.codec_args().map(OsString::from));
+    args.push(temp_path.as_os_str().to_os_string());
+
+    let (tx, mut rx) = mpsc::channel(100);
+    transcoder
+        .process_sender
+        .send(ProcessMessage::SpawnProcess {
+            cmd: transcoder.ffmpeg.as_os_str().to_os_string(),
+            args,
+            output_stream: tx,
+        })
+        .await?;
+    let mut errors = Vec::new();
+    let mut success = false;
+    while let Some(msg) = rx.recv().await {
+        match msg {
+            ChildMessage::StdErr(line) => errors.push(line),
+            ChildMessage::Exit(status) => success = status.success(),
+            ChildMessage::StdOut(_) => {}
+        }
+    }
+    let result = if success {
+        let tags = TrackTags::from_track(library, track);
+        let temp = temp_path.clone();
+        let destination = destination.to_path_buf();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            file::tags::write_tags(&temp, &tags)?;
+            std::fs::rename(&temp, &destination)?;
+            Ok(())
+        })
+        .await?
+    } else {
+        Err(anyhow!("ffmpeg failed: {}", errors.join("\n")))
+    };
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result
+}
+
+// Removes the folders above a deleted file that are now empty, stopping at the destination
+async fn remove_empty_parents(destination: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d == destination || !d.starts_with(destination) {
+            break;
+        }
+        // fails if the folder isn't empty
+        if tokio::fs::remove_dir(d).await.is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+// `base.extension`, numbered if another file already has that path
+fn unique_path(base: &str, extension: &str, taken: &mut HashSet<String>) -> String {
+    let with_extension = |base: &str| match extension {
+        "" => base.to_string(),
+        extension => format!("{}.{}", base, extension),
+    };
+    let mut path = with_extension(base);
+    let mut n = 2;
+    while !taken.insert(path.to_lowercase()) {
+        path = with_extension(&format!("{} ({})", base, n));
+        n += 1;
+    }
+    path
+}
+
+// Makes a name safe to use as a single path component on fat32 / ntfs / ext4 alike
+fn sanitize_component(name: &str, fallback: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_COMPONENT_LEN)
+        .collect();
+    // windows doesn't allow trailing dots or spaces, and leading dots hide files elsewhere
+    let sanitized = sanitized
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches('.')
+        .trim();
+    if sanitized.is_empty() {
+        fallback.to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::service::{
+    audio::enums::AlbumKind,
     id::structs::Id,
-    playlist::structs::{Track, TrackIdVec, TrackVec},
+    playlist::structs::{Playlist, Track, TrackIdVec, TrackVec},
 };
 
 /// Takes the playlist service's track cache and returns a playlist specific list of tracks.
@@ -18,3 +19,73 @@ pub fn clone_tracks_from_cache(
             .collect(),
     )
 }
+
+/// Counts how many times each track is referenced across all playlists.
+pub fn count_track_references(playlists: &HashMap<Id, Playlist>) -> HashMap<Id, usize> {
+    let mut references = HashMap::new();
+    for playlist in playlists.values() {
+        for id in playlist.tracks.0.iter() {
+            *references.entry(id.clone()).or_insert(0) += 1;
+        }
+    }
+    references
+}
+
+/// Returns the tracks in the given playlist that would no longer be referenced by any playlist
+/// if it were removed.
+pub fn unreferenced_tracks_after_removal(
+    playlist: &Playlist,
+    track_references: &HashMap<Id, usize>,
+) -> Vec<Id> {
+    // count occurences in this playlist in case a track appears more than once
+    let mut local_counts: HashMap<&Id, usize> = HashMap::new();
+    for id in playlist.tracks.0.iter() {
+        *local_counts.entry(id).or_insert(0) += 1;
+    }
+    local_counts
+        .into_iter()
+        .filter(|(id, count)| track_references.get(*id).copied().unwrap_or(0) <= *count)
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Returns the albums used by the removed tracks that none of the remaining tracks use.
+pub fn unreferenced_albums_after_removal(
+    removed_tracks: &[Id],
+    track_cache: &HashMap<Id, Track>,
+) -> Vec<Id> {
+    let removed: HashSet<&Id> = removed_tracks.iter().collect();
+    let mut candidates = HashSet::new();
+    let mut still_used = HashSet::new();
+    for (id, track) in track_cache.iter() {
+        if let AlbumKind::Album(album) = &track.album_kind {
+            if removed.contains(id) {
+                candidates.insert(album.id().clone());
+            } else {
+                still_used.insert(album.id().clone());
+            }
+        }
+    }
+    candidates.difference(&still_used).cloned().collect()
+}
+
+/// Returns the downloaded tracks that can be evicted, in the order they should be: least
+/// recently played first (never played before anything else), then biggest first.
+pub fn eviction_order(
+    downloaded_tracks: &HashSet<Id>,
+    keep: &HashSet<&Id>,
+    last_played: &HashMap<Id, i64>,
+    track_sizes: &HashMap<Id, u64>,
+) -> Vec<Id> {
+    let mut candidates: Vec<&Id> = downloaded_tracks
+        .iter()
+        .filter(|id| !keep.contains(id))
+        .collect();
+    candidates.sort_by_key(|id| {
+        (
+            last_played.get(*id).copied(),
+            std::cmp::Reverse(track_sizes.get(*id).copied().unwrap_or(0)),
+        )
+    });
+    candidates.into_iter().cloned().collect()
+}